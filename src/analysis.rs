@@ -0,0 +1,445 @@
+use chrono::{DateTime, NaiveDateTime, Timelike, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::args::{Args, Browser, SortBy};
+use crate::categories::{Categories, CategoryHours, UNCATEGORIZED};
+use crate::navigation::NavigationGraph;
+use crate::schema::{Schema, Transition};
+use crate::search::SearchTerms;
+use crate::stats::{AnalysisResult, TransitionStats};
+use crate::temporal::{
+    DomainFrecency, DomainNovelty, DomainSpans, DomainStreaks, DomainTrends, PeriodBreakdown,
+    PeriodComparison, SessionStats, TemporalStats, TimeSpent,
+};
+
+/// A visit handed to each analysis, with its domain and local time resolved once
+#[derive(Debug, Clone, Copy)]
+pub struct VisitEvent<'a> {
+    pub id: i64,
+    /// The visit this one was navigated from, when the browser recorded it
+    pub from_visit: Option<i64>,
+    pub url_id: i64,
+    pub url: &'a str,
+    pub timestamp: DateTime<Utc>,
+    /// Wall-clock time of the visit in the report's zone
+    pub local: NaiveDateTime,
+    /// Normalized domain; always `None` when no analysis needs domains
+    pub domain: Option<&'a str>,
+    pub transition: Transition,
+    /// Time spent on the page, when the browser records it
+    pub duration: Option<chrono::Duration>,
+}
+
+/// A metric computed in the single pass over the visit stream
+pub trait Analysis: Send {
+    fn name(&self) -> &str;
+
+    /// Whether visits must carry their domain; resolving domains costs a URL parse per URL
+    fn needs_domains(&self) -> bool {
+        true
+    }
+
+    /// Called once per visit, in chronological order
+    fn accumulate(&mut self, visit: &VisitEvent<'_>);
+
+    /// Stores the finished metric in `result`
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult);
+}
+
+/// Text section added to the report by an analysis outside this crate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// Creates a fresh analysis for each browser analyzed
+pub type AnalysisFactory = Arc<dyn Fn() -> Box<dyn Analysis> + Send + Sync>;
+
+impl Analysis for TemporalStats {
+    fn name(&self) -> &str {
+        "temporal"
+    }
+
+    fn needs_domains(&self) -> bool {
+        false
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        self.record(visit.local);
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.temporal = Some(*self);
+    }
+}
+
+impl Analysis for PeriodBreakdown {
+    fn name(&self) -> &str {
+        "periods"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        self.record(visit.local, visit.domain);
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.periods = Some(*self);
+    }
+}
+
+impl Analysis for SessionStats {
+    fn name(&self) -> &str {
+        "sessions"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        self.record(visit.timestamp, visit.domain);
+    }
+
+    fn finalize(mut self: Box<Self>, result: &mut AnalysisResult) {
+        self.finish();
+        result.sessions = Some(*self);
+    }
+}
+
+impl Analysis for TransitionStats {
+    fn name(&self) -> &str {
+        "transitions"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        self.record(visit.transition, visit.domain);
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.transitions = Some(*self);
+    }
+}
+
+impl Analysis for DomainTrends {
+    fn name(&self) -> &str {
+        "trends"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.local, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.trends = Some(*self);
+    }
+}
+
+impl Analysis for PeriodComparison {
+    fn name(&self) -> &str {
+        "compare-periods"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.timestamp, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.period_comparison = Some(*self);
+    }
+}
+
+impl Analysis for DomainStreaks {
+    fn name(&self) -> &str {
+        "streaks"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.local, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.streaks = Some(*self);
+    }
+}
+
+impl Analysis for DomainNovelty {
+    fn name(&self) -> &str {
+        "novelty"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.local, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.novelty = Some(*self);
+    }
+}
+
+impl Analysis for DomainSpans {
+    fn name(&self) -> &str {
+        "spans"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.timestamp, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.spans = Some(*self);
+    }
+}
+
+impl Analysis for DomainFrecency {
+    fn name(&self) -> &str {
+        "frecency"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let Some(domain) = visit.domain {
+            self.record(visit.timestamp, domain);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.frecency = Some(*self);
+    }
+}
+
+impl Analysis for TimeSpent {
+    fn name(&self) -> &str {
+        "time spent"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        if let (Some(domain), Some(duration)) = (visit.domain, visit.duration) {
+            self.record(domain, duration);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.time_spent = Some(*self);
+    }
+}
+
+/// Builds the navigation graph by resolving each visit's referring visit to its domain
+#[derive(Default)]
+pub(crate) struct NavigationAnalysis {
+    graph: NavigationGraph,
+    /// Index into `domains` for every visit seen so far; referrers precede the visits
+    /// they lead to, so a single chronological pass resolves them
+    visits: HashMap<i64, u32>,
+    domains: Vec<String>,
+    domain_ids: HashMap<String, u32>,
+}
+
+impl Analysis for NavigationAnalysis {
+    fn name(&self) -> &str {
+        "navigation"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        let Some(domain) = visit.domain else {
+            return;
+        };
+        let id = match self.domain_ids.get(domain) {
+            Some(id) => *id,
+            None => {
+                let id = self.domains.len() as u32;
+                self.domains.push(domain.to_string());
+                self.domain_ids.insert(domain.to_string(), id);
+                id
+            }
+        };
+        self.visits.insert(visit.id, id);
+        if let Some(from) = visit.from_visit.and_then(|from| self.visits.get(&from)) {
+            self.graph.record(&self.domains[*from as usize], domain, 1);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.navigation = Some(self.graph);
+    }
+}
+
+/// Visits per category and hour, categorizing each domain once
+pub(crate) struct CategoryHoursAnalysis {
+    categories: Categories,
+    hours: CategoryHours,
+    resolved: HashMap<String, String>,
+}
+
+impl CategoryHoursAnalysis {
+    pub(crate) fn new(categories: Categories) -> Self {
+        Self {
+            categories,
+            hours: CategoryHours::default(),
+            resolved: HashMap::new(),
+        }
+    }
+}
+
+impl Analysis for CategoryHoursAnalysis {
+    fn name(&self) -> &str {
+        "category hours"
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        let Some(domain) = visit.domain else {
+            return;
+        };
+        let category = match self.resolved.get(domain) {
+            Some(category) => category,
+            None => {
+                let category = self.categories.category_of(domain).unwrap_or(UNCATEGORIZED);
+                self.resolved
+                    .entry(domain.to_string())
+                    .or_insert_with(|| category.to_string())
+            }
+        };
+        self.hours.record(category, visit.local.hour() as usize);
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.category_hours = Some(self.hours);
+    }
+}
+
+/// Search terms from search-engine URLs, falling back to the terms the browser recorded
+pub(crate) struct SearchAnalysis {
+    terms: SearchTerms,
+    recorded: HashMap<i64, String>,
+    /// Also read the generic query parameters on unknown sites
+    generic: bool,
+    /// Visits repeat URLs heavily, so each URL's term is resolved only once
+    resolved: HashMap<i64, Option<String>>,
+}
+
+impl SearchAnalysis {
+    pub(crate) fn new(recorded: HashMap<i64, String>, generic: bool) -> Self {
+        Self {
+            terms: SearchTerms::default(),
+            recorded,
+            generic,
+            resolved: HashMap::new(),
+        }
+    }
+}
+
+impl Analysis for SearchAnalysis {
+    fn name(&self) -> &str {
+        "searches"
+    }
+
+    fn needs_domains(&self) -> bool {
+        false
+    }
+
+    fn accumulate(&mut self, visit: &VisitEvent<'_>) {
+        let (recorded, generic) = (&self.recorded, self.generic);
+        let term = self.resolved.entry(visit.url_id).or_insert_with(|| {
+            url::Url::parse(visit.url)
+                .ok()
+                .and_then(|url| crate::search::search_term(&url, generic))
+                .or_else(|| recorded.get(&visit.url_id).cloned())
+        });
+        if let Some(term) = term {
+            self.terms.record(term);
+        }
+    }
+
+    fn finalize(self: Box<Self>, result: &mut AnalysisResult) {
+        result.searches = Some(self.terms);
+    }
+}
+
+/// The built-in per-visit analyses selected by `args`; searches are added separately,
+/// since they need the terms the browser recorded
+/// Whether any built-in analysis runs, and so the visit stream has to be read
+pub(crate) fn needs_visits(args: &Args) -> bool {
+    args.temporal
+        || args.period().is_some()
+        || args.trends
+        || args.compare_periods.is_some()
+        || args.streaks
+        || args.novelty
+        || matches!(args.sort, SortBy::FirstVisit | SortBy::LastVisit)
+        || args.stale.is_some()
+        || crate::report::needs_html(args)
+        || args.sessions.is_some()
+        || args.time_spent
+        || args.transitions
+        || args.nav_graph
+        || args.referrers.is_some()
+        || args.category_hours
+}
+
+pub(crate) fn builtin(
+    args: &Args,
+    schema: &Schema,
+    browser: &Browser,
+    categories: Option<&Categories>,
+) -> Vec<Box<dyn Analysis>> {
+    let mut analyses: Vec<Box<dyn Analysis>> = Vec::new();
+    if args.temporal {
+        analyses.push(Box::new(TemporalStats::default()));
+    }
+    if let Some(period) = args.period() {
+        analyses.push(Box::new(PeriodBreakdown::new(period)));
+    }
+    if args.trends {
+        analyses.push(Box::new(DomainTrends::default()));
+    }
+    if let Some(window) = args.compare_periods {
+        analyses.push(Box::new(PeriodComparison::new(Utc::now(), window)));
+    }
+    if args.streaks {
+        analyses.push(Box::new(DomainStreaks::default()));
+    }
+    if args.novelty {
+        analyses.push(Box::new(DomainNovelty::default()));
+    }
+    if matches!(args.sort, SortBy::FirstVisit | SortBy::LastVisit) || args.stale.is_some() {
+        analyses.push(Box::new(DomainSpans::default()));
+    }
+    if crate::report::needs_html(args) {
+        analyses.push(Box::new(DomainFrecency::new(Utc::now())));
+    }
+    if let Some(minutes) = args.sessions {
+        analyses.push(Box::new(SessionStats::new(chrono::Duration::minutes(
+            minutes.into(),
+        ))));
+    }
+    if args.time_spent {
+        if schema.queries.visit_duration_column.is_some() {
+            analyses.push(Box::new(TimeSpent::default()));
+        } else {
+            warn!(
+                action = "configure",
+                component = "time_spent",
+                browser = ?browser,
+                "Browser does not record visit durations; skipping time spent"
+            );
+        }
+    }
+    if args.transitions {
+        analyses.push(Box::new(TransitionStats::default()));
+    }
+    if args.nav_graph || args.referrers.is_some() {
+        analyses.push(Box::new(NavigationAnalysis::default()));
+    }
+    if let Some(categories) = categories.filter(|_| args.category_hours) {
+        analyses.push(Box::new(CategoryHoursAnalysis::new(categories.clone())));
+    }
+    analyses
+}
@@ -0,0 +1,417 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::analysis::{Analysis, AnalysisFactory};
+use crate::args::{Args, Browser, Weight};
+use crate::cancel::CancellationToken;
+use crate::domain::{DomainFilter, Normalizer, ValidationPolicy};
+use crate::error::HistoreeError;
+use crate::lists::DomainLists;
+use crate::patterns::{self, PatternSet};
+use crate::progress::Progress;
+use crate::sqlite::{self, HistoryNotFound};
+use crate::stats::{AnalysisResult, Sampling};
+use crate::timezone::Timezone;
+
+/// Visits buffered ahead of the consumer by `Analyzer::visits`
+const VISIT_BUFFER: usize = 1024;
+
+/// A configured analysis for library use; it never prints, draws progress, reads
+/// pattern, alias or domain list files it was not given or, unless enabled with
+/// `AnalyzerBuilder::cache`, touches the result cache
+#[derive(Clone)]
+pub struct Analyzer {
+    args: Args,
+    normalizer: Normalizer,
+    lists: DomainLists,
+    analyses: Vec<AnalysisFactory>,
+    cancel: CancellationToken,
+}
+
+impl std::fmt::Debug for Analyzer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Analyzer")
+            .field("args", &self.args)
+            .field("normalizer", &self.normalizer)
+            .field("lists", &self.lists)
+            .field("analyses", &self.analyses.len())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl Analyzer {
+    pub fn builder() -> AnalyzerBuilder {
+        AnalyzerBuilder::default()
+    }
+
+    /// Copies the history database, analyzes it and returns the typed result
+    pub fn run(&self) -> Result<AnalysisResult, HistoreeError> {
+        Ok(crate::browser::analyze_with(
+            &self.args,
+            self.normalizer.clone(),
+            self.lists.clone(),
+            self.analyses.clone(),
+            Progress::new(false),
+            &self.cancel,
+            Instant::now(),
+        )?)
+    }
+
+    /// Token that stops a running analysis with a `Cancelled` error
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Streams visits in the date range, oldest first per browser, without aggregating them;
+    /// with `all_browsers`, browsers without history are skipped
+    pub fn visits(&self) -> Result<Visits, HistoreeError> {
+        let browsers = self.args.browsers();
+        let filter = DomainFilter::new(&self.args.include, &self.args.exclude)?;
+        let (sender, receiver) = mpsc::sync_channel(VISIT_BUFFER);
+        let stop = CancellationToken::new();
+        let worker = {
+            let (analyzer, stop) = (self.clone(), stop.clone());
+            std::thread::spawn(move || {
+                for browser in browsers {
+                    match analyzer.stream_visits(browser, &filter, &sender, &stop) {
+                        Ok(()) => {}
+                        Err(_) if stop.is_cancelled() => return,
+                        Err(e) if analyzer.args.all_browsers && e.is::<HistoryNotFound>() => {}
+                        Err(e) => {
+                            let _ = sender.send(Err(e.into()));
+                            return;
+                        }
+                    }
+                }
+            })
+        };
+        Ok(Visits {
+            receiver: Some(receiver),
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    fn stream_visits(
+        &self,
+        browser: Browser,
+        filter: &DomainFilter,
+        sender: &SyncSender<Result<Visit, HistoreeError>>,
+        stop: &CancellationToken,
+    ) -> anyhow::Result<()> {
+        let (temp_database, family) = crate::browser::history_database(&browser, &self.args)?;
+        let conn = Connection::open(temp_database.path())?;
+        let schema = crate::schema::detect_schema(&conn, family)?;
+        let schemes = self.args.scheme_filter();
+        let visit_filter =
+            sqlite::VisitFilter::from_dates(self.args.since, self.args.until, self.args.zone())
+                .schemes(&schemes)
+                .collapse_redirects(self.args.collapse_redirects);
+        let sampling = self.args.sample.map(|rate| Sampling {
+            rate,
+            seed: self.args.seed.unwrap_or_default(),
+        });
+
+        sqlite::for_each_visit(&conn, &schema, &visit_filter, sampling, stop, |visit| {
+            if self.cancel.is_cancelled() {
+                let _ = sender.send(Err(HistoreeError::Cancelled));
+                stop.cancel();
+                return;
+            }
+            let domain = url::Url::parse(&visit.url)
+                .ok()
+                .and_then(|url| self.normalizer.canonical_url(&url));
+            if !filter.allows(domain.as_deref()) {
+                return;
+            }
+            let visit = Visit {
+                url: visit.url,
+                domain,
+                timestamp: visit.timestamp,
+                browser,
+            };
+            // The receiver is gone once the consumer drops the iterator
+            if sender.send(Ok(visit)).is_err() {
+                stop.cancel();
+            }
+        })?;
+        Ok(())
+    }
+}
+
+/// One history visit yielded by `Analyzer::visits`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Visit {
+    pub url: String,
+    /// Normalized domain, or `None` for hosts that are not counted (IPs, invalid hosts)
+    pub domain: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub browser: Browser,
+}
+
+/// Iterator over visits read on a background thread; dropping it stops the reading
+/// and removes the temporary database copy
+#[derive(Debug)]
+pub struct Visits {
+    receiver: Option<Receiver<Result<Visit, HistoreeError>>>,
+    stop: CancellationToken,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Iterator for Visits {
+    type Item = Result<Visit, HistoreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for Visits {
+    fn drop(&mut self) {
+        self.stop.cancel();
+        // Dropping the receiver unblocks a worker waiting on a full buffer
+        drop(self.receiver.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Options for an `Analyzer`, starting from the command-line defaults
+#[derive(Clone)]
+pub struct AnalyzerBuilder {
+    args: Args,
+    patterns: Option<PathBuf>,
+    aliases: Option<PathBuf>,
+    ignore_file: Option<PathBuf>,
+    pin_file: Option<PathBuf>,
+    analyses: Vec<AnalysisFactory>,
+    cancel: CancellationToken,
+}
+
+impl std::fmt::Debug for AnalyzerBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnalyzerBuilder")
+            .field("args", &self.args)
+            .field("patterns", &self.patterns)
+            .field("aliases", &self.aliases)
+            .field("ignore_file", &self.ignore_file)
+            .field("pin_file", &self.pin_file)
+            .field("analyses", &self.analyses.len())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl Default for AnalyzerBuilder {
+    fn default() -> Self {
+        let mut args = Args::defaults();
+        args.no_cache = true;
+        Self {
+            args,
+            patterns: None,
+            aliases: None,
+            ignore_file: None,
+            pin_file: None,
+            analyses: Vec::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+impl AnalyzerBuilder {
+    pub fn browser(mut self, browser: Browser) -> Self {
+        self.args.browser = browser;
+        self.args.all_browsers = false;
+        self
+    }
+
+    /// Analyzes every supported browser and merges the counts
+    pub fn all_browsers(mut self) -> Self {
+        self.args.all_browsers = true;
+        self
+    }
+
+    /// Normalization patterns file (plain, TOML or JSON); none are applied by default
+    pub fn patterns(mut self, path: impl AsRef<Path>) -> Self {
+        self.patterns = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Domain alias file; no aliases are applied by default
+    pub fn aliases(mut self, path: impl AsRef<Path>) -> Self {
+        self.aliases = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Domains left out of the analysis, one per line as in `ignore.txt`; none by default
+    pub fn ignore_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.ignore_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Domains always listed with their rank, one per line as in `pin.txt`; none by default
+    pub fn pin_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.pin_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Only counts visits on or after `since` and on or before `until`
+    pub fn date_range(mut self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> Self {
+        self.args.since = since;
+        self.args.until = until;
+        self
+    }
+
+    /// Zone that days and hours are bucketed in; defaults to the local zone
+    pub fn timezone(mut self, zone: Timezone) -> Self {
+        self.args.utc = false;
+        self.args.timezone = Some(zone.to_string());
+        self
+    }
+
+    pub fn weight(mut self, weight: Weight) -> Self {
+        self.args.weight = weight;
+        self
+    }
+
+    pub fn validation(mut self, validation: ValidationPolicy) -> Self {
+        self.args.validation = validation;
+        self
+    }
+
+    /// Ranks IP addresses, localhost and intranet hosts instead of only counting them
+    pub fn include_private_hosts(mut self, include: bool) -> Self {
+        self.args.include_private_hosts = include;
+        self
+    }
+
+    /// Counts full hostnames such as `mail.google.com` instead of collapsing subdomains
+    pub fn keep_subdomains(mut self, keep: bool) -> Self {
+        self.args.keep_subdomains = keep;
+        self
+    }
+
+    /// Counts `host:port` separately for URLs with an explicit non-default port
+    pub fn keep_ports(mut self, keep: bool) -> Self {
+        self.args.keep_ports = keep;
+        self
+    }
+
+    /// Domain patterns to keep, as accepted by `--include`
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.args.include.push(pattern.to_string());
+        self
+    }
+
+    /// Domain patterns to drop, as accepted by `--exclude`
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.args.exclude.push(pattern.to_string());
+        self
+    }
+
+    pub fn min_visits(mut self, threshold: u32) -> Self {
+        self.args.min_visits = Some(threshold);
+        self
+    }
+
+    /// Analyzes a reproducible sample of history entries
+    pub fn sample(mut self, rate: f64, seed: u64) -> Self {
+        self.args.sample = Some(rate);
+        self.args.seed = Some(seed);
+        self
+    }
+
+    /// Collects hourly and weekday visit histograms
+    pub fn temporal(mut self, temporal: bool) -> Self {
+        self.args.temporal = temporal;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.args.workers = Some(workers);
+        self
+    }
+
+    /// Reads a Google Takeout BrowserHistory.json instead of a local profile
+    pub fn takeout(mut self, path: impl AsRef<Path>) -> Self {
+        self.args.takeout = Some(path.as_ref().to_path_buf());
+        self.args.browser = Browser::Chrome;
+        self.args.all_browsers = false;
+        self
+    }
+
+    /// Reads URLs from a file of `url` or `timestamp<TAB>url` lines instead of a local profile
+    pub fn url_list(mut self, path: impl AsRef<Path>) -> Self {
+        self.args.from_file = Some(path.as_ref().to_path_buf());
+        self.args.all_browsers = false;
+        self
+    }
+
+    /// Reuses and stores results in the cache directory, as the command line does; off by
+    /// default so runs leave no files behind
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.args.no_cache = !cache;
+        self
+    }
+
+    /// Where the history database is copied before reading; defaults to the temp directory
+    pub fn temp_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.args.temp_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Runs a custom analysis over each browser's visits in the same pass as the built-in
+    /// ones; `factory` is called once per browser analyzed
+    pub fn analysis<A: Analysis + 'static>(
+        mut self,
+        factory: impl Fn() -> A + Send + Sync + 'static,
+    ) -> Self {
+        self.analyses
+            .push(Arc::new(move || Box::new(factory()) as Box<dyn Analysis>));
+        self
+    }
+
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Validates the options and loads the pattern, alias and domain list files
+    pub fn build(self) -> Result<Analyzer, HistoreeError> {
+        crate::utils::validate_args(&self.args)?;
+        let normalizer = Normalizer {
+            patterns: match &self.patterns {
+                Some(path) => patterns::load_domain_patterns(Some(path))?,
+                None => PatternSet::default(),
+            },
+            aliases: match &self.aliases {
+                Some(path) => patterns::load_aliases(Some(path))?,
+                None => Default::default(),
+            },
+            include_private: self.args.include_private_hosts,
+            validation: self.args.validation,
+            keep_subdomains: self.args.keep_subdomains,
+            keep_ports: self.args.keep_ports,
+        };
+        let lists = DomainLists::from_files(
+            self.ignore_file.as_deref(),
+            self.pin_file.as_deref(),
+            &normalizer,
+        )?;
+        Ok(Analyzer {
+            args: self.args,
+            normalizer,
+            lists,
+            analyses: self.analyses,
+            cancel: self.cancel,
+        })
+    }
+}
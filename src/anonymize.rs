@@ -0,0 +1,288 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::args::Args;
+use crate::psl;
+
+/// Hex characters kept from each label's HMAC; 40 bits keeps collisions unlikely
+const PSEUDONYM_HEX_LEN: usize = 10;
+
+const SHA256_INITIAL: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_ROUNDS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 (FIPS 180-4) of `data`
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = SHA256_INITIAL;
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(SHA256_ROUNDS[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, added) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// HMAC-SHA256 (RFC 2104) of `message` under `key`
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+/// Replaces domains with salted HMAC pseudonyms, label by label, so subdomains still
+/// share their parent's pseudonym and public suffixes stay readable
+#[derive(Debug, Clone)]
+pub struct Anonymizer {
+    key: Vec<u8>,
+}
+
+impl Anonymizer {
+    /// Pseudonyms are stable across runs that use the same salt
+    pub fn new(salt: &str) -> Self {
+        Self {
+            key: salt.as_bytes().to_vec(),
+        }
+    }
+
+    /// A fresh random salt, so pseudonyms are only stable within this run
+    pub fn random() -> Self {
+        let mut key = Vec::with_capacity(32);
+        for _ in 0..4 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            hasher.write_u32(std::process::id());
+            key.extend_from_slice(&hasher.finish().to_le_bytes());
+        }
+        Self { key }
+    }
+
+    /// The anonymizer requested by `--anonymize` and `--anonymize-salt`, if any
+    pub(crate) fn for_args(args: &Args) -> Option<Self> {
+        match &args.anonymize_salt {
+            Some(salt) => Some(Self::new(salt)),
+            None => args.anonymize.then(Self::random),
+        }
+    }
+
+    /// Pseudonym for any text, such as a domain label, search term or path
+    pub fn token(&self, text: &str) -> String {
+        hmac_sha256(&self.key, text.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()[..PSEUDONYM_HEX_LEN]
+            .to_string()
+    }
+
+    /// Pseudonym for `domain`, normalized like the domains in reports so a URL's host and
+    /// its domain agree; placeholders such as `(unknown)` are kept as they are
+    pub fn domain(&self, domain: &str) -> String {
+        if domain.starts_with('(') {
+            return domain.to_string();
+        }
+        let domain = crate::domain::normalize_host(domain);
+        let domain = domain.as_ref();
+        if domain.parse::<std::net::IpAddr>().is_ok() {
+            return self.token(domain);
+        }
+        let labels: Vec<&str> = domain.split('.').collect();
+        let suffix_labels = psl::suffix_list()
+            .suffix_labels(domain)
+            .min(labels.len() - 1);
+        let (private, suffix) = labels.split_at(labels.len() - suffix_labels);
+        private
+            .iter()
+            .map(|label| self.token(label))
+            .chain(suffix.iter().map(|label| label.to_string()))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// `url` with its host replaced by the domain pseudonym and its path, query and
+    /// fragment by a single hashed segment
+    pub fn url(&self, url: &str) -> String {
+        let Ok(parsed) = url::Url::parse(url) else {
+            return self.token(url);
+        };
+        let host = parsed.host_str().map(|host| self.domain(host));
+        let rest = &parsed[url::Position::AfterPort..];
+        let mut out = format!("{}://", parsed.scheme());
+        if let Some(host) = host {
+            out.push_str(&host);
+        }
+        out.push('/');
+        if !matches!(rest, "" | "/") {
+            out.push_str(&self.token(rest));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(digest: [u8; 32]) -> String {
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256_matches_fips_180_4_vectors() {
+        assert_eq!(
+            hex(sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(sha256(&[b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn sha256_pads_messages_around_the_block_boundary() {
+        let cases = [
+            (
+                55,
+                "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+            ),
+            (
+                56,
+                "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+            ),
+            (
+                63,
+                "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34",
+            ),
+            (
+                64,
+                "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+            ),
+            (
+                65,
+                "635361c48bb9eab14198e76ea8ab7f1a41685d6ad62aa9146d301d4f17eb0ae0",
+            ),
+        ];
+        for (len, expected) in cases {
+            assert_eq!(hex(sha256(&vec![b'a'; len])), expected, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231_vectors() {
+        assert_eq!(
+            hex(hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(hmac_sha256(&[0xaa; 20], &[0xdd; 50])),
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"
+        );
+        // Keys longer than a block are hashed first
+        assert_eq!(
+            hex(hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn url_hosts_match_their_domain_pseudonyms() {
+        let anonymizer = Anonymizer::new("salt");
+        let domain = anonymizer.domain("example.org");
+        assert!(domain.ends_with(".org"));
+        assert_eq!(anonymizer.domain("EXAMPLE.ORG."), domain);
+        assert_eq!(
+            anonymizer.url("https://EXAMPLE.ORG./a?b=c"),
+            format!("https://{domain}/{}", anonymizer.token("/a?b=c"))
+        );
+        assert_eq!(
+            anonymizer.url("http://example.org"),
+            format!("http://{domain}/")
+        );
+    }
+}
@@ -1,15 +1,33 @@
+use crate::domain::ValidationPolicy;
+use crate::timezone::Timezone;
+#[cfg(feature = "cli")]
 use clap::Parser;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum Browser {
     Chrome,
     Edge,
     Firefox,
+    #[default]
     Vivaldi,
     Zen,
 }
 
+impl Browser {
+    /// Every supported browser, in the order `--all-browsers` analyzes them
+    pub const ALL: [Browser; 5] = [
+        Browser::Chrome,
+        Browser::Edge,
+        Browser::Firefox,
+        Browser::Vivaldi,
+        Browser::Zen,
+    ];
+}
+
 impl std::fmt::Display for Browser {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -22,55 +40,1026 @@ impl std::fmt::Display for Browser {
     }
 }
 
-#[derive(Parser, Debug)]
-#[command(
+/// How log lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum LogFormat {
+    /// Human-readable lines with a local timestamp
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Most verbose level of log lines that are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+/// Output format for analysis results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Format {
+    #[default]
+    Text,
+    /// Standalone HTML report
+    #[cfg(feature = "html-report")]
+    Html,
+}
+
+/// How each history entry contributes to a domain's count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Weight {
+    /// Count each distinct URL once
+    Unique,
+    /// Count every recorded visit
+    #[default]
+    Visits,
+    /// Use the browser's all-time per-URL visit counter
+    VisitCount,
+    /// Count only visits where the address was typed
+    Typed,
+}
+
+impl Weight {
+    /// Unit used when printing counts
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Weight::Unique => "URLs",
+            Weight::Visits | Weight::VisitCount => "visits",
+            Weight::Typed => "typed visits",
+        }
+    }
+}
+
+impl std::fmt::Display for Weight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Weight::Unique => write!(f, "unique"),
+            Weight::Visits => write!(f, "visits"),
+            Weight::VisitCount => write!(f, "visit-count"),
+            Weight::Typed => write!(f, "typed"),
+        }
+    }
+}
+
+/// Order of the domain list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortBy {
+    /// Most visited first
+    #[default]
+    Count,
+    /// Alphabetical by domain
+    Alpha,
+    /// Earliest first visit first, a timeline of discovering sites
+    FirstVisit,
+    /// Most recent last visit first
+    LastVisit,
+}
+
+impl std::fmt::Display for SortBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortBy::Count => write!(f, "count"),
+            SortBy::Alpha => write!(f, "name"),
+            SortBy::FirstVisit => write!(f, "first visit"),
+            SortBy::LastVisit => write!(f, "last visit"),
+        }
+    }
+}
+
+/// When text output uses ANSI colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// What the top/bottom rankings are made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RankBy {
+    #[default]
+    Domains,
+    /// Individual pages, with their titles
+    Urls,
+    /// Top-level domains, with country-code TLDs listed by country
+    Tld,
+}
+
+/// Calendar period used to group visits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Period {
+    Day,
+    Month,
+    Year,
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Period::Day => write!(f, "day"),
+            Period::Month => write!(f, "month"),
+            Period::Year => write!(f, "year"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(not(feature = "cli"), derive(Default))]
+#[cfg_attr(feature = "cli", command(
     name = "historee",
     about = "Analyze browser history to find unique domains and their visit counts",
     version,
     long_about = None
-)]
+))]
 pub struct Args {
+    #[cfg(feature = "cli")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Browser to analyze
-    #[arg(short, long, default_value = "vivaldi")]
+    #[cfg_attr(feature = "cli", arg(short, long, default_value = "vivaldi"))]
     pub browser: Browser,
 
+    /// Browser profile to read: a Chromium profile directory such as `Profile 1`, or a
+    /// Firefox profile name; defaults to the browser's default profile
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PROFILE"))]
+    pub profile: Option<String>,
+
     /// Analyze all supported browsers
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub all_browsers: bool,
 
+    /// Leave a browser out of --all-browsers (repeatable)
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long = "exclude-browser",
+            value_name = "BROWSER",
+            requires = "all_browsers"
+        )
+    )]
+    pub exclude_browsers: Vec<Browser>,
+
     /// Number of top domains to display
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub top: Option<usize>,
 
+    /// Display as many top domains as it takes to cover PERCENT of visits, instead of a
+    /// fixed --top
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "PERCENT", conflicts_with = "top")
+    )]
+    pub top_share: Option<f64>,
+
     /// Number of bottom domains to display
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub bottom: Option<usize>,
 
-    /// Path to custom domain pattern file
-    #[arg(short, long)]
+    /// How to weight domain counts
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = Weight::Visits))]
+    pub weight: Weight,
+
+    /// List the most visited paths under one domain
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DOMAIN"))]
+    pub domain: Option<String>,
+
+    /// Group --domain paths by their first N segments (e.g. 2 for /owner/repo)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", requires = "domain"))]
+    pub path_depth: Option<usize>,
+
+    /// Rank domains or individual pages in the top/bottom lists
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = RankBy::Domains))]
+    pub by: RankBy,
+
+    /// Only include visits on or after this date (YYYY-MM-DD)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DATE"))]
+    pub since: Option<chrono::NaiveDate>,
+
+    /// Only include visits on or before this date (YYYY-MM-DD)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DATE"))]
+    pub until: Option<chrono::NaiveDate>,
+
+    /// Print dates as iso, long, short or a strftime string such as "%d.%m.%Y"
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FORMAT", value_parser = crate::utils::DateFormat::parse))]
+    pub date_format: Option<crate::utils::DateFormat>,
+
+    /// Bucket days and hours in UTC instead of the local time zone
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub utc: bool,
+
+    /// Bucket days and hours in this zone: a name such as America/New_York, or an offset such as +05:30
+    #[cfg_attr(feature = "cli", arg(long, value_name = "TZ", value_parser = crate::timezone::parse_name, conflicts_with = "utc"))]
+    pub timezone: Option<String>,
+
+    /// Count only the final destination of redirect chains
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub collapse_redirects: bool,
+
+    /// Analyze a random fraction of history entries (0 < RATE <= 1)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "RATE"))]
+    pub sample: Option<f64>,
+
+    /// Seed for --sample, to make sampled runs reproducible
+    #[cfg_attr(feature = "cli", arg(long, requires = "sample"))]
+    pub seed: Option<u64>,
+
+    /// Show how much of each domain's traffic lands on its homepage
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub landing: bool,
+
+    /// Show visit histograms by hour of day and day of week
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub temporal: bool,
+
+    /// Show visit totals and unique domains per period
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_name = "PERIOD"))]
+    pub group_by: Option<Period>,
+
+    /// Order of the top domain list
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = SortBy::Count))]
+    pub sort: SortBy,
+
+    /// Reverse the order chosen with --sort
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub reverse: bool,
+
+    /// Draw bars next to the top domains and a visits-per-month sparkline
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub chart: bool,
+
+    /// Show the domains whose visits grew or declined the most over the date range
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub trends: bool,
+
+    /// Compare domain rankings in the last WINDOW (e.g. 30d) with the WINDOW before it,
+    /// showing how many positions each domain moved
+    #[cfg_attr(feature = "cli", arg(long, value_name = "WINDOW", value_parser = crate::utils::parse_window))]
+    pub compare_periods: Option<chrono::Duration>,
+
+    /// Show the longest and current runs of consecutive days each domain was visited
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub streaks: bool,
+
+    /// Show once frequent domains (see --stale-min) not visited in the last DAYS days
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DAYS"))]
+    pub stale: Option<u32>,
+
+    /// Visits a domain needs to be listed by --stale
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "N", default_value_t = 10, requires = "stale")
+    )]
+    pub stale_min: u32,
+
+    /// Show how many domains each month were visited for the first time versus returning
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub novelty: bool,
+
+    /// Show the most common navigations from one domain to another, following each
+    /// visit's referring visit
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub nav_graph: bool,
+
+    /// Show which other domains most often lead to DOMAIN (search engines, aggregators,
+    /// chat apps), following each visit's referring visit
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DOMAIN"))]
+    pub referrers: Option<String>,
+
+    /// Group visits into sessions split by idle gaps of MINUTES (default 30)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MINUTES", num_args = 0..=1, default_missing_value = "30"))]
+    pub sessions: Option<u32>,
+
+    /// Estimate time spent per domain from recorded visit durations (Chromium browsers)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub time_spent: bool,
+
+    /// Break visits down by how they were initiated (typed, link, reload, redirect, ...)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub transitions: bool,
+
+    /// Report the most frequent search terms
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub searches: bool,
+
+    /// With --searches, also count `q`, `query` and `search` parameters on sites that are
+    /// not known search engines
+    #[cfg_attr(feature = "cli", arg(long, requires = "searches"))]
+    pub generic_searches: bool,
+
+    /// Report download sources, file types and bytes downloaded per month (Chromium browsers)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub downloads: bool,
+
+    /// Report visits per domain per day from the browser's Top Sites counters (Chromium browsers)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub segments: bool,
+
+    /// Rank domains by media watch and listen time from the Media History database (Chromium browsers)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub media: bool,
+
+    /// Hosts-format or domain-list blocklist used to tag tracker and ad domains (repeatable)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub blocklist: Vec<PathBuf>,
+
+    /// Leave blocklisted domains out of the rankings
+    #[cfg_attr(feature = "cli", arg(long, requires = "blocklist"))]
+    pub exclude_blocked: bool,
+
+    /// Domains to leave out of every analysis, one per line, covering subdomains
+    /// [default: ignore.txt in the config directory]
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub ignore_file: Option<PathBuf>,
+
+    /// Count the domains in the ignore list for this run
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_ignore: bool,
+
+    /// Domains to always list with their rank, even outside --top, one per line
+    /// [default: pin.txt in the config directory]
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub pin_file: Option<PathBuf>,
+
+    /// Report the share of visits per category (social, video, development, ...)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub categories: bool,
+
+    /// Report when each category is visited, by hour of day
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub category_hours: bool,
+
+    /// Category mapping file with `category: domain, domain` lines [default: categories.txt,
+    /// else built-in]
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub category_file: Option<PathBuf>,
+
+    /// Report the most frequent words in page titles (restricted to --domain if given)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub keywords: bool,
+
+    /// Path to custom domain pattern file (one regex per line, or .toml/.json rules)
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub patterns: Option<PathBuf>,
 
+    /// Only analyze domains matching this regex, after normalization (repeatable)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "REGEX"))]
+    pub include: Vec<String>,
+
+    /// Leave out domains matching this regex, after normalization (repeatable)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "REGEX"))]
+    pub exclude: Vec<String>,
+
+    /// Drop domains counted fewer than N times, summarizing them as a long tail
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub min_visits: Option<u32>,
+
+    /// URL schemes to analyze, comma-separated, or "all" for every scheme
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            value_name = "SCHEMES",
+            value_delimiter = ',',
+            default_value = "http,https"
+        )
+    )]
+    pub schemes: Vec<String>,
+
+    /// Report how many visits each URL scheme contributed
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub scheme_stats: bool,
+
+    /// How closely hosts must resemble public domain names to be counted
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = ValidationPolicy::Strict))]
+    pub validation: ValidationPolicy,
+
+    /// Report how many distinct hosts each normalization pattern rewrote
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub pattern_stats: bool,
+
+    /// Rank IP addresses, localhost and intranet hosts instead of counting them as removed
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub include_private_hosts: bool,
+
+    /// Count full hostnames such as mail.google.com separately instead of collapsing
+    /// subdomains; only a leading www. is dropped and patterns are not applied
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub keep_subdomains: bool,
+
+    /// Count hosts on an explicit non-default port separately (example.com:8080), for
+    /// telling self-hosted services apart; ports are dropped by default
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub keep_ports: bool,
+
+    /// Nest the top domains' subdomains under their registrable domain
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tree: bool,
+
+    /// Alias rules file with `alias youtu.be -> youtube.com` lines [default: domain_aliases.txt]
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub aliases: Option<PathBuf>,
+
     /// Disable pattern-based domain normalization
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub no_patterns: bool,
 
+    /// Record unreadable profiles and tables as warnings instead of failing the run
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub best_effort: bool,
+
+    /// Always re-read the history database instead of reusing cached domain counts
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_cache: bool,
+
     /// Custom temporary file path for database copy
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub temp_path: Option<PathBuf>,
 
-    /// Enable verbose logging
-    #[arg(short, long)]
+    /// List each domain's all-time count from the browser's per-URL counters next to its
+    /// count over the recent window the browser still keeps visits for
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub retention: bool,
+
+    /// Note in the summary when the all-time counters exceed the kept visits by more than
+    /// PERCENT, a sign the browser has expired older visits
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "PERCENT", default_value_t = 10.0)
+    )]
+    pub retention_warning: f64,
+
+    /// Also read Chromium's `Archived History`, where long-lived profiles keep visits older
+    /// than the browser's retention window
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub include_archived: bool,
+
+    /// When the browser is running, wait up to SECS for it to release its profile lock
+    /// before reading its history through the SQLite backup API
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SECS"))]
+    pub wait_for_unlock: Option<u64>,
+
+    /// Analyze a Google Takeout BrowserHistory.json instead of the browser's own history
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "PATH", conflicts_with = "all_browsers")
+    )]
+    pub takeout: Option<PathBuf>,
+
+    /// Analyze the URLs in a file, one `url` or `timestamp<TAB>url` per line
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATH", conflicts_with_all = ["all_browsers", "takeout", "stdin"]))]
+    pub from_file: Option<PathBuf>,
+
+    /// Analyze URLs read from stdin, in the same format as --from-file
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["all_browsers", "takeout"]))]
+    pub stdin: bool,
+
+    /// List the N most frequent removed hosts for each removal reason (default 5)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "5"))]
+    pub show_removed: Option<usize>,
+
+    /// Look up registration dates of the top N domains via RDAP (default 25)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "25"))]
+    pub rdap_enrich: Option<usize>,
+
+    /// Enable verbose logging (same as --log-level info)
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub verbose: bool,
 
+    /// Most verbose log level to write (default: error, or info with --verbose); RUST_LOG
+    /// directives still apply on top
+    #[cfg_attr(feature = "cli", arg(long, value_enum))]
+    pub log_level: Option<LogLevel>,
+
+    /// Format of log lines
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = LogFormat::Text))]
+    pub log_format: LogFormat,
+
+    /// Append log lines to this file instead of writing them to stderr
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATH"))]
+    pub log_file: Option<PathBuf>,
+
+    /// Print only tab-separated name and count lines, for scripts
+    #[cfg_attr(feature = "cli", arg(short, long, conflicts_with = "verbose"))]
+    pub quiet: bool,
+
+    /// Print how long each analysis phase took to stderr after the report
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub timings: bool,
+
     /// Number of worker threads
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub workers: Option<usize>,
 
-    /// Redact domain names for privacy
-    #[arg(long)]
-    pub redact: bool,
+    /// Output format
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = Format::Text))]
+    pub format: Format,
+
+    /// Render the report through a Handlebars-style template file instead of --format;
+    /// `{{value}}` is HTML-escaped in .html templates
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "FILE", conflicts_with = "format")
+    )]
+    pub template: Option<PathBuf>,
+
+    /// Write the report to this file instead of stdout, replacing it atomically. Repeat to
+    /// write several formats from one analysis: .json and .csv files get domain data, .html
+    /// the HTML report (unless --template is given), anything else --format
+    #[cfg_attr(feature = "cli", arg(short, long, value_name = "PATH"))]
+    pub output: Vec<PathBuf>,
+
+    /// Redact domain names, search terms and keywords for privacy: stars (default),
+    /// tld-only, hash or first-letter
+    #[cfg_attr(feature = "cli", arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "stars"
+    ))]
+    pub redact: Option<crate::utils::RedactionPolicy>,
+
+    /// Replace domains, URLs and terms with salted HMAC pseudonyms in every output format
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub anonymize: bool,
+
+    /// Anonymize with this salt, so pseudonyms stay the same across runs (--anonymize alone
+    /// uses a random salt per run)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SALT"))]
+    pub anonymize_salt: Option<String>,
+
+    /// Show internationalized domains in Unicode instead of punycode (xn--)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub decode_idn: bool,
 
-    /// Initialize domain_patterns.txt with default patterns
-    #[arg(long)]
+    /// Abbreviate large counts (e.g. 1.2M, 45.3k) in text output
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub humanize: bool,
+
+    /// When to color text output; `auto` colors terminals unless NO_COLOR is set
+    #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = ColorChoice::Auto))]
+    pub color: ColorChoice,
+
+    /// Write the default patterns to domain_patterns.txt in the config directory
+    #[cfg_attr(feature = "cli", arg(long))]
     pub init: bool,
 }
+
+impl Args {
+    /// The command-line defaults, as when `historee` runs without arguments
+    pub fn defaults() -> Self {
+        #[cfg(feature = "cli")]
+        let args = Args::parse_from(["historee"]);
+        #[cfg(not(feature = "cli"))]
+        let args = Args {
+            stale_min: 10,
+            schemes: vec!["http".to_string(), "https".to_string()],
+            retention_warning: 10.0,
+            ..Args::default()
+        };
+        args
+    }
+
+    /// The `--log-level`, else `info` with `--verbose` and `error` without
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level.unwrap_or(if self.verbose {
+            LogLevel::Info
+        } else {
+            LogLevel::Error
+        })
+    }
+
+    /// Lowercase schemes to keep, or an empty list when every scheme is analyzed
+    pub fn scheme_filter(&self) -> Vec<String> {
+        if self
+            .schemes
+            .iter()
+            .any(|scheme| scheme.eq_ignore_ascii_case("all"))
+        {
+            return Vec::new();
+        }
+        self.schemes
+            .iter()
+            .map(|scheme| scheme.trim().to_lowercase())
+            .collect()
+    }
+
+    /// Period to aggregate visits by: `--group-by`, else months when `--chart` needs them
+    pub fn period(&self) -> Option<Period> {
+        self.group_by.or(self.chart.then_some(Period::Month))
+    }
+
+    /// Zone that dates are bucketed and printed in
+    pub fn zone(&self) -> Timezone {
+        if self.utc {
+            return Timezone::Utc;
+        }
+        self.timezone
+            .as_deref()
+            .and_then(|tz| Timezone::parse(tz).ok())
+            .unwrap_or_default()
+    }
+
+    /// Zone that dates are bucketed in, for report headings
+    pub fn zone_label(&self) -> String {
+        match (self.utc, &self.timezone) {
+            (true, _) => "UTC".to_string(),
+            (false, Some(name)) => name.clone(),
+            (false, None) => "local time".to_string(),
+        }
+    }
+
+    /// The `--output` file of commands that write a single document
+    pub fn single_output(&self) -> anyhow::Result<Option<&std::path::Path>> {
+        match self.output.as_slice() {
+            [] => Ok(None),
+            [path] => Ok(Some(path)),
+            _ => {
+                anyhow::bail!("Only the report can be written to several files; give --output once")
+            }
+        }
+    }
+
+    /// How long to wait for a running browser to release its profile lock
+    pub fn unlock_wait(&self) -> Option<std::time::Duration> {
+        self.wait_for_unlock.map(std::time::Duration::from_secs)
+    }
+
+    /// Browsers to read: `--browser`, or every supported one not excluded with
+    /// `--exclude-browser` when `--all-browsers` is set
+    pub fn browsers(&self) -> Vec<Browser> {
+        if self.all_browsers {
+            Browser::ALL
+                .into_iter()
+                .filter(|browser| !self.exclude_browsers.contains(browser))
+                .collect()
+        } else {
+            vec![self.browser]
+        }
+    }
+
+    /// What the analysis read, for report headings
+    pub fn source_label(&self) -> String {
+        if self.all_browsers && !self.exclude_browsers.is_empty() {
+            let excluded: Vec<String> = self
+                .exclude_browsers
+                .iter()
+                .map(|browser| browser.to_string())
+                .collect();
+            format!("All Browsers except {}", excluded.join(", "))
+        } else if self.all_browsers {
+            "All Browsers".to_string()
+        } else if self.from_file.is_some() || self.stdin {
+            "URL List".to_string()
+        } else {
+            self.browser.to_string()
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum Command {
+    /// Manage a scheduled report run by the platform scheduler
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    Schedule(ScheduleCommand),
+    /// Interactively choose defaults and write the config file
+    Setup,
+    /// Inspect domain normalization patterns
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    Patterns(PatternsCommand),
+    /// Manage the defaults config file
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    Config(ConfigCommand),
+    /// Check each browser's history location, permissions, schema and locks
+    Doctor,
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        #[cfg_attr(feature = "cli", arg(value_enum))]
+        shell: Shell,
+    },
+    /// Print a man page in roff format
+    Manpage,
+    /// Save analyses for later comparison
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    Snapshot(SnapshotCommand),
+    /// Show new, vanished and changed domains between two snapshots
+    Diff {
+        /// Earlier snapshot: a name from `snapshot list` or a file path
+        before: String,
+        /// Later snapshot: a name from `snapshot list` or a file path
+        after: String,
+    },
+    /// Combine snapshots from several machines into one report with a column per source
+    Merge {
+        /// Snapshots to merge: names from `snapshot list` or file paths; give at least two
+        #[cfg_attr(feature = "cli", arg(required = true, num_args = 2..))]
+        snapshots: Vec<String>,
+        /// Also save the merged counts as a snapshot with this name
+        #[cfg_attr(feature = "cli", arg(long, value_name = "NAME"))]
+        save: Option<String>,
+    },
+    /// Compare the most recent complete period with the one before: visits, new domains,
+    /// biggest risers and fallers
+    Digest {
+        /// Length of the compared periods
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = DigestPeriod::Week))]
+        period: DigestPeriod,
+        /// Output format; markdown suits reports mailed from cron
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = DigestFormat::Text))]
+        format: DigestFormat,
+    },
+    /// Summarize a year of browsing: top domains and searches, busiest day, longest streak,
+    /// late-night share and new discoveries
+    Wrapped {
+        /// Calendar year to summarize (default: the current year so far)
+        #[cfg_attr(feature = "cli", arg(long))]
+        year: Option<i32>,
+    },
+    /// Show per-browser domain counts side by side, with domains exclusive to each
+    Compare {
+        /// Browser to include; give at least two
+        #[cfg_attr(
+            feature = "cli",
+            arg(short, long = "browser", value_enum, required = true)
+        )]
+        browsers: Vec<Browser>,
+    },
+    /// Export raw history data for use in other tools
+    #[cfg_attr(feature = "cli", command(subcommand))]
+    Export(ExportCommand),
+    /// Run a read-only SQL query against a copy of the history database
+    Query {
+        /// SQL statement, e.g. "SELECT url, visit_count FROM urls LIMIT 10"
+        sql: String,
+        /// Browser whose history is queried (default: the global --browser)
+        #[cfg_attr(feature = "cli", arg(short, long, value_enum))]
+        browser: Option<Browser>,
+        /// Output format
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = QueryFormat::Table))]
+        format: QueryFormat,
+    },
+    /// Find visits whose URL or title contains a term, with matches per domain
+    Search {
+        /// Text to look for in URLs and titles, ignoring case
+        term: String,
+        /// Most recent matching visits to list
+        #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value_t = 50))]
+        limit: usize,
+    },
+    /// Show the earliest visit to a domain, with its URL, browser and profile, and the
+    /// visits since
+    First {
+        /// Domain to look up; normalized like the analyzed domains
+        domain: String,
+    },
+    /// Show one domain's visits, rank, first and last visit, monthly sparkline, top paths
+    /// and top referring domains
+    Show {
+        /// Domain to describe, normalized like history hosts (e.g. www.github.com)
+        domain: String,
+    },
+    /// Re-analyze on an interval and report new domains and count increases
+    Watch {
+        /// Time between analyses, e.g. 30m, 1h or 1d
+        #[cfg_attr(feature = "cli", arg(long, default_value = "1h", value_parser = crate::utils::parse_interval))]
+        interval: std::time::Duration,
+        /// Where to send changes: stdout, notify, file:<path> or webhook:<url> (default: stdout)
+        #[cfg_attr(feature = "cli", arg(long = "sink", value_name = "SINK"))]
+        sinks: Vec<crate::sink::SinkSpec>,
+        /// Rewrite this file with Prometheus metrics after every run
+        #[cfg_attr(feature = "cli", arg(long, value_name = "PATH"))]
+        metrics_file: Option<PathBuf>,
+    },
+    /// Serve analyses as JSON at /summary, /domains?top=N and /trends?by=PERIOD,
+    /// and Prometheus metrics at /metrics
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Address to listen on
+        #[cfg_attr(feature = "cli", arg(long, default_value = "127.0.0.1:8080"))]
+        bind: String,
+        /// How long an analysis is reused before history is read again
+        #[cfg_attr(feature = "cli", arg(long, default_value = "5m", value_parser = crate::utils::parse_interval))]
+        cache_ttl: std::time::Duration,
+        /// Let pages from this origin (e.g. http://localhost:3000) read responses; without
+        /// it browsers keep other sites from reading your history
+        #[cfg_attr(feature = "cli", arg(long, value_name = "ORIGIN"))]
+        allow_origin: Option<String>,
+    },
+    /// Browse domains in an interactive table with search, sorting, monthly sparklines
+    /// and top paths; switch browsers and time windows with b and w
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum SnapshotCommand {
+    /// Analyze history and save the domain counts to the data directory
+    Save {
+        /// Snapshot name (default: the current date and time)
+        #[cfg_attr(feature = "cli", arg(long))]
+        name: Option<String>,
+    },
+    /// List saved snapshots, oldest first
+    List,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum ExportCommand {
+    /// Write every visit in the date window with its timestamp, URL, domain, title and browser
+    Visits {
+        /// Export format
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = ExportFormat::Jsonl))]
+        format: ExportFormat,
+    },
+    /// Write each domain with its count, most visited first
+    Domains {
+        /// Export format
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = ExportFormat::Jsonl))]
+        format: ExportFormat,
+    },
+    /// Write visits per category and local hour of day, one row per pair
+    CategoryHours {
+        /// Export format
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = TableFormat::Jsonl))]
+        format: TableFormat,
+    },
+    /// Write the domain-to-domain navigation graph for Graphviz or Gephi
+    Graph {
+        /// Export format
+        #[cfg_attr(feature = "cli", arg(long, value_enum, default_value_t = GraphFormat::Dot))]
+        format: GraphFormat,
+        /// Leave out hops with fewer navigations than this
+        #[cfg_attr(feature = "cli", arg(long, value_name = "N", default_value_t = 1))]
+        min_weight: u32,
+    },
+    /// Write the JSON Schema of historee's JSON output, for validating it downstream
+    Schema,
+    /// Append this run's domain counts, daily totals and metadata to a SQLite database
+    Sqlite {
+        /// Database to create or append to
+        #[cfg_attr(feature = "cli", arg(default_value = "historee.sqlite"))]
+        database: PathBuf,
+    },
+}
+
+/// Formats for `export visits`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ExportFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Columnar Parquet file, written with --output
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// Formats for `export category-hours`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum TableFormat {
+    /// One JSON object per line
+    Jsonl,
+    Csv,
+}
+
+/// Formats for `export graph`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    Graphml,
+}
+
+/// Period lengths for `digest`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DigestPeriod {
+    /// Yesterday against the day before
+    Day,
+    /// Last Monday-to-Sunday week against the week before
+    Week,
+    /// Last calendar month against the month before
+    Month,
+}
+
+/// Formats for `digest`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DigestFormat {
+    Text,
+    Markdown,
+}
+
+/// Formats for `query`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum QueryFormat {
+    /// Aligned columns
+    Table,
+    Csv,
+    /// One JSON object per row
+    Jsonl,
+}
+
+/// Shells with generated completion scripts
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum ConfigCommand {
+    /// Write a commented config file listing every setting
+    Init {
+        /// Overwrite an existing config file
+        #[cfg_attr(feature = "cli", arg(long))]
+        force: bool,
+    },
+    /// Print the location of the config file
+    Path,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum PatternsCommand {
+    /// Show each normalization step for a domain or URL
+    Test {
+        /// Domain or URL to normalize
+        domain: String,
+    },
+}
+
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Subcommand))]
+pub enum ScheduleCommand {
+    /// Install a user-level scheduled task running historee with the given arguments
+    Install {
+        /// Run once a week (default)
+        #[cfg_attr(feature = "cli", arg(long, conflicts_with = "daily"))]
+        weekly: bool,
+
+        /// Run once a day
+        #[cfg_attr(feature = "cli", arg(long))]
+        daily: bool,
+
+        /// Arguments passed to historee on each run, after `--`
+        #[cfg_attr(feature = "cli", arg(last = true))]
+        report_args: Vec<String>,
+    },
+    /// Remove the scheduled task installed by `schedule install`
+    Remove,
+}
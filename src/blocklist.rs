@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Entries in hosts files that name the machine itself rather than a blocked domain
+const HOSTS_BOILERPLATE: [&str; 6] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// Tracker and ad domains read from hosts-format or plain domain-list files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blocklist {
+    domains: HashSet<String>,
+}
+
+impl Blocklist {
+    /// Parses `0.0.0.0 ads.example.com` hosts lines and bare `ads.example.com` lines
+    pub fn parse(content: &str) -> Self {
+        let mut domains = HashSet::new();
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut fields = line.split_whitespace();
+            let Some(first) = fields.next() else {
+                continue;
+            };
+            let hosts: Vec<&str> = if first.parse::<std::net::IpAddr>().is_ok() {
+                fields.collect()
+            } else {
+                vec![first]
+            };
+            for host in hosts {
+                let host = host.trim_end_matches('.').to_lowercase();
+                if !HOSTS_BOILERPLATE.contains(&host.as_str()) {
+                    domains.insert(host);
+                }
+            }
+        }
+        Self { domains }
+    }
+
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut blocklist = Blocklist::default();
+        for path in paths {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read blocklist {path:?}"))?;
+            let parsed = Self::parse(&content);
+            info!(action = "loaded", component = "blocklist", domain_count = parsed.domains.len(), file_path = ?path, "Loaded blocklist");
+            blocklist.domains.extend(parsed.domains);
+        }
+        Ok(blocklist)
+    }
+
+    /// Whether `domain` or one of its parent domains is listed
+    pub fn is_blocked(&self, domain: &str) -> bool {
+        let mut candidate = domain;
+        loop {
+            if self.domains.contains(candidate) {
+                return true;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => candidate = parent,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Visited domains that matched the blocklist, against the totals they were drawn from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockedStats {
+    pub domain_counts: HashMap<String, u32>,
+    pub total_domains: u32,
+    pub total_count: u32,
+}
+
+impl BlockedStats {
+    pub fn from_counts(blocklist: &Blocklist, domain_counts: &HashMap<String, u32>) -> Self {
+        Self {
+            domain_counts: domain_counts
+                .iter()
+                .filter(|(domain, _)| blocklist.is_blocked(domain))
+                .map(|(domain, count)| (domain.clone(), *count))
+                .collect(),
+            total_domains: domain_counts.len() as u32,
+            total_count: domain_counts.values().sum(),
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.domain_counts.values().sum()
+    }
+
+    /// Most visited blocked domains, ties broken alphabetically
+    pub fn top(&self, n: usize) -> Vec<(String, u32)> {
+        let mut domains: Vec<(String, u32)> = self
+            .domain_counts
+            .iter()
+            .map(|(domain, count)| (domain.clone(), *count))
+            .collect();
+        domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        domains.truncate(n);
+        domains
+    }
+}
@@ -1,22 +1,56 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use anyhow::{Context, Result};
+use chrono::Utc;
 use rusqlite::Connection;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use tracing::{info, warn};
+use tracing::{info, info_span, warn};
 
-use crate::{args::Browser, patterns, sqlite, stats::AnalysisResult, Args};
+pub use crate::sqlite::HistoryNotFound;
+
+use crate::analysis::{self, AnalysisFactory, Report, VisitEvent};
+use crate::anonymize::Anonymizer;
+use crate::blocklist::{BlockedStats, Blocklist};
+use crate::cache::{CachedAggregates, Lookup, ResultCache, Resumed};
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::categories::{Categories, CategoryBreakdown, CategoryHours};
+use crate::domain::{DomainFilter, HostClass, Normalizer};
+use crate::downloads::DownloadStats;
+use crate::keywords::TitleKeywords;
+use crate::lists::DomainLists;
+use crate::media::MediaStats;
+use crate::navigation::{DomainReferrers, NavigationGraph};
+use crate::progress::Progress;
+use crate::schema::{self, Schema, SchemaFamily};
+use crate::search::SearchTerms;
+use crate::segments::SegmentUsage;
+use crate::stats::{
+    AnalysisResult, DateRange, DomainPaths, Normalization, Page, PageStats, Provenance, Retention,
+    Sampling, SchemeCounts, SkippedBrowser, Timings, TransitionStats, VisitTotals,
+};
+use crate::temporal::{
+    DomainFrecency, DomainNovelty, DomainSpans, DomainStreaks, DomainTrends, PeriodBreakdown,
+    PeriodComparison, RankMovement, SessionStats, TemporalStats, TimeSpent,
+};
+use crate::tld::TldBreakdown;
+use crate::{
+    args::{Args, Browser, RankBy, SortBy, Weight},
+    import, patterns, rdap, sqlite,
+    utils::DateFormat,
+};
+
+/// Days of Top Sites counters listed in the text report
+const SEGMENT_DAYS: usize = 14;
 
 /// Trait for browser-specific operations
 pub trait BrowserHandler {
     fn get_history_path(&self) -> Result<std::path::PathBuf>;
-    fn get_date_range(&self, conn: &Connection) -> Result<(String, String, i64)>;
-    fn extract_domains(
-        &self,
-        conn: &Connection,
-        patterns: &[regex::Regex],
-        workers: Option<usize>,
-    ) -> Result<crate::stats::DomainStats>;
+    fn schema_family(&self) -> SchemaFamily;
+
+    fn detect_schema(&self, conn: &Connection) -> Result<Schema> {
+        schema::detect_schema(conn, self.schema_family())
+    }
 }
 
 impl BrowserHandler for Browser {
@@ -28,37 +62,382 @@ impl BrowserHandler for Browser {
         }
     }
 
-    fn get_date_range(&self, conn: &Connection) -> Result<(String, String, i64)> {
+    fn schema_family(&self) -> SchemaFamily {
         match self {
-            Browser::Firefox | Browser::Zen => sqlite::get_firefox_date_range(conn),
-            _ => sqlite::get_date_range(conn),
+            Browser::Firefox | Browser::Zen => SchemaFamily::Firefox,
+            _ => SchemaFamily::Chromium,
         }
     }
+}
 
-    fn extract_domains(
-        &self,
-        conn: &Connection,
-        patterns: &[regex::Regex],
-        workers: Option<usize>,
-    ) -> Result<crate::stats::DomainStats> {
-        match self {
-            Browser::Firefox | Browser::Zen => {
-                sqlite::extract_domains_from_firefox_urls(conn, patterns, workers)
-            }
-            _ => sqlite::extract_domains_from_urls(conn, patterns, workers),
+/// Settings resolved once per run and shared by every analyzed browser
+struct RunContext {
+    normalizer: Normalizer,
+    /// Analyses added by library callers, run alongside the built-in ones
+    analyses: Vec<AnalysisFactory>,
+    /// Domain categories, loaded for `--categories` and `--category-hours`
+    categories: Option<Categories>,
+    filter: DomainFilter,
+    sampling: Option<Sampling>,
+    cancel: CancellationToken,
+    progress: Progress,
+}
+
+pub fn analyze_browser_history(args: &Args) -> Result<AnalysisResult> {
+    analyze_browser_history_with_cancel(args, &CancellationToken::new())
+}
+
+/// Like `analyze_browser_history`, but stops with a `Cancelled` error as soon as
+/// `cancel` is triggered; temporary database copies are always removed
+pub fn analyze_browser_history_with_cancel(
+    args: &Args,
+    cancel: &CancellationToken,
+) -> Result<AnalysisResult> {
+    let start = Instant::now();
+    let normalizer = patterns::load_normalizer(args)?;
+    let lists = DomainLists::load(args, &normalizer)?;
+    analyze_with(
+        args,
+        normalizer,
+        lists,
+        Vec::new(),
+        Progress::for_args(args),
+        cancel,
+        start,
+    )
+}
+
+/// Runs an analysis with already resolved normalization rules and domain lists; `start`
+/// is when setup began
+pub(crate) fn analyze_with(
+    args: &Args,
+    normalizer: Normalizer,
+    lists: DomainLists,
+    analyses: Vec<AnalysisFactory>,
+    progress: Progress,
+    cancel: &CancellationToken,
+    start: Instant,
+) -> Result<AnalysisResult> {
+    // Resolve the seed once so every browser in a multi-browser run shares it
+    let sampling = args.sample.map(|rate| Sampling {
+        rate,
+        seed: args
+            .seed
+            .unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64),
+    });
+
+    let categories = if args.categories || args.category_hours {
+        Some(Categories::load(args.category_file.as_deref())?)
+    } else {
+        None
+    };
+    let ctx = RunContext {
+        normalizer,
+        analyses,
+        categories,
+        filter: DomainFilter::new(&args.include, &args.exclude)?,
+        sampling,
+        cancel: cancel.clone(),
+        progress,
+    };
+    info!(
+        action = "configure",
+        component = "normalization",
+        fingerprint = ctx.normalizer.fingerprint(),
+        "Normalization rules resolved"
+    );
+    let setup_time = start.elapsed();
+
+    let mut result = if args.all_browsers {
+        analyze_all_browsers(args, &ctx)?
+    } else {
+        let mut warnings = Vec::new();
+        soften(
+            analyze_single_browser(&args.browser, args, &ctx),
+            &format!("analyzing {}", args.browser),
+            args,
+            &mut warnings,
+        )?
+        .unwrap_or_else(|| empty_result(args, &ctx, warnings))
+    };
+
+    let post_start = Instant::now();
+    // First, so no later step or output sees the ignored domains
+    let ignored: HashSet<String> = result
+        .stats
+        .domain_counts
+        .keys()
+        .filter(|domain| lists.ignored.is_blocked(domain))
+        .cloned()
+        .collect();
+    if !ignored.is_empty() {
+        info!(
+            action = "filter",
+            component = "ignore_list",
+            domain_count = ignored.len(),
+            "Left out ignored domains"
+        );
+        result.remove_domains(&ignored);
+    }
+    result.pinned = lists.pinned;
+
+    // Categorize the merged counts before the long tail is dropped, so shares stay complete
+    if let Some(categories) = ctx.categories.as_ref().filter(|_| args.categories) {
+        result.categories = Some(CategoryBreakdown::from_counts(
+            categories,
+            &result.stats.domain_counts,
+        ));
+    }
+
+    if !args.blocklist.is_empty() {
+        let blocklist = Blocklist::load(&args.blocklist)?;
+        let blocked = BlockedStats::from_counts(&blocklist, &result.stats.domain_counts);
+        if args.exclude_blocked {
+            result.remove_domains(&blocked.domain_counts.keys().cloned().collect());
         }
+        result.blocked = Some(blocked);
+    }
+
+    if let (Some(domain), Some(navigation)) = (&args.referrers, &result.navigation) {
+        let domain = selected_domain(domain, &ctx.normalizer);
+        result.referrers = Some(DomainReferrers {
+            referrers: navigation.referrers(&domain),
+            domain,
+        });
+    }
+
+    // Before the long tail is dropped, so every domain is counted under its TLD
+    if args.by == RankBy::Tld {
+        result.tlds = Some(TldBreakdown::from_counts(&result.stats.domain_counts));
     }
+
+    if let Some(threshold) = args.min_visits {
+        result.apply_min_visits(threshold);
+    }
+
+    if let Some(limit) = args.rdap_enrich {
+        let ages = rdap::enrich(&result.stats.top(limit), cancel);
+        result.domain_ages =
+            soften(ages, "RDAP enrichment", args, &mut result.warnings)?.unwrap_or_default();
+    }
+
+    // Last, so RDAP lookups and blocklists still see the real domains
+    if let Some(anonymizer) = Anonymizer::for_args(args) {
+        result.anonymize(&anonymizer);
+    }
+
+    if let Some(timings) = &mut result.timings {
+        timings.phases.insert(0, ("setup".to_string(), setup_time));
+        timings.record("post-processing", post_start);
+        timings.record("total", start);
+    }
+    if !args.timings {
+        result.timings = None;
+    }
+    Ok(result)
 }
 
-pub fn analyze_browser_history(args: &Args) -> Result<AnalysisResult> {
-    if args.all_browsers {
-        analyze_all_browsers(args)
+fn provenance(args: &Args, ctx: &RunContext) -> Provenance {
+    Provenance {
+        generated_at: Utc::now(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        weight: args.weight,
+        sampling: ctx.sampling,
+        normalization: Normalization {
+            fingerprint: ctx.normalizer.fingerprint(),
+            patterns: ctx
+                .normalizer
+                .patterns
+                .rules()
+                .iter()
+                .map(|p| p.source())
+                .collect(),
+        },
+    }
+}
+
+/// In `--best-effort` mode, records a failed step as a warning instead of aborting the run
+fn soften<T>(
+    result: Result<T>,
+    step: &str,
+    args: &Args,
+    warnings: &mut Vec<String>,
+) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if args.best_effort && !e.is::<Cancelled>() => {
+            warn!(action = "soft_fail", component = "browser_analysis", step, error = %e, "Continuing after failed step");
+            warnings.push(format!("{step}: {e:#}"));
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn empty_result(args: &Args, ctx: &RunContext, warnings: Vec<String>) -> AnalysisResult {
+    AnalysisResult {
+        date_range: None,
+        totals: None,
+        retention: None,
+        stats: crate::stats::DomainStats::default(),
+        provenance: provenance(args, ctx),
+        domain_ages: Vec::new(),
+        temporal: None,
+        periods: None,
+        trends: None,
+        period_comparison: None,
+        streaks: None,
+        novelty: None,
+        spans: None,
+        sessions: None,
+        time_spent: None,
+        transitions: None,
+        searches: None,
+        pages: None,
+        paths: None,
+        schemes: None,
+        downloads: None,
+        segments: None,
+        media: None,
+        navigation: None,
+        pinned: Vec::new(),
+        referrers: None,
+        keywords: None,
+        blocked: None,
+        categories: None,
+        category_hours: None,
+        tlds: None,
+        frecency: None,
+        reports: Vec::new(),
+        warnings,
+        skipped_browsers: Vec::new(),
+        timings: Some(Timings::default()),
+    }
+}
+
+/// Whether the run only needs the per-domain aggregates that `ResultCache` keeps;
+/// anything reading individual visits or URLs has to scan the database
+fn cacheable(args: &Args, ctx: &RunContext) -> bool {
+    !args.no_cache
+        && args.takeout.is_none()
+        && !args.include_archived
+        && args.from_file.is_none()
+        && !args.stdin
+        && ctx.analyses.is_empty()
+        && !analysis::needs_visits(args)
+        && !args.searches
+        && args.by != RankBy::Urls
+        && args.domain.is_none()
+        && !args.scheme_stats
+        && !args.downloads
+        && !args.segments
+        && !args.media
+        && !args.keywords
+        && !args.pattern_stats
+        && !args.retention
+        && args.show_removed.is_none()
+}
+
+/// Canonical form of a domain given on the command line, so it matches extracted domains
+pub(crate) fn selected_domain(domain: &str, normalizer: &Normalizer) -> String {
+    let domain = domain.trim().to_lowercase();
+    // Parsed as a URL so a port or `user@` given with it is handled like in history URLs
+    let url = if domain.contains("://") {
+        url::Url::parse(&domain)
     } else {
-        analyze_single_browser(&args.browser, args)
+        url::Url::parse(&format!("http://{domain}"))
+    };
+    url.ok()
+        .and_then(|url| normalizer.canonical_url(&url))
+        .unwrap_or(domain)
+}
+
+/// Where to copy `browser`'s database; browsers analyzed together each get their own copy
+pub(crate) fn temp_path(browser: &Browser, args: &Args) -> Option<PathBuf> {
+    if !args.all_browsers {
+        return args.temp_path.clone();
+    }
+    let base = args
+        .temp_path
+        .clone()
+        .unwrap_or_else(sqlite::default_temp_path);
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(extension) => format!(
+            "{stem}-{}.{}",
+            browser.to_string().to_lowercase(),
+            extension.to_string_lossy()
+        ),
+        None => format!("{stem}-{}", browser.to_string().to_lowercase()),
+    };
+    Some(base.with_file_name(name))
+}
+
+/// `browser`'s history database, in the `--profile` profile when it is the selected browser
+pub(crate) fn history_path(browser: &Browser, args: &Args) -> Result<std::path::PathBuf> {
+    match args.profile.as_deref().filter(|_| *browser == args.browser) {
+        Some(profile) => sqlite::get_profile_history_path(browser, profile),
+        None => browser.get_history_path(),
+    }
+}
+
+/// Temporary copy of `browser`'s own history database, with its archived history merged in
+/// under `--include-archived`
+pub(crate) fn copy_browser_history(
+    browser: &Browser,
+    args: &Args,
+    temp_path: Option<&Path>,
+) -> Result<sqlite::TempDatabase> {
+    let history = history_path(browser, args)?;
+    let database = sqlite::copy_profile_database(
+        &history,
+        browser.schema_family(),
+        temp_path,
+        args.unlock_wait(),
+    )?;
+    if args.include_archived && browser.schema_family() == SchemaFamily::Chromium {
+        let archived = history.with_file_name(sqlite::ARCHIVED_HISTORY_FILE);
+        if archived.is_file() {
+            sqlite::merge_archived_history(&database, &archived)?;
+        } else {
+            info!(action = "skip", component = "archived_history", browser = ?browser, "No archived history in this profile");
+        }
     }
+    Ok(database)
+}
+
+/// Temporary copy of `browser`'s history database, or of the `--takeout`, `--from-file` or
+/// `--stdin` import in its place, with the schema family to read it as
+pub(crate) fn history_database(
+    browser: &Browser,
+    args: &Args,
+) -> Result<(sqlite::TempDatabase, SchemaFamily)> {
+    let temp_path = temp_path(browser, args);
+    let temp_path = temp_path.as_deref();
+    let database = if let Some(takeout) = &args.takeout {
+        import::takeout_database(takeout, temp_path)?
+    } else if let Some(path) = &args.from_file {
+        if !path.exists() {
+            return Err(HistoryNotFound::new("URL list", path).into());
+        }
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+        import::url_list_database(std::io::BufReader::new(file), temp_path)?
+    } else if args.stdin {
+        import::url_list_database(std::io::stdin().lock(), temp_path)?
+    } else {
+        return Ok((
+            copy_browser_history(browser, args, temp_path)?,
+            browser.schema_family(),
+        ));
+    };
+    Ok((database, SchemaFamily::Chromium))
 }
 
-fn analyze_single_browser(browser: &Browser, args: &Args) -> Result<AnalysisResult> {
+fn analyze_single_browser(
+    browser: &Browser,
+    args: &Args,
+    ctx: &RunContext,
+) -> Result<AnalysisResult> {
     let total_start_time = Instant::now();
     info!(
         action = "start",
@@ -67,25 +446,339 @@ fn analyze_single_browser(browser: &Browser, args: &Args) -> Result<AnalysisResu
         "Starting browser history analysis"
     );
 
-    let history_path = browser.get_history_path()?;
-    let temp_history_path =
-        sqlite::copy_history_database(&history_path, args.temp_path.as_deref())?;
-
-    let patterns = if args.no_patterns {
-        Vec::new()
+    let mut timings = Timings::default();
+    let progress = ctx.progress.bar(&browser.to_string());
+    let cache = if cacheable(args, ctx) {
+        let history = history_path(browser, args)?;
+        ResultCache::new(
+            &browser.to_string(),
+            &history,
+            args,
+            &ctx.normalizer,
+            &ctx.filter,
+            ctx.sampling,
+        )
+        .map_err(|e| {
+            warn!(action = "configure", component = "cache", error = %e, "Result cache unavailable");
+        })
+        .ok()
     } else {
-        patterns::load_domain_patterns(args.patterns.as_deref())?
+        None
+    };
+    let previous = match cache.as_ref().map(ResultCache::lookup) {
+        Some(Lookup::Hit(cached)) => {
+            progress.finish("cached");
+            let mut result = empty_result(args, ctx, Vec::new());
+            result.date_range = cached.date_range;
+            result.stats = cached.stats();
+            result.totals = cached.totals;
+            result.retention = cached.retention;
+            return Ok(result);
+        }
+        // Other weights count each URL once or read per-URL counters, so newer visits
+        // can't simply be added on
+        Some(Lookup::Stale(cached)) if args.weight == Weight::Visits => Some(cached),
+        _ => None,
     };
+    let (temp_database, family) = timings.time("copy", || history_database(browser, args))?;
 
-    let conn = Connection::open(&temp_history_path)?;
+    let conn = Connection::open(temp_database.path())?;
     info!(
         action = "connect",
         component = "database",
         "Connected to database"
     );
 
-    let date_range = browser.get_date_range(&conn)?;
-    let stats = browser.extract_domains(&conn, &patterns, args.workers)?;
+    let schema = schema::detect_schema(&conn, family)?;
+    let schemes = args.scheme_filter();
+    let zone = args.zone();
+    let window = sqlite::VisitFilter::from_dates(args.since, args.until, zone)
+        .schemes(&schemes)
+        .collapse_redirects(args.collapse_redirects);
+    let mut warnings = Vec::new();
+    let resumed = match previous {
+        Some(previous) => timings
+            .time("resume", || {
+                soften(
+                    Resumed::check(&conn, &schema, &window, previous),
+                    "checking cached counts",
+                    args,
+                    &mut warnings,
+                )
+            })?
+            .flatten(),
+        None => None,
+    };
+    let visit_filter = match &resumed {
+        Some(resumed) => window.after(resumed.watermark),
+        None => window,
+    };
+    let date_range = timings
+        .time("date range", || {
+            soften(
+                sqlite::get_date_range(&conn, &schema, &visit_filter),
+                "reading date range",
+                args,
+                &mut warnings,
+            )
+        })?
+        .flatten();
+    let totals = timings.time("totals", || {
+        soften(
+            sqlite::visit_totals(&conn, &schema, &visit_filter, zone),
+            "counting visits",
+            args,
+            &mut warnings,
+        )
+    })?;
+    let retention = timings.time("retention", || {
+        soften(
+            sqlite::retention_totals(&conn, &schema),
+            "comparing visit counters",
+            args,
+            &mut warnings,
+        )
+    })?;
+    let options = sqlite::ExtractOptions {
+        normalizer: &ctx.normalizer,
+        workers: args.workers,
+        weight: args.weight,
+        sampling: ctx.sampling,
+        visit_filter,
+        zone,
+        filter: &ctx.filter,
+        pattern_stats: args.pattern_stats,
+        removed_samples: args.show_removed.is_some(),
+        cancel: &ctx.cancel,
+        progress: Some(progress),
+    };
+    let stats = timings
+        .time("extraction", || {
+            soften(
+                sqlite::extract_domains(&conn, &schema, &options),
+                "extracting domains",
+                args,
+                &mut warnings,
+            )
+        })?
+        .unwrap_or_default();
+    let (date_range, totals, stats) = match resumed {
+        Some(resumed) => resumed.extend(date_range, totals, stats),
+        None => (date_range, totals, stats),
+    };
+    let all_time_counts = match args.weight {
+        _ if !args.retention => None,
+        Weight::VisitCount => Some(stats.domain_counts.clone()),
+        _ => timings
+            .time("all-time counts", || {
+                soften(
+                    sqlite::extract_domains(
+                        &conn,
+                        &schema,
+                        &sqlite::ExtractOptions {
+                            weight: Weight::VisitCount,
+                            pattern_stats: false,
+                            removed_samples: false,
+                            progress: None,
+                            ..options
+                        },
+                    ),
+                    "counting all-time visits",
+                    args,
+                    &mut warnings,
+                )
+            })?
+            .map(|stats| stats.domain_counts),
+    };
+    let retention = retention.map(|retention| Retention {
+        all_time_counts,
+        ..retention
+    });
+    let queries_start = Instant::now();
+    let pages = match args.by {
+        RankBy::Urls => soften(
+            sqlite::extract_pages(&conn, &schema, &options),
+            "extracting pages",
+            args,
+            &mut warnings,
+        )?,
+        RankBy::Domains | RankBy::Tld => None,
+    };
+    let paths = match &args.domain {
+        Some(domain) => soften(
+            sqlite::extract_domain_paths(
+                &conn,
+                &schema,
+                &options,
+                &selected_domain(domain, &ctx.normalizer),
+                args.path_depth,
+            ),
+            "extracting domain paths",
+            args,
+            &mut warnings,
+        )?,
+        None => None,
+    };
+    let scheme_counts = if args.scheme_stats {
+        soften(
+            sqlite::scheme_counts(&conn, &schema, &visit_filter),
+            "counting URL schemes",
+            args,
+            &mut warnings,
+        )?
+    } else {
+        None
+    };
+    let downloads = if !args.downloads {
+        None
+    } else if schema.queries.downloads_query.is_none() {
+        warn!(
+            action = "configure",
+            component = "downloads",
+            browser = ?browser,
+            "Browser does not keep downloads in its history database; skipping downloads"
+        );
+        None
+    } else {
+        soften(
+            sqlite::extract_downloads(&conn, &schema, &options),
+            "extracting downloads",
+            args,
+            &mut warnings,
+        )?
+    };
+    let segments = if !args.segments {
+        None
+    } else if schema.queries.segment_usage_query.is_none() {
+        warn!(
+            action = "configure",
+            component = "segment_usage",
+            browser = ?browser,
+            "Browser does not keep Top Sites counters; skipping segments"
+        );
+        None
+    } else {
+        soften(
+            sqlite::extract_segment_usage(&conn, &schema, &options),
+            "reading segment usage",
+            args,
+            &mut warnings,
+        )?
+    };
+    let media = if !args.media {
+        None
+    } else if schema.family != SchemaFamily::Chromium
+        || args.takeout.is_some()
+        || args.from_file.is_some()
+        || args.stdin
+    {
+        warn!(
+            action = "configure",
+            component = "media_history",
+            browser = ?browser,
+            "Only Chromium browsers keep a Media History database; skipping media"
+        );
+        None
+    } else {
+        let base = temp_path(browser, args).unwrap_or_else(sqlite::default_temp_path);
+        let media_temp = base.with_file_name(format!(
+            "{}-media",
+            base.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        let media = soften(
+            crate::media::extract(&history_path(browser, args)?, &media_temp, &options),
+            "reading media history",
+            args,
+            &mut warnings,
+        )?
+        .flatten();
+        if media.is_none() {
+            warn!(
+                action = "configure",
+                component = "media_history",
+                browser = ?browser,
+                "No Media History database next to the history; skipping media"
+            );
+        }
+        media
+    };
+    let keywords = if args.keywords {
+        let domain = args
+            .domain
+            .as_ref()
+            .map(|domain| selected_domain(domain, &ctx.normalizer));
+        soften(
+            sqlite::extract_title_keywords(&conn, &schema, &options, domain.as_deref()),
+            "extracting title keywords",
+            args,
+            &mut warnings,
+        )?
+    } else {
+        None
+    };
+    timings.record("queries", queries_start);
+
+    let mut analyses = analysis::builtin(args, &schema, browser, ctx.categories.as_ref());
+    if args.searches {
+        let recorded = soften(
+            sqlite::load_search_terms(&conn, &schema),
+            "reading recorded search terms",
+            args,
+            &mut warnings,
+        )?
+        .unwrap_or_default();
+        analyses.push(Box::new(analysis::SearchAnalysis::new(
+            recorded,
+            args.generic_searches,
+        )));
+    }
+    analyses.extend(ctx.analyses.iter().map(|factory| factory()));
+    let needs_domains =
+        !ctx.filter.is_empty() || analyses.iter().any(|analysis| analysis.needs_domains());
+    if !analyses.is_empty() {
+        let visits_start = Instant::now();
+        // Visits repeat URLs heavily, so resolve each URL's domain only once
+        let mut domains: HashMap<i64, Option<String>> = HashMap::new();
+        let streamed = sqlite::for_each_visit(
+            &conn,
+            &schema,
+            &visit_filter,
+            ctx.sampling,
+            &ctx.cancel,
+            |visit| {
+                let domain = if needs_domains {
+                    let domain = domains
+                        .entry(visit.url_id)
+                        .or_insert_with(|| options.domain_of(&visit.url));
+                    if !ctx.filter.allows(domain.as_deref()) {
+                        return;
+                    }
+                    domain.as_deref()
+                } else {
+                    None
+                };
+                let event = VisitEvent {
+                    id: visit.id,
+                    from_visit: visit.from_visit,
+                    url_id: visit.url_id,
+                    url: &visit.url,
+                    timestamp: visit.timestamp,
+                    local: zone.local(visit.timestamp),
+                    domain,
+                    transition: visit.transition,
+                    duration: visit.duration,
+                };
+                for analysis in &mut analyses {
+                    analysis.accumulate(&event);
+                }
+            },
+        );
+        // Partial histograms would be misleading, so drop them all if the stream failed
+        if soften(streamed, "reading visits", args, &mut warnings)?.is_none() {
+            analyses.clear();
+        }
+        timings.record("visits", visits_start);
+    }
 
     info!(
         action = "disconnect",
@@ -93,13 +786,11 @@ fn analyze_single_browser(browser: &Browser, args: &Args) -> Result<AnalysisResu
         "Closing database connection"
     );
     drop(conn);
-
-    // Clean up temporary file
-    if let Err(e) = fs::remove_file(&temp_history_path) {
-        warn!(action = "cleanup", component = "temp_file", error = %e, "Failed to remove temporary file");
-    }
+    drop(temp_database);
 
     let total_time = total_start_time.elapsed();
+    progress.finish("done");
+    timings.phases.push(("analysis".to_string(), total_time));
     info!(
         action = "complete",
         component = "browser_analysis",
@@ -108,165 +799,1675 @@ fn analyze_single_browser(browser: &Browser, args: &Args) -> Result<AnalysisResu
         "Analysis completed successfully"
     );
 
-    Ok(AnalysisResult { date_range, stats })
-}
+    if let Some(cache) = cache.filter(|_| warnings.is_empty()) {
+        let aggregates = CachedAggregates {
+            date_range,
+            totals: totals.clone(),
+            retention: retention.clone(),
+            domain_counts: stats.domain_counts.clone(),
+            root_counts: stats.root_counts.clone(),
+            domains_removed: stats.domains_removed,
+        };
+        if let Err(e) = cache.store(&aggregates) {
+            warn!(action = "store", component = "cache", error = %e, "Failed to write result cache");
+        }
+    }
 
-fn analyze_all_browsers(args: &Args) -> Result<AnalysisResult> {
-    let browsers = [
-        Browser::Chrome,
-        Browser::Edge,
-        Browser::Firefox,
-        Browser::Vivaldi,
-        Browser::Zen,
-    ];
-    let mut all_stats = crate::stats::DomainStats {
-        unique_domains: Vec::new(),
-        domain_counts: std::collections::HashMap::new(),
-        domains_removed: 0,
+    let mut result = AnalysisResult {
+        date_range,
+        totals,
+        retention,
+        stats,
+        provenance: provenance(args, ctx),
+        domain_ages: Vec::new(),
+        temporal: None,
+        periods: None,
+        trends: None,
+        period_comparison: None,
+        streaks: None,
+        novelty: None,
+        spans: None,
+        sessions: None,
+        time_spent: None,
+        transitions: None,
+        searches: None,
+        pages,
+        paths,
+        schemes: scheme_counts,
+        downloads,
+        segments,
+        media,
+        navigation: None,
+        pinned: Vec::new(),
+        referrers: None,
+        keywords,
+        blocked: None,
+        categories: None,
+        category_hours: None,
+        tlds: None,
+        frecency: None,
+        reports: Vec::new(),
+        warnings,
+        skipped_browsers: Vec::new(),
+        timings: Some(timings),
     };
+    for analysis in analyses {
+        analysis.finalize(&mut result);
+    }
+    Ok(result)
+}
 
-    let mut earliest_date_str = None;
-    let mut latest_date_str = None;
-    let mut earliest_timestamp: Option<DateTime<Utc>> = None;
-    let mut latest_timestamp: Option<DateTime<Utc>> = None;
+fn analyze_all_browsers(args: &Args, ctx: &RunContext) -> Result<AnalysisResult> {
+    let browsers = args.browsers();
+    let mut all_stats = crate::stats::DomainStats::default();
+    let mut all_temporal: Option<TemporalStats> = None;
+    let mut all_periods = args.period().map(PeriodBreakdown::new);
+    let mut all_trends = args.trends.then(DomainTrends::default);
+    let mut all_comparison = args
+        .compare_periods
+        .map(|window| PeriodComparison::new(Utc::now(), window));
+    let mut all_streaks = args.streaks.then(DomainStreaks::default);
+    let mut all_novelty = args.novelty.then(DomainNovelty::default);
+    let mut all_spans: Option<DomainSpans> = None;
+    let mut all_sessions = args
+        .sessions
+        .map(|minutes| SessionStats::new(chrono::Duration::minutes(minutes.into())));
+    let mut all_time_spent: Option<TimeSpent> = None;
+    let mut all_transitions = args.transitions.then(TransitionStats::default);
+    let mut all_searches = args.searches.then(SearchTerms::default);
+    let mut all_pages = (args.by == RankBy::Urls).then(PageStats::default);
+    let mut all_paths = args
+        .domain
+        .as_ref()
+        .map(|domain| DomainPaths::new(selected_domain(domain, &ctx.normalizer)));
+    let mut all_schemes = args.scheme_stats.then(SchemeCounts::default);
+    let mut all_downloads = args.downloads.then(DownloadStats::default);
+    let mut all_segments = args.segments.then(SegmentUsage::default);
+    let mut all_media = args.media.then(MediaStats::default);
+    let mut all_navigation =
+        (args.nav_graph || args.referrers.is_some()).then(NavigationGraph::default);
+    let mut all_keywords = args.keywords.then(|| {
+        TitleKeywords::new(
+            args.domain
+                .as_ref()
+                .map(|domain| selected_domain(domain, &ctx.normalizer)),
+        )
+    });
+    let mut all_frecency = crate::report::needs_html(args).then(|| DomainFrecency::new(Utc::now()));
+    let mut all_category_hours: Option<CategoryHours> = None;
+    let mut all_reports = Vec::new();
+    let mut all_warnings = Vec::new();
+    let mut skipped_browsers = Vec::new();
+    let mut timings = Timings::default();
 
+    let mut date_range: Option<DateRange> = None;
+    let mut totals: Option<VisitTotals> = None;
+    let mut retention: Option<Retention> = None;
+
+    // Create the progress lines up front so they are listed in browser order
     for browser in &browsers {
-        match analyze_single_browser(browser, args) {
+        ctx.progress.bar(&browser.to_string());
+    }
+
+    // Analyze every browser on its own thread; results are merged in browser order below
+    let analyses: Vec<(Browser, Result<AnalysisResult>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = browsers
+            .iter()
+            .map(|browser| {
+                let browser = *browser;
+                let handle = scope.spawn(move || {
+                    let _span = info_span!("browser", browser = %browser).entered();
+                    let analysis = analyze_single_browser(&browser, args, ctx);
+                    if let Err(e) = &analysis {
+                        let status = if e.is::<HistoryNotFound>() {
+                            "not installed"
+                        } else {
+                            "skipped"
+                        };
+                        ctx.progress.bar(&browser.to_string()).finish(status);
+                    }
+                    analysis
+                });
+                (browser, handle)
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|(browser, handle)| {
+                let analysis = handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                (browser, analysis)
+            })
+            .collect()
+    });
+
+    let merge_start = Instant::now();
+    for (browser, analysis) in analyses {
+        ctx.cancel.check()?;
+        match analysis {
             Ok(result) => {
-                // Merge stats
-                for (domain, count) in &result.stats.domain_counts {
-                    *all_stats.domain_counts.entry(domain.clone()).or_insert(0) += count;
+                if let Some(range) = result.date_range {
+                    date_range = Some(match date_range {
+                        Some(merged) => range.merge(merged),
+                        None => range,
+                    });
                 }
-                all_stats.domains_removed += result.stats.domains_removed;
-
-                // Update date range - only if we have valid data
-                let (earliest, latest, _) = &result.date_range;
-                if earliest != "No data available" && latest != "No data available" {
-                    // Try to parse the date strings to compare them properly
-                    if let (Ok(earliest_parsed), Ok(latest_parsed)) = (
-                        chrono::NaiveDate::parse_from_str(earliest, "%B %d, %Y")
-                            .or_else(|_| chrono::NaiveDate::parse_from_str(earliest, "%B %-d, %Y")),
-                        chrono::NaiveDate::parse_from_str(latest, "%B %d, %Y")
-                            .or_else(|_| chrono::NaiveDate::parse_from_str(latest, "%B %-d, %Y")),
-                    ) {
-                        let earliest_utc = earliest_parsed.and_hms_opt(0, 0, 0).unwrap().and_utc();
-                        let latest_utc = latest_parsed.and_hms_opt(0, 0, 0).unwrap().and_utc();
-
-                        if earliest_timestamp.is_none()
-                            || earliest_utc < earliest_timestamp.unwrap()
-                        {
-                            earliest_timestamp = Some(earliest_utc);
-                            earliest_date_str = Some(earliest.clone());
-                        }
-                        if latest_timestamp.is_none() || latest_utc > latest_timestamp.unwrap() {
-                            latest_timestamp = Some(latest_utc);
-                            latest_date_str = Some(latest.clone());
-                        }
-                    }
+
+                if let Some(browser_totals) = &result.totals {
+                    totals
+                        .get_or_insert_with(VisitTotals::default)
+                        .merge(browser_totals);
+                }
+
+                if let Some(browser_retention) = &result.retention {
+                    retention
+                        .get_or_insert_with(Retention::default)
+                        .merge(browser_retention);
+                }
+
+                if let Some(browser_timings) = result.timings {
+                    timings.extend_prefixed(&browser.to_string(), browser_timings);
+                }
+                all_stats.merge(result.stats);
+                if let (Some(all_pages), Some(pages)) = (&mut all_pages, result.pages) {
+                    all_pages.merge(pages);
+                }
+                if let (Some(all_paths), Some(paths)) = (&mut all_paths, &result.paths) {
+                    all_paths.merge(paths);
+                }
+                if let (Some(all_schemes), Some(schemes)) = (&mut all_schemes, &result.schemes) {
+                    all_schemes.merge(schemes);
+                }
+                if let (Some(all_segments), Some(segments)) = (&mut all_segments, &result.segments)
+                {
+                    all_segments.merge(segments);
+                }
+                if let (Some(all_media), Some(media)) = (&mut all_media, &result.media) {
+                    all_media.merge(media);
+                }
+                if let (Some(all_navigation), Some(navigation)) =
+                    (&mut all_navigation, &result.navigation)
+                {
+                    all_navigation.merge(navigation);
+                }
+                if let (Some(all_downloads), Some(downloads)) =
+                    (&mut all_downloads, &result.downloads)
+                {
+                    all_downloads.merge(downloads);
+                }
+                if let (Some(all_keywords), Some(keywords)) = (&mut all_keywords, &result.keywords)
+                {
+                    all_keywords.merge(keywords);
+                }
+                all_warnings.extend(
+                    result
+                        .warnings
+                        .into_iter()
+                        .map(|warning| format!("{browser}: {warning}")),
+                );
+                if let Some(temporal) = &result.temporal {
+                    all_temporal
+                        .get_or_insert_with(Default::default)
+                        .merge(temporal);
+                }
+                if let Some(category_hours) = &result.category_hours {
+                    all_category_hours
+                        .get_or_insert_with(CategoryHours::default)
+                        .merge(category_hours);
+                }
+                if let (Some(all_periods), Some(periods)) = (&mut all_periods, &result.periods) {
+                    all_periods.merge(periods);
+                }
+                if let (Some(all_trends), Some(trends)) = (&mut all_trends, &result.trends) {
+                    all_trends.merge(trends);
+                }
+                if let (Some(all_comparison), Some(comparison)) =
+                    (&mut all_comparison, &result.period_comparison)
+                {
+                    all_comparison.merge(comparison);
+                }
+                if let (Some(all_streaks), Some(streaks)) = (&mut all_streaks, &result.streaks) {
+                    all_streaks.merge(streaks);
+                }
+                if let (Some(all_novelty), Some(novelty)) = (&mut all_novelty, &result.novelty) {
+                    all_novelty.merge(novelty);
+                }
+                if let Some(spans) = &result.spans {
+                    all_spans
+                        .get_or_insert_with(DomainSpans::default)
+                        .merge(spans);
+                }
+                if let (Some(all_sessions), Some(sessions)) = (&mut all_sessions, &result.sessions)
+                {
+                    all_sessions.merge(sessions);
+                }
+                if let (Some(all_transitions), Some(transitions)) =
+                    (&mut all_transitions, &result.transitions)
+                {
+                    all_transitions.merge(transitions);
                 }
+                if let (Some(all_searches), Some(searches)) = (&mut all_searches, &result.searches)
+                {
+                    all_searches.merge(searches);
+                }
+                if let Some(time_spent) = &result.time_spent {
+                    all_time_spent
+                        .get_or_insert_with(Default::default)
+                        .merge(time_spent);
+                }
+                if let (Some(all_frecency), Some(frecency)) = (&mut all_frecency, &result.frecency)
+                {
+                    all_frecency.merge(frecency);
+                }
+                // Custom analyses cannot be merged generically, so each browser keeps its own
+                all_reports.extend(result.reports.into_iter().map(|report| Report {
+                    title: format!("{} ({browser})", report.title),
+                    lines: report.lines,
+                }));
             }
+            Err(e) if e.is::<Cancelled>() => return Err(e),
             Err(e) => {
                 warn!(browser = ?browser, error = %e, "Failed to analyze browser");
+                skipped_browsers.push(SkippedBrowser {
+                    browser,
+                    not_installed: e.is::<HistoryNotFound>(),
+                    reason: format!("{e:#}"),
+                });
             }
         }
     }
 
-    // Update unique_domains from the final domain_counts
-    all_stats.unique_domains = all_stats.domain_counts.keys().cloned().collect();
-
-    // Calculate the total days between earliest and latest
-    let total_days = if let (Some(earliest), Some(latest)) = (earliest_timestamp, latest_timestamp)
-    {
-        (latest - earliest).num_days()
-    } else {
-        0
-    };
-
-    let date_range = (
-        earliest_date_str.unwrap_or_else(|| "No data available".to_string()),
-        latest_date_str.unwrap_or_else(|| "No data available".to_string()),
-        total_days,
-    );
+    timings.record("merge", merge_start);
 
     Ok(AnalysisResult {
         date_range,
+        totals,
+        retention,
         stats: all_stats,
+        provenance: provenance(args, ctx),
+        domain_ages: Vec::new(),
+        temporal: all_temporal,
+        periods: all_periods,
+        trends: all_trends,
+        period_comparison: all_comparison,
+        streaks: all_streaks,
+        novelty: all_novelty,
+        spans: all_spans,
+        sessions: all_sessions,
+        time_spent: all_time_spent,
+        transitions: all_transitions,
+        searches: all_searches,
+        pages: all_pages,
+        paths: all_paths,
+        schemes: all_schemes,
+        downloads: all_downloads,
+        segments: all_segments,
+        media: all_media,
+        navigation: all_navigation,
+        pinned: Vec::new(),
+        referrers: None,
+        keywords: all_keywords,
+        blocked: None,
+        categories: None,
+        category_hours: all_category_hours,
+        tlds: None,
+        frecency: all_frecency,
+        reports: all_reports,
+        warnings: all_warnings,
+        skipped_browsers,
+        timings: Some(timings),
     })
 }
 
-pub fn print_analysis_results(result: &AnalysisResult, args: &Args) {
-    let (earliest_date, latest_date, days_between) = &result.date_range;
+/// Orders domains by `--sort` and `--reverse`; domains without recorded visits sort last
+fn sort_domains(domains: &mut [(&String, &u32)], result: &AnalysisResult, args: &Args) {
+    let spans = result.spans.as_ref();
+    match args.sort {
+        SortBy::Count => domains.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0))),
+        SortBy::Alpha => domains.sort_by(|a, b| a.0.cmp(b.0)),
+        SortBy::FirstVisit => domains.sort_by_key(|(domain, _)| {
+            let first = spans.and_then(|spans| spans.first(domain));
+            (first.is_none(), first, *domain)
+        }),
+        SortBy::LastVisit => domains.sort_by_key(|(domain, _)| {
+            let last = spans.and_then(|spans| spans.last(domain));
+            (last.is_none(), std::cmp::Reverse(last), *domain)
+        }),
+    }
+    if args.reverse {
+        domains.reverse();
+    }
+}
+
+/// Writes the pinned domains missing from `listed`, with their rank by count
+fn write_pinned(
+    out: &mut String,
+    listed: &[(&String, &u32)],
+    result: &AnalysisResult,
+    args: &Args,
+) -> fmt::Result {
+    let missing: Vec<&String> = result
+        .pinned
+        .iter()
+        .filter(|pinned| !listed.iter().any(|(domain, _)| domain == pinned))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let ranked = result.stats.top_with_pins(0, &result.pinned);
+    let total = result.stats.total();
+    writeln!(out, "\nPinned domains outside the top {}:", listed.len())?;
+    for pinned in missing {
+        let display_domain = crate::utils::display_domain(pinned, args);
+        match ranked.iter().find(|(_, domain, _)| domain == pinned) {
+            Some((rank, _, count)) => writeln!(
+                out,
+                "- #{rank} {display_domain}: {} {} ({:.1}%)",
+                crate::utils::format_count(*count, args.humanize),
+                args.weight.unit(),
+                crate::utils::percent(*count, total)
+            )?,
+            None => writeln!(out, "- {display_domain}: not visited")?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes ranked domains with aligned name and count columns, their share of the total
+/// and, when `cumulative`, the running share down the list
+fn write_domain_list(
+    out: &mut String,
+    domains: &[(&String, &u32)],
+    cumulative: bool,
+    result: &AnalysisResult,
+    args: &Args,
+) -> fmt::Result {
+    let color = crate::utils::color_enabled(args);
+    let total = result.stats.total();
+    let mut running = 0u32;
+    let rows: Vec<(String, String)> = domains
+        .iter()
+        .map(|(domain, count)| {
+            (
+                crate::utils::display_domain(domain, args),
+                crate::utils::format_count(**count, args.humanize),
+            )
+        })
+        .collect();
+    let rank_width = domains.len().to_string().len();
+    let domain_width = rows
+        .iter()
+        .map(|(domain, _)| domain.chars().count())
+        .max()
+        .unwrap_or(0);
+    let count_width = rows
+        .iter()
+        .map(|(_, count)| count.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_count = domains.iter().map(|(_, count)| **count).max().unwrap_or(0);
 
-    let browser_name = if args.all_browsers {
-        "All Browsers".to_string()
+    for (rank, ((domain, raw_count), (display_domain, count))) in
+        domains.iter().zip(rows).enumerate()
+    {
+        let landing = match result.stats.root_share(domain) {
+            Some(share) if args.landing => format!(" ({:.0}% homepage)", share * 100.0),
+            _ => String::new(),
+        };
+        let padded_domain = format!("{display_domain:<domain_width$}");
+        let padded_domain = if args.redact.is_some() {
+            crate::utils::paint(&padded_domain, crate::utils::Style::Dim, color)
+        } else {
+            padded_domain
+        };
+        running += **raw_count;
+        let mut share = format!("  {:>5.1}%", crate::utils::percent(**raw_count, total));
+        if cumulative {
+            share.push_str(&format!(
+                "  (cum. {:>5.1}%)",
+                crate::utils::percent(running, total)
+            ));
+        }
+        let visited = match (args.sort, &result.spans) {
+            (SortBy::FirstVisit, Some(spans)) => spans.first(domain),
+            (SortBy::LastVisit, Some(spans)) => spans.last(domain),
+            _ => None,
+        };
+        if let Some(visited) = visited {
+            share.push_str(&format!(
+                "  {} {}",
+                args.sort,
+                crate::utils::format_date(
+                    args.zone().local(visited).date(),
+                    args,
+                    &DateFormat::Iso
+                )
+            ));
+        }
+        let chart = if args.chart {
+            format!("  {:<30}", crate::utils::bar(**raw_count, max_count, 30))
+        } else {
+            String::new()
+        };
+        let line = format!(
+            "{:>rank_width$}. {}  {} {}{}{}{}",
+            rank + 1,
+            padded_domain,
+            crate::utils::paint(
+                &format!("{count:>count_width$}"),
+                crate::utils::Style::Green,
+                color
+            ),
+            args.weight.unit(),
+            share,
+            chart,
+            landing
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// A page as shown under `--redact`: only its host, redacted like domains
+fn redacted_page(url: &str, args: &Args) -> String {
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.host_str()
+                .map(|host| crate::utils::display_domain(host, args))
+        })
+        .unwrap_or_else(|| "???".to_string());
+    format!("{host}/…")
+}
+
+fn write_page_line(out: &mut String, page: &Page, args: &Args) -> fmt::Result {
+    let (url, title) = if args.redact.is_some() {
+        (redacted_page(&page.url, args), None)
     } else {
-        args.browser.to_string()
+        (
+            crate::utils::truncate(&page.url, 80),
+            page.title
+                .as_deref()
+                .map(|title| crate::utils::truncate(title, 60)),
+        )
     };
 
-    println!("\n--- {browser_name} History Analysis ---");
+    writeln!(
+        out,
+        "- {}: {} {}",
+        url,
+        crate::utils::format_count(page.count, args.humanize),
+        args.weight.unit()
+    )?;
+    if let Some(title) = title {
+        writeln!(out, "    {title}")?;
+    }
+    Ok(())
+}
+
+/// Writes only `name<TAB>count` lines: the top `--top` entries in `--sort` order, or all of them,
+/// with names shown as `--redact` and `--decode-idn` ask
+fn write_quiet(out: &mut String, result: &AnalysisResult, args: &Args) -> fmt::Result {
+    if let Some(pages) = &result.pages {
+        let sorted = pages.sorted(true);
+        let limit = args.top.unwrap_or(sorted.len());
+        for page in sorted.into_iter().take(limit) {
+            let url = match args.redact {
+                Some(_) => redacted_page(&page.url, args),
+                None => page.url.clone(),
+            };
+            writeln!(out, "{url}\t{}", page.count)?;
+        }
+        return Ok(());
+    }
+    if let Some(tlds) = &result.tlds {
+        let sorted = tlds.sorted();
+        let limit = args.top.unwrap_or(sorted.len());
+        for (tld, count) in sorted.into_iter().take(limit) {
+            writeln!(
+                out,
+                "{}\t{}",
+                crate::utils::display_domain(tld, args),
+                count.visits
+            )?;
+        }
+        return Ok(());
+    }
+
+    let mut domains: Vec<(&String, &u32)> = result.stats.domain_counts.iter().collect();
+    sort_domains(&mut domains, result, args);
+    let limit = args.top.unwrap_or(domains.len());
+    for (domain, count) in domains.into_iter().take(limit) {
+        writeln!(
+            out,
+            "{}\t{count}",
+            crate::utils::display_domain(domain, args)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_page_lists(out: &mut String, pages: &PageStats, args: &Args) -> fmt::Result {
+    if let Some(top_count) = args.top {
+        let sorted = pages.sorted(true);
+        writeln!(
+            out,
+            "\nTop {} most visited pages:",
+            top_count.min(sorted.len())
+        )?;
+        for page in sorted.into_iter().take(top_count) {
+            write_page_line(out, page, args)?;
+        }
+    }
+
+    if let Some(bottom_count) = args.bottom {
+        let sorted = pages.sorted(false);
+        writeln!(
+            out,
+            "\nBottom {} least visited pages:",
+            bottom_count.min(sorted.len())
+        )?;
+        for page in sorted.into_iter().take(bottom_count) {
+            write_page_line(out, page, args)?;
+        }
+    }
+    Ok(())
+}
 
-    if *days_between > 0 {
-        println!(
+/// Lists the top registrable domains, each with its counted subdomains indented beneath
+fn write_domain_tree(
+    out: &mut String,
+    result: &AnalysisResult,
+    top_count: usize,
+    args: &Args,
+) -> fmt::Result {
+    let total = result.stats.total();
+    let groups = result.stats.grouped();
+    writeln!(
+        out,
+        "\nTop {} most visited registrable domains:",
+        top_count.min(groups.len())
+    )?;
+    for (rank, group) in groups.into_iter().take(top_count).enumerate() {
+        writeln!(
+            out,
+            "{}. {}: {} {} ({:.1}%)",
+            rank + 1,
+            crate::utils::display_domain(&group.domain, args),
+            crate::utils::format_count(group.count, args.humanize),
+            args.weight.unit(),
+            crate::utils::percent(group.count, total)
+        )?;
+        // A group holding only the registrable domain itself has nothing to nest
+        if group.subdomains.len() == 1 && group.subdomains[0].0 == group.domain {
+            continue;
+        }
+        for (domain, count) in &group.subdomains {
+            writeln!(
+                out,
+                "   - {}: {}",
+                crate::utils::display_domain(domain, args),
+                crate::utils::format_count(*count, args.humanize)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write_tld_lists(out: &mut String, tlds: &TldBreakdown, args: &Args) -> fmt::Result {
+    let total = tlds.total();
+    let limit = args.top.unwrap_or(20);
+    let sorted = tlds.sorted();
+    writeln!(out, "\nTop {} top-level domains:", limit.min(sorted.len()))?;
+    for (tld, count) in sorted.into_iter().take(limit) {
+        writeln!(
+            out,
+            "- .{}: {} {} ({:.1}%), {} domains",
+            tld,
+            crate::utils::format_count(count.visits, args.humanize),
+            args.weight.unit(),
+            crate::utils::percent(count.visits, total),
+            crate::utils::format_count(count.domains, args.humanize)
+        )?;
+    }
+
+    let countries = tlds.countries();
+    if !countries.is_empty() {
+        writeln!(out, "\nCountry-code TLDs by country:")?;
+    }
+    for (tld, country, count) in countries.into_iter().take(limit) {
+        writeln!(
+            out,
+            "- {} (.{}): {} {} ({:.1}%), {} domains",
+            country,
+            tld,
+            crate::utils::format_count(count.visits, args.humanize),
+            args.weight.unit(),
+            crate::utils::percent(count.visits, total),
+            crate::utils::format_count(count.domains, args.humanize)
+        )?;
+    }
+    Ok(())
+}
+
+/// Renders the plain-text report
+pub fn render_text(result: &AnalysisResult, args: &Args) -> String {
+    let mut out = String::new();
+    write_text(&mut out, result, args).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_text(out: &mut String, result: &AnalysisResult, args: &Args) -> fmt::Result {
+    if args.quiet {
+        return write_quiet(out, result, args);
+    }
+
+    let (earliest_date, latest_date) = crate::utils::date_range_labels(result.date_range, args);
+    let days_between = result.date_range.map_or(0, |range| range.days());
+
+    writeln!(out, "\n--- {} History Analysis ---", args.source_label())?;
+    let (missing, failed): (Vec<&SkippedBrowser>, Vec<&SkippedBrowser>) = result
+        .skipped_browsers
+        .iter()
+        .partition(|skipped| skipped.not_installed);
+    for skipped in failed {
+        writeln!(out, "Skipped {}: {}", skipped.browser, skipped.reason)?;
+    }
+    if !missing.is_empty() {
+        let names: Vec<String> = missing
+            .iter()
+            .map(|skipped| skipped.browser.to_string())
+            .collect();
+        writeln!(out, "Not installed: {}", names.join(", "))?;
+    }
+
+    if days_between > 0 {
+        writeln!(
+            out,
             "Date range: {} to {} ({} days)",
             earliest_date,
             latest_date,
-            crate::utils::format_number(*days_between as u32)
-        );
+            crate::utils::format_number(days_between as u32)
+        )?;
     } else {
-        println!("Date range: {earliest_date} to {latest_date}");
+        writeln!(out, "Date range: {earliest_date} to {latest_date}")?;
+    }
+
+    if let Some(sampling) = &result.provenance.sampling {
+        writeln!(
+            out,
+            "Sampled {:.1}% of history entries (seed {})",
+            sampling.rate * 100.0,
+            sampling.seed
+        )?;
     }
 
-    println!(
+    writeln!(
+        out,
         "Total unique domains found: {}",
-        crate::utils::format_number(result.stats.unique_domains.len() as u32)
-    );
-    println!(
-        "Domains removed (no valid TLD): {}",
-        crate::utils::format_number(result.stats.domains_removed)
-    );
+        crate::utils::format_count(result.stats.unique_domains() as u32, args.humanize)
+    )?;
+    if let Some(totals) = result.totals.as_ref().filter(|totals| totals.visits > 0) {
+        writeln!(
+            out,
+            "Total visits: {} across {} unique URLs ({:.1} per day)",
+            crate::utils::format_count(totals.visits, args.humanize),
+            crate::utils::format_count(totals.unique_urls, args.humanize),
+            totals.average_per_day()
+        )?;
+        if let Some((day, visits)) = totals.busiest_day() {
+            writeln!(
+                out,
+                "Busiest day: {} ({} visits)",
+                crate::utils::format_date(day, args, &crate::utils::DateFormat::Long),
+                crate::utils::format_count(visits, args.humanize)
+            )?;
+        }
+    }
+    if let Some(retention) = result
+        .retention
+        .as_ref()
+        .filter(|retention| retention.excess_percent() > args.retention_warning)
+    {
+        let count =
+            |n: u64| crate::utils::format_count(n.min(u32::MAX as u64) as u32, args.humanize);
+        writeln!(
+            out,
+            "Note: only {} of {} all-time visits are still kept; the browser expired older ones",
+            count(retention.recorded),
+            count(retention.all_time)
+        )?;
+        let scope = if args.weight == Weight::VisitCount {
+            "Counts are all-time, but the date range only covers kept visits"
+        } else {
+            "Counts cover the kept window (--weight visit-count counts all time)"
+        };
+        writeln!(out, "      {scope}; --retention lists both")?;
+    }
+    if let Some(diversity) = result.stats.diversity(crate::stats::CONCENTRATION_TOP) {
+        writeln!(
+            out,
+            "Diversity: {:.2} bits of entropy (evenness {:.0}%), Gini {:.2}; {:.0}% of visits in the top {} domains",
+            diversity.entropy,
+            diversity.evenness * 100.0,
+            diversity.gini,
+            diversity.top_share * 100.0,
+            diversity.top
+        )?;
+    }
+    let removed = &result.stats.domains_removed;
+    let breakdown: Vec<String> = HostClass::ALL
+        .iter()
+        .filter(|class| removed.get(**class) > 0)
+        .map(|class| {
+            format!(
+                "{}: {}",
+                class,
+                crate::utils::format_count(removed.get(*class), args.humanize)
+            )
+        })
+        .collect();
+    if breakdown.is_empty() {
+        writeln!(out, "Domains removed: 0")?;
+    } else {
+        writeln!(
+            out,
+            "Domains removed: {} ({})",
+            crate::utils::format_count(removed.total(), args.humanize),
+            breakdown.join(", ")
+        )?;
+    }
+    if let (Some(samples), Some(n)) = (&result.stats.removed_samples, args.show_removed) {
+        for class in HostClass::ALL {
+            let hosts = samples.top(class, n);
+            if hosts.is_empty() {
+                continue;
+            }
+            let hosts: Vec<String> = hosts
+                .iter()
+                .map(|(host, count)| {
+                    format!(
+                        "{} ({})",
+                        crate::utils::display_domain(host, args),
+                        crate::utils::format_count(*count, args.humanize)
+                    )
+                })
+                .collect();
+            writeln!(out, "  {class}: {}", hosts.join(", "))?;
+        }
+    }
+
+    if let Some(long_tail) = &result.stats.long_tail {
+        writeln!(
+            out,
+            "Long tail: {} domains with fewer than {} {} ({} {} in total, not listed)",
+            crate::utils::format_count(long_tail.domains, args.humanize),
+            crate::utils::format_count(long_tail.threshold, args.humanize),
+            args.weight.unit(),
+            crate::utils::format_count(long_tail.count, args.humanize),
+            args.weight.unit()
+        )?;
+    }
+
+    if let Some(periods) = result.periods.as_ref().filter(|_| args.chart) {
+        let series = periods.series();
+        if let (Some((first, _)), Some((last, _))) = (series.first(), series.last()) {
+            let visits: Vec<u32> = series.iter().map(|(_, visits)| *visits).collect();
+            writeln!(
+                out,
+                "Visits per {}: {} ({first} to {last})",
+                periods.period,
+                crate::utils::sparkline(&visits)
+            )?;
+        }
+    }
+
+    if let Some(pages) = &result.pages {
+        write_page_lists(out, pages, args)?;
+    }
+    if let Some(tlds) = &result.tlds {
+        write_tld_lists(out, tlds, args)?;
+    }
 
     // Sort domains by count
     let mut sorted_domains: Vec<(&String, &u32)> = result.stats.domain_counts.iter().collect();
-    sorted_domains.sort_by(|a, b| b.1.cmp(a.1));
+    sorted_domains.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
 
-    if let Some(top_count) = args.top {
-        println!(
-            "\nTop {} most visited domains:",
-            std::cmp::min(top_count, sorted_domains.len())
-        );
-        for (domain, count) in sorted_domains.iter().take(top_count) {
-            let display_domain = if args.redact {
-                crate::utils::redact_domain(domain)
-            } else {
-                domain.to_string()
-            };
-            println!(
-                "- {}: {} visits",
-                display_domain,
-                crate::utils::format_number(**count)
-            );
+    let ranks_domains = result.pages.is_none() && result.tlds.is_none();
+    if let Some(top_count) = args.top.filter(|_| ranks_domains && args.tree) {
+        write_domain_tree(out, result, top_count, args)?;
+    }
+    if let Some(top_count) = args.top.filter(|_| ranks_domains && !args.tree) {
+        let by_count = args.sort == SortBy::Count && !args.reverse;
+        let mut listed = sorted_domains.clone();
+        if !by_count {
+            sort_domains(&mut listed, result, args);
+        }
+        let top: Vec<_> = listed.into_iter().take(top_count).collect();
+        if by_count {
+            writeln!(out, "\nTop {} most visited domains:", top.len())?;
+        } else {
+            writeln!(
+                out,
+                "\n{} domains by {}{}:",
+                top.len(),
+                args.sort,
+                if args.reverse { " (reversed)" } else { "" }
+            )?;
         }
+        write_domain_list(out, &top, by_count, result, args)?;
+        if by_count && !top.is_empty() {
+            let top_total: u32 = top.iter().map(|(_, count)| **count).sum();
+            writeln!(
+                out,
+                "Top {} domains account for {:.1}% of {}",
+                top.len(),
+                crate::utils::percent(top_total, result.stats.total()),
+                args.weight.unit()
+            )?;
+        }
+        write_pinned(out, &top, result, args)?;
     }
 
-    if let Some(bottom_count) = args.bottom {
+    if let Some(bottom_count) = args.bottom.filter(|_| ranks_domains) {
         let mut bottom_sorted = sorted_domains.clone();
         bottom_sorted.sort_by(|a, b| a.1.cmp(b.1));
 
-        println!(
+        writeln!(
+            out,
             "\nBottom {} least visited domains:",
             std::cmp::min(bottom_count, bottom_sorted.len())
+        )?;
+        let bottom: Vec<_> = bottom_sorted.iter().take(bottom_count).copied().collect();
+        write_domain_list(out, &bottom, false, result, args)?;
+    }
+
+    if let Some(paths) = &result.paths {
+        let display_domain = crate::utils::display_domain(&paths.domain, args);
+        let top = paths.top(args.top.unwrap_or(20));
+        if top.is_empty() {
+            writeln!(out, "\nNo visits found for {display_domain}")?;
+        } else {
+            writeln!(out, "\nTop paths on {display_domain}:")?;
+        }
+        for (path, count) in top {
+            let display_path = if let Some(policy) = args.redact {
+                format!("/{}", policy.text(&path))
+            } else {
+                crate::utils::truncate(&path, 80)
+            };
+            writeln!(
+                out,
+                "- {}: {} {}",
+                display_path,
+                crate::utils::format_count(count, args.humanize),
+                args.weight.unit()
+            )?;
+        }
+    }
+
+    if let Some(temporal) = &result.temporal {
+        let zone = args.zone_label();
+        crate::utils::write_histogram(
+            out,
+            &format!("Visits by hour of day ({zone})"),
+            &temporal.hour_buckets(),
+            args.humanize,
+        )?;
+        crate::utils::write_histogram(
+            out,
+            &format!("Visits by day of week ({zone})"),
+            &temporal.weekday_buckets(),
+            args.humanize,
+        )?;
+    }
+
+    if let Some(blocked) = &result.blocked {
+        writeln!(
+            out,
+            "\nTracker/ad domains{}: {} of {} domains ({:.1}%), {} of {} {} ({:.1}%)",
+            if args.exclude_blocked {
+                " (excluded)"
+            } else {
+                ""
+            },
+            crate::utils::format_count(blocked.domain_counts.len() as u32, args.humanize),
+            crate::utils::format_count(blocked.total_domains, args.humanize),
+            crate::utils::percent(blocked.domain_counts.len() as u32, blocked.total_domains),
+            crate::utils::format_count(blocked.count(), args.humanize),
+            crate::utils::format_count(blocked.total_count, args.humanize),
+            args.weight.unit(),
+            crate::utils::percent(blocked.count(), blocked.total_count)
+        )?;
+        for (domain, count) in blocked.top(args.top.unwrap_or(10)) {
+            writeln!(
+                out,
+                "- {}: {} {}",
+                crate::utils::display_domain(&domain, args),
+                crate::utils::format_count(count, args.humanize),
+                args.weight.unit()
+            )?;
+        }
+    }
+
+    if let Some(categories) = &result.categories {
+        let total = categories.total();
+        let sorted = categories.sorted();
+        let max = sorted.first().map_or(0, |(_, count)| *count);
+        writeln!(out, "\nShare by category:")?;
+        for (category, count) in sorted {
+            writeln!(
+                out,
+                "{:<14} {:<30} {:>9} {} ({:.1}%)",
+                category,
+                crate::utils::bar(count, max, 30),
+                crate::utils::format_count(count, args.humanize),
+                args.weight.unit(),
+                count as f64 / total.max(1) as f64 * 100.0
+            )?;
+        }
+    }
+
+    if let Some(category_hours) = &result.category_hours {
+        let sorted = category_hours.sorted();
+        let width = sorted
+            .iter()
+            .map(|(category, _)| category.chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "\nVisits by category and hour of day:")?;
+        writeln!(out, "{:<width$}  0     6     12    18", "")?;
+        for (category, hours) in sorted {
+            let start = crate::categories::peak_start(hours);
+            let end = (start + crate::categories::PEAK_HOURS) % 24;
+            writeln!(
+                out,
+                "{category:<width$}  {}  peak {start:02}:00-{end:02}:00, {} visits",
+                crate::utils::sparkline(hours),
+                crate::utils::format_count(hours.iter().sum(), args.humanize)
+            )?;
+        }
+    }
+
+    if let Some(periods) = result.periods.as_ref().filter(|_| args.group_by.is_some()) {
+        let max_visits = periods
+            .buckets
+            .values()
+            .map(|b| b.visits)
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "\nVisits by {}:", periods.period)?;
+        for (key, bucket) in &periods.buckets {
+            writeln!(
+                out,
+                "{:<7} {:<30} {:>9} visits, {:>7} domains",
+                key,
+                crate::utils::bar(bucket.visits, max_visits, 30),
+                crate::utils::format_count(bucket.visits, args.humanize),
+                crate::utils::format_count(bucket.domains.len() as u32, args.humanize)
+            )?;
+        }
+    }
+
+    if let Some(trends) = &result.trends {
+        let months = trends.months();
+        if let (Some(first), Some(last)) = (months.first(), months.last()) {
+            writeln!(
+                out,
+                "\nDomain trends ({first} to {last}, earlier vs later half):"
+            )?;
+        }
+        for (title, list) in [
+            ("Fastest growing", trends.growing(10)),
+            ("Fastest declining", trends.declining(10)),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+            writeln!(out, "{title}:")?;
+            for trend in list {
+                let display_domain = crate::utils::display_domain(&trend.domain, args);
+                writeln!(
+                    out,
+                    "- {}: {} -> {} visits ({:+})",
+                    display_domain,
+                    crate::utils::format_count(trend.earlier, args.humanize),
+                    crate::utils::format_count(trend.later, args.humanize),
+                    trend.change()
+                )?;
+            }
+        }
+    }
+
+    if let Some(comparison) = &result.period_comparison {
+        let window = crate::utils::format_window(comparison.length);
+        let top = args.top.unwrap_or(10);
+        let movements = comparison.movements();
+        writeln!(
+            out,
+            "\nRank movement, last {window} vs the {window} before:"
+        )?;
+        if comparison.recent.is_empty() && comparison.previous.is_empty() {
+            writeln!(out, "(no visits in either window)")?;
+        }
+        let width = top.min(comparison.recent.len()).to_string().len();
+        for movement in movements
+            .iter()
+            .filter(|m| m.recent_rank.is_some_and(|r| r <= top))
+        {
+            let change = match (movement.change(), movement.previous_rank) {
+                (Some(0), _) => "=".to_string(),
+                (Some(change), Some(previous)) if change > 0 => {
+                    format!("▲{change} (was #{previous})")
+                }
+                (Some(change), Some(previous)) => format!("▼{} (was #{previous})", -change),
+                _ => "new".to_string(),
+            };
+            writeln!(
+                out,
+                "{:>width$}. {}: {} -> {} visits  {change}",
+                movement.recent_rank.unwrap_or_default(),
+                crate::utils::display_domain(&movement.domain, args),
+                crate::utils::format_count(movement.previous, args.humanize),
+                crate::utils::format_count(movement.recent, args.humanize),
+            )?;
+        }
+        let mut dropped: Vec<&RankMovement> = movements
+            .iter()
+            .filter(|m| m.previous_rank.is_some_and(|r| r <= top))
+            .filter(|m| m.recent_rank.is_none_or(|r| r > top))
+            .collect();
+        dropped.sort_by_key(|m| m.previous_rank);
+        let dropped: Vec<String> = dropped
+            .into_iter()
+            .map(|m| {
+                let now = m
+                    .recent_rank
+                    .map_or("unvisited".to_string(), |r| format!("now #{r}"));
+                format!(
+                    "{} (was #{}, {now})",
+                    crate::utils::display_domain(&m.domain, args),
+                    m.previous_rank.unwrap_or_default()
+                )
+            })
+            .collect();
+        if !dropped.is_empty() {
+            writeln!(out, "Fell out of the top {top}: {}", dropped.join(", "))?;
+        }
+    }
+
+    if let Some(novelty) = &result.novelty {
+        let series = novelty.series();
+        if !series.is_empty() {
+            writeln!(out, "\nNew vs returning domains per month:")?;
+        }
+        for month in series {
+            writeln!(
+                out,
+                "{}  {:>6} new  {:>6} returning  ({:.0}% new)",
+                month.month,
+                crate::utils::format_count(month.new, args.humanize),
+                crate::utils::format_count(month.returning, args.humanize),
+                month.rate() * 100.0
+            )?;
+        }
+    }
+
+    if let (Some(days), Some(spans)) = (args.stale, &result.spans) {
+        let now = Utc::now();
+        let stale = spans.stale(
+            &result.stats.domain_counts,
+            args.stale_min,
+            now - chrono::Duration::days(days.into()),
         );
-        for (domain, count) in bottom_sorted.iter().take(bottom_count) {
-            let display_domain = if args.redact {
-                crate::utils::redact_domain(domain)
+        writeln!(
+            out,
+            "\nDomains with {}+ visits not visited in the last {days} days:",
+            args.stale_min
+        )?;
+        if stale.is_empty() {
+            writeln!(out, "(none)")?;
+        }
+        for domain in stale.iter().take(args.top.unwrap_or(20)) {
+            let last = args.zone().local(domain.last).date();
+            writeln!(
+                out,
+                "- {}: {} visits, last on {} ({} days ago)",
+                crate::utils::display_domain(&domain.domain, args),
+                crate::utils::format_count(domain.count, args.humanize),
+                crate::utils::format_date(last, args, &DateFormat::Long),
+                (now - domain.last).num_days()
+            )?;
+        }
+    }
+
+    if let Some(transitions) = &result.transitions {
+        let total: u32 = transitions.overall.iter().sum();
+        writeln!(out, "\nVisits by transition type:")?;
+        for (transition, count) in TransitionStats::breakdown(&transitions.overall) {
+            writeln!(
+                out,
+                "- {}: {} ({:.1}%)",
+                transition,
+                crate::utils::format_count(count, args.humanize),
+                crate::utils::percent(count, total)
+            )?;
+        }
+
+        writeln!(out, "\nTransition types of top domains:")?;
+        for (domain, _) in result.stats.top(args.top.unwrap_or(10)) {
+            let Some(counts) = transitions.by_domain.get(&domain) else {
+                continue;
+            };
+            let domain_total: u32 = counts.iter().sum();
+            let summary: Vec<String> = TransitionStats::breakdown(counts)
+                .into_iter()
+                .map(|(transition, count)| {
+                    format!(
+                        "{} {:.0}%",
+                        transition,
+                        crate::utils::percent(count, domain_total)
+                    )
+                })
+                .collect();
+            let display_domain = crate::utils::display_domain(&domain, args);
+            writeln!(out, "- {}: {}", display_domain, summary.join(", "))?;
+        }
+    }
+
+    if let Some(navigation) = result.navigation.as_ref().filter(|_| args.nav_graph) {
+        let total = navigation.total();
+        writeln!(out, "\nMost common navigations between domains:")?;
+        for (from, to, count) in navigation.top_hops(args.top.unwrap_or(10)) {
+            writeln!(
+                out,
+                "- {} -> {}: {} ({:.1}%)",
+                crate::utils::display_domain(from, args),
+                crate::utils::display_domain(to, args),
+                crate::utils::format_count(count, args.humanize),
+                crate::utils::percent(count, total)
+            )?;
+        }
+
+        writeln!(out, "\nLinked domains of top domains:")?;
+        for (domain, _) in result.stats.top(args.top.unwrap_or(10)) {
+            let degree = navigation.degree(&domain);
+            writeln!(
+                out,
+                "- {}: {} in, {} out",
+                crate::utils::display_domain(&domain, args),
+                degree.incoming,
+                degree.outgoing
+            )?;
+        }
+    }
+
+    if let Some(referrers) = &result.referrers {
+        let domain = crate::utils::display_domain(&referrers.domain, args);
+        let total = referrers.total();
+        if total == 0 {
+            writeln!(out, "\nNo navigations from other domains to {domain}")?;
+        } else {
+            writeln!(
+                out,
+                "\nTop referrers to {domain} ({} navigations from other domains):",
+                crate::utils::format_count(total, args.humanize)
+            )?;
+            for (referrer, count) in referrers.referrers.iter().take(args.top.unwrap_or(10)) {
+                writeln!(
+                    out,
+                    "- {}: {} ({:.1}%)",
+                    crate::utils::display_domain(referrer, args),
+                    crate::utils::format_count(*count, args.humanize),
+                    crate::utils::percent(*count, total)
+                )?;
+            }
+        }
+    }
+
+    if let Some(searches) = &result.searches {
+        writeln!(
+            out,
+            "\nTop search terms ({} searches):",
+            crate::utils::format_count(searches.total(), args.humanize)
+        )?;
+        for (term, count) in searches.top(args.top.unwrap_or(10)) {
+            let display_term = if let Some(policy) = args.redact {
+                policy.text(&term)
             } else {
-                domain.to_string()
+                term
             };
-            println!(
-                "- {}: {} visits",
+            writeln!(
+                out,
+                "- {}: {}",
+                display_term,
+                crate::utils::format_count(count, args.humanize)
+            )?;
+        }
+    }
+
+    if let Some(schemes) = &result.schemes {
+        let kept = args.scheme_filter();
+        writeln!(out, "\nVisits by URL scheme:")?;
+        for (scheme, count) in schemes.sorted() {
+            let excluded = !kept.is_empty() && !kept.contains(&scheme);
+            writeln!(
+                out,
+                "- {}: {}{}",
+                scheme,
+                crate::utils::format_count(count, args.humanize),
+                if excluded { " (excluded)" } else { "" }
+            )?;
+        }
+    }
+
+    if let Some(hits) = &result.stats.pattern_hits {
+        let counts = hits.counts();
+        let width = counts
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "\nPattern hits (distinct hosts rewritten):")?;
+        for (label, count) in counts {
+            writeln!(
+                out,
+                "{:<width$}  {:>7}{}",
+                label,
+                crate::utils::format_count(count as u32, args.humanize),
+                if count == 0 { "  (unused)" } else { "" }
+            )?;
+        }
+    }
+
+    if let Some(downloads) = &result.downloads {
+        let total = downloads.total();
+        writeln!(
+            out,
+            "\nDownloads ({} files, {}):",
+            crate::utils::format_count(total.downloads, args.humanize),
+            crate::utils::format_bytes(total.bytes)
+        )?;
+        writeln!(out, "Top download sources:")?;
+        for (source, count) in downloads.top_sources(args.top.unwrap_or(10)) {
+            let display_source = crate::utils::display_domain(&source, args);
+            writeln!(
+                out,
+                "- {}: {} ({})",
+                display_source,
+                crate::utils::format_count(count.downloads, args.humanize),
+                crate::utils::format_bytes(count.bytes)
+            )?;
+        }
+        writeln!(out, "File types:")?;
+        for (file_type, count) in downloads.top_file_types(args.top.unwrap_or(10)) {
+            writeln!(
+                out,
+                "- {}: {} ({})",
+                file_type,
+                crate::utils::format_count(count.downloads, args.humanize),
+                crate::utils::format_bytes(count.bytes)
+            )?;
+        }
+        writeln!(out, "Downloaded per month:")?;
+        for (month, count) in &downloads.months {
+            writeln!(
+                out,
+                "  {}  {:>10}  {} {}",
+                month,
+                crate::utils::format_bytes(count.bytes),
+                crate::utils::format_count(count.downloads, args.humanize),
+                if count.downloads == 1 {
+                    "file"
+                } else {
+                    "files"
+                }
+            )?;
+        }
+    }
+
+    if let Some(counts) = result
+        .retention
+        .as_ref()
+        .and_then(|retention| retention.all_time_counts.as_ref())
+    {
+        let mut domains: Vec<(&String, u32, u32)> = counts
+            .iter()
+            .map(|(domain, all_time)| {
+                let kept = result.stats.domain_counts.get(domain).copied().unwrap_or(0);
+                (domain, kept, *all_time)
+            })
+            .collect();
+        domains.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+        domains.truncate(args.top.unwrap_or(10));
+        let names: Vec<String> = domains
+            .iter()
+            .map(|(domain, _, _)| crate::utils::display_domain(domain, args))
+            .collect();
+        let width = names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let count = |n: u32| crate::utils::format_count(n, args.humanize);
+        let unit = args.weight.unit();
+        writeln!(
+            out,
+            "\nRecent window ({unit}) vs all time (visit counters):"
+        )?;
+        for (name, (_, kept, all_time)) in names.iter().zip(&domains) {
+            writeln!(
+                out,
+                "- {name:<width$}  {:>8} recent  {:>8} all time  ({:.0}% kept)",
+                count(*kept),
+                count(*all_time),
+                crate::utils::percent(*kept, *all_time)
+            )?;
+        }
+    }
+
+    if let Some(segments) = &result.segments {
+        let totals = segments.totals();
+        let max = totals.iter().map(|(_, visits)| *visits).max().unwrap_or(0);
+        writeln!(out, "\nVisits per day (Top Sites counters):")?;
+        if totals.is_empty() {
+            writeln!(out, "No segment usage recorded")?;
+        }
+        // The browser keeps roughly three months of counters; show the latest weeks
+        for (day, visits) in totals
+            .iter()
+            .skip(totals.len().saturating_sub(SEGMENT_DAYS))
+        {
+            let top: Vec<String> = segments
+                .top(*day, 3)
+                .iter()
+                .map(|(domain, visits)| {
+                    format!(
+                        "{} {}",
+                        crate::utils::display_domain(domain, args),
+                        crate::utils::format_count(*visits, args.humanize)
+                    )
+                })
+                .collect();
+            writeln!(
+                out,
+                "  {}  {:<20}  {:>6}  {}",
+                crate::utils::format_date(*day, args, &DateFormat::Iso),
+                crate::utils::bar(*visits, max, 20),
+                crate::utils::format_count(*visits, args.humanize),
+                top.join(", ")
+            )?;
+        }
+    }
+
+    if let Some(media) = &result.media {
+        let total = media.total();
+        writeln!(
+            out,
+            "\nTop domains by media watch time ({} over {} playbacks):",
+            crate::utils::format_duration(total.watch_time()),
+            crate::utils::format_count(total.playbacks, args.humanize)
+        )?;
+        if total.playbacks == 0 {
+            writeln!(out, "No playbacks recorded")?;
+        }
+        for (domain, time) in media.top(args.top.unwrap_or(10)) {
+            writeln!(
+                out,
+                "- {}: {} ({:.0}% video, {} {})",
+                crate::utils::display_domain(domain, args),
+                crate::utils::format_duration(time.watch_time()),
+                time.video_seconds as f64 * 100.0 / time.watch_seconds.max(1) as f64,
+                crate::utils::format_count(time.playbacks, args.humanize),
+                if time.playbacks == 1 {
+                    "playback"
+                } else {
+                    "playbacks"
+                }
+            )?;
+        }
+    }
+
+    if let Some(keywords) = &result.keywords {
+        match &keywords.domain {
+            Some(domain) => {
+                let display_domain = crate::utils::display_domain(domain, args);
+                writeln!(out, "\nTop page-title keywords on {display_domain}:")?;
+            }
+            None => writeln!(out, "\nTop page-title keywords:")?,
+        }
+        for (word, count) in keywords.top(args.top.unwrap_or(20)) {
+            let display_word = if let Some(policy) = args.redact {
+                policy.text(&word)
+            } else {
+                word
+            };
+            writeln!(
+                out,
+                "- {}: {}",
+                display_word,
+                crate::utils::format_count(count, args.humanize)
+            )?;
+        }
+    }
+
+    if let Some(time_spent) = &result.time_spent {
+        writeln!(
+            out,
+            "\nTop domains by estimated time spent ({} total):",
+            crate::utils::format_duration(time_spent.total())
+        )?;
+        for (domain, duration) in time_spent.top(args.top.unwrap_or(10)) {
+            let display_domain = crate::utils::display_domain(&domain, args);
+            writeln!(
+                out,
+                "- {}: {}",
                 display_domain,
-                crate::utils::format_number(**count)
-            );
+                crate::utils::format_duration(duration)
+            )?;
+        }
+    }
+
+    if let Some(sessions) = &result.sessions {
+        writeln!(
+            out,
+            "\nSessions (split after {} idle):",
+            crate::utils::format_duration(sessions.gap)
+        )?;
+        writeln!(
+            out,
+            "- {} sessions, average length {}, longest {}",
+            crate::utils::format_count(sessions.sessions, args.humanize),
+            crate::utils::format_duration(sessions.average_duration()),
+            crate::utils::format_duration(sessions.longest)
+        )?;
+        writeln!(
+            out,
+            "- {:.1} domains per session on average",
+            sessions.average_domains()
+        )?;
+    }
+
+    if let Some(streaks) = &result.streaks {
+        writeln!(out, "\nLongest daily visit streaks:")?;
+        for streak in streaks.longest(args.zone().today(), 10) {
+            let display_domain = crate::utils::display_domain(&streak.domain, args);
+            let current = match streak.current {
+                0 => String::new(),
+                1 => ", current streak 1 day".to_string(),
+                days => format!(", current streak {days} days"),
+            };
+            writeln!(
+                out,
+                "- {}: {} days in a row (ended {}){}",
+                display_domain,
+                streak.longest,
+                crate::utils::format_date(streak.longest_end, args, &DateFormat::Long),
+                current
+            )?;
+        }
+    }
+
+    if !result.domain_ages.is_empty() {
+        writeln!(out, "\nYoungest frequently visited domains:")?;
+        for age in result.domain_ages.iter().take(10) {
+            let display_domain = crate::utils::display_domain(&age.domain, args);
+            writeln!(
+                out,
+                "- {}: registered {} ({} days ago), {} {}",
+                display_domain,
+                crate::utils::format_date(age.registered.date_naive(), args, &DateFormat::Long),
+                crate::utils::format_number(age.age_days() as u32),
+                crate::utils::format_count(age.visits, args.humanize),
+                args.weight.unit()
+            )?;
+        }
+    }
+
+    for report in &result.reports {
+        writeln!(out, "\n{}:", report.title)?;
+        for line in &report.lines {
+            writeln!(out, "{line}")?;
+        }
+    }
+
+    if !result.warnings.is_empty() {
+        writeln!(out, "\nSkipped due to errors (--best-effort):")?;
+        for warning in &result.warnings {
+            writeln!(out, "- {warning}")?;
         }
     }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::RedactionPolicy;
+
+    /// 2024-01-01 in Chromium's microseconds since 1601
+    const JANUARY: i64 = 13_348_224_000_000_000;
+    const HOUR: i64 = 3_600_000_000;
+
+    fn quiet(result: &AnalysisResult, redact: Option<RedactionPolicy>) -> String {
+        let args = Args {
+            quiet: true,
+            redact,
+            ..Args::defaults()
+        };
+        render_text(result, &args)
+    }
+
+    #[test]
+    fn quiet_output_follows_redaction() {
+        let args = Args::defaults();
+        let ctx = RunContext {
+            normalizer: Normalizer::default(),
+            analyses: Vec::new(),
+            categories: None,
+            filter: DomainFilter::new(&[], &[]).unwrap(),
+            sampling: None,
+            cancel: CancellationToken::new(),
+            progress: Progress::for_args(&args),
+        };
+        let mut result = empty_result(&args, &ctx, Vec::new());
+        result.stats.domain_counts = [
+            ("github.com".to_string(), 3),
+            ("example.org".to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(quiet(&result, None), "github.com\t3\nexample.org\t1\n");
+        assert_eq!(
+            quiet(&result, Some(RedactionPolicy::Stars)),
+            "******.com\t3\n*******.org\t1\n"
+        );
+
+        result.tlds = Some(TldBreakdown::from_counts(&result.stats.domain_counts));
+        assert_eq!(
+            quiet(&result, Some(RedactionPolicy::TldOnly)),
+            "com\t3\norg\t1\n"
+        );
+
+        let mut pages = PageStats::default();
+        pages.record("https://github.com/user/private-repo".to_string(), None, 2);
+        result.pages = Some(pages);
+        assert_eq!(
+            quiet(&result, None),
+            "https://github.com/user/private-repo\t2\n"
+        );
+        assert_eq!(
+            quiet(&result, Some(RedactionPolicy::Stars)),
+            "******.com/…\t2\n"
+        );
+    }
+
+    fn visit(conn: &Connection, url_id: i64, time: i64, transition: i64) {
+        conn.execute(
+            "INSERT INTO visits (url, visit_time, transition) VALUES (?1, ?2, ?3)",
+            (url_id, time, transition),
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE urls SET visit_count = visit_count + 1,
+                             typed_count = typed_count + (?2 = 1), last_visit_time = ?3
+             WHERE id = ?1",
+            (url_id, transition, time),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn resumed_cache_runs_match_cold_runs() {
+        let home = std::env::temp_dir().join(format!("historee-resume-{}", std::process::id()));
+        // Only this test reads the home and cache directories
+        for name in ["HOME", "USERPROFILE", "LOCALAPPDATA", "XDG_CACHE_HOME"] {
+            std::env::set_var(name, &home);
+        }
+        let history = Browser::Chrome.get_history_path().unwrap();
+        std::fs::create_dir_all(history.parent().unwrap()).unwrap();
+        let conn = Connection::open(&history).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE meta (key TEXT, value TEXT);
+             INSERT INTO meta VALUES ('version', '66');
+             CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT, title TEXT,
+                                visit_count INTEGER DEFAULT 0, typed_count INTEGER DEFAULT 0,
+                                last_visit_time INTEGER DEFAULT 0);
+             CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER,
+                                  from_visit INTEGER DEFAULT 0, transition INTEGER,
+                                  visit_duration INTEGER DEFAULT 0);
+             INSERT INTO urls (id, url, title) VALUES
+                 (1, 'https://github.com/a', 'a'), (2, 'https://github.com/b', 'b'),
+                 (3, 'https://example.com/', 'example');",
+        )
+        .unwrap();
+        visit(&conn, 1, JANUARY, 1);
+        visit(&conn, 1, JANUARY + HOUR, 1);
+        visit(&conn, 2, JANUARY + 2 * HOUR, 1);
+        visit(&conn, 3, JANUARY + 3 * HOUR, 0);
+
+        let run = |weight: Weight, no_cache: bool| {
+            let args = Args {
+                browser: Browser::Chrome,
+                weight,
+                no_cache,
+                temp_path: Some(home.join("copy.db")),
+                ..Args::defaults()
+            };
+            analyze_browser_history(&args).unwrap().stats.domain_counts
+        };
+        for (hour, weight) in [(4, Weight::Visits), (5, Weight::Typed)] {
+            run(weight, false);
+            // A later typed visit to a URL that was already counted makes the cache stale
+            visit(&conn, 1, JANUARY + hour * HOUR, 1);
+            let cold = run(weight, true);
+            assert_eq!(run(weight, false), cold, "{weight:?}");
+        }
+        assert_eq!(run(Weight::Typed, true).get("github.com"), Some(&5));
+
+        drop(conn);
+        std::fs::remove_dir_all(&home).unwrap();
+    }
 }
@@ -0,0 +1,372 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, info};
+
+use crate::args::Args;
+use crate::domain::{DomainFilter, HostClass, Normalizer};
+use crate::json::{self, Value};
+use crate::schema::Schema;
+use crate::sqlite::{self, VisitFilter};
+use crate::stats::{DateRange, DomainStats, RemovedHosts, Retention, Sampling, VisitTotals};
+use crate::utils;
+
+/// Per-domain and per-day aggregates of one browser's history, reusable while the
+/// database and the options that shaped them stay the same
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachedAggregates {
+    pub date_range: Option<DateRange>,
+    pub totals: Option<VisitTotals>,
+    /// Whole-database counter sums, without per-domain counts; `None` when they could not
+    /// be read
+    pub retention: Option<Retention>,
+    pub domain_counts: HashMap<String, u32>,
+    pub root_counts: HashMap<String, u32>,
+    pub domains_removed: RemovedHosts,
+}
+
+impl CachedAggregates {
+    pub fn stats(&self) -> DomainStats {
+        DomainStats {
+            domain_counts: self.domain_counts.clone(),
+            domains_removed: self.domains_removed,
+            root_counts: self.root_counts.clone(),
+            ..DomainStats::default()
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let counts = |counts: &HashMap<String, u32>| {
+            let mut counts: Vec<(&String, &u32)> = counts.iter().collect();
+            counts.sort();
+            Value::Object(
+                counts
+                    .into_iter()
+                    .map(|(domain, count)| (domain.clone(), (*count).into()))
+                    .collect(),
+            )
+        };
+        let mut doc = Value::object()
+            .with("domains", counts(&self.domain_counts))
+            .with("roots", counts(&self.root_counts))
+            .with(
+                "removed",
+                Value::Array(
+                    HostClass::ALL
+                        .iter()
+                        .map(|class| self.domains_removed.get(*class).into())
+                        .collect(),
+                ),
+            );
+        if let Some(range) = self.date_range {
+            doc = doc
+                .with("earliest", range.earliest.to_rfc3339())
+                .with("latest", range.latest.to_rfc3339());
+        }
+        if let Some(totals) = &self.totals {
+            doc = doc
+                .with("visits", totals.visits)
+                .with("unique_urls", totals.unique_urls)
+                .with(
+                    "daily",
+                    Value::Object(
+                        totals
+                            .daily
+                            .iter()
+                            .map(|(day, count)| (day.to_string(), (*count).into()))
+                            .collect(),
+                    ),
+                );
+        }
+        if let Some(retention) = &self.retention {
+            doc = doc
+                .with("all_time_visits", retention.all_time as i64)
+                .with("recorded_visits", retention.recorded as i64);
+        }
+        doc
+    }
+
+    fn from_json(doc: &Value) -> Result<Self> {
+        let count = |value: &Value| -> Result<u32> {
+            value
+                .as_i64()
+                .and_then(|count| u32::try_from(count).ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid count in cache entry"))
+        };
+        let counts = |key: &str| -> Result<HashMap<String, u32>> {
+            doc.get(key)
+                .and_then(Value::as_object)
+                .ok_or_else(|| anyhow::anyhow!("Cache entry is missing \"{key}\""))?
+                .iter()
+                .map(|(domain, value)| Ok((domain.clone(), count(value)?)))
+                .collect()
+        };
+        let time = |key: &str| -> Result<Option<DateTime<Utc>>> {
+            doc.get(key)
+                .and_then(Value::as_str)
+                .map(|time| {
+                    DateTime::parse_from_rfc3339(time)
+                        .map(|time| time.with_timezone(&Utc))
+                        .with_context(|| format!("Cache entry has an invalid \"{key}\""))
+                })
+                .transpose()
+        };
+
+        let mut domains_removed = RemovedHosts::default();
+        let removed = doc
+            .get("removed")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow::anyhow!("Cache entry is missing \"removed\""))?;
+        for (class, value) in HostClass::ALL.into_iter().zip(removed) {
+            domains_removed.add(class, count(value)?);
+        }
+
+        let date_range = match (time("earliest")?, time("latest")?) {
+            (Some(earliest), Some(latest)) => Some(DateRange { earliest, latest }),
+            _ => None,
+        };
+        let totals = match (doc.get("visits"), doc.get("unique_urls"), doc.get("daily")) {
+            (Some(visits), Some(unique_urls), Some(daily)) => {
+                let daily = daily
+                    .as_object()
+                    .ok_or_else(|| anyhow::anyhow!("Cache entry has an invalid \"daily\""))?
+                    .iter()
+                    .map(|(day, value)| {
+                        let day = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                            .with_context(|| format!("Invalid day {day} in cache entry"))?;
+                        Ok((day, count(value)?))
+                    })
+                    .collect::<Result<BTreeMap<_, _>>>()?;
+                Some(VisitTotals {
+                    visits: count(visits)?,
+                    unique_urls: count(unique_urls)?,
+                    daily,
+                })
+            }
+            _ => None,
+        };
+
+        let retention = match (doc.get("all_time_visits"), doc.get("recorded_visits")) {
+            (Some(all_time), Some(recorded)) => {
+                let total = |value: &Value| -> Result<u64> {
+                    value
+                        .as_i64()
+                        .and_then(|total| u64::try_from(total).ok())
+                        .ok_or_else(|| anyhow::anyhow!("Invalid visit total in cache entry"))
+                };
+                Some(Retention {
+                    all_time: total(all_time)?,
+                    recorded: total(recorded)?,
+                    all_time_counts: None,
+                })
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            date_range,
+            totals,
+            retention,
+            domain_counts: counts("domains")?,
+            root_counts: counts("roots")?,
+            domains_removed,
+        })
+    }
+}
+
+/// Layout of cache entries, part of the options key so entries missing newer fields are
+/// rebuilt
+const ENTRY_FORMAT: u32 = 2;
+
+/// FNV-1a over `parts`, each followed by a separator byte
+fn fnv(parts: &[&[u8]]) -> u64 {
+    let mut hash = utils::Fnv::default();
+    for part in parts {
+        hash.write(part);
+        hash.write(&[0xff]);
+    }
+    hash.finish()
+}
+
+/// Modification time and size of a file, or `-` when it does not exist
+fn file_state(path: &Path) -> String {
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_nanos());
+            format!("{modified}:{}", metadata.len())
+        }
+        Err(_) => "-".to_string(),
+    }
+}
+
+/// What the cache holds for a database
+pub enum Lookup {
+    /// Aggregates of the unchanged database
+    Hit(CachedAggregates),
+    /// Aggregates read with the same options from an older state of the database
+    Stale(CachedAggregates),
+    Miss,
+}
+
+/// Cache slot of one history database, valid for as long as its key matches
+pub struct ResultCache {
+    path: PathBuf,
+    /// Hash of the options that shaped the aggregates
+    options: String,
+    /// Modification time and size of the database and its write-ahead log
+    state: String,
+}
+
+impl ResultCache {
+    /// The slot for `history` as read with these options; the state covers the database
+    /// and its write-ahead log, so any change to either invalidates the entry
+    pub fn new(
+        browser: &str,
+        history: &Path,
+        args: &Args,
+        normalizer: &Normalizer,
+        filter: &DomainFilter,
+        sampling: Option<Sampling>,
+    ) -> Result<Self> {
+        let mut wal = history.as_os_str().to_owned();
+        wal.push("-wal");
+        let options = format!(
+            "{}|{ENTRY_FORMAT}|{}|{:?}|{sampling:?}|{:?}|{:?}|{:?}|{:?}|{}|{filter:?}",
+            env!("CARGO_PKG_VERSION"),
+            normalizer.fingerprint(),
+            args.weight,
+            args.since,
+            args.until,
+            args.zone(),
+            args.scheme_filter(),
+            args.collapse_redirects,
+        );
+        let slot = fnv(&[browser.as_bytes(), history.as_os_str().as_encoded_bytes()]);
+        Ok(Self {
+            path: utils::cache_dir()?
+                .join("results")
+                .join(format!("{slot:016x}.json")),
+            options: format!("{:016x}", fnv(&[options.as_bytes()])),
+            state: format!("{}|{}", file_state(history), file_state(Path::new(&wal))),
+        })
+    }
+
+    /// The cached aggregates, if the entry exists and was built with the same options
+    pub fn lookup(&self) -> Lookup {
+        let Ok(content) = std::fs::read_to_string(&self.path) else {
+            return Lookup::Miss;
+        };
+        let doc = match json::parse(&content) {
+            Ok(doc) => doc,
+            Err(e) => {
+                debug!(action = "parse", component = "cache", path = ?self.path, error = %e, "Ignoring unreadable cache entry");
+                return Lookup::Miss;
+            }
+        };
+        if doc.get("options").and_then(Value::as_str) != Some(self.options.as_str()) {
+            debug!(action = "miss", component = "cache", path = ?self.path, "Cache entry was built with other options");
+            return Lookup::Miss;
+        }
+        let aggregates = match CachedAggregates::from_json(&doc) {
+            Ok(aggregates) => aggregates,
+            Err(e) => {
+                debug!(action = "parse", component = "cache", path = ?self.path, error = %e, "Ignoring invalid cache entry");
+                return Lookup::Miss;
+            }
+        };
+        if doc.get("state").and_then(Value::as_str) == Some(self.state.as_str()) {
+            info!(action = "hit", component = "cache", path = ?self.path, "Reusing cached domain counts");
+            Lookup::Hit(aggregates)
+        } else {
+            debug!(action = "stale", component = "cache", path = ?self.path, "Database changed since it was cached");
+            Lookup::Stale(aggregates)
+        }
+    }
+
+    pub fn store(&self, aggregates: &CachedAggregates) -> Result<()> {
+        let doc = aggregates
+            .to_json()
+            .with("options", self.options.as_str())
+            .with("state", self.state.as_str());
+        utils::write_atomic(&self.path, &format!("{doc}\n"))?;
+        info!(action = "store", component = "cache", path = ?self.path, "Cached domain counts");
+        Ok(())
+    }
+}
+
+/// Cached aggregates still valid for the visits up to their last one, the watermark,
+/// so only visits after it need to be scanned
+pub struct Resumed {
+    pub previous: CachedAggregates,
+    pub watermark: DateTime<Utc>,
+    /// Distinct URLs across the whole window, which can't be summed from the two parts
+    pub unique_urls: u32,
+}
+
+impl Resumed {
+    /// Checks that history up to the watermark is unchanged, since deleted or late-synced
+    /// visits there would make the cached counts wrong
+    pub fn check(
+        conn: &Connection,
+        schema: &Schema,
+        window: &VisitFilter<'_>,
+        previous: CachedAggregates,
+    ) -> Result<Option<Self>> {
+        let (Some(range), Some(totals)) = (previous.date_range, &previous.totals) else {
+            return Ok(None);
+        };
+        let (visits, _) = sqlite::count_visits(conn, schema, &window.through(range.latest))?;
+        if visits != totals.visits {
+            info!(
+                action = "rescan",
+                component = "cache",
+                cached = totals.visits,
+                visits,
+                "History before the cached watermark changed; scanning everything"
+            );
+            return Ok(None);
+        }
+        let (_, unique_urls) = sqlite::count_visits(conn, schema, window)?;
+        info!(
+            action = "resume",
+            component = "cache",
+            watermark = %range.latest,
+            "Scanning only visits after the cached watermark"
+        );
+        Ok(Some(Self {
+            previous,
+            watermark: range.latest,
+            unique_urls,
+        }))
+    }
+
+    /// Adds the aggregates of the visits after the watermark to the cached ones; removed-host
+    /// counts are per URL, so a revisited removed URL is counted again until a full scan
+    pub fn extend(
+        self,
+        date_range: Option<DateRange>,
+        totals: Option<VisitTotals>,
+        stats: DomainStats,
+    ) -> (Option<DateRange>, Option<VisitTotals>, DomainStats) {
+        let date_range = match (self.previous.date_range, date_range) {
+            (Some(previous), Some(range)) => Some(previous.merge(range)),
+            (previous, range) => range.or(previous),
+        };
+        let totals = totals.map(|totals| {
+            let mut merged = self.previous.totals.clone().unwrap_or_default();
+            merged.merge(&totals);
+            merged.unique_urls = self.unique_urls;
+            merged
+        });
+        let mut merged = self.previous.stats();
+        merged.merge(stats);
+        (date_range, totals, merged)
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+// Include default categories at compile time
+const DEFAULT_CATEGORIES: &str = include_str!("../default_categories.txt");
+
+/// Category name reported for domains not listed in the mapping
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// Mapping from domains to user-defined categories
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Categories {
+    by_domain: HashMap<String, String>,
+}
+
+impl Categories {
+    /// Parses `category: domain, domain` lines; later lines win for duplicate domains
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut by_domain = HashMap::new();
+        for (line_num, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (category, domains) = line.split_once(':').ok_or_else(|| {
+                anyhow::anyhow!("Expected 'category: domain, ...' at line {}", line_num + 1)
+            })?;
+            let category = category.trim();
+            if category.is_empty() {
+                anyhow::bail!("Missing category name at line {}", line_num + 1);
+            }
+            for domain in domains.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+                by_domain.insert(domain.to_lowercase(), category.to_string());
+            }
+        }
+        Ok(Self { by_domain })
+    }
+
+    /// Loads the given file, else `categories.txt` in the config directory or the working
+    /// directory, else the embedded defaults
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        if let Some(path) = path {
+            if !path.exists() {
+                anyhow::bail!("Category file not found: {:?}", path);
+            }
+            return Self::load_file(path);
+        }
+
+        if let Some(default_file) = crate::utils::find_config_file(&["categories.txt"]) {
+            return Self::load_file(&default_file);
+        }
+
+        info!(
+            action = "load",
+            component = "embedded_categories",
+            "Using embedded default categories"
+        );
+        Self::parse(DEFAULT_CATEGORIES).context("Failed to parse embedded default categories")
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let categories =
+            Self::parse(&content).with_context(|| format!("Failed to load {path:?}"))?;
+        info!(action = "loaded", component = "category_file", domain_count = categories.by_domain.len(), file_path = ?path, "Loaded categories from file");
+        Ok(categories)
+    }
+
+    /// Category of the most specific listed domain that `domain` equals or is a subdomain of
+    pub fn category_of(&self, domain: &str) -> Option<&str> {
+        let mut candidate = domain;
+        loop {
+            if let Some(category) = self.by_domain.get(candidate) {
+                return Some(category);
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+}
+
+/// Domain counts summed per category
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CategoryBreakdown {
+    pub counts: HashMap<String, u32>,
+}
+
+impl CategoryBreakdown {
+    pub fn from_counts(categories: &Categories, domain_counts: &HashMap<String, u32>) -> Self {
+        let mut counts = HashMap::new();
+        for (domain, count) in domain_counts {
+            let category = categories.category_of(domain).unwrap_or(UNCATEGORIZED);
+            *counts.entry(category.to_string()).or_insert(0) += count;
+        }
+        Self { counts }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    /// Categories by descending count, ties broken alphabetically
+    pub fn sorted(&self) -> Vec<(String, u32)> {
+        let mut categories: Vec<(String, u32)> = self
+            .counts
+            .iter()
+            .map(|(category, count)| (category.clone(), *count))
+            .collect();
+        categories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        categories
+    }
+}
+
+/// Length of the busiest stretch of the day reported for each category
+pub const PEAK_HOURS: usize = 3;
+
+/// Visits per category and local hour of day, collected with `--category-hours`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CategoryHours {
+    pub by_category: HashMap<String, [u32; 24]>,
+}
+
+impl CategoryHours {
+    pub fn record(&mut self, category: &str, hour: usize) {
+        match self.by_category.get_mut(category) {
+            Some(hours) => hours[hour] += 1,
+            None => {
+                let mut hours = [0; 24];
+                hours[hour] = 1;
+                self.by_category.insert(category.to_string(), hours);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &CategoryHours) {
+        for (category, other_hours) in &other.by_category {
+            let hours = self.by_category.entry(category.clone()).or_insert([0; 24]);
+            for (total, count) in hours.iter_mut().zip(other_hours) {
+                *total += count;
+            }
+        }
+    }
+
+    /// Categories with their hours, most visited first, ties broken alphabetically
+    pub fn sorted(&self) -> Vec<(&str, &[u32; 24])> {
+        let mut categories: Vec<(&str, &[u32; 24])> = self
+            .by_category
+            .iter()
+            .map(|(category, hours)| (category.as_str(), hours))
+            .collect();
+        categories.sort_by(|a, b| {
+            let total = |hours: &[u32; 24]| hours.iter().sum::<u32>();
+            total(b.1).cmp(&total(a.1)).then_with(|| a.0.cmp(b.0))
+        });
+        categories
+    }
+}
+
+/// First hour of the `PEAK_HOURS` consecutive hours with the most visits, wrapping past
+/// midnight; the earliest wins ties
+pub fn peak_start(hours: &[u32; 24]) -> usize {
+    let window = |start: usize| -> u32 { (0..PEAK_HOURS).map(|i| hours[(start + i) % 24]).sum() };
+    (0..24)
+        .max_by(|a, b| window(*a).cmp(&window(*b)).then_with(|| b.cmp(a)))
+        .unwrap_or(0)
+}
@@ -0,0 +1,154 @@
+//! The command-line program behind the `historee` binary
+
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use tracing::{error, warn};
+
+use crate::args::{Args, Browser, Command};
+use crate::config::{self, Config};
+use crate::{
+    browser, compare, completions, digest, doctor, exit, export, find, logging, patterns, query,
+    report, schedule, setup, show, snapshot, utils, watch, wrapped,
+};
+
+/// Logs the error and exits with the code matching it
+fn fail(e: anyhow::Error) -> ! {
+    error!("Error: {e:#}");
+    std::process::exit(exit::for_error(&e));
+}
+
+/// Parses the command line and runs the requested analysis or subcommand
+pub fn run() -> Result<()> {
+    let matches = match Args::command().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) if e.use_stderr() => {
+            let _ = e.print();
+            std::process::exit(exit::USAGE);
+        }
+        Err(e) => e.exit(),
+    };
+    let mut args = match Args::from_arg_matches(&matches) {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(exit::USAGE);
+        }
+    };
+    if let Err(e) = logging::setup(&args) {
+        eprintln!("Error: {e:#}");
+        std::process::exit(exit::USAGE);
+    }
+
+    // These commands inspect or rewrite the config file, so a broken one must not stop them
+    let repairs_config = matches!(
+        args.command,
+        Some(Command::Config(_) | Command::Setup | Command::Doctor)
+    );
+    match Config::load() {
+        Ok(Some(config)) => config.apply(&mut args, &matches),
+        Ok(None) => {}
+        Err(e) if repairs_config => {
+            warn!(action = "load", component = "config", error = %format!("{e:#}"), "Ignoring the config file")
+        }
+        Err(e) => fail(e),
+    }
+    // Takeout exports come from Chrome, so report them as such unless --browser says otherwise
+    if args.takeout.is_some() && matches.value_source("browser") != Some(ValueSource::CommandLine) {
+        args.browser = Browser::Chrome;
+    }
+
+    // Handle --init option
+    if args.init {
+        match patterns::init_default_patterns() {
+            Ok(()) => return Ok(()),
+            Err(e) => fail(e),
+        }
+    }
+
+    // Every command that reads history takes the analysis options, so check them first
+    let reads_history = !matches!(
+        args.command,
+        Some(
+            Command::Config(_)
+                | Command::Setup
+                | Command::Doctor
+                | Command::Completions { .. }
+                | Command::Manpage
+        )
+    );
+    if reads_history {
+        if let Err(e) = utils::validate_args(&args) {
+            error!("Error: {e:#}");
+            std::process::exit(exit::USAGE);
+        }
+    }
+
+    if let Some(command) = &args.command {
+        let outcome = match command {
+            Command::Schedule(schedule_command) => schedule::run(schedule_command),
+            Command::Setup => setup::run(),
+            Command::Patterns(patterns_command) => patterns::run(patterns_command, &args),
+            Command::Config(config_command) => config::run(config_command),
+            Command::Doctor => doctor::run(&args),
+            Command::Completions { shell } => {
+                print!("{}", completions::generate(*shell));
+                Ok(())
+            }
+            Command::Manpage => {
+                print!("{}", completions::manpage());
+                Ok(())
+            }
+            Command::Snapshot(snapshot_command) => snapshot::run(snapshot_command, &args),
+            Command::Diff { before, after } => snapshot::run_diff(before, after, &args),
+            Command::Merge { snapshots, save } => {
+                snapshot::run_merge(snapshots, save.as_deref(), &args)
+            }
+            Command::Digest { period, format } => digest::run(*period, *format, &args),
+            Command::Wrapped { year } => wrapped::run(*year, &args),
+            Command::Compare { browsers } => compare::run(browsers, &args),
+            Command::Export(export_command) => export::run(export_command, &args),
+            Command::Query {
+                sql,
+                browser,
+                format,
+            } => query::run(sql, *browser, *format, &args),
+            Command::Search { term, limit } => find::run(term, *limit, &args),
+            Command::First { domain } => find::first(domain, &args),
+            Command::Show { domain } => show::run(domain, &args),
+            Command::Watch {
+                interval,
+                sinks,
+                metrics_file,
+            } => watch::run(*interval, sinks, metrics_file.as_deref(), &args),
+            #[cfg(feature = "serve")]
+            Command::Serve {
+                bind,
+                cache_ttl,
+                allow_origin,
+            } => crate::serve::run(bind, *cache_ttl, allow_origin.as_deref(), &args),
+            #[cfg(feature = "tui")]
+            Command::Tui => crate::tui::run(&args),
+        };
+        if let Err(e) = outcome {
+            fail(e);
+        }
+        return Ok(());
+    }
+
+    match browser::analyze_browser_history(&args) {
+        Ok(result) => {
+            if let Err(e) = report::write_report(&result, &args) {
+                fail(e);
+            }
+            if let Some(timings) = &result.timings {
+                eprint!("{timings}");
+            }
+            if result.stats.domain_counts.is_empty() {
+                std::process::exit(exit::NO_DATA);
+            }
+            Ok(())
+        }
+        Err(e) => fail(e),
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::args::{Args, Browser};
+use crate::{browser, utils};
+
+/// Per-browser domain counts from separate analyses of the same options
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Comparison {
+    pub browsers: Vec<Browser>,
+    /// Counts per domain, one column per browser in `browsers` order
+    pub counts: HashMap<String, Vec<u32>>,
+}
+
+impl Comparison {
+    pub fn new(browsers: &[Browser], args: &Args) -> Result<Self> {
+        let mut comparison = Comparison {
+            browsers: browsers.to_vec(),
+            counts: HashMap::new(),
+        };
+        for (column, browser) in browsers.iter().enumerate() {
+            let mut browser_args = args.clone();
+            browser_args.browser = *browser;
+            browser_args.all_browsers = false;
+            let result = browser::analyze_browser_history(&browser_args)?;
+            for (domain, count) in result.stats.domain_counts {
+                comparison
+                    .counts
+                    .entry(domain)
+                    .or_insert_with(|| vec![0; browsers.len()])[column] = count;
+            }
+        }
+        Ok(comparison)
+    }
+
+    /// Domains ordered by their combined count, highest first
+    pub fn ranked(&self) -> Vec<(&String, &Vec<u32>)> {
+        let mut ranked: Vec<_> = self.counts.iter().collect();
+        ranked.sort_by(|a, b| {
+            let total = |counts: &Vec<u32>| counts.iter().sum::<u32>();
+            total(b.1).cmp(&total(a.1)).then_with(|| a.0.cmp(b.0))
+        });
+        ranked
+    }
+
+    /// Number of domains visited only in the browser at `column`
+    pub fn exclusive(&self, column: usize) -> usize {
+        self.counts
+            .values()
+            .filter(|counts| {
+                counts
+                    .iter()
+                    .enumerate()
+                    .all(|(i, count)| (i == column) == (*count > 0))
+            })
+            .count()
+    }
+
+    /// Number of domains visited in every browser
+    pub fn shared(&self) -> usize {
+        self.counts
+            .values()
+            .filter(|counts| counts.iter().all(|count| *count > 0))
+            .count()
+    }
+}
+
+pub fn run(browsers: &[Browser], args: &Args) -> Result<()> {
+    let mut unique: Vec<Browser> = Vec::new();
+    for browser in browsers {
+        if !unique.contains(browser) {
+            unique.push(*browser);
+        }
+    }
+    let browsers = unique;
+    if browsers.len() < 2 {
+        anyhow::bail!("compare needs at least two different browsers (--browser A --browser B)");
+    }
+    let comparison = Comparison::new(&browsers, args)?;
+    let mut out = String::new();
+    if args.quiet {
+        write_quiet(&mut out, &comparison, args)
+    } else {
+        write_comparison(&mut out, &comparison, args)
+    }
+    .expect("writing to a String cannot fail");
+    print!("{out}");
+    Ok(())
+}
+
+/// Writes `domain<TAB>count...` lines, one count per browser
+fn write_quiet(out: &mut String, comparison: &Comparison, args: &Args) -> fmt::Result {
+    let ranked = comparison.ranked();
+    let limit = args.top.unwrap_or(ranked.len());
+    for (domain, counts) in ranked.into_iter().take(limit) {
+        let counts: Vec<String> = counts.iter().map(u32::to_string).collect();
+        writeln!(out, "{domain}\t{}", counts.join("\t"))?;
+    }
+    Ok(())
+}
+
+fn write_comparison(out: &mut String, comparison: &Comparison, args: &Args) -> fmt::Result {
+    let names: Vec<String> = comparison.browsers.iter().map(Browser::to_string).collect();
+    writeln!(out, "\n--- {} ---", names.join(" vs "))?;
+
+    let ranked = comparison.ranked();
+    let listed: Vec<(String, &Vec<u32>)> = ranked
+        .into_iter()
+        .take(args.top.unwrap_or(20))
+        .map(|(domain, counts)| (utils::display_domain(domain, args), counts))
+        .collect();
+    let cells: Vec<Vec<String>> = listed
+        .iter()
+        .map(|(_, counts)| {
+            counts
+                .iter()
+                .map(|count| match count {
+                    0 => "-".to_string(),
+                    count => utils::format_count(*count, args.humanize),
+                })
+                .collect()
+        })
+        .collect();
+    let domain_width = listed
+        .iter()
+        .map(|(domain, _)| domain.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Domain".len());
+    let widths: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            cells
+                .iter()
+                .map(|row| row[column].chars().count())
+                .chain([name.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut header = format!("{:<domain_width$}", "Domain");
+    for (name, width) in names.iter().zip(&widths) {
+        header.push_str(&format!("  {name:>width$}"));
+    }
+    writeln!(out, "{header}")?;
+    for ((domain, counts), row) in listed.iter().zip(&cells) {
+        let mut line = format!("{domain:<domain_width$}");
+        for (cell, width) in row.iter().zip(&widths) {
+            line.push_str(&format!("  {cell:>width$}"));
+        }
+        let only: Vec<usize> = (0..counts.len()).filter(|i| counts[*i] > 0).collect();
+        if let [column] = only[..] {
+            line.push_str(&format!("  only {}", names[column]));
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    writeln!(out)?;
+    for (column, name) in names.iter().enumerate() {
+        writeln!(
+            out,
+            "Exclusive to {name}: {} domains",
+            comparison.exclusive(column)
+        )?;
+    }
+    writeln!(out, "Shared by all: {} domains", comparison.shared())?;
+    Ok(())
+}
@@ -0,0 +1,488 @@
+use clap::builder::PossibleValue;
+use clap::{Arg, ArgAction, Command, CommandFactory, ValueHint};
+use std::any::TypeId;
+use std::fmt::Write;
+
+use crate::args::{Args, Shell};
+
+/// The fully built command tree, including clap's generated help and version flags
+fn command() -> Command {
+    let mut command = Args::command();
+    command.build();
+    command
+}
+
+fn options(command: &Command) -> impl Iterator<Item = &Arg> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+}
+
+fn positionals(command: &Command) -> impl Iterator<Item = &Arg> {
+    command.get_positionals().filter(|arg| !arg.is_hide_set())
+}
+
+fn subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+fn is_repeatable(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Append | ArgAction::Count)
+}
+
+fn is_path(arg: &Arg) -> bool {
+    matches!(
+        arg.get_value_hint(),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath
+    ) || arg.get_value_parser().type_id() == TypeId::of::<std::path::PathBuf>()
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(PossibleValue::get_name)
+        .map(str::to_string)
+        .collect()
+}
+
+fn value_name(arg: &Arg) -> String {
+    arg.get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase())
+}
+
+/// First line of an argument's or command's help
+fn summary(help: Option<&clap::builder::StyledStr>) -> String {
+    help.map(|help| help.to_string())
+        .and_then(|help| help.lines().next().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn flags(arg: &Arg) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{short}"));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{long}"));
+    }
+    flags
+}
+
+/// Completion script for `shell`, to be sourced or placed in the shell's completion directory
+pub fn generate(shell: Shell) -> String {
+    let command = command();
+    match shell {
+        Shell::Bash => bash(&command),
+        Shell::Zsh => zsh(&command),
+        Shell::Fish => fish(&command),
+    }
+}
+
+fn bash(root: &Command) -> String {
+    let name = root.get_name();
+    let mut transitions = String::new();
+    let mut cases = String::new();
+
+    fn walk(command: &Command, key: &str, transitions: &mut String, cases: &mut String) {
+        let mut words: Vec<String> = options(command).flat_map(flags).collect();
+        words.extend(positionals(command).flat_map(values));
+        for sub in subcommands(command) {
+            let sub_key = format!("{key}__{}", sub.get_name());
+            let _ = writeln!(transitions, "            {sub_key}) cmd=\"{sub_key}\" ;;");
+            words.push(sub.get_name().to_string());
+        }
+
+        let _ = writeln!(cases, "        {key})");
+        let value_options: Vec<&Arg> = options(command).filter(|arg| takes_value(arg)).collect();
+        if !value_options.is_empty() {
+            let _ = writeln!(cases, "            case \"${{prev}}\" in");
+            for arg in value_options {
+                let completion = if is_path(arg) {
+                    "COMPREPLY=($(compgen -f -- \"${cur}\"))".to_string()
+                } else {
+                    let values = values(arg);
+                    if values.is_empty() {
+                        String::new()
+                    } else {
+                        format!(
+                            "COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+                            values.join(" ")
+                        )
+                    }
+                };
+                let _ = writeln!(
+                    cases,
+                    "                {}) {}{}return 0 ;;",
+                    flags(arg).join("|"),
+                    completion,
+                    if completion.is_empty() { "" } else { "; " }
+                );
+            }
+            let _ = writeln!(cases, "            esac");
+        }
+        let _ = writeln!(
+            cases,
+            "            COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+            words.join(" ")
+        );
+        let _ = writeln!(cases, "            ;;");
+
+        for sub in subcommands(command) {
+            walk(
+                sub,
+                &format!("{key}__{}", sub.get_name()),
+                transitions,
+                cases,
+            );
+        }
+    }
+    walk(root, name, &mut transitions, &mut cases);
+
+    format!(
+        "_{name}() {{\n\
+         \x20   local cur prev cmd i\n\
+         \x20   COMPREPLY=()\n\
+         \x20   cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n\
+         \x20   prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n\
+         \x20   cmd=\"{name}\"\n\
+         \x20   for ((i = 1; i < COMP_CWORD; i++)); do\n\
+         \x20       case \"${{cmd}}__${{COMP_WORDS[i]}}\" in\n\
+         {transitions}\
+         \x20       esac\n\
+         \x20   done\n\
+         \x20   case \"${{cmd}}\" in\n\
+         {cases}\
+         \x20   esac\n\
+         }}\n\
+         complete -F _{name} -o bashdefault -o default {name}\n"
+    )
+}
+
+/// Escapes text for a single-quoted zsh `_arguments` description
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh(root: &Command) -> String {
+    let mut out = format!("#compdef {}\n", root.get_name());
+
+    fn walk(command: &Command, function: &str, out: &mut String) {
+        let mut specs = Vec::new();
+        for arg in options(command) {
+            let flags = flags(arg);
+            let help = zsh_escape(&summary(arg.get_help()));
+            let exclusion = if is_repeatable(arg) {
+                "'*'".to_string()
+            } else if flags.len() > 1 {
+                format!("'({})'", flags.join(" "))
+            } else {
+                String::new()
+            };
+            let names = if flags.len() > 1 {
+                format!("{{{}}}", flags.join(","))
+            } else {
+                flags.join("")
+            };
+            let value = if !takes_value(arg) {
+                String::new()
+            } else if is_path(arg) {
+                format!(":{}:_files", value_name(arg).to_lowercase())
+            } else {
+                let values = values(arg);
+                format!(
+                    ":{}:{}",
+                    value_name(arg).to_lowercase(),
+                    if values.is_empty() {
+                        String::new()
+                    } else {
+                        format!("({})", values.join(" "))
+                    }
+                )
+            };
+            specs.push(format!("{exclusion}{names}'[{help}]{value}'"));
+        }
+
+        let children: Vec<&Command> = subcommands(command).collect();
+        if children.is_empty() {
+            for (index, arg) in positionals(command).enumerate() {
+                let name = value_name(arg).to_lowercase();
+                let values = values(arg);
+                let action = if is_path(arg) {
+                    "_files".to_string()
+                } else if values.is_empty() {
+                    String::new()
+                } else {
+                    format!("({})", values.join(" "))
+                };
+                if arg.is_last_set() || is_repeatable(arg) {
+                    specs.push(format!("'*::{name}:{action}'"));
+                } else {
+                    specs.push(format!("'{}:{name}:{action}'", index + 1));
+                }
+            }
+        } else {
+            specs.push("'1: :->command'".to_string());
+            specs.push("'*:: :->args'".to_string());
+        }
+
+        let _ = writeln!(out, "\n{function}() {{");
+        if !children.is_empty() {
+            let _ = writeln!(out, "    local line state");
+        }
+        let _ = write!(out, "    _arguments -C");
+        for spec in &specs {
+            let _ = write!(out, " \\\n        {spec}");
+        }
+        let _ = writeln!(out);
+
+        if !children.is_empty() {
+            let _ = writeln!(out, "    case $state in");
+            let _ = writeln!(out, "        command)");
+            let _ = writeln!(out, "            local -a commands");
+            let _ = writeln!(out, "            commands=(");
+            for sub in &children {
+                let _ = writeln!(
+                    out,
+                    "                '{}:{}'",
+                    sub.get_name(),
+                    zsh_escape(&summary(sub.get_about()))
+                );
+            }
+            let _ = writeln!(out, "            )");
+            let _ = writeln!(out, "            _describe 'command' commands ;;");
+            let _ = writeln!(out, "        args)");
+            let _ = writeln!(out, "            case $line[1] in");
+            for sub in &children {
+                let _ = writeln!(
+                    out,
+                    "                {}) {function}__{} ;;",
+                    sub.get_name(),
+                    sub.get_name()
+                );
+            }
+            let _ = writeln!(out, "            esac ;;");
+            let _ = writeln!(out, "    esac");
+        }
+        let _ = writeln!(out, "}}");
+
+        for sub in children {
+            walk(sub, &format!("{function}__{}", sub.get_name()), out);
+        }
+    }
+    let function = format!("_{}", root.get_name());
+    walk(root, &function, &mut out);
+    let _ = writeln!(out, "\n{function} \"$@\"");
+    out
+}
+
+/// Escapes text for a single-quoted fish string
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(root: &Command) -> String {
+    let mut out = String::new();
+
+    fn walk(command: &Command, path: &[&str], root_name: &str, out: &mut String) {
+        let children: Vec<&Command> = subcommands(command).collect();
+        let mut condition = if path.is_empty() {
+            "__fish_use_subcommand".to_string()
+        } else {
+            path.iter()
+                .map(|name| format!("__fish_seen_subcommand_from {name}"))
+                .collect::<Vec<_>>()
+                .join("; and ")
+        };
+        if !path.is_empty() && !children.is_empty() {
+            let names: Vec<&str> = children.iter().map(|sub| sub.get_name()).collect();
+            condition.push_str(&format!(
+                "; and not __fish_seen_subcommand_from {}",
+                names.join(" ")
+            ));
+        }
+
+        for arg in options(command) {
+            let mut line = format!("complete -c {root_name} -n \"{condition}\"");
+            if let Some(short) = arg.get_short() {
+                line.push_str(&format!(" -s {short}"));
+            }
+            if let Some(long) = arg.get_long() {
+                line.push_str(&format!(" -l {long}"));
+            }
+            line.push_str(&format!(" -d '{}'", fish_escape(&summary(arg.get_help()))));
+            if takes_value(arg) {
+                if is_path(arg) {
+                    line.push_str(" -r -F");
+                } else {
+                    let values = values(arg);
+                    line.push_str(" -r -f");
+                    if !values.is_empty() {
+                        line.push_str(&format!(" -a \"{}\"", values.join(" ")));
+                    }
+                }
+            }
+            let _ = writeln!(out, "{line}");
+        }
+        for arg in positionals(command) {
+            let values = values(arg);
+            if !values.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "complete -c {root_name} -n \"{condition}\" -f -a \"{}\"",
+                    values.join(" ")
+                );
+            }
+        }
+        for sub in &children {
+            let _ = writeln!(
+                out,
+                "complete -c {root_name} -n \"{condition}\" -f -a \"{}\" -d '{}'",
+                sub.get_name(),
+                fish_escape(&summary(sub.get_about()))
+            );
+        }
+
+        for sub in children {
+            let mut sub_path = path.to_vec();
+            sub_path.push(sub.get_name());
+            walk(sub, &sub_path, root_name, out);
+        }
+    }
+    walk(root, &[], root.get_name(), &mut out);
+    out
+}
+
+/// Escapes text for roff, so dashes and leading dots print literally
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{escaped}")
+    } else {
+        escaped
+    }
+}
+
+fn roff_paragraphs(out: &mut String, text: &str) {
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            out.push_str(".PP\n");
+        } else {
+            out.push_str(&roff_escape(line.trim_end()));
+            out.push('\n');
+        }
+    }
+}
+
+fn roff_options(out: &mut String, command: &Command) {
+    for arg in options(command) {
+        out.push_str(".TP\n");
+        let mut names: Vec<String> = flags(arg)
+            .iter()
+            .map(|flag| format!("\\fB{}\\fR", roff_escape(flag)))
+            .collect();
+        if takes_value(arg) {
+            let value = format!("\\fI<{}>\\fR", roff_escape(&value_name(arg)));
+            if let Some(last) = names.last_mut() {
+                last.push(' ');
+                last.push_str(&value);
+            }
+        }
+        out.push_str(&names.join(", "));
+        out.push('\n');
+        let help = arg
+            .get_long_help()
+            .or(arg.get_help())
+            .map(|help| help.to_string())
+            .unwrap_or_default();
+        roff_paragraphs(out, &help);
+        let values = values(arg);
+        if !values.is_empty() && !help.contains("Possible values") {
+            out.push_str(&roff_escape(&format!(
+                "[possible values: {}]",
+                values.join(", ")
+            )));
+            out.push('\n');
+        }
+        let defaults: Vec<String> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| value.to_string_lossy().into_owned())
+            .collect();
+        if !defaults.is_empty() && takes_value(arg) {
+            out.push_str(&roff_escape(&format!("[default: {}]", defaults.join(","))));
+            out.push('\n');
+        }
+    }
+}
+
+/// Section 1 man page in roff format
+pub fn manpage() -> String {
+    let root = command();
+    let name = root.get_name();
+    let version = root.get_version().unwrap_or_default();
+    let mut out = format!(
+        ".TH {} 1 \"\" \"{name} {version}\" \"User Commands\"\n",
+        name.to_uppercase()
+    );
+
+    out.push_str(".SH NAME\n");
+    out.push_str(&roff_escape(&format!(
+        "{name} - {}",
+        summary(root.get_about())
+    )));
+    out.push('\n');
+
+    out.push_str(".SH SYNOPSIS\n");
+    out.push_str(&format!(
+        "\\fB{name}\\fR [\\fIOPTIONS\\fR] [\\fICOMMAND\\fR]\n"
+    ));
+
+    if let Some(about) = root.get_long_about().or(root.get_about()) {
+        out.push_str(".SH DESCRIPTION\n");
+        roff_paragraphs(&mut out, &about.to_string());
+    }
+
+    out.push_str(".SH OPTIONS\n");
+    roff_options(&mut out, &root);
+
+    out.push_str(".SH COMMANDS\n");
+    fn commands(out: &mut String, command: &Command, prefix: &str) {
+        for sub in subcommands(command) {
+            let full_name = format!("{prefix} {}", sub.get_name());
+            let mut usage = format!("\\fB{}\\fR", roff_escape(&full_name));
+            for arg in positionals(sub) {
+                let value = value_name(arg);
+                usage.push_str(&if arg.is_last_set() {
+                    format!(" [\\-\\- \\fI{}\\fR...]", roff_escape(&value))
+                } else {
+                    format!(" \\fI<{}>\\fR", roff_escape(&value))
+                });
+            }
+            out.push_str(".TP\n");
+            out.push_str(&usage);
+            out.push('\n');
+            roff_paragraphs(out, &summary(sub.get_about()));
+            if options(sub).any(|arg| arg.get_long() != Some("help")) {
+                out.push_str(".RS\n");
+                roff_options(out, sub);
+                out.push_str(".RE\n");
+            }
+            commands(out, sub, &full_name);
+        }
+    }
+    commands(&mut out, &root, name);
+
+    out
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::args::{Args, Browser, ConfigCommand, Format};
+use crate::utils::{self, RedactionPolicy};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Defaults read from the config file; command-line flags take precedence
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub browser: Option<Browser>,
+    /// Profile of `browser` used when `--profile` is not given
+    pub profile: Option<String>,
+    pub top: Option<usize>,
+    pub redact: Option<RedactionPolicy>,
+    pub format: Option<Format>,
+    /// Domain pattern file used when `--patterns` is not given
+    pub patterns: Option<PathBuf>,
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    Ok(utils::config_dir()?.join(CONFIG_FILE))
+}
+
+fn parse_string(value: &str) -> Result<&str> {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| anyhow::anyhow!("expected a quoted string, found {value}"))
+}
+
+/// Quoted path, with a leading `~/` expanded to the home directory
+fn parse_path(value: &str) -> Result<PathBuf> {
+    let value = parse_string(value)?;
+    Ok(match (value.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(value),
+    })
+}
+
+fn parse_enum<T: ValueEnum>(value: &str) -> Result<T> {
+    let value = parse_string(value)?;
+    T::from_str(value, true).map_err(|_| anyhow::anyhow!("unknown value \"{value}\""))
+}
+
+impl Config {
+    /// Parses the flat `key = value` subset of TOML used by the config file
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = Config::default();
+
+        for (line_num, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("Expected 'key = value' at line {}", line_num + 1)
+            })?;
+            let value = value.trim();
+
+            let parsed: Result<()> = match key.trim() {
+                "browser" => parse_enum(value).map(|v| config.browser = Some(v)),
+                "profile" => parse_string(value).map(|v| config.profile = Some(v.to_string())),
+                "top" => value
+                    .parse()
+                    .map(|v| config.top = Some(v))
+                    .map_err(Into::into),
+                // `true` and `false` predate the redaction modes
+                "redact" => match value {
+                    "true" => Ok(Some(RedactionPolicy::Stars)),
+                    "false" => Ok(None),
+                    _ => parse_enum(value).map(Some),
+                }
+                .map(|v| config.redact = v),
+                "format" => parse_enum(value).map(|v| config.format = Some(v)),
+                "patterns" => parse_path(value).map(|v| config.patterns = Some(v)),
+                other => Err(anyhow::anyhow!("unknown key '{other}'")),
+            };
+            parsed.with_context(|| format!("Invalid config at line {}", line_num + 1))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the config file, if one exists
+    pub fn load() -> Result<Option<Self>> {
+        let path = config_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let config = Self::parse(&content).with_context(|| format!("Failed to load {path:?}"))?;
+        info!(action = "load", component = "config", path = ?path, "Loaded config file");
+        Ok(Some(config))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("# historee defaults; command-line flags override these\n");
+        if let Some(browser) = self.browser {
+            out.push_str(&format!(
+                "browser = \"{}\"\n",
+                browser.to_possible_value().unwrap().get_name()
+            ));
+        }
+        if let Some(profile) = &self.profile {
+            out.push_str(&format!("profile = \"{profile}\"\n"));
+        }
+        if let Some(top) = self.top {
+            out.push_str(&format!("top = {top}\n"));
+        }
+        if let Some(redact) = self.redact {
+            out.push_str(&format!("redact = \"{redact}\"\n"));
+        }
+        if let Some(format) = self.format {
+            out.push_str(&format!(
+                "format = \"{}\"\n",
+                format.to_possible_value().unwrap().get_name()
+            ));
+        }
+        if let Some(patterns) = &self.patterns {
+            out.push_str(&format!("patterns = \"{}\"\n", patterns.display()));
+        }
+        out
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_toml()).with_context(|| format!("Failed to write {path:?}"))
+    }
+
+    /// Fills in every setting that was not given on the command line
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(browser) = self.browser.filter(|_| !from_cli("browser")) {
+            args.browser = browser;
+        }
+        // The profile belongs to the configured browser, not one chosen with --browser
+        if let Some(profile) = self
+            .profile
+            .as_ref()
+            .filter(|_| !from_cli("profile") && self.browser.is_none_or(|b| b == args.browser))
+        {
+            args.profile = Some(profile.clone());
+        }
+        if let Some(top) = self.top.filter(|_| !from_cli("top")) {
+            args.top = Some(top);
+        }
+        if let Some(redact) = self.redact.filter(|_| !from_cli("redact")) {
+            args.redact = Some(redact);
+        }
+        if let Some(format) = self.format.filter(|_| !from_cli("format")) {
+            args.format = format;
+        }
+        if let Some(patterns) = self.patterns.as_ref().filter(|_| !from_cli("patterns")) {
+            args.patterns = Some(patterns.clone());
+        }
+    }
+}
+
+/// Commented-out template listing every setting the config file understands
+pub fn scaffold() -> String {
+    let names = |values: Vec<String>| values.join(", ");
+    let browsers = names(
+        Browser::value_variants()
+            .iter()
+            .filter_map(|b| b.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect(),
+    );
+    let formats = names(
+        Format::value_variants()
+            .iter()
+            .filter_map(|f| f.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect(),
+    );
+    let redactions = names(
+        RedactionPolicy::value_variants()
+            .iter()
+            .filter_map(|r| r.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect(),
+    );
+    format!(
+        "# historee defaults; command-line flags override these\n\
+         # Uncomment a setting to use it.\n\
+         \n\
+         # Browser to analyze: {browsers}\n\
+         # browser = \"vivaldi\"\n\
+         \n\
+         # Browser profile to read, e.g. a Chromium \"Profile 1\" or a Firefox profile name\n\
+         # profile = \"Default\"\n\
+         \n\
+         # Number of top domains to display\n\
+         # top = 20\n\
+         \n\
+         # Redact domain names in output: {redactions}\n\
+         # redact = \"stars\"\n\
+         \n\
+         # Output format: {formats}\n\
+         # format = \"text\"\n\
+         \n\
+         # Domain pattern file (one regex per line, or .toml/.json rules)\n\
+         # patterns = \"~/.config/historee/domain_patterns.toml\"\n"
+    )
+}
+
+pub fn run(command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Init { force } => {
+            let path = config_path()?;
+            if path.exists() && !force {
+                anyhow::bail!(
+                    "{} already exists. Use --force to overwrite it.",
+                    path.display()
+                );
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, scaffold()).with_context(|| format!("Failed to write {path:?}"))?;
+            println!("Created {}", path.display());
+            Ok(())
+        }
+        ConfigCommand::Path => {
+            println!("{}", config_path()?.display());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::fmt::{self, Write};
+
+use crate::args::{Args, DigestFormat, DigestPeriod};
+use crate::browser;
+use crate::sink::DomainChange;
+use crate::snapshot::SnapshotDiff;
+use crate::utils::{self, DateFormat};
+
+/// Inclusive range of days
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Window {
+    /// The complete period before `first`
+    fn preceding(self, period: DigestPeriod) -> Window {
+        let last = self.first - Duration::days(1);
+        let first = match period {
+            DigestPeriod::Day => last,
+            DigestPeriod::Week => last - Duration::days(6),
+            DigestPeriod::Month => last.with_day(1).unwrap_or(last),
+        };
+        Window { first, last }
+    }
+}
+
+/// The most recent complete period before `today`: yesterday, last Monday-to-Sunday week,
+/// or last calendar month
+pub fn last_complete(period: DigestPeriod, today: NaiveDate) -> Window {
+    let next = match period {
+        DigestPeriod::Day => today,
+        DigestPeriod::Week => today - Duration::days(today.weekday().num_days_from_monday().into()),
+        DigestPeriod::Month => today.with_day(1).unwrap_or(today),
+    };
+    Window {
+        first: next,
+        last: next,
+    }
+    .preceding(period)
+}
+
+/// Visits and domains of one period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeriodTotals {
+    pub visits: u32,
+    pub domains: usize,
+}
+
+/// The most recent complete period compared with the one before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub period: DigestPeriod,
+    pub current: Window,
+    pub previous: Window,
+    pub current_totals: PeriodTotals,
+    pub previous_totals: PeriodTotals,
+    /// Domains visited in the current period but not the previous one, most visited first
+    pub new: Vec<DomainChange>,
+    /// Domains visited more than in the previous period, largest gain first
+    pub risers: Vec<DomainChange>,
+    /// Domains visited less, including ones not visited at all, largest drop first
+    pub fallers: Vec<DomainChange>,
+}
+
+impl Digest {
+    /// Analyzes both periods with the other options in `args`
+    pub fn new(period: DigestPeriod, args: &Args) -> Result<Self> {
+        let current = last_complete(period, args.zone().today());
+        let previous = current.preceding(period);
+        let analyze = |window: Window| -> Result<_> {
+            let mut window_args = args.clone();
+            window_args.since = Some(window.first);
+            window_args.until = Some(window.last);
+            Ok(browser::analyze_browser_history(&window_args)?.stats)
+        };
+        let before = analyze(previous)?;
+        let after = analyze(current)?;
+
+        let diff = SnapshotDiff::from_counts(&before.domain_counts, &after.domain_counts);
+        let (risers, mut fallers): (Vec<_>, Vec<_>) = diff
+            .changed
+            .into_iter()
+            .partition(|change| change.current > change.previous);
+        fallers.extend(diff.removed);
+        fallers.sort_by(|a, b| {
+            (b.previous - b.current)
+                .cmp(&(a.previous - a.current))
+                .then_with(|| a.domain.cmp(&b.domain))
+        });
+        Ok(Digest {
+            period,
+            current,
+            previous,
+            current_totals: PeriodTotals {
+                visits: after.total(),
+                domains: after.unique_domains(),
+            },
+            previous_totals: PeriodTotals {
+                visits: before.total(),
+                domains: before.unique_domains(),
+            },
+            new: diff.added,
+            risers,
+            fallers,
+        })
+    }
+}
+
+pub fn run(period: DigestPeriod, format: DigestFormat, args: &Args) -> Result<()> {
+    let digest = Digest::new(period, args)?;
+    let mut out = String::new();
+    match format {
+        DigestFormat::Text => write_text(&mut out, &digest, args),
+        DigestFormat::Markdown => write_markdown(&mut out, &digest, args),
+    }
+    .expect("writing to a String cannot fail");
+    print!("{out}");
+    Ok(())
+}
+
+fn window_label(window: Window, args: &Args) -> String {
+    let date = |date| utils::format_date(date, args, &DateFormat::Long);
+    if window.first == window.last {
+        date(window.first)
+    } else {
+        format!("{} to {}", date(window.first), date(window.last))
+    }
+}
+
+/// `+12, +8.5%`, leaving out the percentage when there is nothing to compare with
+fn delta(previous: u32, current: u32) -> String {
+    let change = current as i64 - previous as i64;
+    if previous == 0 {
+        return format!("{change:+}");
+    }
+    format!(
+        "{change:+}, {:+.1}%",
+        change as f64 * 100.0 / previous as f64
+    )
+}
+
+fn title(period: DigestPeriod) -> &'static str {
+    match period {
+        DigestPeriod::Day => "Daily digest",
+        DigestPeriod::Week => "Weekly digest",
+        DigestPeriod::Month => "Monthly digest",
+    }
+}
+
+fn write_text(out: &mut String, digest: &Digest, args: &Args) -> fmt::Result {
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    let (before, after) = (digest.previous_totals, digest.current_totals);
+    writeln!(
+        out,
+        "\n--- {}: {} ---",
+        title(digest.period),
+        window_label(digest.current, args)
+    )?;
+    writeln!(out, "Compared with {}", window_label(digest.previous, args))?;
+    writeln!(
+        out,
+        "Visits: {} -> {} ({})",
+        count(before.visits),
+        count(after.visits),
+        delta(before.visits, after.visits)
+    )?;
+    writeln!(
+        out,
+        "Domains: {} -> {} ({})",
+        before.domains,
+        after.domains,
+        delta(before.domains as u32, after.domains as u32)
+    )?;
+
+    let sections: [(&str, &[DomainChange]); 3] = [
+        ("New domains", &digest.new),
+        ("Biggest risers", &digest.risers),
+        ("Biggest fallers", &digest.fallers),
+    ];
+    for (heading, changes) in sections {
+        if changes.is_empty() {
+            continue;
+        }
+        let listed: Vec<(String, &DomainChange)> = changes
+            .iter()
+            .take(args.top.unwrap_or(10))
+            .map(|change| (utils::display_domain(&change.domain, args), change))
+            .collect();
+        let width = listed
+            .iter()
+            .map(|(domain, _)| domain.chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "\n{heading} ({}):", changes.len())?;
+        for (domain, change) in listed {
+            let detail = if change.is_new() {
+                count(change.current)
+            } else {
+                format!(
+                    "{} -> {} ({:+})",
+                    count(change.previous),
+                    count(change.current),
+                    change.current as i64 - change.previous as i64
+                )
+            };
+            writeln!(out, "  {domain:<width$}  {detail}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_markdown(out: &mut String, digest: &Digest, args: &Args) -> fmt::Result {
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    let (before, after) = (digest.previous_totals, digest.current_totals);
+    writeln!(
+        out,
+        "## {}: {}\n",
+        title(digest.period),
+        window_label(digest.current, args)
+    )?;
+    writeln!(
+        out,
+        "Compared with {}.\n",
+        window_label(digest.previous, args)
+    )?;
+    writeln!(out, "| | Previous | Current | Change |")?;
+    writeln!(out, "|---|---:|---:|---:|")?;
+    writeln!(
+        out,
+        "| Visits | {} | {} | {} |",
+        count(before.visits),
+        count(after.visits),
+        delta(before.visits, after.visits)
+    )?;
+    writeln!(
+        out,
+        "| Domains | {} | {} | {} |",
+        before.domains,
+        after.domains,
+        delta(before.domains as u32, after.domains as u32)
+    )?;
+
+    let top = args.top.unwrap_or(10);
+    if !digest.new.is_empty() {
+        writeln!(out, "\n### New domains ({})\n", digest.new.len())?;
+        writeln!(out, "| Domain | Visits |")?;
+        writeln!(out, "|---|---:|")?;
+        for change in digest.new.iter().take(top) {
+            writeln!(
+                out,
+                "| {} | {} |",
+                utils::display_domain(&change.domain, args),
+                count(change.current)
+            )?;
+        }
+    }
+    let sections: [(&str, &[DomainChange]); 2] = [
+        ("Biggest risers", &digest.risers),
+        ("Biggest fallers", &digest.fallers),
+    ];
+    for (heading, changes) in sections {
+        if changes.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n### {heading} ({})\n", changes.len())?;
+        writeln!(out, "| Domain | Previous | Current | Change |")?;
+        writeln!(out, "|---|---:|---:|---:|")?;
+        for change in changes.iter().take(top) {
+            writeln!(
+                out,
+                "| {} | {} | {} | {:+} |",
+                utils::display_domain(&change.domain, args),
+                count(change.previous),
+                count(change.current),
+                change.current as i64 - change.previous as i64
+            )?;
+        }
+    }
+    Ok(())
+}
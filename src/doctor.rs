@@ -0,0 +1,352 @@
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::args::{Args, Browser};
+use crate::browser::{self, BrowserHandler};
+use crate::error::HistoreeError;
+use crate::schema::SchemaFamily;
+use crate::utils::{self, Style};
+use crate::{patterns, sqlite};
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    status: Status,
+    label: &'static str,
+    detail: String,
+}
+
+impl Check {
+    fn new(status: Status, label: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            label,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Process names each browser runs under on Linux, macOS and Windows
+fn process_names(browser: Browser) -> &'static [&'static str] {
+    match browser {
+        Browser::Chrome => &["chrome", "google-chrome", "Google Chrome", "chrome.exe"],
+        Browser::Edge => &["msedge", "microsoft-edge", "Microsoft Edge", "msedge.exe"],
+        Browser::Firefox => &["firefox", "firefox-bin", "firefox-esr", "firefox.exe"],
+        Browser::Vivaldi => &["vivaldi", "vivaldi-bin", "Vivaldi", "vivaldi.exe"],
+        Browser::Zen => &["zen", "zen-bin", "Zen", "zen.exe"],
+    }
+}
+
+/// Names of running processes, or `None` when the process list can't be read
+fn running_processes() -> Option<Vec<String>> {
+    let output = if cfg!(windows) {
+        Command::new("tasklist")
+            .args(["/FO", "CSV", "/NH"])
+            .output()
+    } else {
+        Command::new("ps").args(["-A", "-o", "comm="]).output()
+    }
+    .ok()
+    .filter(|output| output.status.success())?;
+    let names = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let name = if cfg!(windows) {
+                line.split(',').next()?.trim_matches('"')
+            } else {
+                // macOS lists the full executable path
+                line.trim().rsplit('/').next()?
+            };
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect();
+    Some(names)
+}
+
+/// Directory a browser keeps its profiles in, whether or not it exists
+fn install_dir(browser: Browser) -> Result<PathBuf> {
+    let path = sqlite::get_browser_history_path(&browser)?;
+    Ok(match browser.schema_family() {
+        // Firefox-based browsers resolve to the profiles directory itself
+        SchemaFamily::Firefox => path,
+        // Chromium paths end in `<profile>/History`
+        SchemaFamily::Chromium => path
+            .parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or(path),
+    })
+}
+
+fn readable(path: &Path) -> Check {
+    match std::fs::File::open(path).and_then(|mut file| file.read(&mut [0; 1])) {
+        Ok(_) => Check::new(Status::Ok, "readable", "yes"),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && cfg!(target_os = "macos") => {
+            Check::new(
+                Status::Fail,
+                "readable",
+                "permission denied; grant your terminal Full Disk Access in System Settings > Privacy & Security",
+            )
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Check::new(
+            Status::Fail,
+            "readable",
+            "permission denied; check the file's owner and permissions",
+        ),
+        Err(e) => Check::new(Status::Fail, "readable", e.to_string()),
+    }
+}
+
+fn size(path: &Path) -> Check {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return Check::new(Status::Warn, "size", "unknown");
+    };
+    let mut detail = utils::format_bytes(metadata.len());
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    if let Ok(wal) = std::fs::metadata(PathBuf::from(wal)) {
+        detail.push_str(&format!(
+            " plus {} write-ahead log",
+            utils::format_bytes(wal.len())
+        ));
+    }
+    Check::new(Status::Ok, "size", detail)
+}
+
+/// Older history stores a Chromium profile keeps next to `History`, when it has any
+fn auxiliary_stores(path: &Path, args: &Args) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let archived = path.with_file_name(sqlite::ARCHIVED_HISTORY_FILE);
+    if let Ok(metadata) = std::fs::metadata(&archived) {
+        let detail = if args.include_archived {
+            "included"
+        } else {
+            "not included; add --include-archived to read it"
+        };
+        checks.push(Check::new(
+            Status::Ok,
+            "archived",
+            format!("{}, {detail}", utils::format_bytes(metadata.len())),
+        ));
+    }
+    if path.with_file_name("Sync Data").is_dir() {
+        checks.push(Check::new(
+            Status::Warn,
+            "sync data",
+            "present; synced history is kept as LevelDB records historee can't read",
+        ));
+    }
+    checks
+}
+
+/// Reads the schema from a temporary copy, the same way an analysis does
+fn schema(browser: Browser, path: &Path, args: &Args) -> Check {
+    let mut copy_args = args.clone();
+    copy_args.all_browsers = true;
+    let temp_path = browser::temp_path(&browser, &copy_args);
+    let detected = sqlite::copy_history_database(path, temp_path.as_deref()).and_then(|copy| {
+        let conn = Connection::open(copy.path())?;
+        crate::schema::detect_schema(&conn, browser.schema_family())
+    });
+    match detected {
+        Ok(schema) => Check::new(
+            Status::Ok,
+            "schema",
+            format!("{} version {} (supported)", schema.family, schema.version),
+        ),
+        Err(e) => {
+            let reason = match HistoreeError::from(e) {
+                HistoreeError::Other(e) => format!("{e:#}"),
+                e => e.to_string(),
+            };
+            Check::new(Status::Fail, "schema", reason)
+        }
+    }
+}
+
+/// Whether the browser holds its profile lock, or a lock on the live database, which a
+/// read-only probe reports as busy
+fn lock(browser: Browser, path: &Path, processes: Option<&[String]>) -> Check {
+    let running = processes.map(|processes| {
+        processes
+            .iter()
+            .any(|name| process_names(browser).contains(&name.as_str()))
+    });
+    let probe = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .and_then(|conn| conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(())));
+    let locked = probe.is_err_and(|e| {
+        matches!(
+            HistoreeError::from(anyhow::Error::from(e)),
+            HistoreeError::DatabaseLocked { .. }
+        )
+    });
+    if let Some(lock) = sqlite::held_profile_lock(path, browser.schema_family()) {
+        return Check::new(
+            Status::Warn,
+            "in use",
+            format!(
+                "the browser holds {}; historee reads a snapshot through the SQLite backup API, or waits with --wait-for-unlock",
+                lock.display()
+            ),
+        );
+    }
+    match (locked, running) {
+        (true, _) => Check::new(
+            Status::Warn,
+            "in use",
+            "the database is locked by the running browser; historee reads a copy, but the newest visits may not be flushed yet",
+        ),
+        (false, Some(true)) => Check::new(
+            Status::Ok,
+            "in use",
+            "the browser is running; historee reads a copy, so this is fine",
+        ),
+        (false, Some(false)) => Check::new(Status::Ok, "in use", "no"),
+        (false, None) => Check::new(Status::Ok, "in use", "not locked"),
+    }
+}
+
+/// Runs every check for one browser, stopping at the first one later checks depend on
+fn check_browser(browser: Browser, args: &Args, processes: Option<&[String]>) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match install_dir(browser) {
+        Ok(dir) if dir.is_dir() => checks.push(Check::new(
+            Status::Ok,
+            "installed",
+            dir.display().to_string(),
+        )),
+        Ok(dir) => {
+            checks.push(Check::new(
+                Status::Fail,
+                "installed",
+                format!("no data directory at {}", dir.display()),
+            ));
+            return checks;
+        }
+        Err(e) => {
+            checks.push(Check::new(Status::Fail, "installed", format!("{e:#}")));
+            return checks;
+        }
+    }
+    let path = match browser::history_path(&browser, args) {
+        Ok(path) if path.is_file() => path,
+        Ok(path) => {
+            checks.push(Check::new(
+                Status::Fail,
+                "history",
+                format!("no database at {}", path.display()),
+            ));
+            return checks;
+        }
+        Err(e) => {
+            checks.push(Check::new(
+                Status::Fail,
+                "history",
+                HistoreeError::from(e).to_string(),
+            ));
+            return checks;
+        }
+    };
+    checks.push(Check::new(
+        Status::Ok,
+        "history",
+        path.display().to_string(),
+    ));
+    let readable = readable(&path);
+    let can_read = readable.status == Status::Ok;
+    checks.push(readable);
+    if !can_read {
+        return checks;
+    }
+    checks.push(size(&path));
+    if browser.schema_family() == SchemaFamily::Chromium {
+        checks.extend(auxiliary_stores(&path, args));
+    }
+    checks.push(schema(browser, &path, args));
+    checks.push(lock(browser, &path, processes));
+    checks
+}
+
+/// Checks where historee looks for each browser's history and whether it can read it
+pub fn run(args: &Args) -> Result<()> {
+    let color = utils::color_enabled(args);
+    let marker = |status: Status| match status {
+        Status::Ok => utils::paint("ok  ", Style::Green, color),
+        Status::Warn => utils::paint("warn", Style::Dim, color),
+        Status::Fail => utils::paint("fail", Style::Bold, color),
+    };
+    let processes = running_processes();
+
+    let mut usable = 0;
+    for browser in Browser::ALL {
+        let checks = check_browser(browser, args, processes.as_deref());
+        if checks.iter().all(|check| check.status != Status::Fail) {
+            usable += 1;
+        }
+        println!("{}", utils::paint(&browser.to_string(), Style::Bold, color));
+        for check in checks {
+            println!(
+                "  [{}] {:<9} {}",
+                marker(check.status),
+                check.label,
+                check.detail
+            );
+        }
+    }
+
+    println!("{}", utils::paint("Environment", Style::Bold, color));
+    let patterns = match &args.patterns {
+        Some(path) => path.display().to_string(),
+        None => utils::find_config_file(&patterns::DEFAULT_PATTERN_FILES).map_or_else(
+            || "built-in defaults".to_string(),
+            |path| path.display().to_string(),
+        ),
+    };
+    println!("  config    {}", display(utils::config_dir()));
+    println!("  patterns  {patterns}");
+    for (label, given, default) in [
+        ("ignore", &args.ignore_file, crate::lists::IGNORE_FILE),
+        ("pin", &args.pin_file, crate::lists::PIN_FILE),
+    ] {
+        let path = given
+            .clone()
+            .or_else(|| utils::find_config_file(&[default]))
+            .map_or_else(|| "none".to_string(), |path| path.display().to_string());
+        println!("  {label:<9} {path}");
+    }
+    println!("  cache     {}", display(utils::cache_dir()));
+    println!(
+        "  temp copy {}",
+        args.temp_path
+            .clone()
+            .unwrap_or_else(sqlite::default_temp_path)
+            .display()
+    );
+    if let Err(e) = patterns::load_normalizer(args) {
+        println!("  [{}] {e:#}", marker(Status::Fail));
+    }
+
+    if usable == 0 {
+        anyhow::bail!("No browser history is readable");
+    }
+    Ok(())
+}
+
+fn display(path: Result<PathBuf>) -> String {
+    match path {
+        Ok(path) => path.display().to_string(),
+        Err(e) => format!("unavailable ({e:#})"),
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::args::Period;
+
+/// File type of a download: its lowercase extension, else its MIME type
+pub fn file_type(target_path: &str, mime_type: &str) -> String {
+    let file_name = target_path.rsplit(['/', '\\']).next().unwrap_or_default();
+    match file_name.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty()
+                && !extension.is_empty()
+                && extension.len() <= 8
+                && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            extension.to_lowercase()
+        }
+        _ if !mime_type.is_empty() => mime_type.to_lowercase(),
+        _ => "(unknown)".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DownloadCount {
+    pub downloads: u32,
+    pub bytes: u64,
+}
+
+impl DownloadCount {
+    fn add(&mut self, other: DownloadCount) {
+        self.downloads += other.downloads;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Downloads grouped by source domain, file type and month
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DownloadStats {
+    pub sources: HashMap<String, DownloadCount>,
+    pub file_types: HashMap<String, DownloadCount>,
+    /// Keyed by `YYYY-MM`, so iteration is chronological
+    pub months: BTreeMap<String, DownloadCount>,
+}
+
+impl DownloadStats {
+    pub fn record(
+        &mut self,
+        source: &str,
+        file_type: &str,
+        started: chrono::NaiveDateTime,
+        bytes: u64,
+    ) {
+        let count = DownloadCount {
+            downloads: 1,
+            bytes,
+        };
+        self.sources
+            .entry(source.to_string())
+            .or_default()
+            .add(count);
+        self.file_types
+            .entry(file_type.to_string())
+            .or_default()
+            .add(count);
+        self.months
+            .entry(crate::temporal::period_key(Period::Month, started))
+            .or_default()
+            .add(count);
+    }
+
+    pub fn merge(&mut self, other: &DownloadStats) {
+        for (source, count) in &other.sources {
+            self.sources.entry(source.clone()).or_default().add(*count);
+        }
+        for (file_type, count) in &other.file_types {
+            self.file_types
+                .entry(file_type.clone())
+                .or_default()
+                .add(*count);
+        }
+        for (month, count) in &other.months {
+            self.months.entry(month.clone()).or_default().add(*count);
+        }
+    }
+
+    pub fn total(&self) -> DownloadCount {
+        let mut total = DownloadCount::default();
+        for count in self.months.values() {
+            total.add(*count);
+        }
+        total
+    }
+
+    /// Most frequent download source domains, ties broken alphabetically
+    pub fn top_sources(&self, n: usize) -> Vec<(String, DownloadCount)> {
+        Self::top(&self.sources, n)
+    }
+
+    /// Most frequent file types, ties broken alphabetically
+    pub fn top_file_types(&self, n: usize) -> Vec<(String, DownloadCount)> {
+        Self::top(&self.file_types, n)
+    }
+
+    fn top(counts: &HashMap<String, DownloadCount>, n: usize) -> Vec<(String, DownloadCount)> {
+        let mut entries: Vec<(String, DownloadCount)> = counts
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect();
+        entries.sort_by(|a, b| {
+            b.1.downloads
+                .cmp(&a.1.downloads)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        entries.truncate(n);
+        entries
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::cancel::Cancelled;
+use crate::sqlite::HistoryNotFound;
+
+/// Failure returned by the public analysis functions, so callers can match on its kind
+#[derive(Debug)]
+pub enum HistoreeError {
+    /// The browser's profile directory or history database does not exist
+    BrowserNotFound(HistoryNotFound),
+    /// profiles.ini lists no usable profile
+    ProfileNotFound { browser: String, path: PathBuf },
+    /// The history database has a layout this version cannot read
+    UnsupportedSchema { reason: String },
+    /// The history database is locked, usually by the running browser
+    DatabaseLocked { reason: String },
+    /// The analysis was cancelled through its `CancellationToken`
+    Cancelled,
+    /// Any other failure, with its full context
+    Other(anyhow::Error),
+}
+
+impl HistoreeError {
+    /// Copy of a kind-specific error found inside another error's chain
+    fn specific(&self) -> Option<Self> {
+        match self {
+            HistoreeError::BrowserNotFound(e) => Some(HistoreeError::BrowserNotFound(e.clone())),
+            HistoreeError::ProfileNotFound { browser, path } => {
+                Some(HistoreeError::ProfileNotFound {
+                    browser: browser.clone(),
+                    path: path.clone(),
+                })
+            }
+            HistoreeError::UnsupportedSchema { reason } => Some(HistoreeError::UnsupportedSchema {
+                reason: reason.clone(),
+            }),
+            HistoreeError::DatabaseLocked { reason } => Some(HistoreeError::DatabaseLocked {
+                reason: reason.clone(),
+            }),
+            HistoreeError::Cancelled => Some(HistoreeError::Cancelled),
+            HistoreeError::Other(_) => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for HistoreeError {
+    /// Classifies an error by the first recognized cause in its chain
+    fn from(error: anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(specific) = cause
+                .downcast_ref::<HistoreeError>()
+                .and_then(HistoreeError::specific)
+            {
+                return specific;
+            }
+            if let Some(e) = cause.downcast_ref::<HistoryNotFound>() {
+                return HistoreeError::BrowserNotFound(e.clone());
+            }
+            if cause.is::<Cancelled>() {
+                return HistoreeError::Cancelled;
+            }
+            if let Some(rusqlite::Error::SqliteFailure(failure, _)) = cause.downcast_ref() {
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) {
+                    return HistoreeError::DatabaseLocked {
+                        reason: format!("{error:#}"),
+                    };
+                }
+            }
+        }
+        HistoreeError::Other(error)
+    }
+}
+
+impl fmt::Display for HistoreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // The wrapped error is the source, so chains do not print it twice
+            HistoreeError::BrowserNotFound(_) => write!(f, "Browser history not found"),
+            HistoreeError::ProfileNotFound { browser, path } => {
+                write!(f, "Could not find default {browser} profile in {path:?}")
+            }
+            HistoreeError::UnsupportedSchema { reason } => write!(f, "{reason}"),
+            HistoreeError::DatabaseLocked { reason } => {
+                write!(
+                    f,
+                    "History database is locked; close the browser and retry ({reason})"
+                )
+            }
+            HistoreeError::Cancelled => write!(f, "{Cancelled}"),
+            HistoreeError::Other(_) => write!(f, "Analysis failed"),
+        }
+    }
+}
+
+impl std::error::Error for HistoreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HistoreeError::BrowserNotFound(e) => Some(e),
+            HistoreeError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn source_is_the_wrapped_error() {
+        let missing = HistoryNotFound::new("History file", "/nowhere/History");
+        let error = HistoreeError::from(anyhow::Error::new(missing.clone()));
+        let source = error
+            .source()
+            .and_then(|e| e.downcast_ref::<HistoryNotFound>());
+        assert_eq!(source.map(|e| &e.path), Some(&missing.path));
+
+        let error = HistoreeError::from(anyhow::anyhow!("disk full").context("Failed to copy"));
+        let chain: Vec<String> = anyhow::Error::new(error)
+            .chain()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(chain, ["Analysis failed", "Failed to copy", "disk full"]);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::browser::HistoryNotFound;
+use crate::cancel::Cancelled;
+use crate::error::HistoreeError;
+
+/// Analysis completed and found history data; returning from `main` already exits with it
+#[allow(dead_code)]
+pub const OK: i32 = 0;
+/// Any failure without a more specific code
+pub const ERROR: i32 = 1;
+/// The browser's profile or history database does not exist
+pub const BROWSER_NOT_FOUND: i32 = 2;
+/// Analysis completed but no domains matched
+pub const NO_DATA: i32 = 3;
+/// Invalid command-line arguments
+pub const USAGE: i32 = 64;
+/// Analysis was cancelled
+pub const CANCELLED: i32 = 130;
+
+/// Maps an error to its exit code, looking through any added context
+pub fn for_error(error: &anyhow::Error) -> i32 {
+    error.chain().find_map(for_cause).unwrap_or(ERROR)
+}
+
+fn for_cause(cause: &(dyn std::error::Error + 'static)) -> Option<i32> {
+    match cause.downcast_ref::<HistoreeError>() {
+        Some(HistoreeError::BrowserNotFound(_) | HistoreeError::ProfileNotFound { .. }) => {
+            Some(BROWSER_NOT_FOUND)
+        }
+        Some(HistoreeError::Cancelled) => Some(CANCELLED),
+        _ if cause.is::<HistoryNotFound>() => Some(BROWSER_NOT_FOUND),
+        _ if cause.is::<Cancelled>() => Some(CANCELLED),
+        _ => None,
+    }
+}
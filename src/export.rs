@@ -0,0 +1,686 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::anonymize::Anonymizer;
+use crate::args::{Args, Browser, ExportCommand, ExportFormat, Period, TableFormat};
+use crate::browser::{self, BrowserHandler, HistoryNotFound};
+use crate::cancel::CancellationToken;
+use crate::categories::CategoryHours;
+use crate::domain::Normalizer;
+use crate::json::Value;
+#[cfg(feature = "parquet")]
+use crate::parquet::{Column, Table};
+use crate::sqlite::Visit;
+use crate::stats::AnalysisResult;
+use crate::{graph, patterns, sqlite, utils};
+
+pub fn run(command: &ExportCommand, args: &Args) -> Result<()> {
+    match command {
+        ExportCommand::Visits { format } => {
+            write_output(args, Some(*format), |out| export_visits(out, *format, args))
+        }
+        ExportCommand::Domains { format } => {
+            let result = browser::analyze_browser_history(args)?;
+            let mut domains: Vec<(String, u32)> = result
+                .stats
+                .domain_counts
+                .iter()
+                .map(|(domain, count)| (utils::display_domain(domain, args), *count))
+                .collect();
+            domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            write_output(args, Some(*format), |out| {
+                write_domains(out, *format, domains)
+            })
+        }
+        ExportCommand::CategoryHours { format } => {
+            let mut args = args.clone();
+            args.category_hours = true;
+            let result = browser::analyze_browser_history(&args)?;
+            let hours = result.category_hours.unwrap_or_default();
+            write_output(&args, None, |out| {
+                write_category_hours(out, *format, &hours)
+            })
+        }
+        ExportCommand::Graph { format, min_weight } => {
+            let mut args = args.clone();
+            args.nav_graph = true;
+            let result = browser::analyze_browser_history(&args)?;
+            write_output(&args, None, |out| {
+                graph::write(out, &result, *format, *min_weight, &args)
+            })
+        }
+        ExportCommand::Schema => write_output(args, None, |out| {
+            writeln!(out, "{}", json_schema())?;
+            Ok(())
+        }),
+        ExportCommand::Sqlite { database } => {
+            let mut args = args.clone();
+            args.group_by = Some(Period::Day);
+            let result = browser::analyze_browser_history(&args)?;
+            let run_id = write_sqlite(database, &result, &args)?;
+            info!(action = "write", component = "export", path = ?database, run_id, "Recorded run");
+            if !args.quiet {
+                println!("Recorded run {run_id} in {}", database.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// JSON Schema (draft 2020-12) of every JSON structure historee writes, under `$defs`
+fn json_schema() -> Value {
+    let text = |description: &str| property("string", description);
+    let count = |description: &str| property("integer", description);
+    let time = |description: &str| property("string", description).with("format", "date-time");
+    let version =
+        || count("Version of these structures; compare with the schema's own schema_version");
+    let domain_row = object(
+        "A domain and its count: `export domains` lines and `serve` /domains items",
+        vec![
+            (
+                "domain",
+                text("Normalized domain, or its pseudonym with --anonymize"),
+            ),
+            ("count", count("Visits, or URLs with --weight unique")),
+        ],
+    );
+    let top_domains = object(
+        "The most visited domains: `serve` /domains",
+        vec![
+            ("schema_version", version()),
+            ("domains", array(domain_row.clone())),
+        ],
+    );
+    let summary_fields = vec![
+        ("schema_version", version()),
+        ("browser", text("Browser analyzed, or \"all\"")),
+        ("generated_at", time("When the analysis ran")),
+        (
+            "earliest",
+            text("First day with visits, formatted with --date-format"),
+        ),
+        (
+            "latest",
+            text("Last day with visits, formatted with --date-format"),
+        ),
+        ("first_visit", nullable(time("First visit"))),
+        ("last_visit", nullable(time("Last visit"))),
+        ("days", count("Days between the first and last visit")),
+        ("unique_domains", count("Distinct domains counted")),
+        ("total", count("Sum of all domain counts")),
+        ("visits", nullable(count("Visits read from history"))),
+        (
+            "unique_urls",
+            nullable(count("Distinct URLs read from history")),
+        ),
+        (
+            "entropy",
+            nullable(property("number", "Shannon entropy of domain counts")),
+        ),
+        (
+            "gini",
+            nullable(property("number", "Gini coefficient of domain counts")),
+        ),
+        (
+            "domains_removed",
+            count("Domains dropped by filters and validation"),
+        ),
+        ("weight", text("How visits were weighted (--weight)")),
+    ];
+    let summary = object(
+        "Headline figures: `serve` /summary, also available to --template",
+        summary_fields.clone(),
+    );
+    let mut report_fields = summary_fields;
+    report_fields.push((
+        "domains",
+        array(object(
+            "A ranked domain; pinned domains follow the top ones with their own rank",
+            vec![
+                ("rank", count("Position by count, from 1")),
+                (
+                    "domain",
+                    text("Normalized domain, or its pseudonym with --anonymize"),
+                ),
+                ("count", count("Visits, or URLs with --weight unique")),
+            ],
+        )),
+    ));
+    let report = object(
+        "A report written to a .json --output: the summary plus the ranked domains",
+        report_fields,
+    );
+    let trends = object(
+        "Visits per period: `serve` /trends",
+        vec![
+            ("schema_version", version()),
+            (
+                "by",
+                text("Period length").with("enum", vec!["day", "month", "year"]),
+            ),
+            (
+                "buckets",
+                array(object(
+                    "One period",
+                    vec![
+                        ("period", text("Period label, e.g. 2025-03")),
+                        ("visits", count("Visits in the period")),
+                        ("domains", count("Distinct domains visited in the period")),
+                    ],
+                )),
+            ),
+        ],
+    );
+    let visit_row = object(
+        "One visit: `export visits` lines",
+        vec![
+            ("timestamp", time("When the visit happened")),
+            ("url", text("Visited URL")),
+            (
+                "domain",
+                nullable(text("Normalized domain, null when none was extracted")),
+            ),
+            ("title", nullable(text("Page title"))),
+            ("browser", text("Browser the visit came from")),
+        ],
+    );
+    let category_hour_row = object(
+        "Visits to one category in one hour of day: `export category-hours` lines",
+        vec![
+            ("category", text("Category name")),
+            ("hour", count("Local hour of day, 0 to 23")),
+            ("visits", count("Visits in that hour")),
+        ],
+    );
+    let snapshot = optional(
+        object(
+            "Saved domain counts: files written by `snapshot save`",
+            vec![
+                ("schema_version", version()),
+                ("name", text("Snapshot name")),
+                ("generated_at", time("When the analysis ran")),
+                ("version", text("historee version that wrote the snapshot")),
+                ("browser", text("Browser analyzed, or \"all\"")),
+                ("weight", text("How visits were weighted (--weight)")),
+                (
+                    "fingerprint",
+                    text("Normalization settings; counts only compare between equal ones"),
+                ),
+                ("earliest", text("First day with visits")),
+                ("latest", text("Last day with visits")),
+                (
+                    "domains",
+                    property("object", "Count per domain").with(
+                        "additionalProperties",
+                        Value::object().with("type", "integer"),
+                    ),
+                ),
+            ],
+        ),
+        vec![
+            ("host", text("Machine the snapshot was taken on")),
+            (
+                "patterns",
+                array(text("Pattern as written in the patterns file"))
+                    .with("description", "Normalization patterns in effect"),
+            ),
+            ("first_visit", time("First visit")),
+            ("last_visit", time("Last visit")),
+        ],
+    );
+    let event = optional(
+        object(
+            "A `watch` event: sink lines",
+            vec![
+                ("schema_version", version()),
+                (
+                    "type",
+                    text("Event kind").with("enum", vec!["delta", "alert"]),
+                ),
+                ("timestamp", time("When the event happened")),
+                ("source", text("What produced the event")),
+            ],
+        ),
+        vec![
+            ("message", text("Alert text, for alerts")),
+            (
+                "changes",
+                array(object(
+                    "A domain whose count changed, for deltas",
+                    vec![
+                        ("domain", text("Domain")),
+                        ("previous", count("Count before")),
+                        ("current", count("Count now")),
+                    ],
+                )),
+            ),
+        ],
+    );
+
+    let defs = [
+        ("summary", summary),
+        ("report", report),
+        ("domain", domain_row),
+        ("domains", top_domains),
+        ("trends", trends),
+        ("visit", visit_row),
+        ("category_hour", category_hour_row),
+        ("snapshot", snapshot),
+        ("event", event),
+    ];
+    let any_of: Vec<Value> = defs
+        .iter()
+        .map(|(name, _)| Value::object().with("$ref", format!("#/$defs/{name}")))
+        .collect();
+    Value::object()
+        .with("$schema", "https://json-schema.org/draft/2020-12/schema")
+        .with("title", "historee JSON output")
+        .with("schema_version", crate::json::SCHEMA_VERSION)
+        .with("version", env!("CARGO_PKG_VERSION"))
+        .with("anyOf", any_of)
+        .with(
+            "$defs",
+            Value::Object(
+                defs.into_iter()
+                    .map(|(name, def)| (name.to_string(), def))
+                    .collect(),
+            ),
+        )
+}
+
+fn property(kind: &str, description: &str) -> Value {
+    Value::object()
+        .with("type", kind)
+        .with("description", description)
+}
+
+/// Allows `null` besides the property's own type
+fn nullable(property: Value) -> Value {
+    let Value::Object(fields) = property else {
+        return property;
+    };
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| match (key.as_str(), value) {
+                ("type", Value::String(kind)) => (key, vec![kind, "null".to_string()].into()),
+                (_, value) => (key, value),
+            })
+            .collect(),
+    )
+}
+
+/// Adds properties that are only present in some documents
+fn optional(mut object: Value, properties: Vec<(&str, Value)>) -> Value {
+    if let Value::Object(fields) = &mut object {
+        if let Some((_, Value::Object(listed))) =
+            fields.iter_mut().find(|(key, _)| key == "properties")
+        {
+            listed.extend(
+                properties
+                    .into_iter()
+                    .map(|(name, property)| (name.to_string(), property)),
+            );
+        }
+    }
+    object
+}
+
+fn array(items: Value) -> Value {
+    Value::object().with("type", "array").with("items", items)
+}
+
+/// An object whose listed properties are all required; later fields may be added
+fn object(description: &str, properties: Vec<(&str, Value)>) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    Value::object()
+        .with("type", "object")
+        .with("description", description)
+        .with(
+            "properties",
+            Value::Object(
+                properties
+                    .into_iter()
+                    .map(|(name, property)| (name.to_string(), property))
+                    .collect(),
+            ),
+        )
+        .with("required", required)
+}
+
+/// Whether a format is binary and must be written to `--output` rather than stdout
+fn is_binary(format: ExportFormat) -> bool {
+    match format {
+        ExportFormat::Jsonl => false,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => true,
+    }
+}
+
+/// Sends an export to `--output`, replaced atomically, or to stdout; `format` is `None`
+/// for exports that are always text
+fn write_output(
+    args: &Args,
+    format: Option<ExportFormat>,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    let output = args.single_output()?;
+    if let (None, Some(format)) = (output, format.filter(|format| is_binary(*format))) {
+        anyhow::bail!("{format:?} exports are binary; choose a file with --output");
+    }
+    match output {
+        Some(path) => {
+            utils::write_atomic_with(path, write)?;
+            info!(action = "write", component = "export", path = ?path, "Wrote export");
+            Ok(())
+        }
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            let exported = write(&mut out).and_then(|()| Ok(out.flush()?));
+            // A reader such as `head` closing the pipe early is not a failure
+            match exported {
+                Err(e) if is_broken_pipe(&e) => Ok(()),
+                exported => exported,
+            }
+        }
+    }
+}
+
+pub(crate) fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn write_domains(
+    out: &mut dyn Write,
+    format: ExportFormat,
+    domains: Vec<(String, u32)>,
+) -> Result<()> {
+    match format {
+        ExportFormat::Jsonl => {
+            for (domain, count) in domains {
+                let record = Value::object().with("domain", domain).with("count", count);
+                writeln!(out, "{record}")?;
+            }
+        }
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => {
+            let (names, counts): (Vec<String>, Vec<i64>) = domains
+                .into_iter()
+                .map(|(domain, count)| (domain, i64::from(count)))
+                .unzip();
+            Table::new()
+                .column("domain", Column::String(names))
+                .column("count", Column::Int64(counts))
+                .write(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_category_hours(
+    out: &mut dyn Write,
+    format: TableFormat,
+    hours: &CategoryHours,
+) -> Result<()> {
+    if format == TableFormat::Csv {
+        writeln!(out, "category,hour,visits")?;
+    }
+    for (category, counts) in hours.sorted() {
+        for (hour, visits) in counts.iter().enumerate() {
+            match format {
+                TableFormat::Jsonl => {
+                    let record = Value::object()
+                        .with("category", category)
+                        .with("hour", hour)
+                        .with("visits", *visits);
+                    writeln!(out, "{record}")?;
+                }
+                TableFormat::Csv => writeln!(
+                    out,
+                    "{},{hour},{visits}",
+                    crate::report::csv_field(category)
+                )?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Where exported visits go: streamed as JSON Lines, or buffered per column for Parquet
+enum VisitSink<'a> {
+    Jsonl(&'a mut dyn Write),
+    #[cfg(feature = "parquet")]
+    Parquet(VisitColumns),
+}
+
+impl VisitSink<'_> {
+    fn push(&mut self, visit: Visit, domain: Option<String>, browser: Browser) -> io::Result<()> {
+        match self {
+            VisitSink::Jsonl(out) => {
+                let record = Value::object()
+                    .with("timestamp", visit.timestamp.to_rfc3339())
+                    .with("url", visit.url)
+                    .with("domain", domain)
+                    .with("title", visit.title)
+                    .with("browser", browser.to_string());
+                writeln!(out, "{record}")
+            }
+            #[cfg(feature = "parquet")]
+            VisitSink::Parquet(columns) => {
+                columns.timestamps.push(visit.timestamp.timestamp_micros());
+                columns.urls.push(visit.url);
+                columns.domains.push(domain);
+                columns.titles.push(visit.title);
+                columns.browsers.push(browser.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+#[derive(Default)]
+struct VisitColumns {
+    timestamps: Vec<i64>,
+    urls: Vec<String>,
+    domains: Vec<Option<String>>,
+    titles: Vec<Option<String>>,
+    browsers: Vec<String>,
+}
+
+#[cfg(feature = "parquet")]
+impl VisitColumns {
+    fn into_table(self) -> Table {
+        Table::new()
+            .column("timestamp", Column::TimestampMicros(self.timestamps))
+            .column("url", Column::String(self.urls))
+            .column("domain", Column::OptionalString(self.domains))
+            .column("title", Column::OptionalString(self.titles))
+            .column("browser", Column::String(self.browsers))
+    }
+}
+
+/// Exports the visits of `--browser`, or of every installed browser with `--all-browsers`
+fn export_visits(out: &mut dyn Write, format: ExportFormat, args: &Args) -> Result<()> {
+    let normalizer = patterns::load_normalizer(args)?;
+    let anonymizer = Anonymizer::for_args(args);
+    let browsers = args.browsers();
+
+    let mut sink = match format {
+        ExportFormat::Jsonl => VisitSink::Jsonl(&mut *out),
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => VisitSink::Parquet(VisitColumns::default()),
+    };
+    let mut total = 0;
+    for browser in browsers {
+        match export_browser(&mut sink, browser, &normalizer, anonymizer.as_ref(), args) {
+            Ok(count) => total += count,
+            Err(e) if args.all_browsers && e.is::<HistoryNotFound>() => {
+                warn!(browser = ?browser, error = %e, "Skipping browser without history");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    #[cfg(feature = "parquet")]
+    if let VisitSink::Parquet(columns) = sink {
+        columns.into_table().write(out)?;
+    }
+
+    info!(
+        action = "complete",
+        component = "export",
+        visit_count = total,
+        "Exported visits"
+    );
+    Ok(())
+}
+
+fn export_browser(
+    sink: &mut VisitSink<'_>,
+    browser: Browser,
+    normalizer: &Normalizer,
+    anonymizer: Option<&Anonymizer>,
+    args: &Args,
+) -> Result<usize> {
+    let temp_database = browser::copy_browser_history(&browser, args, args.temp_path.as_deref())?;
+    let conn = Connection::open(temp_database.path())?;
+    let schema = browser.detect_schema(&conn)?;
+
+    let schemes = args.scheme_filter();
+    let visit_filter = sqlite::VisitFilter::from_dates(args.since, args.until, args.zone())
+        .schemes(&schemes)
+        .collapse_redirects(args.collapse_redirects);
+
+    // A failed write stops the visit stream through the token, then surfaces as the error
+    let stop = CancellationToken::new();
+    let mut write_error = None;
+    let streamed =
+        sqlite::for_each_visit(&conn, &schema, &visit_filter, None, &stop, |mut visit| {
+            if write_error.is_some() {
+                return;
+            }
+            let mut domain = url::Url::parse(&visit.url)
+                .ok()
+                .and_then(|url| normalizer.canonical_url(&url));
+            if let Some(anonymizer) = anonymizer {
+                visit.url = anonymizer.url(&visit.url);
+                visit.title = None;
+                domain = domain.map(|domain| anonymizer.domain(&domain));
+            }
+            if let Err(e) = sink.push(visit, domain, browser) {
+                write_error = Some(e);
+                stop.cancel();
+            }
+        });
+    match write_error {
+        Some(e) => Err(e.into()),
+        None => streamed,
+    }
+}
+
+/// Version of the export database layout, stored in `PRAGMA user_version`
+const SQLITE_SCHEMA_VERSION: i64 = 1;
+
+const SQLITE_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    generated_at TEXT NOT NULL,
+    version TEXT NOT NULL,
+    browser TEXT NOT NULL,
+    weight TEXT NOT NULL,
+    since TEXT,
+    until TEXT,
+    first_day TEXT,
+    last_day TEXT,
+    fingerprint TEXT NOT NULL,
+    sample_rate REAL,
+    sample_seed INTEGER
+);
+CREATE TABLE IF NOT EXISTS domains (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    domain TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (run_id, domain)
+);
+CREATE TABLE IF NOT EXISTS days (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    day TEXT NOT NULL,
+    visits INTEGER NOT NULL,
+    domains INTEGER NOT NULL,
+    PRIMARY KEY (run_id, day)
+);
+";
+
+/// Appends `result` as a new run in `database`, creating the schema if needed; returns the run id
+fn write_sqlite(database: &Path, result: &AnalysisResult, args: &Args) -> Result<i64> {
+    if let Some(parent) = database.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {parent:?}"))?;
+    }
+    let mut conn =
+        Connection::open(database).with_context(|| format!("Failed to open {database:?}"))?;
+
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SQLITE_SCHEMA_VERSION {
+        anyhow::bail!(
+            "{database:?} uses export schema version {version}, newer than this historee supports ({SQLITE_SCHEMA_VERSION})"
+        );
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute_batch(SQLITE_SCHEMA)?;
+    tx.pragma_update(None, "user_version", SQLITE_SCHEMA_VERSION)?;
+
+    let provenance = &result.provenance;
+    let days = result.periods.as_ref().map(|periods| &periods.buckets);
+    let first_day = days.and_then(|days| days.keys().next());
+    let last_day = days.and_then(|days| days.keys().last());
+    let browser = if args.all_browsers {
+        "all".to_string()
+    } else {
+        args.browser.to_string()
+    };
+    tx.execute(
+        "INSERT INTO runs (generated_at, version, browser, weight, since, until, first_day, \
+         last_day, fingerprint, sample_rate, sample_seed) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            provenance.generated_at.to_rfc3339(),
+            provenance.version,
+            browser,
+            provenance.weight.to_string(),
+            args.since.map(|date| date.to_string()),
+            args.until.map(|date| date.to_string()),
+            first_day,
+            last_day,
+            provenance.normalization.fingerprint,
+            provenance.sampling.map(|sampling| sampling.rate),
+            provenance.sampling.map(|sampling| sampling.seed as i64),
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    {
+        let mut insert =
+            tx.prepare("INSERT INTO domains (run_id, domain, count) VALUES (?1, ?2, ?3)")?;
+        for (domain, count) in &result.stats.domain_counts {
+            insert.execute(params![run_id, utils::display_domain(domain, args), count])?;
+        }
+        let mut insert =
+            tx.prepare("INSERT INTO days (run_id, day, visits, domains) VALUES (?1, ?2, ?3, ?4)")?;
+        for (day, bucket) in days.into_iter().flatten() {
+            insert.execute(params![
+                run_id,
+                day,
+                bucket.visits,
+                bucket.domains.len() as i64
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(run_id)
+}
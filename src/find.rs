@@ -0,0 +1,344 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufWriter, Write};
+use tracing::{info, warn};
+
+use crate::anonymize::Anonymizer;
+use crate::args::{Args, Browser};
+use crate::browser::{self, HistoryNotFound};
+use crate::cancel::CancellationToken;
+use crate::domain::Normalizer;
+use crate::{export, patterns, schema, sqlite, utils};
+
+/// Domains listed in the per-domain summary
+const SUMMARY_DOMAINS: usize = 10;
+
+/// A visit whose URL or title contains the search term
+struct Match {
+    timestamp: DateTime<Utc>,
+    browser: Browser,
+    url: String,
+    title: Option<String>,
+    domain: Option<String>,
+}
+
+/// Collects the matching visits of one browser
+fn search_browser(
+    term: &str,
+    browser: Browser,
+    normalizer: &Normalizer,
+    args: &Args,
+    matches: &mut Vec<Match>,
+) -> Result<usize> {
+    let (temp_database, family) = browser::history_database(&browser, args)?;
+    let conn = Connection::open(temp_database.path())?;
+    let schema = schema::detect_schema(&conn, family)?;
+
+    let schemes = args.scheme_filter();
+    let visit_filter = sqlite::VisitFilter::from_dates(args.since, args.until, args.zone())
+        .schemes(&schemes)
+        .collapse_redirects(args.collapse_redirects)
+        .matching(term);
+    sqlite::for_each_visit(
+        &conn,
+        &schema,
+        &visit_filter,
+        None,
+        &CancellationToken::new(),
+        |visit| {
+            let domain = url::Url::parse(&visit.url)
+                .ok()
+                .and_then(|url| normalizer.canonical_url(&url));
+            matches.push(Match {
+                timestamp: visit.timestamp,
+                browser,
+                url: visit.url,
+                title: visit.title,
+                domain,
+            });
+        },
+    )
+}
+
+/// Prints visits of `--browser`, or of every installed browser with `--all-browsers`,
+/// whose URL or title contains `term`
+pub fn run(term: &str, limit: usize, args: &Args) -> Result<()> {
+    let browsers = args.browsers();
+    let normalizer = patterns::load_normalizer(args)?;
+    let mut matches = Vec::new();
+    for browser in browsers {
+        match search_browser(term, browser, &normalizer, args, &mut matches) {
+            Ok(count) => {
+                info!(action = "search", component = "search", browser = ?browser, matches = count, "Searched history")
+            }
+            Err(e) if args.all_browsers && e.is::<HistoryNotFound>() => {
+                warn!(browser = ?browser, error = %e, "Skipping browser without history");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    matches.sort_by_key(|m| std::cmp::Reverse(m.timestamp));
+
+    if let Some(anonymizer) = Anonymizer::for_args(args) {
+        for m in &mut matches {
+            m.url = anonymizer.url(&m.url);
+            m.title = None;
+            m.domain = m.domain.as_deref().map(|domain| anonymizer.domain(domain));
+        }
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let written =
+        write_matches(&mut out, term, &matches, limit, args).and_then(|()| Ok(out.flush()?));
+    match written {
+        Err(e) if export::is_broken_pipe(&e) => Ok(()),
+        written => written,
+    }
+}
+
+fn write_matches(
+    out: &mut dyn Write,
+    term: &str,
+    matches: &[Match],
+    limit: usize,
+    args: &Args,
+) -> Result<()> {
+    let zone = args.zone();
+    let time = |m: &Match| zone.local(m.timestamp).format("%Y-%m-%d %H:%M").to_string();
+    if args.quiet {
+        for m in matches.iter().take(limit) {
+            writeln!(out, "{}\t{}\t{}", time(m), m.browser, m.url)?;
+        }
+        return Ok(());
+    }
+
+    let urls: HashSet<&str> = matches.iter().map(|m| m.url.as_str()).collect();
+    let mut per_domain: HashMap<&str, u32> = HashMap::new();
+    for m in matches {
+        if let Some(domain) = &m.domain {
+            *per_domain.entry(domain).or_insert(0) += 1;
+        }
+    }
+    let visits = u32::try_from(matches.len()).unwrap_or(u32::MAX);
+    writeln!(
+        out,
+        "{} visits match \"{term}\" ({} URLs across {} domains)",
+        utils::format_count(visits, args.humanize),
+        utils::format_count(urls.len() as u32, args.humanize),
+        per_domain.len()
+    )?;
+    if matches.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "\nMatching visits, newest first:")?;
+    let show_browser = args.all_browsers;
+    for m in matches.iter().take(limit) {
+        let source = if show_browser {
+            format!("  {:<8}", m.browser.to_string())
+        } else {
+            String::new()
+        };
+        let prefix = format!("{}{source}  ", time(m));
+        match &m.title {
+            // The URL goes on its own line under the title, aligned with it
+            Some(title) => writeln!(
+                out,
+                "{prefix}{title}\n{:width$}{}",
+                "",
+                m.url,
+                width = prefix.chars().count()
+            )?,
+            None => writeln!(out, "{prefix}{}", m.url)?,
+        }
+    }
+    if matches.len() > limit {
+        writeln!(
+            out,
+            "({} more; raise --limit to list them)",
+            matches.len() - limit
+        )?;
+    }
+
+    let mut domains: Vec<(&str, u32)> = per_domain.into_iter().collect();
+    domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    writeln!(out, "\nMatches per domain:")?;
+    for (domain, count) in domains.iter().take(SUMMARY_DOMAINS) {
+        writeln!(
+            out,
+            "- {}: {} visits ({:.1}%)",
+            utils::display_domain(domain, args),
+            utils::format_count(*count, args.humanize),
+            utils::percent(*count, visits)
+        )?;
+    }
+    Ok(())
+}
+
+/// Earliest visit to a domain in one browser, with how often it was visited overall
+struct FirstVisit {
+    browser: Browser,
+    /// Directory holding the history database, which names the profile
+    profile: Option<String>,
+    timestamp: DateTime<Utc>,
+    url: String,
+    title: Option<String>,
+    last: DateTime<Utc>,
+    visits: u32,
+}
+
+fn first_visit(
+    domain: &str,
+    browser: Browser,
+    normalizer: &Normalizer,
+    args: &Args,
+) -> Result<Option<FirstVisit>> {
+    let (temp_database, family) = browser::history_database(&browser, args)?;
+    let conn = Connection::open(temp_database.path())?;
+    let schema = schema::detect_schema(&conn, family)?;
+    let profile = if args.takeout.is_none() && args.from_file.is_none() && !args.stdin {
+        browser::history_path(&browser, args)
+            .ok()
+            .and_then(|path| Some(path.parent()?.file_name()?.to_string_lossy().into_owned()))
+    } else {
+        None
+    };
+
+    let schemes = args.scheme_filter();
+    let visit_filter = sqlite::VisitFilter::from_dates(args.since, args.until, args.zone())
+        .schemes(&schemes)
+        .collapse_redirects(args.collapse_redirects);
+    // Visits repeat URLs heavily, so resolve each URL's domain only once
+    let mut matches_domain: HashMap<i64, bool> = HashMap::new();
+    let mut first: Option<FirstVisit> = None;
+    sqlite::for_each_visit(
+        &conn,
+        &schema,
+        &visit_filter,
+        None,
+        &CancellationToken::new(),
+        |visit| {
+            let matched = *matches_domain.entry(visit.url_id).or_insert_with(|| {
+                url::Url::parse(&visit.url)
+                    .ok()
+                    .and_then(|url| normalizer.canonical_url(&url))
+                    .is_some_and(|canonical| canonical == domain)
+            });
+            if !matched {
+                return;
+            }
+            // Visits stream oldest first, so the first match is the earliest
+            match &mut first {
+                Some(first) => {
+                    first.last = visit.timestamp;
+                    first.visits += 1;
+                }
+                None => {
+                    first = Some(FirstVisit {
+                        browser,
+                        profile: profile.clone(),
+                        timestamp: visit.timestamp,
+                        url: visit.url,
+                        title: visit.title,
+                        last: visit.timestamp,
+                        visits: 1,
+                    })
+                }
+            }
+        },
+    )?;
+    Ok(first)
+}
+
+/// Prints the earliest visit to `domain` in `--browser`, or in every installed browser
+/// with `--all-browsers`, and the visits since
+pub fn first(domain: &str, args: &Args) -> Result<()> {
+    let normalizer = patterns::load_normalizer(args)?;
+    let domain = browser::selected_domain(domain, &normalizer);
+    let browsers = args.browsers();
+
+    let mut found = Vec::new();
+    for browser in browsers {
+        match first_visit(&domain, browser, &normalizer, args) {
+            Ok(first) => found.extend(first),
+            Err(e) if args.all_browsers && e.is::<HistoryNotFound>() => {
+                warn!(browser = ?browser, error = %e, "Skipping browser without history");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    found.sort_by_key(|first| first.timestamp);
+    let Some(earliest) = found.first() else {
+        anyhow::bail!("No visits to {domain} found");
+    };
+
+    let anonymizer = Anonymizer::for_args(args);
+    let shown_domain = match &anonymizer {
+        Some(anonymizer) => anonymizer.domain(&domain),
+        None => utils::display_domain(&domain, args),
+    };
+    let url = match &anonymizer {
+        Some(anonymizer) => anonymizer.url(&earliest.url),
+        None => earliest.url.clone(),
+    };
+    let zone = args.zone();
+    let when = |timestamp: DateTime<Utc>| {
+        let local = zone.local(timestamp);
+        format!(
+            "{} {}",
+            utils::format_date(local.date(), args, &utils::DateFormat::Long),
+            local.format("%H:%M")
+        )
+    };
+    let visits: u32 = found.iter().map(|first| first.visits).sum();
+    let last = found
+        .iter()
+        .map(|first| first.last)
+        .max()
+        .unwrap_or(earliest.last);
+
+    let mut out = io::stdout().lock();
+    if args.quiet {
+        writeln!(
+            out,
+            "{}\t{}\t{url}",
+            earliest.timestamp.to_rfc3339(),
+            earliest.browser
+        )?;
+        return Ok(());
+    }
+    let source = match &earliest.profile {
+        Some(profile) => format!("{}, profile {profile}", earliest.browser),
+        None => earliest.browser.to_string(),
+    };
+    writeln!(
+        out,
+        "First visit to {shown_domain}: {} ({source})",
+        when(earliest.timestamp)
+    )?;
+    writeln!(out, "URL: {url}")?;
+    if let (Some(title), None) = (&earliest.title, &anonymizer) {
+        writeln!(out, "Title: {title}")?;
+    }
+    writeln!(
+        out,
+        "Visits since: {} over {} days, last on {}",
+        utils::format_count(visits, args.humanize),
+        (last - earliest.timestamp).num_days(),
+        when(last)
+    )?;
+    if found.len() > 1 {
+        for first in &found {
+            writeln!(
+                out,
+                "- {}: {} visits since {}",
+                first.browser,
+                utils::format_count(first.visits, args.humanize),
+                when(first.timestamp)
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use std::io::Write;
+
+use crate::args::{Args, GraphFormat};
+use crate::navigation::NavigationGraph;
+use crate::stats::AnalysisResult;
+use crate::utils;
+
+/// Navigation hops kept for export, with every domain that appears in one
+struct Edges {
+    nodes: Vec<(String, u32)>,
+    edges: Vec<(String, String, u32)>,
+}
+
+impl Edges {
+    /// Hops with at least `min_weight` navigations, and their domains' visit counts
+    fn new(graph: &NavigationGraph, result: &AnalysisResult, min_weight: u32, args: &Args) -> Self {
+        let hops: Vec<(&str, &str, u32)> = graph
+            .top_hops(usize::MAX)
+            .into_iter()
+            .filter(|(_, _, count)| *count >= min_weight)
+            .collect();
+
+        let mut nodes: Vec<(String, u32)> = hops
+            .iter()
+            .flat_map(|(from, to, _)| [*from, *to])
+            .map(|domain| {
+                let visits = result.stats.domain_counts.get(domain).copied();
+                (utils::display_domain(domain, args), visits.unwrap_or(0))
+            })
+            .collect();
+        nodes.sort();
+        nodes.dedup_by(|a, b| a.0 == b.0);
+
+        let mut edges: Vec<(String, String, u32)> = hops
+            .into_iter()
+            .map(|(from, to, count)| {
+                (
+                    utils::display_domain(from, args),
+                    utils::display_domain(to, args),
+                    count,
+                )
+            })
+            .collect();
+        edges.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        Self { nodes, edges }
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_dot(out: &mut dyn Write, graph: &Edges) -> Result<()> {
+    writeln!(out, "digraph historee {{")?;
+    for (domain, visits) in &graph.nodes {
+        writeln!(out, "  \"{}\" [visits={visits}];", dot_escape(domain))?;
+    }
+    for (from, to, count) in &graph.edges {
+        writeln!(
+            out,
+            "  \"{}\" -> \"{}\" [weight={count}, label=\"{count}\"];",
+            dot_escape(from),
+            dot_escape(to)
+        )?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn write_graphml(out: &mut dyn Write, graph: &Edges) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="visits" for="node" attr.name="visits" attr.type="int"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+    )?;
+    writeln!(out, r#"  <graph id="historee" edgedefault="directed">"#)?;
+    for (domain, visits) in &graph.nodes {
+        writeln!(
+            out,
+            r#"    <node id="{}"><data key="visits">{visits}</data></node>"#,
+            xml_escape(domain)
+        )?;
+    }
+    for (from, to, count) in &graph.edges {
+        writeln!(
+            out,
+            r#"    <edge source="{}" target="{}"><data key="weight">{count}</data></edge>"#,
+            xml_escape(from),
+            xml_escape(to)
+        )?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    Ok(())
+}
+
+/// Writes the navigation graph in `format`, leaving out hops seen fewer than `min_weight` times
+pub fn write(
+    out: &mut dyn Write,
+    result: &AnalysisResult,
+    format: GraphFormat,
+    min_weight: u32,
+    args: &Args,
+) -> Result<()> {
+    let empty = NavigationGraph::default();
+    let graph = Edges::new(
+        result.navigation.as_ref().unwrap_or(&empty),
+        result,
+        min_weight,
+        args,
+    );
+    match format {
+        GraphFormat::Dot => write_dot(out, &graph),
+        GraphFormat::Graphml => write_graphml(out, &graph),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::args::Args;
+use crate::report::{escape_html, RenderOptions, Renderer};
+use crate::stats::AnalysisResult;
+use crate::utils;
+
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String {
+        render_html(result, options.args())
+    }
+}
+
+/// Number of domains listed in the HTML report when `--top` is not given
+const DEFAULT_HTML_TOP: usize = 50;
+
+/// Background color for a row, from cold blue (0.0) to hot red (1.0)
+fn intensity_color(intensity: f64) -> String {
+    let hue = 220.0 * (1.0 - intensity.clamp(0.0, 1.0));
+    format!("hsl({hue:.0}, 85%, 88%)")
+}
+
+/// Renders the analysis as a standalone HTML document
+pub(crate) fn render_html(result: &AnalysisResult, args: &Args) -> String {
+    let (earliest_date, latest_date) = utils::date_range_labels(result.date_range, args);
+    let days_between = result.date_range.map_or(0, |range| range.days());
+    let title = args.source_label();
+
+    let mut rows = String::new();
+    for (rank, domain, count) in result
+        .stats
+        .top_with_pins(args.top.unwrap_or(DEFAULT_HTML_TOP), &result.pinned)
+        .iter()
+    {
+        let display_domain = utils::display_domain(domain, args);
+        let intensity = result
+            .frecency
+            .as_ref()
+            .map_or(0.0, |frecency| frecency.intensity(domain));
+        rows.push_str(&format!(
+            "      <tr style=\"background: {color}\" data-intensity=\"{intensity:.3}\">\
+             <td>{rank}</td><td>{domain}</td><td class=\"num\">{count}</td>\
+             <td class=\"num\">{percent:.0}%</td></tr>\n",
+            color = intensity_color(intensity),
+            domain = escape_html(&display_domain),
+            count = utils::format_count(*count, args.humanize),
+            percent = intensity * 100.0
+        ));
+    }
+
+    let mut skipped = String::new();
+    for browser in &result.skipped_browsers {
+        let reason = if browser.not_installed {
+            "not installed"
+        } else {
+            &browser.reason
+        };
+        skipped.push_str(&format!(
+            "  <p class=\"skipped\">Skipped {}: {}</p>\n",
+            browser.browser,
+            escape_html(reason)
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>{title} History Analysis</title>
+  <style>
+    body {{ font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 56rem; color: #222; }}
+    table {{ border-collapse: collapse; width: 100%; }}
+    th, td {{ padding: 0.3rem 0.6rem; text-align: left; }}
+    .num {{ text-align: right; font-variant-numeric: tabular-nums; }}
+    .legend span {{ padding: 0.2rem 0.6rem; }}
+  </style>
+</head>
+<body>
+  <h1>{title} History Analysis</h1>
+  <p>{earliest} to {latest} ({days} days) &middot; {unique} unique domains</p>
+{skipped}  <p class="legend">Intensity (recency-weighted visits):
+    <span style="background: {hot}">hot</span><span style="background: {cold}">cold</span></p>
+  <table>
+    <thead>
+      <tr><th>#</th><th>Domain</th><th class="num">{unit}</th><th class="num">Intensity</th></tr>
+    </thead>
+    <tbody>
+{rows}    </tbody>
+  </table>
+  <footer><small>Generated {generated} by historee {version}</small></footer>
+</body>
+</html>
+"#,
+        title = escape_html(&title),
+        skipped = skipped,
+        earliest = escape_html(&earliest_date),
+        latest = escape_html(&latest_date),
+        days = utils::format_number(days_between as u32),
+        unique = utils::format_count(result.stats.unique_domains() as u32, args.humanize),
+        hot = intensity_color(1.0),
+        cold = intensity_color(0.0),
+        unit = escape_html(args.weight.unit()),
+        generated = result.provenance.generated_at.format("%Y-%m-%d %H:%M UTC"),
+        version = result.provenance.version,
+    )
+}
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// HTTP requests are delegated to the system curl binary so that TLS support does
+// not have to be compiled into historee.
+const CURL: &str = "curl";
+
+/// Seconds to wait for a connection, then for the whole request, so an unresponsive server
+/// can't stall a scheduled run
+const CONNECT_TIMEOUT_SECS: &str = "10";
+const MAX_TIME_SECS: &str = "30";
+
+/// Options shared by every request
+const COMMON_ARGS: [&str; 7] = [
+    "--silent",
+    "--show-error",
+    "--fail",
+    "--connect-timeout",
+    CONNECT_TIMEOUT_SECS,
+    "--max-time",
+    MAX_TIME_SECS,
+];
+
+pub fn get(url: &str) -> Result<String> {
+    // `--` keeps a URL starting with `-` from being read as an option
+    let output = Command::new(CURL)
+        .args(COMMON_ARGS)
+        .args(["--location", "--", url])
+        .output()
+        .with_context(|| format!("Failed to run {CURL} for GET {url}"))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "GET {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn post_json(url: &str, body: &str) -> Result<()> {
+    let mut child = Command::new(CURL)
+        .args(COMMON_ARGS)
+        .args([
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            "--",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {CURL} for POST {url}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "POST {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
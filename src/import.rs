@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::json::{self, Value};
+use crate::sqlite::{self, HistoryNotFound, TempDatabase};
+
+/// Chromium history schema version written to `meta`, the oldest one the queries support
+const SCHEMA_VERSION: i64 = 20;
+
+/// Microseconds between 1601-01-01 and 1970-01-01
+const CHROMIUM_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+/// CHAIN_START | CHAIN_END, so imported visits are never mistaken for redirect sources
+const CHAIN_BOUNDS: i64 = 0x3000_0000;
+
+/// Chromium tables read by the history queries, with only the columns they use
+const TABLES: &str = "
+    CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT);
+    CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT NOT NULL, title TEXT,
+        visit_count INTEGER NOT NULL DEFAULT 0, typed_count INTEGER NOT NULL DEFAULT 0);
+    CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER NOT NULL,
+        visit_time INTEGER NOT NULL, transition INTEGER NOT NULL DEFAULT 0,
+        visit_duration INTEGER NOT NULL DEFAULT 0, from_visit INTEGER NOT NULL DEFAULT 0);
+    CREATE TABLE keyword_search_terms (keyword_id INTEGER, url_id INTEGER, term TEXT);
+    CREATE TABLE downloads (id INTEGER PRIMARY KEY, start_time INTEGER,
+        received_bytes INTEGER, target_path TEXT, mime_type TEXT);
+    CREATE TABLE downloads_url_chains (id INTEGER, chain_index INTEGER, url TEXT);
+    CREATE TABLE segments (id INTEGER PRIMARY KEY, name TEXT, url_id INTEGER);
+    CREATE TABLE segment_usage (id INTEGER PRIMARY KEY, segment_id INTEGER,
+        time_slot INTEGER, visit_count INTEGER);
+";
+
+/// A visit read from an export, before it is written to the temporary database
+struct ImportedVisit<'a> {
+    url: &'a str,
+    title: Option<&'a str>,
+    /// Microseconds since the Unix epoch
+    time_usec: i64,
+    /// Chromium core transition type
+    transition: i64,
+}
+
+/// Chromium core transition type for a Takeout `page_transition` name
+fn transition(name: &str) -> i64 {
+    match name.to_ascii_uppercase().as_str() {
+        "TYPED" => 1,
+        "AUTO_BOOKMARK" => 2,
+        "AUTO_SUBFRAME" => 3,
+        "MANUAL_SUBFRAME" => 4,
+        "GENERATED" => 5,
+        "AUTO_TOPLEVEL" => 6,
+        "FORM_SUBMIT" => 7,
+        "RELOAD" => 8,
+        "KEYWORD" => 9,
+        "KEYWORD_GENERATED" => 10,
+        _ => 0,
+    }
+}
+
+/// Parses Unix seconds, milliseconds or microseconds, RFC 3339, `YYYY-MM-DD HH:MM:SS`
+/// (UTC) or a bare date into microseconds since the Unix epoch
+fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<i64>() {
+        // Pick the unit by magnitude; 1e11 seconds and 1e14 milliseconds are past the year 5000
+        let scale = match n.unsigned_abs() {
+            n if n < 100_000_000_000 => 1_000_000,
+            n if n < 100_000_000_000_000 => 1_000,
+            _ => 1,
+        };
+        return n.checked_mul(scale);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime.timestamp_micros());
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|datetime| datetime.and_utc().timestamp_micros())
+}
+
+/// Path of the temporary database, replacing any file left there
+fn prepare_temp_path(temp_path: Option<&Path>) -> Result<TempDatabase> {
+    let temp_path = temp_path
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(sqlite::default_temp_path);
+    if temp_path.exists() {
+        fs::remove_file(&temp_path).with_context(|| format!("Failed to replace {temp_path:?}"))?;
+    }
+    Ok(TempDatabase::new(temp_path))
+}
+
+/// Writes imported visits as a Chromium history database, so they go through the same
+/// queries as a local profile
+fn write_database(visits: &[ImportedVisit], temp_path: Option<&Path>) -> Result<TempDatabase> {
+    // Created before the tables so the file is removed if building them fails
+    let database = prepare_temp_path(temp_path)?;
+    let mut conn = Connection::open(database.path())?;
+    conn.execute_batch(TABLES)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO meta (key, value) VALUES ('version', ?1)",
+        [SCHEMA_VERSION.to_string()],
+    )?;
+    {
+        let mut url_ids: HashMap<&str, i64> = HashMap::new();
+        let mut insert_url = tx.prepare("INSERT INTO urls (url, title) VALUES (?1, ?2)")?;
+        let mut insert_visit =
+            tx.prepare("INSERT INTO visits (url, visit_time, transition) VALUES (?1, ?2, ?3)")?;
+        for visit in visits {
+            let url_id = match url_ids.get(visit.url) {
+                Some(id) => *id,
+                None => {
+                    insert_url.execute(params![visit.url, visit.title])?;
+                    let id = tx.last_insert_rowid();
+                    url_ids.insert(visit.url, id);
+                    id
+                }
+            };
+            insert_visit.execute(params![
+                url_id,
+                visit.time_usec + CHROMIUM_EPOCH_OFFSET_MICROS,
+                visit.transition | CHAIN_BOUNDS
+            ])?;
+        }
+    }
+    tx.execute_batch(
+        "UPDATE urls SET
+            visit_count = (SELECT COUNT(*) FROM visits v WHERE v.url = urls.id),
+            typed_count = (SELECT COUNT(*) FROM visits v
+                           WHERE v.url = urls.id AND (v.transition & 255) = 1)",
+    )?;
+    tx.commit()?;
+    Ok(database)
+}
+
+fn log_import(source: &str, imported: usize, skipped: usize, start_time: Instant) {
+    if skipped > 0 {
+        warn!(
+            action = "import",
+            component = "import",
+            source,
+            skipped,
+            "Skipped entries without a usable URL or timestamp"
+        );
+    }
+    info!(
+        action = "complete",
+        component = "import",
+        source,
+        visits = imported,
+        duration_ms = start_time.elapsed().as_millis(),
+        "History imported"
+    );
+}
+
+/// Converts a Google Takeout `BrowserHistory.json` into a temporary Chromium history database
+pub(crate) fn takeout_database(
+    takeout_path: &Path,
+    temp_path: Option<&Path>,
+) -> Result<TempDatabase> {
+    let start_time = Instant::now();
+    if !takeout_path.exists() {
+        return Err(HistoryNotFound::new("Takeout file", takeout_path).into());
+    }
+    let contents = fs::read_to_string(takeout_path)
+        .with_context(|| format!("Failed to read {takeout_path:?}"))?;
+    let document =
+        json::parse(&contents).with_context(|| format!("Failed to parse {takeout_path:?}"))?;
+    let entries = document
+        .get("Browser History")
+        .unwrap_or(&document)
+        .as_array()
+        .with_context(|| format!("{takeout_path:?} has no \"Browser History\" list"))?;
+
+    let visits: Vec<ImportedVisit> = entries
+        .iter()
+        .filter_map(|entry| {
+            Some(ImportedVisit {
+                url: entry.get("url")?.as_str()?,
+                title: entry.get("title").and_then(Value::as_str),
+                time_usec: entry.get("time_usec")?.as_i64()?,
+                transition: entry
+                    .get("page_transition")
+                    .and_then(Value::as_str)
+                    .map_or(0, transition),
+            })
+        })
+        .collect();
+    let database = write_database(&visits, temp_path)?;
+    log_import(
+        "takeout",
+        visits.len(),
+        entries.len() - visits.len(),
+        start_time,
+    );
+    Ok(database)
+}
+
+/// Converts lines of `url` or `timestamp<TAB>url` into a temporary Chromium history
+/// database; URLs without a timestamp are dated now, and blank and `#` lines are ignored
+pub(crate) fn url_list_database(
+    reader: impl BufRead,
+    temp_path: Option<&Path>,
+) -> Result<TempDatabase> {
+    let start_time = Instant::now();
+    let now = Utc::now().timestamp_micros();
+    let mut lines = Vec::new();
+    let mut skipped = 0;
+    for line in reader.lines() {
+        let line = line.context("Failed to read URL list")?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (time_usec, url) = match line.split_once('\t') {
+            Some((timestamp, url)) => match parse_timestamp(timestamp) {
+                Some(time_usec) => (time_usec, url.trim()),
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            },
+            None => (now, line),
+        };
+        // Proxy logs and plain dumps often omit the scheme
+        let url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("http://{url}")
+        };
+        lines.push((time_usec, url));
+    }
+
+    let visits: Vec<ImportedVisit> = lines
+        .iter()
+        .map(|(time_usec, url)| ImportedVisit {
+            url,
+            title: None,
+            time_usec: *time_usec,
+            transition: 0,
+        })
+        .collect();
+    let database = write_database(&visits, temp_path)?;
+    log_import("url list", visits.len(), skipped, start_time);
+    Ok(database)
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+/// Common English words that carry no topic on their own
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "back", "been", "before", "being",
+    "between", "but", "can", "could", "did", "does", "down", "each", "for", "from", "get", "had",
+    "has", "have", "her", "here", "him", "his", "home", "how", "into", "its", "just", "like",
+    "login", "made", "make", "many", "more", "most", "new", "not", "now", "off", "one", "only",
+    "other", "our", "out", "over", "page", "sign", "some", "than", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "those", "through", "too", "under", "use", "using",
+    "very", "was", "way", "were", "what", "when", "where", "which", "while", "who", "why", "will",
+    "with", "would", "you", "your",
+];
+
+/// Shortest word counted as a keyword
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Splits a page title into lowercase keywords, dropping stopwords, numbers and words
+/// that only repeat the site name (e.g. "YouTube" on youtube.com)
+pub fn title_keywords(title: &str, host: &str) -> Vec<String> {
+    let host = host.to_lowercase();
+    let host_labels: Vec<&str> = host.split('.').collect();
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .filter(|word| !word.chars().all(|c| c.is_ascii_digit()))
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .filter(|word| !host_labels.contains(&word.as_str()))
+        .collect()
+}
+
+/// Weighted keyword counts across page titles
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TitleKeywords {
+    /// Domain the keywords were restricted to, if any
+    pub domain: Option<String>,
+    pub counts: HashMap<String, u32>,
+}
+
+impl TitleKeywords {
+    pub fn new(domain: Option<String>) -> Self {
+        Self {
+            domain,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Counts each keyword of `title` once, weighted by the page's weight
+    pub fn record(&mut self, title: &str, host: &str, weight: u32) {
+        let mut words = title_keywords(title, host);
+        words.sort_unstable();
+        words.dedup();
+        for word in words {
+            *self.counts.entry(word).or_insert(0) += weight;
+        }
+    }
+
+    pub fn merge(&mut self, other: &TitleKeywords) {
+        for (word, count) in &other.counts {
+            *self.counts.entry(word.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Most frequent keywords, ties broken alphabetically
+    pub fn top(&self, n: usize) -> Vec<(String, u32)> {
+        let mut words: Vec<(String, u32)> = self
+            .counts
+            .iter()
+            .map(|(word, count)| (word.clone(), *count))
+            .collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        words.truncate(n);
+        words
+    }
+}
@@ -1,12 +1,96 @@
-pub mod args;
-pub mod browser;
+// Without the command line, the crate-private helpers only it calls go unused
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+pub mod analysis;
+pub mod analyzer;
+pub mod anonymize;
+pub(crate) mod args;
+pub mod blocklist;
+pub(crate) mod browser;
+pub(crate) mod cache;
+pub mod cancel;
+pub mod categories;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+pub(crate) mod compare;
+#[cfg(feature = "cli")]
+pub(crate) mod completions;
+#[cfg(feature = "cli")]
+pub(crate) mod config;
+#[cfg(feature = "cli")]
+pub(crate) mod digest;
+#[cfg(feature = "cli")]
+pub(crate) mod doctor;
 pub mod domain;
+pub mod downloads;
+pub mod error;
+#[cfg(feature = "cli")]
+pub(crate) mod exit;
+#[cfg(feature = "cli")]
+pub(crate) mod export;
+#[cfg(feature = "cli")]
+pub(crate) mod find;
+#[cfg(feature = "cli")]
+pub(crate) mod graph;
+#[cfg(feature = "html-report")]
+pub mod html;
+pub(crate) mod http;
+pub(crate) mod import;
+pub(crate) mod json;
+pub mod keywords;
+pub(crate) mod lists;
+#[cfg(feature = "cli")]
+pub(crate) mod logging;
+pub mod media;
+#[cfg(feature = "cli")]
+pub(crate) mod metrics;
+pub mod navigation;
+#[cfg(feature = "parquet")]
+pub(crate) mod parquet;
 pub mod patterns;
-pub mod sqlite;
+pub mod prelude;
+pub(crate) mod profiles;
+pub(crate) mod progress;
+pub(crate) mod psl;
+#[cfg(feature = "cli")]
+pub(crate) mod query;
+pub(crate) mod rdap;
+pub mod report;
+#[cfg(feature = "cli")]
+pub(crate) mod schedule;
+pub mod schema;
+pub mod search;
+pub mod segments;
+#[cfg(feature = "serve")]
+pub(crate) mod serve;
+#[cfg(feature = "cli")]
+pub(crate) mod setup;
+#[cfg(feature = "cli")]
+pub(crate) mod show;
+pub mod sink;
+#[cfg(feature = "cli")]
+pub(crate) mod snapshot;
+pub(crate) mod sqlite;
 pub mod stats;
-pub mod utils;
+pub(crate) mod template;
+pub mod temporal;
+pub mod timezone;
+pub mod tld;
+pub(crate) mod toml;
+#[cfg(feature = "tui")]
+pub(crate) mod tui;
+pub(crate) mod utils;
+#[cfg(feature = "cli")]
+pub(crate) mod watch;
+#[cfg(feature = "cli")]
+pub(crate) mod wrapped;
 
-pub use args::{Args, Browser};
-pub use browser::{analyze_browser_history, BrowserHandler};
-pub use patterns::init_default_patterns;
-pub use stats::{AnalysisResult, DomainStats};
+pub use analyzer::{Analyzer, AnalyzerBuilder, Visit, Visits};
+pub use args::{Browser, Format, Period, Weight};
+pub use browser::BrowserHandler;
+pub use cancel::{CancellationToken, Cancelled};
+pub use error::HistoreeError;
+pub use rdap::DomainAge;
+pub use sink::{Event, EventSink, SinkSpec, Sinks};
+pub use stats::{AnalysisResult, DomainStats, Normalization, Provenance, Sampling};
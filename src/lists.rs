@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::args::Args;
+use crate::blocklist::Blocklist;
+use crate::domain::Normalizer;
+
+pub const IGNORE_FILE: &str = "ignore.txt";
+pub const PIN_FILE: &str = "pin.txt";
+
+/// Domains kept in the config directory rather than given per run: ignored ones are left
+/// out of every analysis, pinned ones are always listed with their rank
+#[derive(Debug, Clone, Default)]
+pub struct DomainLists {
+    /// Matched like a blocklist, so a listed domain also covers its subdomains
+    pub ignored: Blocklist,
+    /// Canonical domains, in file order
+    pub pinned: Vec<String>,
+}
+
+impl DomainLists {
+    /// Loads `--ignore-file`/`--pin-file`, else `ignore.txt`/`pin.txt` from the config
+    /// directory; missing default files are empty lists
+    pub fn load(args: &Args, normalizer: &Normalizer) -> Result<Self> {
+        let ignore = match args.no_ignore {
+            true => None,
+            false => list_path(args.ignore_file.as_deref(), IGNORE_FILE)?,
+        };
+        let pin = list_path(args.pin_file.as_deref(), PIN_FILE)?;
+        Self::from_files(ignore.as_deref(), pin.as_deref(), normalizer)
+    }
+
+    /// Loads only the given files, without looking in the config directory
+    pub fn from_files(
+        ignore: Option<&Path>,
+        pin: Option<&Path>,
+        normalizer: &Normalizer,
+    ) -> Result<Self> {
+        let ignored = match ignore {
+            Some(path) => {
+                let ignored = Blocklist::parse(&read_list(path)?);
+                info!(action = "loaded", component = "ignore_list", file_path = ?path, "Loaded ignored domains");
+                ignored
+            }
+            None => Blocklist::default(),
+        };
+        let pinned = match pin {
+            Some(path) => {
+                let pinned = parse_pins(&read_list(path)?, normalizer);
+                info!(action = "loaded", component = "pin_list", domain_count = pinned.len(), file_path = ?path, "Loaded pinned domains");
+                pinned
+            }
+            None => Vec::new(),
+        };
+        Ok(Self { ignored, pinned })
+    }
+}
+
+/// One domain per line; `#` starts a comment
+fn parse_pins(content: &str, normalizer: &Normalizer) -> Vec<String> {
+    let mut pinned: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let domain = crate::browser::selected_domain(line, normalizer);
+        if !pinned.contains(&domain) {
+            pinned.push(domain);
+        }
+    }
+    pinned
+}
+
+fn list_path(given: Option<&Path>, default: &str) -> Result<Option<PathBuf>> {
+    match given {
+        Some(path) if !path.exists() => anyhow::bail!("Domain list not found: {path:?}"),
+        Some(path) => Ok(Some(path.to_path_buf())),
+        None => Ok(crate::utils::find_config_file(&[default])),
+    }
+}
+
+fn read_list(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read domain list {path:?}"))
+}
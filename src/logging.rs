@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use std::fmt;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+use crate::args::{Args, LogFormat};
+use crate::json::Value;
+
+/// Installs the global subscriber for `--log-level`, `--log-format` and `--log-file`
+pub fn setup(args: &Args) -> Result<()> {
+    let filter = EnvFilter::from_default_env().add_directive(args.log_level().as_str().parse()?);
+    let (writer, ansi) = match &args.log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {path:?}"))?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        // Colors would end up inside JSON strings, so only plain text on stderr gets them
+        .with_ansi(ansi && args.log_format == LogFormat::Text);
+    match args.log_format {
+        LogFormat::Text => builder
+            .with_timer(tracing_subscriber::fmt::time::LocalTime::new(
+                time::macros::format_description!(
+                    "[hour]:[minute]:[second].[subsecond digits:3] [period]"
+                ),
+            ))
+            .with_target(false)
+            .with_thread_ids(false)
+            .with_thread_names(false)
+            .with_file(false)
+            .with_line_number(false)
+            .init(),
+        LogFormat::Json => builder.event_format(JsonFormat).init(),
+    }
+    Ok(())
+}
+
+/// Writes each event as one JSON object: timestamp, level, message, then its fields
+/// (`action`, `component`, ...) and the spans it happened in
+struct JsonFormat;
+
+/// Collects an event's fields as JSON values
+#[derive(Default)]
+struct JsonFields {
+    message: Option<String>,
+    fields: Vec<(String, Value)>,
+}
+
+impl JsonFields {
+    fn push(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = Some(message),
+            (name, value) => self.fields.push((name.to_string(), value)),
+        }
+    }
+}
+
+impl Visit for JsonFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).map_or_else(|_| Value::Float(value as f64), Value::Int);
+        self.push(field, value);
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        let value = i64::try_from(value).map_or_else(|_| Value::Float(value as f64), Value::Int);
+        self.push(field, value);
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        let value = i64::try_from(value).map_or_else(|_| Value::Float(value as f64), Value::Int);
+        self.push(field, value);
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{value:?}").into());
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let mut line = Value::object()
+            .with(
+                "timestamp",
+                Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            )
+            .with("level", metadata.level().as_str())
+            .with("message", fields.message.unwrap_or_default());
+        for (name, value) in fields.fields {
+            line = line.with(&name, value);
+        }
+        let spans: Vec<Value> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let extensions = span.extensions();
+                let span_fields = extensions
+                    .get::<FormattedFields<N>>()
+                    .map(|fields| fields.fields.clone())
+                    .unwrap_or_default();
+                Value::object()
+                    .with("name", span.name())
+                    .with("fields", span_fields)
+            })
+            .collect();
+        if !spans.is_empty() {
+            line = line.with("spans", spans);
+        }
+        writeln!(writer, "{line}")
+    }
+}
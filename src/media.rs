@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration};
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Instant;
+use tracing::info;
+
+use crate::sqlite::{self, ExtractOptions};
+
+/// Seconds between 1601-01-01, where Media History's clock starts, and the Unix epoch
+const WINDOWS_EPOCH_OFFSET_SECS: i64 = 11_644_473_600;
+
+/// Name of the database Chromium keeps playbacks in, next to `History`
+pub const MEDIA_HISTORY_FILE: &str = "Media History";
+
+/// Playback recorded for a domain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaTime {
+    pub playbacks: u32,
+    /// Seconds of playback, audio and video alike
+    pub watch_seconds: u64,
+    /// The part of `watch_seconds` spent on media with a video track
+    pub video_seconds: u64,
+}
+
+impl MediaTime {
+    fn add(&mut self, other: MediaTime) {
+        self.playbacks += other.playbacks;
+        self.watch_seconds += other.watch_seconds;
+        self.video_seconds += other.video_seconds;
+    }
+
+    pub fn watch_time(&self) -> Duration {
+        Duration::seconds(self.watch_seconds as i64)
+    }
+}
+
+/// Watch and listen time per domain from Chromium's `Media History` database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaStats {
+    pub by_domain: HashMap<String, MediaTime>,
+}
+
+impl MediaStats {
+    pub fn record(&mut self, domain: &str, time: MediaTime) {
+        match self.by_domain.get_mut(domain) {
+            Some(total) => total.add(time),
+            None => {
+                self.by_domain.insert(domain.to_string(), time);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &MediaStats) {
+        for (domain, time) in &other.by_domain {
+            self.record(domain, *time);
+        }
+    }
+
+    pub fn total(&self) -> MediaTime {
+        let mut total = MediaTime::default();
+        for time in self.by_domain.values() {
+            total.add(*time);
+        }
+        total
+    }
+
+    /// Domains with the most watch time, longest first
+    pub fn top(&self, n: usize) -> Vec<(&str, MediaTime)> {
+        let mut domains: Vec<(&str, MediaTime)> = self
+            .by_domain
+            .iter()
+            .map(|(domain, time)| (domain.as_str(), *time))
+            .collect();
+        domains.sort_by(|a, b| {
+            b.1.watch_seconds
+                .cmp(&a.1.watch_seconds)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        domains.truncate(n);
+        domains
+    }
+
+    pub fn remove_domains(&mut self, dropped: &HashSet<String>) {
+        self.by_domain.retain(|domain, _| !dropped.contains(domain));
+    }
+}
+
+/// Reads the playbacks in the `Media History` database next to `history`, copying it to
+/// `temp_path` first since the browser keeps it locked; `None` when the browser has none,
+/// as Chromium stopped recording media history in version 114
+pub fn extract(
+    history: &Path,
+    temp_path: &Path,
+    options: &ExtractOptions,
+) -> Result<Option<MediaStats>> {
+    let path = history.with_file_name(MEDIA_HISTORY_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let start_time = Instant::now();
+    let database = sqlite::copy_history_database(&path, Some(temp_path))?;
+    let conn = Connection::open(database.path())?;
+
+    let mut media = MediaStats::default();
+    let mut stmt = conn
+        .prepare(
+            "SELECT url, watch_time_s, has_video, last_updated_time_s FROM playback
+             WHERE watch_time_s > 0",
+        )
+        .context("Media History has no playback table")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        options.cancel.check()?;
+        let updated: i64 = row.get(3)?;
+        let updated =
+            DateTime::from_timestamp(updated - WINDOWS_EPOCH_OFFSET_SECS, 0).unwrap_or_default();
+        let filter = &options.visit_filter;
+        if filter.since.is_some_and(|since| updated < since)
+            || filter.until.is_some_and(|until| updated >= until)
+        {
+            continue;
+        }
+
+        let url: String = row.get(0)?;
+        let domain = options.domain_of(&url);
+        if !options.filter.allows(domain.as_deref()) {
+            continue;
+        }
+        let Some(domain) = domain else {
+            continue;
+        };
+        let seconds = row.get::<_, i64>(1)?.max(0) as u64;
+        let has_video: bool = row.get::<_, Option<bool>>(2)?.unwrap_or(false);
+        media.record(
+            &domain,
+            MediaTime {
+                playbacks: 1,
+                watch_seconds: seconds,
+                video_seconds: if has_video { seconds } else { 0 },
+            },
+        );
+    }
+
+    info!(
+        action = "complete",
+        component = "media_history",
+        playbacks = media.total().playbacks,
+        duration_ms = start_time.elapsed().as_millis(),
+        "Media history extraction completed"
+    );
+    Ok(Some(media))
+}
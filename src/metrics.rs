@@ -0,0 +1,96 @@
+use std::fmt::{self, Write};
+
+use crate::args::Args;
+use crate::stats::AnalysisResult;
+use crate::utils;
+
+/// Domains given their own `historee_domain_visits` series when `--top` is not set
+const DEFAULT_TOP: usize = 10;
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} gauge")
+}
+
+/// Renders an analysis in the Prometheus text exposition format; `interval_visits` is the
+/// growth since the previous run in watch mode
+pub fn render(result: &AnalysisResult, args: &Args, interval_visits: Option<u32>) -> String {
+    let mut out = String::new();
+    write_metrics(&mut out, result, args, interval_visits)
+        .expect("writing to a String cannot fail");
+    out
+}
+
+fn write_metrics(
+    out: &mut String,
+    result: &AnalysisResult,
+    args: &Args,
+    interval_visits: Option<u32>,
+) -> fmt::Result {
+    let browser = if args.all_browsers {
+        "all".to_string()
+    } else {
+        args.browser.to_string().to_lowercase()
+    };
+    let labels = format!("browser=\"{}\"", escape_label(&browser));
+
+    gauge(
+        out,
+        "historee_unique_domains",
+        "Distinct domains in the analyzed history",
+    )?;
+    writeln!(
+        out,
+        "historee_unique_domains{{{labels}}} {}",
+        result.stats.unique_domains()
+    )?;
+    gauge(out, "historee_visits", "Counted visits across all domains")?;
+    writeln!(out, "historee_visits{{{labels}}} {}", result.stats.total())?;
+    gauge(
+        out,
+        "historee_domains_removed",
+        "URLs whose host was not counted",
+    )?;
+    writeln!(
+        out,
+        "historee_domains_removed{{{labels}}} {}",
+        result.stats.domains_removed.total()
+    )?;
+    if let Some(visits) = interval_visits {
+        gauge(
+            out,
+            "historee_interval_visits",
+            "Visits added since the previous watch run",
+        )?;
+        writeln!(out, "historee_interval_visits{{{labels}}} {visits}")?;
+    }
+    gauge(
+        out,
+        "historee_domain_visits",
+        "Counted visits of the most visited domains",
+    )?;
+    for (domain, count) in result.stats.top(args.top.unwrap_or(DEFAULT_TOP)) {
+        writeln!(
+            out,
+            "historee_domain_visits{{{labels},domain=\"{}\"}} {count}",
+            escape_label(&utils::display_domain(&domain, args))
+        )?;
+    }
+    gauge(
+        out,
+        "historee_last_analysis_timestamp_seconds",
+        "Unix time the analysis was generated",
+    )?;
+    writeln!(
+        out,
+        "historee_last_analysis_timestamp_seconds{{{labels}}} {}",
+        result.provenance.generated_at.timestamp()
+    )
+}
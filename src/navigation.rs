@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+
+/// Domain-to-domain navigations reconstructed from each visit's referring visit;
+/// navigations within a domain are not counted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavigationGraph {
+    /// Navigations per source domain, per target domain
+    pub hops: HashMap<String, HashMap<String, u32>>,
+}
+
+/// Other domains whose pages led to one domain, collected with `--referrers`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainReferrers {
+    pub domain: String,
+    /// Referring domains with their navigations, most first
+    pub referrers: Vec<(String, u32)>,
+}
+
+impl DomainReferrers {
+    /// Navigations into the domain from all other domains
+    pub fn total(&self) -> u32 {
+        self.referrers.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// A domain's distinct neighbours in the navigation graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Degree {
+    /// Domains that navigated to this one
+    pub incoming: usize,
+    /// Domains this one navigated to
+    pub outgoing: usize,
+}
+
+impl NavigationGraph {
+    pub fn record(&mut self, from: &str, to: &str, count: u32) {
+        if from == to {
+            return;
+        }
+        let targets = match self.hops.get_mut(from) {
+            Some(targets) => targets,
+            None => self.hops.entry(from.to_string()).or_default(),
+        };
+        match targets.get_mut(to) {
+            Some(hops) => *hops += count,
+            None => {
+                targets.insert(to.to_string(), count);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &NavigationGraph) {
+        for (from, targets) in &other.hops {
+            for (to, count) in targets {
+                self.record(from, to, *count);
+            }
+        }
+    }
+
+    /// Total navigations between different domains
+    pub fn total(&self) -> u32 {
+        self.hops
+            .values()
+            .flat_map(|targets| targets.values())
+            .sum()
+    }
+
+    /// The `n` most frequent hops as `(from, to, count)`, most frequent first
+    pub fn top_hops(&self, n: usize) -> Vec<(&str, &str, u32)> {
+        let mut hops: Vec<(&str, &str, u32)> = self
+            .hops
+            .iter()
+            .flat_map(|(from, targets)| {
+                targets
+                    .iter()
+                    .map(move |(to, count)| (from.as_str(), to.as_str(), *count))
+            })
+            .collect();
+        hops.sort_by(|a, b| {
+            b.2.cmp(&a.2)
+                .then_with(|| a.0.cmp(b.0))
+                .then_with(|| a.1.cmp(b.1))
+        });
+        hops.truncate(n);
+        hops
+    }
+
+    /// Domains that navigated to `domain`, most navigations first, ties alphabetically
+    pub fn referrers(&self, domain: &str) -> Vec<(String, u32)> {
+        let mut referrers: Vec<(String, u32)> = self
+            .hops
+            .iter()
+            .filter_map(|(from, targets)| Some((from.clone(), *targets.get(domain)?)))
+            .collect();
+        referrers.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        referrers
+    }
+
+    pub fn degree(&self, domain: &str) -> Degree {
+        Degree {
+            incoming: self
+                .hops
+                .values()
+                .filter(|targets| targets.contains_key(domain))
+                .count(),
+            outgoing: self.hops.get(domain).map_or(0, HashMap::len),
+        }
+    }
+
+    /// Re-keys every domain through `rename`, merging domains that collide
+    pub fn rename(&mut self, rename: impl Fn(&str) -> String) {
+        let hops = std::mem::take(&mut self.hops);
+        for (from, targets) in hops {
+            for (to, count) in targets {
+                self.record(&rename(&from), &rename(&to), count);
+            }
+        }
+    }
+
+    pub fn remove_domains(&mut self, dropped: &HashSet<String>) {
+        self.hops.retain(|from, _| !dropped.contains(from));
+        for targets in self.hops.values_mut() {
+            targets.retain(|to, _| !dropped.contains(to));
+        }
+        self.hops.retain(|_, targets| !targets.is_empty());
+    }
+}
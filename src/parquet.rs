@@ -0,0 +1,411 @@
+use std::io::{self, Write};
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Thrift compact protocol field types
+const TYPE_I32: u8 = 5;
+const TYPE_I64: u8 = 6;
+const TYPE_BINARY: u8 = 8;
+const TYPE_LIST: u8 = 9;
+const TYPE_STRUCT: u8 = 12;
+
+// Parquet enum values from parquet.thrift
+const PHYSICAL_INT64: i32 = 2;
+const PHYSICAL_BYTE_ARRAY: i32 = 6;
+const REPETITION_REQUIRED: i32 = 0;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MICROS: i32 = 10;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+/// Values of one Parquet column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Int64(Vec<i64>),
+    /// Microseconds since the Unix epoch, in UTC
+    TimestampMicros(Vec<i64>),
+    String(Vec<String>),
+    OptionalString(Vec<Option<String>>),
+}
+
+impl Column {
+    fn len(&self) -> usize {
+        match self {
+            Column::Int64(values) | Column::TimestampMicros(values) => values.len(),
+            Column::String(values) => values.len(),
+            Column::OptionalString(values) => values.len(),
+        }
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self {
+            Column::Int64(_) | Column::TimestampMicros(_) => PHYSICAL_INT64,
+            Column::String(_) | Column::OptionalString(_) => PHYSICAL_BYTE_ARRAY,
+        }
+    }
+
+    fn converted_type(&self) -> Option<i32> {
+        match self {
+            Column::Int64(_) => None,
+            Column::TimestampMicros(_) => Some(CONVERTED_TIMESTAMP_MICROS),
+            Column::String(_) | Column::OptionalString(_) => Some(CONVERTED_UTF8),
+        }
+    }
+
+    fn is_optional(&self) -> bool {
+        matches!(self, Column::OptionalString(_))
+    }
+
+    /// Data page body: definition levels for optional columns, then PLAIN-encoded values
+    fn page_body(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let plain_bytes = |body: &mut Vec<u8>, value: &str| {
+            body.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            body.extend_from_slice(value.as_bytes());
+        };
+        match self {
+            Column::Int64(values) | Column::TimestampMicros(values) => {
+                for value in values {
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Column::String(values) => {
+                for value in values {
+                    plain_bytes(&mut body, value);
+                }
+            }
+            Column::OptionalString(values) => {
+                let levels = definition_levels(values.iter().map(Option::is_some));
+                body.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+                body.extend_from_slice(&levels);
+                for value in values.iter().flatten() {
+                    plain_bytes(&mut body, value);
+                }
+            }
+        }
+        body
+    }
+}
+
+/// RLE-encoded definition levels of bit width 1, one run per stretch of equal levels
+fn definition_levels(present: impl Iterator<Item = bool>) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut run: Option<(bool, u64)> = None;
+    let flush = |encoded: &mut Vec<u8>, (level, count): (bool, u64)| {
+        write_varint(encoded, count << 1);
+        encoded.push(level as u8);
+    };
+    for level in present {
+        run = match run {
+            Some((current, count)) if current == level => Some((current, count + 1)),
+            Some(finished) => {
+                flush(&mut encoded, finished);
+                Some((level, 1))
+            }
+            None => Some((level, 1)),
+        };
+    }
+    if let Some(finished) = run {
+        flush(&mut encoded, finished);
+    }
+    encoded
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Thrift compact protocol encoder covering the structures a Parquet footer needs
+#[derive(Default)]
+struct Compact {
+    buf: Vec<u8>,
+    /// Last field id written in each open struct
+    last_field: Vec<i16>,
+}
+
+impl Compact {
+    fn zigzag(&mut self, value: i64) {
+        write_varint(&mut self.buf, ((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        write_varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self
+            .last_field
+            .last_mut()
+            .expect("field written outside a struct");
+        let delta = id - *last;
+        *last = id;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | field_type);
+        } else {
+            self.buf.push(field_type);
+            self.zigzag(id.into());
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, TYPE_I32);
+        self.zigzag(value.into());
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, TYPE_I64);
+        self.zigzag(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, TYPE_BINARY);
+        self.bytes(value);
+    }
+
+    fn list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, TYPE_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element_type);
+        } else {
+            self.buf.push(0xf0 | element_type);
+            write_varint(&mut self.buf, len as u64);
+        }
+    }
+
+    /// Opens a struct written as a list element or as the top-level value
+    fn begin(&mut self) {
+        self.last_field.push(0);
+    }
+
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, TYPE_STRUCT);
+        self.begin();
+    }
+
+    fn end(&mut self) {
+        self.buf.push(0);
+        self.last_field.pop();
+    }
+}
+
+/// Named columns of equal length, written as an uncompressed single-row-group Parquet file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    columns: Vec<(String, Column)>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn column(mut self, name: &str, column: Column) -> Self {
+        self.columns.push((name.to_string(), column));
+        self
+    }
+
+    pub fn rows(&self) -> usize {
+        self.columns.first().map_or(0, |(_, column)| column.len())
+    }
+
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        let rows = self.rows();
+        if self.columns.iter().any(|(_, column)| column.len() != rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Parquet columns differ in length",
+            ));
+        }
+
+        let mut file = MAGIC.to_vec();
+        let mut chunks = Vec::new();
+        for (_, column) in &self.columns {
+            let body = column.page_body();
+            let mut header = Compact::default();
+            header.begin();
+            header.i32(1, PAGE_DATA);
+            header.i32(2, body.len() as i32);
+            header.i32(3, body.len() as i32);
+            header.struct_field(5);
+            header.i32(1, rows as i32);
+            header.i32(2, ENCODING_PLAIN);
+            header.i32(3, ENCODING_RLE);
+            header.i32(4, ENCODING_RLE);
+            header.end();
+            header.end();
+
+            let offset = file.len() as i64;
+            file.extend_from_slice(&header.buf);
+            file.extend_from_slice(&body);
+            chunks.push((offset, (header.buf.len() + body.len()) as i64));
+        }
+
+        let mut footer = Compact::default();
+        footer.begin();
+        footer.i32(1, 1);
+        footer.list(2, TYPE_STRUCT, self.columns.len() + 1);
+        footer.begin();
+        footer.binary(4, b"schema");
+        footer.i32(5, self.columns.len() as i32);
+        footer.end();
+        for (name, column) in &self.columns {
+            footer.begin();
+            footer.i32(1, column.physical_type());
+            let repetition = if column.is_optional() {
+                REPETITION_OPTIONAL
+            } else {
+                REPETITION_REQUIRED
+            };
+            footer.i32(3, repetition);
+            footer.binary(4, name.as_bytes());
+            if let Some(converted) = column.converted_type() {
+                footer.i32(6, converted);
+            }
+            footer.end();
+        }
+        footer.i64(3, rows as i64);
+        footer.list(4, TYPE_STRUCT, 1);
+        footer.begin();
+        footer.list(1, TYPE_STRUCT, self.columns.len());
+        for ((name, column), (offset, size)) in self.columns.iter().zip(&chunks) {
+            footer.begin();
+            footer.i64(2, *offset);
+            footer.struct_field(3);
+            footer.i32(1, column.physical_type());
+            footer.list(2, TYPE_I32, 2);
+            footer.zigzag(ENCODING_PLAIN.into());
+            footer.zigzag(ENCODING_RLE.into());
+            footer.list(3, TYPE_BINARY, 1);
+            footer.bytes(name.as_bytes());
+            footer.i32(4, CODEC_UNCOMPRESSED);
+            footer.i64(5, rows as i64);
+            footer.i64(6, *size);
+            footer.i64(7, *size);
+            footer.i64(9, *offset);
+            footer.end();
+            footer.end();
+        }
+        footer.i64(2, chunks.iter().map(|(_, size)| size).sum());
+        footer.i64(3, rows as i64);
+        footer.end();
+        footer.binary(
+            6,
+            format!("historee {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        footer.end();
+
+        file.extend_from_slice(&footer.buf);
+        file.extend_from_slice(&(footer.buf.len() as u32).to_le_bytes());
+        file.extend_from_slice(MAGIC);
+        out.write_all(&file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(value: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, value);
+        buf
+    }
+
+    fn write(table: &Table) -> Vec<u8> {
+        let mut file = Vec::new();
+        table.write(&mut file).unwrap();
+        file
+    }
+
+    /// Thrift footer of a written file, checked against its trailing length and magic
+    fn footer(file: &[u8]) -> &[u8] {
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let len_at = file.len() - MAGIC.len() - 4;
+        let len = u32::from_le_bytes(file[len_at..len_at + 4].try_into().unwrap()) as usize;
+        &file[len_at - len..len_at]
+    }
+
+    #[test]
+    fn varints_use_seven_bits_per_byte() {
+        assert_eq!(varint(0), [0x00]);
+        assert_eq!(varint(127), [0x7f]);
+        assert_eq!(varint(128), [0x80, 0x01]);
+        assert_eq!(varint(300), [0xac, 0x02]);
+        assert_eq!(varint(u64::MAX).len(), 10);
+    }
+
+    #[test]
+    fn definition_levels_are_run_length_encoded() {
+        let levels = definition_levels([true, true, false, true].into_iter());
+        assert_eq!(levels, [4, 1, 2, 0, 2, 1]);
+        let levels = definition_levels([true; 100].into_iter());
+        assert_eq!(levels, [0xc8, 0x01, 1]);
+        assert!(definition_levels(std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn compact_fields_use_short_deltas_and_zigzag_values() {
+        let mut compact = Compact::default();
+        compact.begin();
+        compact.i32(1, 5);
+        compact.i32(20, -1);
+        compact.end();
+        assert_eq!(compact.buf, [0x15, 0x0a, 0x05, 0x28, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn optional_strings_write_levels_then_present_values() {
+        let column = Column::OptionalString(vec![Some("ab".into()), None]);
+        assert_eq!(
+            column.page_body(),
+            [4, 0, 0, 0, 2, 1, 2, 0, 2, 0, 0, 0, b'a', b'b']
+        );
+    }
+
+    #[test]
+    fn written_files_are_framed_by_magic_and_footer_length() {
+        let table = Table::new()
+            .column("visited", Column::TimestampMicros(vec![1, -1]))
+            .column(
+                "domain",
+                Column::String(vec!["a.com".into(), "b.org".into()]),
+            );
+        let file = write(&table);
+        let footer = footer(&file);
+        let footer_at = file.len() - MAGIC.len() - 4 - footer.len();
+        // The last column's page ends where the footer begins
+        let body = table.columns[1].1.page_body();
+        assert_eq!(&file[footer_at - body.len()..footer_at], &body[..]);
+        for name in ["visited", "domain", "historee "] {
+            let name = name.as_bytes();
+            assert!(footer.windows(name.len()).any(|window| window == name));
+        }
+    }
+
+    #[test]
+    fn empty_tables_still_write_a_footer() {
+        let file = write(&Table::new());
+        assert!(!footer(&file).is_empty());
+    }
+
+    #[test]
+    fn columns_of_different_lengths_are_rejected() {
+        let table = Table::new()
+            .column("a", Column::Int64(vec![1, 2]))
+            .column("b", Column::Int64(vec![1]));
+        let error = table.write(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
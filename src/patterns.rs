@@ -1,14 +1,237 @@
 use anyhow::{Context, Result};
-use regex::Regex;
+use regex::{Regex, RegexSet};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tracing::{info, warn};
 
+use crate::args::Args;
+#[cfg(feature = "cli")]
+use crate::args::PatternsCommand;
+use crate::domain::{classify_host, normalize_host, Normalizer, ValidationPolicy};
+use crate::json::Value;
+
 // Include default patterns at compile time
 const DEFAULT_PATTERNS_BYTES: &[u8] = include_bytes!("../default_domain_patterns.txt");
 
-pub fn load_domain_patterns(pattern_file_path: Option<&Path>) -> Result<Vec<Regex>> {
+pub(crate) const DEFAULT_PATTERN_FILES: [&str; 3] = [
+    "domain_patterns.toml",
+    "domain_patterns.json",
+    "domain_patterns.txt",
+];
+
+/// A normalization rule: a regex plus how a matching domain is rewritten
+#[derive(Debug, Clone)]
+pub struct PatternRule {
+    pub name: Option<String>,
+    pub regex: Regex,
+    /// Template such as `$1.example.com`; None keeps capture group 1, as legacy patterns do
+    pub replacement: Option<String>,
+    /// Rules with higher priority are tried first
+    pub priority: i64,
+}
+
+impl PatternRule {
+    /// The rewritten domain, or None when this rule does not apply
+    pub fn apply(&self, domain: &str) -> Option<String> {
+        let captures = self.regex.captures(domain)?;
+        match &self.replacement {
+            Some(template) => {
+                let mut rewritten = String::new();
+                captures.expand(template, &mut rewritten);
+                Some(rewritten)
+            }
+            None => captures.get(1).map(|matched| matched.as_str().to_string()),
+        }
+    }
+
+    /// The rule's name, or its regex for unnamed rules
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.regex.as_str())
+    }
+
+    /// Behavior-defining text of the rule, stored in provenance and fed into fingerprints
+    pub fn source(&self) -> String {
+        match &self.replacement {
+            Some(template) => format!("{}\t{}", self.regex.as_str(), template),
+            None => self.regex.as_str().to_string(),
+        }
+    }
+
+    /// Inverse of `source`
+    pub fn from_source(source: &str) -> Result<Self> {
+        let (pattern, replacement) = match source.split_once('\t') {
+            Some((pattern, template)) => (pattern, Some(template.to_string())),
+            None => (source, None),
+        };
+        Ok(Self {
+            name: None,
+            regex: Regex::new(pattern).context("Invalid stored pattern")?,
+            replacement,
+            priority: 0,
+        })
+    }
+}
+
+impl From<Regex> for PatternRule {
+    fn from(regex: Regex) -> Self {
+        Self {
+            name: None,
+            regex,
+            replacement: None,
+            priority: 0,
+        }
+    }
+}
+
+/// Ordered normalization rules, prefiltered with a single `RegexSet` pass per domain
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    rules: Vec<PatternRule>,
+    set: RegexSet,
+}
+
+impl Default for PatternSet {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            set: RegexSet::empty(),
+        }
+    }
+}
+
+impl PatternSet {
+    pub fn new(rules: Vec<PatternRule>) -> Result<Self> {
+        let set = RegexSet::new(rules.iter().map(|rule| rule.regex.as_str()))
+            .context("Failed to compile pattern set")?;
+        Ok(Self { rules, set })
+    }
+
+    pub fn rules(&self) -> &[PatternRule] {
+        &self.rules
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Index and output of the first rule that rewrites `domain`; only rules the set
+    /// matched are run again for captures
+    pub fn apply(&self, domain: &str) -> Option<(usize, String)> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        self.set
+            .matches(domain)
+            .into_iter()
+            .find_map(|index| self.rules[index].apply(domain).map(|out| (index, out)))
+    }
+}
+
+/// Parses one regex per line; invalid lines are errors when `strict`, else skipped with a warning
+fn parse_legacy_patterns(content: &str, strict: bool, component: &str) -> Result<Vec<PatternRule>> {
+    let mut patterns = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            match Regex::new(line) {
+                Ok(regex) => patterns.push(regex.into()),
+                Err(e) if strict => {
+                    anyhow::bail!("Invalid regex pattern at line {}: {}", line_num + 1, e)
+                }
+                Err(e) => {
+                    warn!(action = "parse", component = component, line_number = line_num + 1, error = %e, "Invalid regex pattern")
+                }
+            }
+        }
+    }
+    Ok(patterns)
+}
+
+/// Parses a v2 document: a `rules` array of tables with `match` and optional `name`,
+/// `replace` and `priority`, ordered by descending priority
+pub fn parse_structured_patterns(document: &Value) -> Result<Vec<PatternRule>> {
+    let rules = match document.get("rules") {
+        Some(rules) => rules
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("'rules' must be an array of tables"))?,
+        None => &[],
+    };
+
+    let mut patterns = Vec::with_capacity(rules.len());
+    for (index, rule) in rules.iter().enumerate() {
+        let label = match rule.get("name").and_then(Value::as_str) {
+            Some(name) => format!("rule '{name}'"),
+            None => format!("rule #{}", index + 1),
+        };
+        let Value::Object(fields) = rule else {
+            anyhow::bail!("{label} must be a table");
+        };
+        if let Some((key, _)) = fields
+            .iter()
+            .find(|(key, _)| !matches!(key.as_str(), "name" | "match" | "replace" | "priority"))
+        {
+            anyhow::bail!("{label} has unknown key '{key}'");
+        }
+
+        let string_field = |key: &str| -> Result<Option<String>> {
+            match rule.get(key) {
+                None => Ok(None),
+                Some(value) => value
+                    .as_str()
+                    .map(|s| Some(s.to_string()))
+                    .ok_or_else(|| anyhow::anyhow!("{label}: '{key}' must be a string")),
+            }
+        };
+        let pattern =
+            string_field("match")?.ok_or_else(|| anyhow::anyhow!("{label} is missing 'match'"))?;
+        let regex =
+            Regex::new(&pattern).with_context(|| format!("{label} has an invalid regex"))?;
+        let replacement = string_field("replace")?;
+        if replacement.is_none() && regex.captures_len() < 2 {
+            anyhow::bail!("{label} needs a capture group or a 'replace' template");
+        }
+        let priority = match rule.get("priority") {
+            None => 0,
+            Some(value) => value
+                .as_i64()
+                .ok_or_else(|| anyhow::anyhow!("{label}: 'priority' must be an integer"))?,
+        };
+
+        patterns.push(PatternRule {
+            name: string_field("name")?,
+            regex,
+            replacement,
+            priority,
+        });
+    }
+
+    // Stable, so equal priorities keep file order
+    patterns.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+    Ok(patterns)
+}
+
+/// Parses a pattern file by extension: `.toml` and `.json` use the v2 rule format,
+/// anything else is one regex per line
+fn parse_pattern_file(path: &Path, content: &str, strict: bool) -> Result<Vec<PatternRule>> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    let parsed = match extension.as_deref() {
+        Some("toml") => crate::toml::parse(content).and_then(|doc| parse_structured_patterns(&doc)),
+        Some("json") => crate::json::parse(content).and_then(|doc| parse_structured_patterns(&doc)),
+        _ => parse_legacy_patterns(content, strict, "regex_pattern"),
+    };
+    parsed.with_context(|| format!("Failed to load patterns from {path:?}"))
+}
+
+pub fn load_domain_patterns(pattern_file_path: Option<&Path>) -> Result<PatternSet> {
     let start_time = Instant::now();
     info!(
         action = "start",
@@ -25,35 +248,14 @@ pub fn load_domain_patterns(pattern_file_path: Option<&Path>) -> Result<Vec<Rege
         }
 
         let content = fs::read_to_string(path)?;
-        for (line_num, line) in content.lines().enumerate() {
-            let line = line.trim();
-            if !line.is_empty() && !line.starts_with('#') {
-                match Regex::new(line) {
-                    Ok(regex) => patterns.push(regex),
-                    Err(e) => {
-                        anyhow::bail!("Invalid regex pattern at line {}: {}", line_num + 1, e)
-                    }
-                }
-            }
-        }
+        patterns = parse_pattern_file(path, &content, true)?;
         info!(action = "loaded", component = "pattern_file", pattern_count = patterns.len(), file_path = ?path, "Loaded patterns from file");
     } else {
-        // Try default file
-        let default_file = Path::new("domain_patterns.txt");
-        if default_file.exists() {
+        // Try default files, preferring the structured formats
+        if let Some(default_file) = crate::utils::find_config_file(&DEFAULT_PATTERN_FILES) {
             info!(action = "load", component = "default_pattern_file", file_path = ?default_file, "Loading patterns from default file");
-            let content = fs::read_to_string(default_file)?;
-            for (line_num, line) in content.lines().enumerate() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    match Regex::new(line) {
-                        Ok(regex) => patterns.push(regex),
-                        Err(e) => {
-                            warn!(action = "parse", component = "regex_pattern", line_number = line_num + 1, error = %e, "Invalid regex pattern")
-                        }
-                    }
-                }
-            }
+            let content = fs::read_to_string(&default_file)?;
+            patterns = parse_pattern_file(&default_file, &content, false)?;
             info!(action = "loaded", component = "default_pattern_file", pattern_count = patterns.len(), file_path = ?default_file, "Loaded patterns from default file");
         }
 
@@ -67,17 +269,7 @@ pub fn load_domain_patterns(pattern_file_path: Option<&Path>) -> Result<Vec<Rege
             let default_content = std::str::from_utf8(DEFAULT_PATTERNS_BYTES)
                 .context("Failed to decode embedded default patterns")?;
 
-            for (line_num, line) in default_content.lines().enumerate() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    match Regex::new(line) {
-                        Ok(regex) => patterns.push(regex),
-                        Err(e) => {
-                            warn!(action = "parse", component = "embedded_regex_pattern", line_number = line_num + 1, error = %e, "Invalid regex pattern")
-                        }
-                    }
-                }
-            }
+            patterns = parse_legacy_patterns(default_content, false, "embedded_regex_pattern")?;
             info!(
                 action = "loaded",
                 component = "embedded_patterns",
@@ -95,23 +287,277 @@ pub fn load_domain_patterns(pattern_file_path: Option<&Path>) -> Result<Vec<Rege
         duration_ms = pattern_time.as_millis(),
         "Successfully compiled patterns"
     );
-    Ok(patterns)
+    PatternSet::new(patterns)
+}
+
+/// Builds the normalizer described by `--patterns`, `--no-patterns`, `--aliases`,
+/// `--include-private-hosts` and `--validation`
+pub(crate) fn load_normalizer(args: &Args) -> Result<Normalizer> {
+    let patterns = if args.no_patterns {
+        PatternSet::default()
+    } else {
+        load_domain_patterns(args.patterns.as_deref())?
+    };
+    if args.validation == ValidationPolicy::Psl && crate::psl::full_list_path().is_none() {
+        warn!(
+            action = "configure",
+            component = "validation",
+            "The built-in suffix list only covers some TLDs; download the full list for --validation psl"
+        );
+    }
+    Ok(Normalizer {
+        patterns,
+        aliases: load_aliases(args.aliases.as_deref())?,
+        include_private: args.include_private_hosts,
+        validation: args.validation,
+        keep_subdomains: args.keep_subdomains,
+        keep_ports: args.keep_ports,
+    })
+}
+
+#[cfg(feature = "cli")]
+pub(crate) fn run(command: &PatternsCommand, args: &Args) -> Result<()> {
+    match command {
+        PatternsCommand::Test { domain } => {
+            let normalizer = load_normalizer(args)?;
+            print!("{}", explain(domain, &normalizer)?);
+            Ok(())
+        }
+    }
+}
+
+/// Walks a domain or URL through validation, suffix trimming, patterns and aliases,
+/// describing each step
+pub fn explain(input: &str, normalizer: &Normalizer) -> Result<String> {
+    let mut out = String::new();
+    let step = |out: &mut String, name: &str, detail: &str| {
+        out.push_str(&format!("{:<12}{}\n", format!("{name}:"), detail));
+    };
+
+    // Parse the input the way history URLs are parsed, so case and IDNA match analysis
+    let url = if input.contains("://") {
+        url::Url::parse(input)
+    } else {
+        url::Url::parse(&format!("http://{input}"))
+    }
+    .with_context(|| format!("Invalid domain or URL: {input}"))?;
+    let raw_host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("No host in {input}"))?;
+    step(&mut out, "Input", input);
+    step(&mut out, "Host", raw_host);
+    let host = normalize_host(raw_host);
+    let host = host.as_ref();
+    if host != raw_host {
+        step(&mut out, "Normalized", host);
+    }
+    if let Some(port) = url.port() {
+        let detail = if normalizer.keep_ports {
+            format!("{port}, kept (--keep-ports)")
+        } else {
+            format!("{port}, dropped")
+        };
+        step(&mut out, "Port", &detail);
+    }
+
+    if let Err(class) = classify_host(host, &PatternSet::default(), normalizer.validation) {
+        if normalizer.include_private && class.is_private() {
+            step(
+                &mut out,
+                "Validation",
+                &format!("{class} host, kept as-is (--include-private-hosts)"),
+            );
+        } else {
+            step(&mut out, "Validation", &format!("rejected ({class})"));
+            step(&mut out, "Result", &format!("removed as {class}"));
+            return Ok(out);
+        }
+    } else if normalizer.keep_subdomains {
+        step(&mut out, "Validation", "ok");
+        step(
+            &mut out,
+            "Trimmed",
+            "full host kept, patterns skipped (--keep-subdomains)",
+        );
+    } else {
+        step(&mut out, "Validation", "ok");
+
+        let list = crate::psl::suffix_list();
+        let suffix_labels = list.suffix_labels(host);
+        let suffix = crate::psl::last_labels(host, suffix_labels).unwrap_or(host);
+        let trimmed = crate::psl::last_labels(host, suffix_labels + 2).unwrap_or(host);
+        step(
+            &mut out,
+            "Trimmed",
+            &format!("{trimmed} (public suffix: {suffix})"),
+        );
+
+        match normalizer.patterns.apply(trimmed) {
+            None => step(&mut out, "Pattern", "none matched"),
+            Some((index, rewritten)) => {
+                let rule = &normalizer.patterns.rules()[index];
+                step(
+                    &mut out,
+                    "Pattern",
+                    &format!("#{} {}", index + 1, rule.label()),
+                );
+                if let Some(captures) = rule.regex.captures(trimmed) {
+                    let groups: Vec<String> = captures
+                        .iter()
+                        .enumerate()
+                        .skip(1)
+                        .map(|(i, group)| {
+                            format!("${i} = {}", group.map_or("(none)", |m| m.as_str()))
+                        })
+                        .collect();
+                    if !groups.is_empty() {
+                        step(&mut out, "Captures", &groups.join(", "));
+                    }
+                }
+                if let Some(template) = &rule.replacement {
+                    step(&mut out, "Template", template);
+                }
+                step(&mut out, "Rewritten", &rewritten);
+            }
+        }
+    }
+
+    match normalizer.classify(host) {
+        Err(class) => {
+            step(
+                &mut out,
+                "Result",
+                &format!("removed as {class} (rewritten domain has no valid TLD)"),
+            );
+        }
+        Ok(domain) => {
+            let unaliased = match normalizer.classify_unaliased(host) {
+                Ok((unaliased, _)) => unaliased,
+                Err(_) => host.to_string(),
+            };
+            if unaliased != domain {
+                step(&mut out, "Alias", &format!("{unaliased} -> {domain}"));
+            }
+            step(&mut out, "Canonical", &normalizer.with_port(domain, &url));
+        }
+    }
+    Ok(out)
+}
+
+/// Where `--init` writes the default patterns: `domain_patterns.txt` in the config directory
+pub fn default_patterns_path() -> Result<PathBuf> {
+    Ok(crate::utils::config_dir()?.join("domain_patterns.txt"))
 }
 
 pub fn init_default_patterns() -> Result<()> {
-    let default_file = Path::new("domain_patterns.txt");
+    let default_file = default_patterns_path()?;
 
     if default_file.exists() {
         anyhow::bail!(
-            "domain_patterns.txt already exists. Remove it first if you want to reinitialize."
+            "{} already exists. Remove it first if you want to reinitialize.",
+            default_file.display()
         );
     }
 
     let default_content = std::str::from_utf8(DEFAULT_PATTERNS_BYTES)
         .context("Failed to decode embedded default patterns")?;
 
-    fs::write(default_file, default_content)?;
-    println!("Created domain_patterns.txt with default patterns");
+    crate::utils::write_atomic(&default_file, default_content)?;
+    println!("Created {} with default patterns", default_file.display());
 
     Ok(())
 }
+
+/// Stable identifier for a set of normalization rules and the built-in normalization logic
+pub fn fingerprint(patterns: &PatternSet) -> String {
+    crate::domain::Normalizer::new(patterns.clone()).fingerprint()
+}
+
+/// Parses `alias youtu.be -> youtube.com` lines, resolving chains to their final name
+pub fn parse_aliases(content: &str) -> Result<HashMap<String, String>> {
+    let mut aliases = HashMap::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let rule = line
+            .strip_prefix("alias ")
+            .and_then(|rule| rule.split_once("->"))
+            .map(|(from, to)| (from.trim().to_lowercase(), to.trim().to_lowercase()))
+            .filter(|(from, to)| !from.is_empty() && !to.is_empty());
+        let Some((from, to)) = rule else {
+            anyhow::bail!(
+                "Expected 'alias <domain> -> <domain>' at line {}",
+                line_num + 1
+            );
+        };
+        aliases.insert(from, to);
+    }
+
+    // Resolve `a -> b`, `b -> c` to `a -> c` so lookups are a single step
+    let mut resolved = HashMap::new();
+    for from in aliases.keys() {
+        let mut to = &aliases[from];
+        let mut seen = vec![from];
+        while let Some(next) = aliases.get(to) {
+            if seen.contains(&to) {
+                anyhow::bail!("Alias cycle involving '{from}'");
+            }
+            seen.push(to);
+            to = next;
+        }
+        if to != from {
+            resolved.insert(from.clone(), to.clone());
+        }
+    }
+    Ok(resolved)
+}
+
+/// Loads alias rules from the given file, else `domain_aliases.txt` in the config directory
+/// or the working directory if present
+pub fn load_aliases(alias_file_path: Option<&Path>) -> Result<HashMap<String, String>> {
+    let path = match alias_file_path {
+        Some(path) if !path.exists() => anyhow::bail!("Alias file not found: {:?}", path),
+        Some(path) => path.to_path_buf(),
+        None => match crate::utils::find_config_file(&["domain_aliases.txt"]) {
+            Some(path) => path,
+            None => return Ok(HashMap::new()),
+        },
+    };
+
+    let content = fs::read_to_string(&path)?;
+    let aliases = parse_aliases(&content).with_context(|| format!("Failed to load {path:?}"))?;
+    info!(action = "loaded", component = "alias_file", alias_count = aliases.len(), file_path = ?path, "Loaded domain aliases");
+    Ok(aliases)
+}
+
+/// Domains whose canonical form differs between two sets of pattern sources.
+/// Count changes for these domains between runs reflect rule changes, not behavior.
+pub fn normalization_drift<'a>(
+    old_patterns: &[String],
+    new_patterns: &[String],
+    domains: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<String>> {
+    let compile = |sources: &[String]| -> Result<PatternSet> {
+        let rules = sources
+            .iter()
+            .map(|source| PatternRule::from_source(source))
+            .collect::<Result<_>>()?;
+        PatternSet::new(rules)
+    };
+    let old_patterns = compile(old_patterns)?;
+    let new_patterns = compile(new_patterns)?;
+
+    let mut drifted: Vec<String> = domains
+        .into_iter()
+        .filter(|domain| {
+            crate::domain::normalize_domain(domain, &old_patterns)
+                != crate::domain::normalize_domain(domain, &new_patterns)
+        })
+        .map(str::to_string)
+        .collect();
+    drifted.sort();
+    drifted.dedup();
+    Ok(drifted)
+}
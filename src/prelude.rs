@@ -0,0 +1,35 @@
+//! The supported library surface: `use historee::prelude::*;`
+
+pub use crate::analysis::{Analysis, Report, VisitEvent};
+pub use crate::analyzer::{Analyzer, AnalyzerBuilder, Visit, Visits};
+pub use crate::args::{Browser, Format, Period, RankBy, Weight};
+pub use crate::blocklist::{BlockedStats, Blocklist};
+pub use crate::browser::{BrowserHandler, HistoryNotFound};
+pub use crate::cancel::{CancellationToken, Cancelled};
+pub use crate::categories::{Categories, CategoryBreakdown};
+pub use crate::domain::{DomainFilter, HostClass, Normalizer, ValidationPolicy};
+pub use crate::downloads::{DownloadCount, DownloadStats};
+pub use crate::error::HistoreeError;
+#[cfg(feature = "html-report")]
+pub use crate::html::HtmlRenderer;
+pub use crate::keywords::TitleKeywords;
+pub use crate::media::{MediaStats, MediaTime};
+pub use crate::navigation::{Degree, NavigationGraph};
+pub use crate::patterns::{PatternRule, PatternSet};
+pub use crate::rdap::DomainAge;
+pub use crate::report::{renderer, RenderOptions, Renderer, TextRenderer};
+pub use crate::schema::{Schema, SchemaFamily, Transition};
+pub use crate::search::SearchTerms;
+pub use crate::segments::SegmentUsage;
+pub use crate::stats::{
+    AnalysisResult, DateRange, Diversity, DomainGroup, DomainPaths, DomainStats, LongTail,
+    Normalization, Page, PageStats, PatternHits, Provenance, RemovedHosts, RemovedSamples,
+    Sampling, SchemeCounts, SkippedBrowser, Timings, TransitionStats, VisitTotals,
+};
+pub use crate::temporal::{
+    DomainFrecency, DomainNovelty, DomainSpans, DomainStreak, DomainStreaks, DomainTrend,
+    DomainTrends, NoveltyMonth, PeriodBreakdown, PeriodBucket, PeriodComparison, RankMovement,
+    SessionStats, StaleDomain, TemporalStats, TimeSpent,
+};
+pub use crate::timezone::Timezone;
+pub use crate::tld::{TldBreakdown, TldCount};
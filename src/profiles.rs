@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+/// One `[ProfileN]` section of a Firefox-style profiles.ini
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: Option<String>,
+    /// `Path=` exactly as written, which is how `[Install…]` sections refer to profiles
+    pub path: String,
+    /// Whether `path` is relative to the directory holding profiles.ini
+    pub is_relative: bool,
+    /// Marked with `Default=1`, the default for builds without an install section
+    pub is_default: bool,
+}
+
+impl Profile {
+    /// Absolute profile directory, resolved against the directory holding profiles.ini
+    pub fn directory(&self, base: &Path) -> PathBuf {
+        if self.is_relative {
+            base.join(&self.path)
+        } else {
+            PathBuf::from(&self.path)
+        }
+    }
+}
+
+/// Default profile recorded for one installation in an `[Install…]` section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Install {
+    pub id: String,
+    /// `Path=` of the installation's default profile
+    pub default: String,
+    /// Set when the user pinned the profile to this installation
+    pub locked: bool,
+}
+
+/// The profiles and installations listed in profiles.ini, in file order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfilesIni {
+    pub profiles: Vec<Profile>,
+    pub installs: Vec<Install>,
+}
+
+impl ProfilesIni {
+    pub fn parse(content: &str) -> Self {
+        let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with([';', '#']) {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.push((name.trim().to_string(), Vec::new()));
+            } else if let (Some((key, value)), Some((_, entries))) =
+                (line.split_once('='), sections.last_mut())
+            {
+                entries.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let mut ini = ProfilesIni::default();
+        for (section, entries) in sections {
+            let get = |key: &str| {
+                entries
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, value)| value.as_str())
+            };
+            if let Some(id) = section.strip_prefix("Install") {
+                if let Some(default) = get("Default").filter(|path| !path.is_empty()) {
+                    ini.installs.push(Install {
+                        id: id.to_string(),
+                        default: default.to_string(),
+                        locked: get("Locked") == Some("1"),
+                    });
+                }
+            } else if section.starts_with("Profile") {
+                if let Some(path) = get("Path").filter(|path| !path.is_empty()) {
+                    ini.profiles.push(Profile {
+                        name: get("Name").map(str::to_string),
+                        path: path.to_string(),
+                        // Firefox treats a missing IsRelative as an absolute path
+                        is_relative: get("IsRelative") == Some("1"),
+                        is_default: get("Default") == Some("1"),
+                    });
+                }
+            }
+        }
+        ini
+    }
+
+    /// Profiles in the order Firefox would pick them as the default: install defaults
+    /// (locked ones first), then the `Default=1` profile, then every other profile
+    pub fn candidates(&self) -> Vec<Profile> {
+        let mut installs: Vec<&Install> = self.installs.iter().collect();
+        installs.sort_by_key(|install| !install.locked);
+
+        let mut candidates: Vec<Profile> = Vec::new();
+        let mut push = |profile: Profile| {
+            if !candidates.iter().any(|p| p.path == profile.path) {
+                candidates.push(profile);
+            }
+        };
+        for install in installs {
+            match self.profiles.iter().find(|p| p.path == install.default) {
+                Some(profile) => push(profile.clone()),
+                // Install sections can outlive their profile entry; their path is relative
+                // unless it is absolute
+                None => push(Profile {
+                    name: None,
+                    path: install.default.clone(),
+                    is_relative: Path::new(&install.default).is_relative(),
+                    is_default: false,
+                }),
+            }
+        }
+        for profile in self.profiles.iter().filter(|p| p.is_default) {
+            push(profile.clone());
+        }
+        for profile in &self.profiles {
+            push(profile.clone());
+        }
+        candidates
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::args::{Args, LogLevel};
+
+/// Minimum time between redraws, so tight loops don't flood the terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+/// Progress lines drawn on stderr, one per bar; a no-op when disabled
+#[derive(Debug)]
+pub struct Progress {
+    enabled: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    bars: Vec<BarState>,
+    /// Lines written by the last redraw, erased before the next one
+    drawn: usize,
+    last_draw: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct BarState {
+    label: String,
+    position: u64,
+    total: u64,
+    message: Option<String>,
+}
+
+impl Progress {
+    /// Shown only when stdout is a terminal, `--quiet` is not set and no info logs go to stderr
+    pub fn for_args(args: &Args) -> Self {
+        Self::new(
+            !args.quiet
+                && (args.log_level() < LogLevel::Info || args.log_file.is_some())
+                && std::io::stdout().is_terminal()
+                && std::io::stderr().is_terminal(),
+        )
+    }
+
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// The bar with `label`, added below the existing bars on first use
+    pub fn bar(&self, label: &str) -> Bar<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let index = match state.bars.iter().position(|bar| bar.label == label) {
+            Some(index) => index,
+            None => {
+                state.bars.push(BarState {
+                    label: label.to_string(),
+                    position: 0,
+                    total: 0,
+                    message: None,
+                });
+                state.bars.len() - 1
+            }
+        };
+        Bar {
+            progress: self,
+            index,
+        }
+    }
+
+    fn update(&self, index: usize, force: bool, change: impl FnOnce(&mut BarState)) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut state.bars[index]);
+        let due = state
+            .last_draw
+            .is_none_or(|last| last.elapsed() >= REDRAW_INTERVAL);
+        if force || due {
+            state.draw();
+        }
+    }
+}
+
+impl Drop for Progress {
+    /// Erases the bars so the report starts on a clean terminal
+    fn drop(&mut self) {
+        if self.enabled {
+            let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "{}", erase(state.drawn));
+            let _ = stderr.flush();
+        }
+    }
+}
+
+/// Escape sequence moving the cursor up over `lines` lines and clearing them
+fn erase(lines: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..lines {
+        out.push_str("\x1b[1A\x1b[2K");
+    }
+    out.push('\r');
+    out
+}
+
+impl State {
+    fn draw(&mut self) {
+        let label_width = self
+            .bars
+            .iter()
+            .map(|bar| bar.label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut out = erase(self.drawn);
+        for bar in &self.bars {
+            let line = match &bar.message {
+                Some(message) => message.clone(),
+                None if bar.total == 0 => "waiting".to_string(),
+                None => {
+                    let ratio = (bar.position as f64 / bar.total as f64).min(1.0);
+                    let filled = (ratio * BAR_WIDTH as f64) as usize;
+                    format!(
+                        "[{}{}] {}/{} rows",
+                        "#".repeat(filled),
+                        "-".repeat(BAR_WIDTH - filled),
+                        crate::utils::format_number(bar.position.min(u32::MAX as u64) as u32),
+                        crate::utils::format_number(bar.total.min(u32::MAX as u64) as u32)
+                    )
+                }
+            };
+            out.push_str(&format!("{:<label_width$}  {line}\n", bar.label));
+        }
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "{out}");
+        let _ = stderr.flush();
+        self.drawn = self.bars.len();
+        self.last_draw = Some(Instant::now());
+    }
+}
+
+/// Handle to one progress line
+#[derive(Debug, Clone, Copy)]
+pub struct Bar<'a> {
+    progress: &'a Progress,
+    index: usize,
+}
+
+impl Bar<'_> {
+    /// Sets the number of rows expected and restarts counting
+    pub fn start(&self, total: u64) {
+        self.progress.update(self.index, true, |bar| {
+            bar.total = total;
+            bar.position = 0;
+            bar.message = None;
+        });
+    }
+
+    pub fn inc(&self, rows: u64) {
+        self.progress
+            .update(self.index, false, |bar| bar.position += rows);
+    }
+
+    /// Replaces the bar with a final message such as "done" or "skipped"
+    pub fn finish(&self, message: &str) {
+        self.progress.update(self.index, true, |bar| {
+            bar.message = Some(message.to_string());
+        });
+    }
+}
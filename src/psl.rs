@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// File name of the full list, looked up in the config directory
+const SUFFIX_LIST_FILE: &str = "public_suffix_list.dat";
+
+#[cfg(feature = "psl")]
+const BUILTIN_SUFFIXES: &str = include_str!("../public_suffixes.txt");
+/// Without the `psl` feature, every suffix not in a downloaded list is one label
+#[cfg(not(feature = "psl"))]
+const BUILTIN_SUFFIXES: &str = "";
+
+/// Public suffix rules in the format of https://publicsuffix.org/list/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuffixList {
+    rules: HashSet<String>,
+    /// Parents of `*.` rules, e.g. `ck` for `*.ck`
+    wildcards: HashSet<String>,
+    /// `!` rules, stored without the `!`
+    exceptions: HashSet<String>,
+    /// Last label of every rule
+    tlds: HashSet<String>,
+    /// Digest of the list source, part of the normalization fingerprint
+    digest: u64,
+}
+
+impl SuffixList {
+    pub fn parse(content: &str) -> Self {
+        let mut list = SuffixList::default();
+        for line in content.lines() {
+            // Rules end at the first whitespace; everything after is ignored
+            let rule = line.split_whitespace().next().unwrap_or_default();
+            if rule.is_empty() || rule.starts_with("//") {
+                continue;
+            }
+            let rule = rule.to_lowercase();
+            if let Some(tld) = rule.rsplit('.').next() {
+                list.tlds.insert(to_ascii(tld));
+            }
+            if let Some(exception) = rule.strip_prefix('!') {
+                list.exceptions.insert(to_ascii(exception));
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                list.wildcards.insert(to_ascii(parent));
+            } else {
+                list.rules.insert(to_ascii(&rule));
+            }
+        }
+
+        list.digest = crate::utils::fnv(content.as_bytes());
+        list
+    }
+
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Whether any rule ends in `tld`; with the full list this covers every delegated TLD
+    pub fn has_tld(&self, tld: &str) -> bool {
+        self.tlds.contains(tld)
+    }
+
+    /// Number of labels in the public suffix of `domain`; unlisted TLDs count as one label
+    pub fn suffix_labels(&self, domain: &str) -> usize {
+        // Candidates run from the whole domain down to its TLD, so the first match is the longest
+        let starts = std::iter::once(0).chain(domain.match_indices('.').map(|(i, _)| i + 1));
+        for start in starts {
+            let candidate = &domain[start..];
+            let labels = candidate.split('.').count();
+            if self.exceptions.contains(candidate) {
+                return labels - 1;
+            }
+            if self.rules.contains(candidate) {
+                return labels;
+            }
+            if let Some((_, parent)) = candidate.split_once('.') {
+                if self.wildcards.contains(parent) {
+                    return labels;
+                }
+            }
+        }
+        1
+    }
+
+    /// The public suffix plus one label, e.g. `example.co.uk` for `www.example.co.uk`
+    pub fn registrable_domain<'a>(&self, domain: &'a str) -> Option<&'a str> {
+        let keep = self.suffix_labels(domain) + 1;
+        last_labels(domain, keep).filter(|registrable| registrable.split('.').count() == keep)
+    }
+}
+
+/// Punycode form of a rule, since hosts parsed from URLs are IDNA-encoded
+fn to_ascii(rule: &str) -> String {
+    if rule.is_ascii() {
+        return rule.to_string();
+    }
+    match url::quirks::domain_to_ascii(rule) {
+        encoded if !encoded.is_empty() => encoded,
+        _ => rule.to_string(),
+    }
+}
+
+/// The last `n` labels of `domain`, or None if it has fewer
+pub fn last_labels(domain: &str, n: usize) -> Option<&str> {
+    if n == 0 {
+        return Some("");
+    }
+    match domain.rmatch_indices('.').nth(n - 1) {
+        Some((dot, _)) => Some(&domain[dot + 1..]),
+        None => (domain.split('.').count() == n).then_some(domain),
+    }
+}
+
+/// Location of the full list in the config directory, if it has been downloaded
+pub fn full_list_path() -> Option<PathBuf> {
+    let path = crate::utils::config_dir().ok()?.join(SUFFIX_LIST_FILE);
+    path.exists().then_some(path)
+}
+
+/// The suffix list used for normalization: the full list from the config directory if
+/// present, otherwise the built-in subset
+pub fn suffix_list() -> &'static SuffixList {
+    static LIST: OnceLock<SuffixList> = OnceLock::new();
+    LIST.get_or_init(|| {
+        let Some(path) = full_list_path() else {
+            return SuffixList::parse(BUILTIN_SUFFIXES);
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                let list = SuffixList::parse(&content);
+                info!(
+                    action = "load",
+                    component = "public_suffix_list",
+                    path = ?path,
+                    rule_count = list.rules.len() + list.wildcards.len() + list.exceptions.len(),
+                    "Loaded public suffix list"
+                );
+                list
+            }
+            Err(e) => {
+                warn!(
+                    action = "load",
+                    component = "public_suffix_list",
+                    path = ?path,
+                    error = %e,
+                    "Failed to read public suffix list, using the built-in subset"
+                );
+                SuffixList::parse(BUILTIN_SUFFIXES)
+            }
+        }
+    })
+}
@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+use tracing::info;
+
+use crate::args::{Args, Browser, QueryFormat};
+use crate::json::Value;
+use crate::report::csv_field;
+use crate::{browser, export, utils};
+
+/// Result rows with their column names, every value already rendered as a cell
+struct Rows {
+    columns: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+enum Cell {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Cell {
+    fn from_sql(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Null => Cell::Null,
+            ValueRef::Integer(n) => Cell::Int(n),
+            ValueRef::Real(n) => Cell::Float(n),
+            ValueRef::Text(text) => Cell::Text(String::from_utf8_lossy(text).into_owned()),
+            // Blobs are shown as hex, since they rarely hold text
+            ValueRef::Blob(bytes) => Cell::Text(bytes.iter().map(|b| format!("{b:02x}")).collect()),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Cell::Null => String::new(),
+            Cell::Int(n) => n.to_string(),
+            Cell::Float(n) => n.to_string(),
+            Cell::Text(text) => text.clone(),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Cell::Null => Value::Null,
+            Cell::Int(n) => Value::Int(*n),
+            Cell::Float(n) => Value::Float(*n),
+            Cell::Text(text) => Value::String(text.clone()),
+        }
+    }
+}
+
+/// Runs one read-only statement against a private copy of the browser's history
+fn run_query(sql: &str, browser: &Browser, args: &Args) -> Result<Rows> {
+    let start_time = Instant::now();
+    let (temp_database, family) = browser::history_database(browser, args)?;
+    // The copy is opened read-only as well, so a statement can never write anywhere
+    let conn = Connection::open_with_flags(
+        temp_database.path(),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut stmt = conn.prepare(sql).context("Invalid query")?;
+    if !stmt.readonly() {
+        anyhow::bail!("Only read-only queries are allowed");
+    }
+
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = Vec::new();
+    let mut query = stmt.query([])?;
+    while let Some(row) = query.next()? {
+        rows.push(
+            (0..columns.len())
+                .map(|i| row.get_ref(i).map(Cell::from_sql))
+                .collect::<rusqlite::Result<_>>()?,
+        );
+    }
+
+    info!(
+        action = "complete",
+        component = "query",
+        browser = ?browser,
+        family = %family,
+        rows = rows.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Query completed"
+    );
+    Ok(Rows { columns, rows })
+}
+
+fn write_rows(out: &mut dyn Write, rows: &Rows, format: QueryFormat) -> Result<()> {
+    match format {
+        QueryFormat::Table => {
+            let cells: Vec<Vec<String>> = rows
+                .rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| cell.text().replace(['\n', '\r', '\t'], " "))
+                        .collect()
+                })
+                .collect();
+            let widths: Vec<usize> = rows
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    cells
+                        .iter()
+                        .map(|row| row[i].chars().count())
+                        .chain([column.chars().count()])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let line = |values: &[String]| -> String {
+                values
+                    .iter()
+                    .zip(&widths)
+                    .map(|(value, width)| format!("{value:<width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            writeln!(out, "{}", line(&rows.columns))?;
+            let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            writeln!(out, "{}", line(&rule))?;
+            for row in &cells {
+                writeln!(out, "{}", line(row))?;
+            }
+            writeln!(out, "({} rows)", rows.rows.len())?;
+        }
+        QueryFormat::Csv => {
+            let header: Vec<String> = rows.columns.iter().map(|c| csv_field(c)).collect();
+            writeln!(out, "{}", header.join(","))?;
+            for row in &rows.rows {
+                let fields: Vec<String> = row.iter().map(|cell| csv_field(&cell.text())).collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        QueryFormat::Jsonl => {
+            for row in &rows.rows {
+                let record = Value::Object(
+                    rows.columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, cell)| (column.clone(), cell.json()))
+                        .collect(),
+                );
+                writeln!(out, "{record}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Handles `historee query`: copies the history database and prints the query's rows
+pub fn run(sql: &str, browser: Option<Browser>, format: QueryFormat, args: &Args) -> Result<()> {
+    let browser = browser.unwrap_or(args.browser);
+    let rows = run_query(sql, &browser, args)?;
+    match args.single_output()? {
+        Some(path) => {
+            utils::write_atomic_with(path, |out| write_rows(out, &rows, format))?;
+            info!(action = "write", component = "query", path = ?path, "Wrote query results");
+            Ok(())
+        }
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            let written = write_rows(&mut out, &rows, format).and_then(|()| Ok(out.flush()?));
+            match written {
+                Err(e) if export::is_broken_pipe(&e) => Ok(()),
+                written => written,
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::cancel::CancellationToken;
+use crate::{http, json, utils};
+
+const RDAP_ENDPOINT: &str = "https://rdap.org/domain/";
+const CACHE_FILE: &str = "rdap.tsv";
+/// How long a failed lookup (no registration event) is remembered before retrying
+const UNKNOWN_TTL_DAYS: i64 = 7;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainAge {
+    pub domain: String,
+    pub registered: DateTime<Utc>,
+    pub visits: u32,
+}
+
+impl DomainAge {
+    pub fn age_days(&self) -> i64 {
+        (Utc::now() - self.registered).num_days()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CacheEntry {
+    registered: Option<DateTime<Utc>>,
+    checked_at: DateTime<Utc>,
+}
+
+/// Local cache of RDAP registration dates, stored as tab-separated lines
+struct RdapCache {
+    path: PathBuf,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl RdapCache {
+    fn load() -> Result<Self> {
+        let path = utils::cache_dir()?.join(CACHE_FILE);
+        let mut entries = HashMap::new();
+
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                let mut fields = line.split('\t');
+                let (Some(domain), Some(registered), Some(checked_at)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    continue;
+                };
+                let Ok(checked_at) = DateTime::parse_from_rfc3339(checked_at) else {
+                    continue;
+                };
+                entries.insert(
+                    domain.to_string(),
+                    CacheEntry {
+                        registered: DateTime::parse_from_rfc3339(registered)
+                            .ok()
+                            .map(|d| d.with_timezone(&Utc)),
+                        checked_at: checked_at.with_timezone(&Utc),
+                    },
+                );
+            }
+        }
+
+        Ok(Self { path, entries })
+    }
+
+    fn get(&self, domain: &str) -> Option<CacheEntry> {
+        let entry = self.entries.get(domain)?;
+        if entry.registered.is_none()
+            && Utc::now() - entry.checked_at > Duration::days(UNKNOWN_TTL_DAYS)
+        {
+            return None;
+        }
+        Some(*entry)
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut domains: Vec<&String> = self.entries.keys().collect();
+        domains.sort();
+
+        let content: String = domains
+            .into_iter()
+            .map(|domain| {
+                let entry = &self.entries[domain];
+                format!(
+                    "{}\t{}\t{}\n",
+                    domain,
+                    entry
+                        .registered
+                        .map(|d| d.to_rfc3339())
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    entry.checked_at.to_rfc3339()
+                )
+            })
+            .collect();
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+}
+
+fn lookup_registration(domain: &str) -> Result<Option<DateTime<Utc>>> {
+    let body = http::get(&format!("{RDAP_ENDPOINT}{domain}"))?;
+    let response = json::parse(&body)?;
+
+    let registered = response
+        .get("events")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .find(|event| {
+            event.get("eventAction").and_then(json::Value::as_str) == Some("registration")
+        })
+        .and_then(|event| event.get("eventDate").and_then(json::Value::as_str))
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.with_timezone(&Utc));
+
+    Ok(registered)
+}
+
+/// Looks up registration dates for the given domains, youngest first
+pub fn enrich(domains: &[(String, u32)], cancel: &CancellationToken) -> Result<Vec<DomainAge>> {
+    let start_time = Instant::now();
+    info!(
+        action = "start",
+        component = "rdap",
+        domain_count = domains.len(),
+        "Looking up domain registration dates"
+    );
+
+    let mut cache = RdapCache::load()?;
+    let mut lookups = 0;
+    let mut ages = Vec::new();
+
+    for (domain, visits) in domains {
+        let registered = match cache.get(domain) {
+            Some(entry) => entry.registered,
+            None => {
+                if cancel.is_cancelled() {
+                    break;
+                }
+                lookups += 1;
+                match lookup_registration(domain) {
+                    Ok(registered) => {
+                        cache.entries.insert(
+                            domain.clone(),
+                            CacheEntry {
+                                registered,
+                                checked_at: Utc::now(),
+                            },
+                        );
+                        registered
+                    }
+                    Err(e) => {
+                        warn!(action = "lookup", component = "rdap", domain = domain, error = %e, "RDAP lookup failed");
+                        None
+                    }
+                }
+            }
+        };
+
+        if let Some(registered) = registered {
+            ages.push(DomainAge {
+                domain: domain.clone(),
+                registered,
+                visits: *visits,
+            });
+        }
+    }
+
+    // Keep whatever was looked up before a cancellation
+    if lookups > 0 {
+        if let Err(e) = cache.save() {
+            warn!(action = "save", component = "rdap_cache", error = %e, "Failed to save RDAP cache");
+        }
+    }
+    cancel.check()?;
+
+    ages.sort_by_key(|age| std::cmp::Reverse(age.registered));
+
+    info!(
+        action = "complete",
+        component = "rdap",
+        lookups,
+        resolved = ages.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Domain registration lookup completed"
+    );
+    Ok(ages)
+}
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use std::fmt::Write;
+use std::path::Path;
+use tracing::info;
+
+use crate::args::{Args, ColorChoice, Format};
+use crate::json::Value;
+use crate::stats::{count_for_share, AnalysisResult};
+use crate::template::{Template, TemplateRenderer};
+use crate::utils;
+
+/// Turns an analysis result into a finished document in one output format
+pub trait Renderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String;
+}
+
+/// How a `Renderer` lays out a result, starting from the command-line defaults without color
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    args: Args,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        let mut args = Args::defaults();
+        args.color = ColorChoice::Never;
+        Self { args }
+    }
+}
+
+impl RenderOptions {
+    /// The options given on the command line, for the reports the binary writes
+    pub(crate) fn from_args(args: &Args) -> Self {
+        Self { args: args.clone() }
+    }
+
+    pub(crate) fn args(&self) -> &Args {
+        &self.args
+    }
+
+    /// Lists only the `top` most visited domains; each format has its own default
+    pub fn top(mut self, top: usize) -> Self {
+        self.args.top = Some(top);
+        self
+    }
+
+    /// Colors the text report with ANSI escapes
+    pub fn color(mut self, color: bool) -> Self {
+        self.args.color = match color {
+            true => ColorChoice::Always,
+            false => ColorChoice::Never,
+        };
+        self
+    }
+
+    /// Shortens large counts, e.g. `12.3k`
+    pub fn humanize(mut self, humanize: bool) -> Self {
+        self.args.humanize = humanize;
+        self
+    }
+
+    /// Leaves out the header and sections, printing only the ranked domains
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.args.quiet = quiet;
+        self
+    }
+}
+
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String {
+        crate::browser::render_text(result, options.args())
+    }
+}
+
+/// The summary fields plus the ranked domains, for `.json` outputs
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String {
+        let args = options.args();
+        let domains: Vec<Value> = ranked_domains(result, args)
+            .into_iter()
+            .map(|(rank, domain, count)| {
+                Value::object()
+                    .with("rank", rank)
+                    .with("domain", utils::display_domain(&domain, args))
+                    .with("count", count)
+            })
+            .collect();
+        format!("{}\n", summary(result, args).with("domains", domains))
+    }
+}
+
+/// One row per ranked domain with its count and share, for `.csv` outputs
+pub struct CsvRenderer;
+
+impl Renderer for CsvRenderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String {
+        let args = options.args();
+        let total = result.stats.total();
+        let mut out = String::from("rank,domain,count,percent\n");
+        for (rank, domain, count) in ranked_domains(result, args) {
+            writeln!(
+                out,
+                "{rank},{},{count},{:.2}",
+                csv_field(&utils::display_domain(&domain, args)),
+                utils::percent(count, total)
+            )
+            .expect("writing to a String cannot fail");
+        }
+        out
+    }
+}
+
+/// Ranked domains, limited to `--top` and the pinned domains when given
+fn ranked_domains(result: &AnalysisResult, args: &Args) -> Vec<(usize, String, u32)> {
+    result
+        .stats
+        .top_with_pins(args.top.unwrap_or(usize::MAX), &result.pinned)
+}
+
+pub fn renderer(format: Format) -> Box<dyn Renderer> {
+    match format {
+        Format::Text => Box::new(TextRenderer),
+        #[cfg(feature = "html-report")]
+        Format::Html => Box::new(crate::html::HtmlRenderer),
+    }
+}
+
+/// Output files whose extension picks their format over `--format` and `--template`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Json,
+    Csv,
+    /// Only used without `--template`, which may itself render HTML
+    #[cfg(feature = "html-report")]
+    Html,
+}
+
+fn file_format(path: &Path) -> Option<FileFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "json" => Some(FileFormat::Json),
+        "csv" => Some(FileFormat::Csv),
+        #[cfg(feature = "html-report")]
+        "html" | "htm" => Some(FileFormat::Html),
+        _ => None,
+    }
+}
+
+/// Whether any output is the HTML report, which needs recency scores
+#[cfg(feature = "html-report")]
+pub(crate) fn needs_html(args: &Args) -> bool {
+    args.format == Format::Html
+        || (args.template.is_none()
+            && args
+                .output
+                .iter()
+                .any(|path| file_format(path) == Some(FileFormat::Html)))
+}
+
+#[cfg(not(feature = "html-report"))]
+pub(crate) fn needs_html(_args: &Args) -> bool {
+    false
+}
+
+/// Renders the report in `--format`, or through `--template`, and writes it to stdout, or to
+/// each `--output` in the format its extension picks
+pub(crate) fn write_report(result: &AnalysisResult, args: &Args) -> Result<()> {
+    // Every format lists the same rows when --top-share is turned into the matching --top
+    let share_args;
+    let args = match args.top_share {
+        Some(share) => {
+            share_args = Args {
+                top: Some(top_for_share(result, share).max(1)),
+                ..args.clone()
+            };
+            &share_args
+        }
+        None => args,
+    };
+    let default: Box<dyn Renderer> = match &args.template {
+        Some(path) => Box::new(TemplateRenderer {
+            template: Template::load(path)?,
+        }),
+        None => renderer(args.format),
+    };
+    let options = RenderOptions::from_args(args);
+    if args.output.is_empty() {
+        print!("{}", default.render(result, &options));
+    }
+    for path in &args.output {
+        let rendered = match file_format(path) {
+            Some(FileFormat::Json) => JsonRenderer.render(result, &options),
+            Some(FileFormat::Csv) => CsvRenderer.render(result, &options),
+            #[cfg(feature = "html-report")]
+            Some(FileFormat::Html) if args.template.is_none() => {
+                crate::html::HtmlRenderer.render(result, &options)
+            }
+            _ => default.render(result, &options),
+        };
+        utils::write_atomic(path, &rendered)?;
+        info!(action = "write", component = "output", path = ?path, "Wrote report");
+    }
+    Ok(())
+}
+
+/// Number of top entries of the ranked list, pages, TLDs or domains, covering `share`
+/// percent of visits
+fn top_for_share(result: &AnalysisResult, share: f64) -> usize {
+    if let Some(pages) = &result.pages {
+        let counts: Vec<u32> = pages.pages.values().map(|page| page.count).collect();
+        let total = counts.iter().sum();
+        count_for_share(counts, total, share)
+    } else if let Some(tlds) = &result.tlds {
+        let counts: Vec<u32> = tlds.tlds.values().map(|count| count.visits).collect();
+        count_for_share(counts, tlds.total(), share)
+    } else {
+        result.stats.count_for_share(share)
+    }
+}
+
+/// Headline figures of an analysis, shared by `serve` and `--template`
+pub(crate) fn summary(result: &AnalysisResult, args: &Args) -> Value {
+    let (earliest, latest) = utils::date_range_labels(result.date_range, args);
+    let diversity = result.stats.diversity(crate::stats::CONCENTRATION_TOP);
+    Value::object()
+        .with("schema_version", crate::json::SCHEMA_VERSION)
+        .with(
+            "browser",
+            if args.all_browsers {
+                "all".to_string()
+            } else {
+                args.browser.to_string()
+            },
+        )
+        .with("generated_at", result.provenance.generated_at.to_rfc3339())
+        .with("earliest", earliest)
+        .with("latest", latest)
+        .with(
+            "first_visit",
+            result.date_range.map(|range| range.earliest.to_rfc3339()),
+        )
+        .with(
+            "last_visit",
+            result.date_range.map(|range| range.latest.to_rfc3339()),
+        )
+        .with("days", result.date_range.map_or(0, |range| range.days()))
+        .with("unique_domains", result.stats.unique_domains())
+        .with("total", result.stats.total())
+        .with("visits", result.totals.as_ref().map(|totals| totals.visits))
+        .with(
+            "unique_urls",
+            result.totals.as_ref().map(|totals| totals.unique_urls),
+        )
+        .with("entropy", diversity.map(|diversity| diversity.entropy))
+        .with("gini", diversity.map(|diversity| diversity.gini))
+        .with("domains_removed", result.stats.domains_removed.total())
+        .with("weight", result.provenance.weight.to_string())
+}
+
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
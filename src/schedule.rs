@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::{info, warn};
+
+use crate::args::ScheduleCommand;
+
+const TASK_NAME: &str = "historee-report";
+const LAUNCHD_LABEL: &str = "to.walters.historee.report";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+}
+
+pub fn run(command: &ScheduleCommand) -> Result<()> {
+    match command {
+        ScheduleCommand::Install {
+            daily, report_args, ..
+        } => {
+            let frequency = if *daily {
+                Frequency::Daily
+            } else {
+                Frequency::Weekly
+            };
+            install(frequency, report_args)
+        }
+        ScheduleCommand::Remove => remove(),
+    }
+}
+
+pub fn install(frequency: Frequency, report_args: &[String]) -> Result<()> {
+    let exe = env::current_exe().context("Failed to locate the historee executable")?;
+    let mut command = vec![exe.to_string_lossy().into_owned()];
+    command.extend(report_args.iter().cloned());
+
+    match env::consts::OS {
+        "linux" => install_systemd(frequency, &command),
+        "macos" => install_launchd(frequency, &command),
+        "windows" => install_schtasks(frequency, &command),
+        os => anyhow::bail!("Scheduling is not supported on {os}"),
+    }
+}
+
+pub fn remove() -> Result<()> {
+    match env::consts::OS {
+        "linux" => remove_systemd(),
+        "macos" => remove_launchd(),
+        "windows" => run_command("schtasks", &["/Delete", "/TN", TASK_NAME, "/F"]),
+        os => anyhow::bail!("Scheduling is not supported on {os}"),
+    }?;
+    println!("Removed scheduled task {TASK_NAME}");
+    Ok(())
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<()> {
+    info!(action = "run", component = "schedule", program, args = ?args, "Running scheduler command");
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !status.success() {
+        anyhow::bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+fn home_dir() -> Result<PathBuf> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow::anyhow!("HOME is not set"))
+}
+
+fn systemd_unit_dir() -> Result<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .map_or_else(|| home_dir().map(|home| home.join(".config")), Ok)?;
+    Ok(config.join("systemd/user"))
+}
+
+/// Quotes an argument for a systemd ExecStart line
+fn systemd_quote(arg: &str) -> String {
+    format!(
+        "\"{}\"",
+        arg.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+    )
+}
+
+fn install_systemd(frequency: Frequency, command: &[String]) -> Result<()> {
+    let unit_dir = systemd_unit_dir()?;
+    fs::create_dir_all(&unit_dir)?;
+
+    let exec_start: Vec<String> = command.iter().map(|arg| systemd_quote(arg)).collect();
+    let service = format!(
+        "[Unit]\nDescription=historee browsing report\n\n\
+         [Service]\nType=oneshot\nExecStart={}\n",
+        exec_start.join(" ")
+    );
+    let calendar = match frequency {
+        Frequency::Daily => "daily",
+        Frequency::Weekly => "weekly",
+    };
+    let timer = format!(
+        "[Unit]\nDescription=Run historee report {calendar}\n\n\
+         [Timer]\nOnCalendar={calendar}\nPersistent=true\n\n\
+         [Install]\nWantedBy=timers.target\n"
+    );
+
+    let service_path = unit_dir.join(format!("{TASK_NAME}.service"));
+    let timer_path = unit_dir.join(format!("{TASK_NAME}.timer"));
+    fs::write(&service_path, service)?;
+    fs::write(&timer_path, timer)?;
+
+    run_command("systemctl", &["--user", "daemon-reload"])?;
+    run_command(
+        "systemctl",
+        &["--user", "enable", "--now", &format!("{TASK_NAME}.timer")],
+    )?;
+
+    println!("Installed systemd timer {}", timer_path.display());
+    println!("Reports are written to the journal: journalctl --user -u {TASK_NAME}");
+    Ok(())
+}
+
+fn remove_systemd() -> Result<()> {
+    let unit_dir = systemd_unit_dir()?;
+    if let Err(e) = run_command(
+        "systemctl",
+        &["--user", "disable", "--now", &format!("{TASK_NAME}.timer")],
+    ) {
+        warn!(action = "disable", component = "schedule", error = %e, "Failed to disable timer");
+    }
+
+    for extension in ["service", "timer"] {
+        let path = unit_dir.join(format!("{TASK_NAME}.{extension}"));
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+    }
+    run_command("systemctl", &["--user", "daemon-reload"])
+}
+
+fn launchd_plist_path() -> Result<PathBuf> {
+    Ok(home_dir()?
+        .join("Library/LaunchAgents")
+        .join(format!("{LAUNCHD_LABEL}.plist")))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn install_launchd(frequency: Frequency, command: &[String]) -> Result<()> {
+    let plist_path = launchd_plist_path()?;
+    if let Some(parent) = plist_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let program_arguments: String = command
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+    let weekday = match frequency {
+        Frequency::Daily => String::new(),
+        Frequency::Weekly => {
+            "        <key>Weekday</key>\n        <integer>1</integer>\n".to_string()
+        }
+    };
+    let log_path = home_dir()?.join("Library/Logs/historee-report.log");
+
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_arguments}    </array>
+    <key>StartCalendarInterval</key>
+    <dict>
+{weekday}        <key>Hour</key>
+        <integer>9</integer>
+        <key>Minute</key>
+        <integer>0</integer>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        log = xml_escape(&log_path.to_string_lossy())
+    );
+
+    fs::write(&plist_path, plist)?;
+    run_command("launchctl", &["load", "-w", &plist_path.to_string_lossy()])?;
+
+    println!("Installed launchd agent {}", plist_path.display());
+    println!("Reports are written to {}", log_path.display());
+    Ok(())
+}
+
+fn remove_launchd() -> Result<()> {
+    let plist_path = launchd_plist_path()?;
+    if plist_path.exists() {
+        if let Err(e) = run_command(
+            "launchctl",
+            &["unload", "-w", &plist_path.to_string_lossy()],
+        ) {
+            warn!(action = "unload", component = "schedule", error = %e, "Failed to unload agent");
+        }
+        fs::remove_file(&plist_path)?;
+    }
+    Ok(())
+}
+
+fn install_schtasks(frequency: Frequency, command: &[String]) -> Result<()> {
+    let task_command: Vec<String> = command
+        .iter()
+        .map(|arg| {
+            if arg.contains(' ') {
+                format!("\"{arg}\"")
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    let schedule = match frequency {
+        Frequency::Daily => "DAILY",
+        Frequency::Weekly => "WEEKLY",
+    };
+
+    run_command(
+        "schtasks",
+        &[
+            "/Create",
+            "/SC",
+            schedule,
+            "/ST",
+            "09:00",
+            "/TN",
+            TASK_NAME,
+            "/TR",
+            &task_command.join(" "),
+            "/F",
+        ],
+    )?;
+
+    println!("Installed scheduled task {TASK_NAME}");
+    Ok(())
+}
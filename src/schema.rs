@@ -0,0 +1,463 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use tracing::info;
+
+use crate::error::HistoreeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFamily {
+    Chromium,
+    Firefox,
+}
+
+impl std::fmt::Display for SchemaFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaFamily::Chromium => write!(f, "Chromium"),
+            SchemaFamily::Firefox => write!(f, "Firefox"),
+        }
+    }
+}
+
+/// Table and column names used to query a particular schema version
+#[derive(Debug)]
+pub struct QuerySet {
+    pub urls_table: &'static str,
+    pub url_id_column: &'static str,
+    pub url_column: &'static str,
+    pub title_column: &'static str,
+    pub visits_table: &'static str,
+    pub visit_url_column: &'static str,
+    pub visit_time_column: &'static str,
+    /// Per-URL all-time visit counter, evaluated against the urls table aliased as `u`
+    pub visit_count_expr: &'static str,
+    /// Per-URL typed visit count, evaluated against the urls table aliased as `u`
+    pub typed_count_expr: &'static str,
+    /// Time spent on each visit in microseconds, if the browser records it
+    pub visit_duration_column: Option<&'static str>,
+    /// How the visit was initiated, decoded by `Schema::decode_transition`
+    pub visit_transition_column: &'static str,
+    pub visit_id_column: &'static str,
+    /// Id of the visit this one was navigated from, zero or NULL when there is none
+    pub from_visit_column: &'static str,
+    /// Query returning `(url id, search term)` pairs recorded by the browser, if any
+    pub search_terms_query: Option<&'static str>,
+    /// Query returning `(start time, received bytes, target path, MIME type, source URL)`
+    /// for each download, if the browser keeps downloads in the history database
+    pub downloads_query: Option<&'static str>,
+    /// Query returning `(segment name, segment URL, day time slot, visit count)` rows from
+    /// the per-day Top Sites counters, if the browser keeps them
+    pub segment_usage_query: Option<&'static str>,
+    /// Condition that holds for visits which redirected onward, with `{v}` standing for
+    /// the visits table alias
+    pub redirect_source_expr: &'static str,
+}
+
+impl QuerySet {
+    fn required_columns(&self) -> [(&'static str, [&'static str; 2]); 2] {
+        [
+            (self.urls_table, [self.url_id_column, self.url_column]),
+            (
+                self.visits_table,
+                [self.visit_url_column, self.visit_time_column],
+            ),
+        ]
+    }
+}
+
+const CHROMIUM_QUERIES: QuerySet = QuerySet {
+    urls_table: "urls",
+    url_id_column: "id",
+    url_column: "url",
+    title_column: "title",
+    visits_table: "visits",
+    visit_url_column: "url",
+    visit_time_column: "visit_time",
+    visit_count_expr: "u.visit_count",
+    typed_count_expr: "u.typed_count",
+    visit_duration_column: Some("visit_duration"),
+    visit_transition_column: "transition",
+    visit_id_column: "id",
+    from_visit_column: "from_visit",
+    search_terms_query: Some("SELECT url_id, term FROM keyword_search_terms"),
+    // The first URL of the chain is the one the download was requested from
+    downloads_query: Some(
+        "SELECT d.start_time, d.received_bytes, d.target_path, d.mime_type, c.url \
+         FROM downloads d LEFT JOIN downloads_url_chains c ON c.id = d.id AND c.chain_index = 0",
+    ),
+    segment_usage_query: Some(
+        "SELECT s.name, u.url, su.time_slot, su.visit_count FROM segment_usage su \
+         JOIN segments s ON s.id = su.segment_id LEFT JOIN urls u ON u.id = s.url_id",
+    ),
+    // Redirect chains are flagged with CHAIN_START/CHAIN_END and redirect qualifier bits;
+    // every visit in a chain except the last lacks CHAIN_END (0x20000000)
+    redirect_source_expr: "({v}.transition & 536870912) = 0 AND ({v}.transition & 3489660928) != 0",
+};
+
+/// Version 24 moved download URLs into `downloads_url_chains` and renamed `full_path` to
+/// `target_path`; `mime_type` arrived in version 29
+const CHROMIUM_V24_QUERIES: QuerySet = QuerySet {
+    downloads_query: Some(
+        "SELECT d.start_time, d.received_bytes, d.target_path, '', c.url \
+         FROM downloads d LEFT JOIN downloads_url_chains c ON c.id = d.id AND c.chain_index = 0",
+    ),
+    ..CHROMIUM_QUERIES
+};
+
+/// Before version 24 each download kept its URL and path inline, timed in Unix seconds
+const CHROMIUM_V20_QUERIES: QuerySet = QuerySet {
+    downloads_query: Some(
+        "SELECT start_time * 1000000 + 11644473600000000, received_bytes, full_path, '', url \
+         FROM downloads",
+    ),
+    ..CHROMIUM_QUERIES
+};
+
+/// Visits gained `visit_duration` in version 20
+const CHROMIUM_V16_QUERIES: QuerySet = QuerySet {
+    visit_duration_column: None,
+    ..CHROMIUM_V20_QUERIES
+};
+
+const FIREFOX_QUERIES: QuerySet = QuerySet {
+    urls_table: "moz_places",
+    url_id_column: "id",
+    url_column: "url",
+    title_column: "title",
+    visits_table: "moz_historyvisits",
+    visit_url_column: "place_id",
+    visit_time_column: "visit_date",
+    visit_count_expr: "COALESCE(u.visit_count, 0)",
+    // Firefox only keeps a typed flag per place, so count visits with TRANSITION_TYPED (2)
+    typed_count_expr: "(SELECT COUNT(*) FROM moz_historyvisits t \
+                       WHERE t.place_id = u.id AND t.visit_type = 2)",
+    visit_duration_column: None,
+    visit_transition_column: "visit_type",
+    visit_id_column: "id",
+    from_visit_column: "from_visit",
+    search_terms_query: None,
+    // Firefox records downloads as page annotations rather than in a table of their own
+    downloads_query: None,
+    segment_usage_query: None,
+    // A visit redirected onward if a later visit came from it with a redirect visit_type
+    redirect_source_expr: "EXISTS (SELECT 1 FROM moz_historyvisits r \
+                           WHERE r.from_visit = {v}.id AND r.visit_type IN (5, 6))",
+};
+
+/// How a visit was initiated, normalized across browser families
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transition {
+    Link,
+    Typed,
+    Bookmark,
+    Reload,
+    Redirect,
+    /// Keyword searches and omnibox suggestions
+    Generated,
+    FormSubmit,
+    /// Frames and embedded content
+    Embedded,
+    Other,
+}
+
+impl Transition {
+    pub const ALL: [Transition; 9] = [
+        Transition::Link,
+        Transition::Typed,
+        Transition::Bookmark,
+        Transition::Reload,
+        Transition::Redirect,
+        Transition::Generated,
+        Transition::FormSubmit,
+        Transition::Embedded,
+        Transition::Other,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::fmt::Display for Transition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Transition::Link => "link",
+            Transition::Typed => "typed",
+            Transition::Bookmark => "bookmark",
+            Transition::Reload => "reload",
+            Transition::Redirect => "redirect",
+            Transition::Generated => "generated",
+            Transition::FormSubmit => "form submit",
+            Transition::Embedded => "embedded",
+            Transition::Other => "other",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Chromium qualifier bits marking the visit as the target of a client or server redirect
+const CHROMIUM_REDIRECT_MASK: i64 = 0x4000_0000 | 0x8000_0000;
+/// Chromium core transition type lives in the low byte
+const CHROMIUM_CORE_MASK: i64 = 0xFF;
+
+/// Query sets by minimum schema version, newest first
+const CHROMIUM_ADAPTERS: &[(i64, &QuerySet)] = &[
+    (29, &CHROMIUM_QUERIES),
+    (24, &CHROMIUM_V24_QUERIES),
+    (20, &CHROMIUM_V20_QUERIES),
+    (16, &CHROMIUM_V16_QUERIES),
+];
+const FIREFOX_ADAPTERS: &[(i64, &QuerySet)] = &[(10, &FIREFOX_QUERIES)];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Schema {
+    pub family: SchemaFamily,
+    pub version: i64,
+    pub queries: &'static QuerySet,
+}
+
+impl Schema {
+    /// Converts a raw visit timestamp into a UTC datetime
+    pub fn timestamp_to_datetime(&self, timestamp: i64) -> DateTime<Utc> {
+        match self.family {
+            // Chromium uses microseconds since 1601-01-01
+            SchemaFamily::Chromium => {
+                DateTime::from_timestamp_micros(timestamp - CHROMIUM_EPOCH_OFFSET_MICROS)
+                    .unwrap_or_default()
+            }
+            // Firefox uses microseconds since 1970-01-01
+            SchemaFamily::Firefox => DateTime::from_timestamp_micros(timestamp).unwrap_or_default(),
+        }
+    }
+
+    /// Decodes a raw transition value (Chromium bitfield or Firefox visit_type)
+    pub fn decode_transition(&self, raw: i64) -> Transition {
+        match self.family {
+            SchemaFamily::Chromium => {
+                if raw & CHROMIUM_REDIRECT_MASK != 0 {
+                    return Transition::Redirect;
+                }
+                match raw & CHROMIUM_CORE_MASK {
+                    0 => Transition::Link,
+                    1 => Transition::Typed,
+                    2 => Transition::Bookmark,
+                    3 | 4 => Transition::Embedded,
+                    5 | 9 | 10 => Transition::Generated,
+                    7 => Transition::FormSubmit,
+                    8 => Transition::Reload,
+                    _ => Transition::Other,
+                }
+            }
+            SchemaFamily::Firefox => match raw {
+                1 => Transition::Link,
+                2 => Transition::Typed,
+                3 => Transition::Bookmark,
+                4 | 8 => Transition::Embedded,
+                5 | 6 => Transition::Redirect,
+                9 => Transition::Reload,
+                _ => Transition::Other,
+            },
+        }
+    }
+
+    /// Converts a UTC datetime into the raw timestamp format stored by this schema
+    pub fn datetime_to_timestamp(&self, datetime: DateTime<Utc>) -> i64 {
+        match self.family {
+            SchemaFamily::Chromium => datetime.timestamp_micros() + CHROMIUM_EPOCH_OFFSET_MICROS,
+            SchemaFamily::Firefox => datetime.timestamp_micros(),
+        }
+    }
+}
+
+/// Microseconds between 1601-01-01 and 1970-01-01
+const CHROMIUM_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+pub fn detect_schema(conn: &Connection, family: SchemaFamily) -> Result<Schema> {
+    let version = match family {
+        SchemaFamily::Chromium => read_chromium_version(conn)?,
+        SchemaFamily::Firefox => conn
+            .query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+            .context("Failed to read Firefox schema version")?,
+    };
+
+    let adapters = match family {
+        SchemaFamily::Chromium => CHROMIUM_ADAPTERS,
+        SchemaFamily::Firefox => FIREFOX_ADAPTERS,
+    };
+
+    let queries = adapters
+        .iter()
+        .find(|(min_version, _)| version >= *min_version)
+        .map(|(_, queries)| *queries)
+        .ok_or_else(|| {
+            unsupported(format!(
+                "Unsupported {} history schema version {} (minimum supported is {})",
+                family,
+                version,
+                adapters.last().map(|(min, _)| *min).unwrap_or_default()
+            ))
+        })?;
+
+    for (table, required) in queries.required_columns() {
+        let columns = table_columns(conn, table)?;
+        if columns.is_empty() {
+            return Err(unsupported(format!(
+                "Unsupported {} history schema version {}: missing table '{}'",
+                family, version, table
+            )));
+        }
+        if let Some(missing) = required
+            .iter()
+            .find(|c| !columns.iter().any(|col| col == *c))
+        {
+            return Err(unsupported(format!(
+                "Unsupported {} history schema version {}: table '{}' has no column '{}'",
+                family, version, table, missing
+            )));
+        }
+    }
+
+    info!(
+        action = "detect",
+        component = "schema",
+        family = %family,
+        version,
+        "Detected history schema"
+    );
+
+    Ok(Schema {
+        family,
+        version,
+        queries,
+    })
+}
+
+fn read_chromium_version(conn: &Connection) -> Result<i64> {
+    if table_columns(conn, "meta")?.is_empty() {
+        return Err(unsupported(
+            "Unsupported Chromium history schema: missing 'meta' table".to_string(),
+        ));
+    }
+
+    // The value column has text affinity, but some forks store the version as an integer
+    let version: Option<rusqlite::types::Value> = conn
+        .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+            row.get(0)
+        })
+        .optional()
+        .context("Failed to read Chromium schema version")?;
+
+    match version {
+        Some(rusqlite::types::Value::Integer(version)) => Ok(version),
+        Some(rusqlite::types::Value::Text(version)) => version.trim().parse().map_err(|_| {
+            unsupported(format!(
+                "Unsupported Chromium history schema version '{version}'"
+            ))
+        }),
+        Some(other) => Err(unsupported(format!(
+            "Unsupported Chromium history schema version {other:?}"
+        ))),
+        None => Err(unsupported(
+            "Unsupported Chromium history schema: no version in 'meta'".to_string(),
+        )),
+    }
+}
+
+fn unsupported(reason: String) -> anyhow::Error {
+    HistoreeError::UnsupportedSchema { reason }.into()
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chromium history database at `version`, with the download layout of that version
+    fn chromium(version: i64) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        let visit_duration = if version >= 20 {
+            ", visit_duration INTEGER"
+        } else {
+            ""
+        };
+        conn.execute_batch(&format!(
+            "CREATE TABLE meta (key TEXT, value TEXT);
+             INSERT INTO meta VALUES ('version', '{version}');
+             CREATE TABLE urls (id INTEGER, url TEXT, title TEXT, visit_count INTEGER,
+                                typed_count INTEGER);
+             CREATE TABLE visits (id INTEGER, url INTEGER, visit_time INTEGER,
+                                  from_visit INTEGER, transition INTEGER{visit_duration});"
+        ))
+        .unwrap();
+        let downloads = match version {
+            29.. => {
+                "CREATE TABLE downloads (id INTEGER, start_time INTEGER, received_bytes INTEGER,
+                                         target_path TEXT, mime_type TEXT);
+                 CREATE TABLE downloads_url_chains (id INTEGER, chain_index INTEGER, url TEXT);"
+            }
+            24.. => {
+                "CREATE TABLE downloads (id INTEGER, start_time INTEGER, received_bytes INTEGER,
+                                         target_path TEXT);
+                 CREATE TABLE downloads_url_chains (id INTEGER, chain_index INTEGER, url TEXT);"
+            }
+            _ => {
+                "CREATE TABLE downloads (id INTEGER, full_path TEXT, url TEXT,
+                                         start_time INTEGER, received_bytes INTEGER)"
+            }
+        };
+        conn.execute_batch(downloads).unwrap();
+        conn
+    }
+
+    #[test]
+    fn each_chromium_version_gets_queries_for_its_layout() {
+        for version in [16, 19, 20, 23, 24, 28, 29, 70] {
+            let conn = chromium(version);
+            let schema = detect_schema(&conn, SchemaFamily::Chromium).unwrap();
+            assert_eq!(
+                schema.queries.visit_duration_column.is_some(),
+                version >= 20,
+                "version {version}"
+            );
+            conn.prepare(schema.queries.downloads_query.unwrap())
+                .unwrap_or_else(|e| panic!("version {version}: {e}"));
+        }
+    }
+
+    #[test]
+    fn legacy_download_times_convert_from_unix_seconds() {
+        let conn = chromium(19);
+        conn.execute(
+            "INSERT INTO downloads VALUES (1, '/tmp/a.zip', 'https://example.com/a.zip', 1000, 5)",
+            [],
+        )
+        .unwrap();
+        let schema = detect_schema(&conn, SchemaFamily::Chromium).unwrap();
+        let start: i64 = conn
+            .query_row(schema.queries.downloads_query.unwrap(), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(
+            schema.timestamp_to_datetime(start),
+            DateTime::from_timestamp(1000, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn versions_older_than_every_adapter_are_unsupported() {
+        let error = detect_schema(&chromium(15), SchemaFamily::Chromium).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported Chromium history schema version 15 (minimum supported is 16)"
+        );
+    }
+}
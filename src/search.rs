@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+/// Search engines recognized by host suffix, with the query parameter holding the terms
+const SEARCH_ENGINES: &[(&str, &str, &str)] = &[
+    // (host suffix, required path prefix, query parameter)
+    ("google.", "/search", "q"),
+    ("bing.com", "/search", "q"),
+    ("duckduckgo.com", "/", "q"),
+    ("search.yahoo.com", "/search", "p"),
+    ("baidu.com", "/s", "wd"),
+    ("yandex.", "/search", "text"),
+    ("ecosia.org", "/search", "q"),
+    ("startpage.com", "/", "query"),
+    ("search.brave.com", "/search", "q"),
+    ("kagi.com", "/search", "q"),
+    ("youtube.com", "/results", "search_query"),
+    ("wikipedia.org", "/", "search"),
+];
+
+/// Query parameters that carry search terms on sites not listed above, read only under
+/// `--generic-searches` since ordinary sites use them for filters and site search
+const GENERIC_PARAMETERS: [&str; 3] = ["q", "query", "search"];
+
+/// Lowercases and collapses whitespace so equivalent searches are counted together
+pub fn normalize_term(term: &str) -> Option<String> {
+    let normalized = term
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (!normalized.is_empty()).then_some(normalized)
+}
+
+fn query_param(url: &url::Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .and_then(|(_, value)| normalize_term(&value))
+}
+
+/// Extracts the search terms from a search results URL; `generic` also reads the common
+/// query parameters on sites that are not known search engines
+pub fn search_term(url: &url::Url, generic: bool) -> Option<String> {
+    let host = crate::domain::normalize_host(url.host_str()?);
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+
+    let known = SEARCH_ENGINES.iter().find(|(suffix, _, _)| {
+        if suffix.ends_with('.') {
+            host.starts_with(suffix) || host.contains(&format!(".{suffix}"))
+        } else {
+            host == *suffix || host.ends_with(&format!(".{suffix}"))
+        }
+    });
+    match known {
+        Some((_, path, param)) if url.path().starts_with(path) => query_param(url, param),
+        Some(_) => None,
+        None if generic => GENERIC_PARAMETERS
+            .iter()
+            .find_map(|param| query_param(url, param)),
+        None => None,
+    }
+}
+
+/// Number of visits per search term
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchTerms {
+    pub counts: HashMap<String, u32>,
+}
+
+impl SearchTerms {
+    pub fn record(&mut self, term: &str) {
+        match self.counts.get_mut(term) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(term.to_string(), 1);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SearchTerms) {
+        for (term, count) in &other.counts {
+            *self.counts.entry(term.clone()).or_insert(0) += count;
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    /// Most frequent search terms, ties broken alphabetically
+    pub fn top(&self, n: usize) -> Vec<(String, u32)> {
+        let mut terms: Vec<(String, u32)> = self
+            .counts
+            .iter()
+            .map(|(term, count)| (term.clone(), *count))
+            .collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(n);
+        terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(url: &str, generic: bool) -> Option<String> {
+        search_term(&url::Url::parse(url).unwrap(), generic)
+    }
+
+    #[test]
+    fn generic_parameters_need_opting_in() {
+        assert_eq!(
+            term("https://www.google.com/search?q=Rust+Borrow", false).as_deref(),
+            Some("rust borrow")
+        );
+        assert_eq!(term("https://docs.rs/releases/search?q=serde", false), None);
+        assert_eq!(term("https://shop.example/?query=shoes", false), None);
+        assert_eq!(
+            term("https://docs.rs/releases/search?q=serde", true).as_deref(),
+            Some("serde")
+        );
+        // Known engines only read their own parameter, with or without the fallback
+        assert_eq!(term("https://www.bing.com/maps?q=paris", true), None);
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Per-day visit counts per domain from Chromium's `segment_usage` table, which the
+/// browser keeps for its Top Sites; days are the browser's local days
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentUsage {
+    /// Visits per domain, keyed by day so iteration is chronological
+    pub days: BTreeMap<NaiveDate, HashMap<String, u32>>,
+}
+
+impl SegmentUsage {
+    pub fn record(&mut self, day: NaiveDate, domain: &str, visits: u32) {
+        let domains = self.days.entry(day).or_default();
+        match domains.get_mut(domain) {
+            Some(count) => *count += visits,
+            None => {
+                domains.insert(domain.to_string(), visits);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SegmentUsage) {
+        for (day, domains) in &other.days {
+            for (domain, visits) in domains {
+                self.record(*day, domain, *visits);
+            }
+        }
+    }
+
+    /// Visits per day across all domains, oldest first
+    pub fn totals(&self) -> Vec<(NaiveDate, u32)> {
+        self.days
+            .iter()
+            .map(|(day, domains)| (*day, domains.values().sum()))
+            .collect()
+    }
+
+    /// The `n` most visited domains on `day`, most visited first
+    pub fn top(&self, day: NaiveDate, n: usize) -> Vec<(&str, u32)> {
+        let mut domains: Vec<(&str, u32)> = self
+            .days
+            .get(&day)
+            .into_iter()
+            .flatten()
+            .map(|(domain, visits)| (domain.as_str(), *visits))
+            .collect();
+        domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        domains.truncate(n);
+        domains
+    }
+
+    pub fn remove_domains(&mut self, dropped: &HashSet<String>) {
+        for domains in self.days.values_mut() {
+            domains.retain(|domain, _| !dropped.contains(domain));
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::args::{Args, Period};
+use crate::browser;
+use crate::json::Value;
+use crate::stats::AnalysisResult;
+use crate::{metrics, utils};
+
+/// Domains returned by `/domains` when `top` is not given
+const DEFAULT_TOP: usize = 50;
+
+/// Bytes of request line and headers read before answering 431
+const HEAD_LIMIT: u64 = 8 * 1024;
+/// Headers read before answering 431
+const HEADER_LIMIT: usize = 64;
+/// Time a client has to send its request line and headers
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Analyses reused until they are older than the TTL, keyed by the period they group visits by
+struct Cache {
+    ttl: Duration,
+    results: HashMap<Option<Period>, (Instant, Arc<AnalysisResult>)>,
+}
+
+impl Cache {
+    fn get(&mut self, group_by: Option<Period>, args: &Args) -> Result<Arc<AnalysisResult>> {
+        if let Some((created, result)) = self.results.get(&group_by) {
+            if created.elapsed() < self.ttl {
+                return Ok(Arc::clone(result));
+            }
+        }
+        let mut args = args.clone();
+        args.group_by = group_by;
+        let result = Arc::new(browser::analyze_browser_history(&args)?);
+        self.results
+            .insert(group_by, (Instant::now(), Arc::clone(&result)));
+        Ok(result)
+    }
+}
+
+/// A response status with its body
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: Value::object().with("error", message).to_string(),
+        }
+    }
+}
+
+/// What a request must carry to be answered, and which origin may read the answers
+struct Access<'a> {
+    address: SocketAddr,
+    allow_origin: Option<&'a str>,
+}
+
+impl Access<'_> {
+    /// Whether a `Host` header names the bound address, so pages on a rebound DNS name
+    /// cannot reach the server; `localhost` stands for loopback, and any IP literal for an
+    /// unspecified address
+    fn allows_host(&self, host: &str) -> bool {
+        let Ok(url) = url::Url::parse(&format!("http://{host}")) else {
+            return false;
+        };
+        if url.port_or_known_default() != Some(self.address.port()) {
+            return false;
+        }
+        let ip = match url.host() {
+            Some(url::Host::Ipv4(ip)) => std::net::IpAddr::V4(ip),
+            Some(url::Host::Ipv6(ip)) => std::net::IpAddr::V6(ip),
+            Some(url::Host::Domain(name)) => {
+                return name.eq_ignore_ascii_case("localhost")
+                    && (self.address.ip().is_loopback() || self.address.ip().is_unspecified());
+            }
+            None => return false,
+        };
+        self.address.ip().is_unspecified() || ip == self.address.ip()
+    }
+}
+
+/// Serves analyses as JSON on `bind` until the process is stopped
+pub fn run(bind: &str, cache_ttl: Duration, allow_origin: Option<&str>, args: &Args) -> Result<()> {
+    let listener =
+        TcpListener::bind(bind).with_context(|| format!("Failed to listen on {bind}"))?;
+    let access = Access {
+        address: listener.local_addr()?,
+        allow_origin,
+    };
+    info!(
+        action = "listen",
+        component = "serve",
+        address = bind,
+        "Serving analyses"
+    );
+    if !args.quiet {
+        println!("Serving on http://{bind} (endpoints: /summary, /domains, /trends, /metrics)");
+    }
+
+    let mut cache = Cache {
+        ttl: cache_ttl,
+        results: HashMap::new(),
+    };
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!(action = "accept", component = "serve", error = %e, "Failed to accept connection");
+                continue;
+            }
+        };
+        if let Err(e) = handle(stream, &mut cache, &access, args) {
+            warn!(action = "respond", component = "serve", error = %e, "Failed to handle request");
+        }
+    }
+    Ok(())
+}
+
+/// Request line and Host header of a request, or `None` if they exceed the head limits
+fn read_head(stream: &TcpStream) -> Result<Option<(String, Option<String>)>> {
+    // One deadline for the whole head, since the server answers one client at a time
+    let deadline = Instant::now() + HEAD_TIMEOUT;
+    let mut reader = BufReader::new(stream.take(HEAD_LIMIT));
+    let mut lines = Vec::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            anyhow::bail!("Request head not received within {HEAD_TIMEOUT:?}");
+        }
+        stream.set_read_timeout(Some(remaining))?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.ends_with('\n') && reader.get_ref().limit() == 0 {
+            return Ok(None);
+        }
+        if line.trim_end().is_empty() {
+            break;
+        }
+        if lines.len() > HEADER_LIMIT {
+            return Ok(None);
+        }
+        lines.push(line);
+    }
+
+    let mut lines = lines.into_iter();
+    let request_line = lines.next().unwrap_or_default();
+    // Only Host matters, but every header must be read before responding
+    let host = lines.find_map(|header| {
+        let (name, value) = header.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("host")
+            .then(|| value.trim().to_string())
+    });
+    Ok(Some((request_line, host)))
+}
+
+fn handle(mut stream: TcpStream, cache: &mut Cache, access: &Access, args: &Args) -> Result<()> {
+    let Some((request_line, host)) = read_head(&stream)? else {
+        warn!(
+            action = "read",
+            component = "serve",
+            "Request head too large"
+        );
+        return respond(
+            &mut stream,
+            &Response::error(431, "Request line and headers are too large"),
+            access,
+        );
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+    let response = if !host.as_deref().is_some_and(|host| access.allows_host(host)) {
+        Response::error(403, "Host header does not name this server")
+    } else if method != "GET" {
+        Response::error(405, "Only GET is supported")
+    } else {
+        route(target, cache, args)
+    };
+    info!(
+        action = "respond",
+        component = "serve",
+        method,
+        target,
+        status = response.status,
+        "Handled request"
+    );
+
+    respond(&mut stream, &response, access)
+}
+
+fn respond(stream: &mut TcpStream, response: &Response, access: &Access) -> Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+    let cors = access.allow_origin.map_or_else(String::new, |origin| {
+        format!("Access-Control-Allow-Origin: {origin}\r\nVary: Origin\r\n")
+    });
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         {cors}Connection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn route(target: &str, cache: &mut Cache, args: &Args) -> Response {
+    let Ok(url) = url::Url::parse(&format!("http://localhost{target}")) else {
+        return Response::error(400, "Invalid request target");
+    };
+    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    if url.path() == "/metrics" {
+        return match cache.get(None, args) {
+            Ok(result) => Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: metrics::render(&result, args, None),
+            },
+            Err(e) => Response::error(500, &format!("{e:#}")),
+        };
+    }
+    let outcome = match url.path() {
+        "/summary" => cache
+            .get(None, args)
+            .map(|result| crate::report::summary(&result, args)),
+        "/domains" => {
+            let top = match query.get("top").map(|top| top.parse::<usize>()) {
+                None => DEFAULT_TOP,
+                Some(Ok(top)) if top > 0 => top,
+                Some(_) => return Response::error(400, "top must be a positive integer"),
+            };
+            cache
+                .get(None, args)
+                .map(|result| domains(&result, top, args))
+        }
+        "/trends" => {
+            let by = query.get("by").map_or("month", String::as_str);
+            let Ok(period) = <Period as clap::ValueEnum>::from_str(by, true) else {
+                return Response::error(400, "by must be day, month or year");
+            };
+            cache
+                .get(Some(period), args)
+                .map(|result| trends(&result, period))
+        }
+        _ => return Response::error(404, "Unknown endpoint"),
+    };
+    match outcome {
+        Ok(body) => Response::ok(body),
+        Err(e) => Response::error(500, &format!("{e:#}")),
+    }
+}
+
+fn domains(result: &AnalysisResult, top: usize, args: &Args) -> Value {
+    let domains: Vec<Value> = result
+        .stats
+        .top(top)
+        .into_iter()
+        .map(|(domain, count)| {
+            Value::object()
+                .with("domain", utils::display_domain(&domain, args))
+                .with("count", count)
+        })
+        .collect();
+    Value::object()
+        .with("schema_version", crate::json::SCHEMA_VERSION)
+        .with("domains", domains)
+}
+
+fn trends(result: &AnalysisResult, period: Period) -> Value {
+    let buckets: Vec<Value> = result
+        .periods
+        .as_ref()
+        .map(|periods| {
+            periods
+                .series()
+                .into_iter()
+                .map(|(label, visits)| {
+                    let domains = periods
+                        .buckets
+                        .get(&label)
+                        .map_or(0, |bucket| bucket.domains.len());
+                    Value::object()
+                        .with("period", label)
+                        .with("visits", visits)
+                        .with("domains", domains)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Value::object()
+        .with("schema_version", crate::json::SCHEMA_VERSION)
+        .with("by", period.to_string())
+        .with("buckets", buckets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `read_head` makes of `request` sent by a client
+    fn head(request: Vec<u8>) -> Option<(String, Option<String>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            // The server may stop reading and close before everything is sent
+            let _ = stream.write_all(&request);
+            stream
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let head = read_head(&stream).unwrap();
+        drop(client.join().unwrap());
+        head
+    }
+
+    #[test]
+    fn request_heads_are_read_up_to_the_limits() {
+        let request =
+            |headers: String| format!("GET /summary HTTP/1.1\r\n{headers}\r\n").into_bytes();
+        assert_eq!(
+            head(request(
+                "Accept: */*\r\nHOST: 127.0.0.1:8080\r\n".to_string()
+            )),
+            Some((
+                "GET /summary HTTP/1.1\r\n".to_string(),
+                Some("127.0.0.1:8080".to_string())
+            ))
+        );
+        let headers = |count: usize| (0..count).map(|i| format!("X-{i}: 1\r\n")).collect();
+        assert!(head(request(headers(HEADER_LIMIT))).is_some());
+        assert_eq!(head(request(headers(HEADER_LIMIT + 1))), None);
+        let long = format!("X-Long: {}\r\n", "a".repeat(HEAD_LIMIT as usize));
+        assert_eq!(head(request(long)), None);
+    }
+}
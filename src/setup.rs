@@ -0,0 +1,194 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::args::{Browser, Format};
+use crate::browser::BrowserHandler;
+use crate::config::{self, Config};
+use crate::patterns;
+use crate::sqlite;
+
+/// Prints `question` and returns the trimmed answer, or `None` for an empty answer
+fn prompt(question: &str) -> Result<Option<String>> {
+    print!("{question} ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        anyhow::bail!("Setup aborted: no more input");
+    }
+    let answer = answer.trim();
+    Ok((!answer.is_empty()).then(|| answer.to_string()))
+}
+
+/// Asks until the answer parses, falling back to `default` on an empty answer
+fn prompt_with<T>(question: &str, default: T, parse: impl Fn(&str) -> Option<T>) -> Result<T> {
+    loop {
+        match prompt(question)? {
+            None => return Ok(default),
+            Some(answer) => match parse(&answer) {
+                Some(value) => return Ok(value),
+                None => println!("  Unrecognized answer '{answer}', please try again."),
+            },
+        }
+    }
+}
+
+fn parse_yes_no(answer: &str) -> Option<bool> {
+    match answer.to_lowercase().as_str() {
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Asks which of `browser`'s profiles to analyze when it has several, returning the
+/// profile to save, or `None` when it is the one read without `--profile`
+fn prompt_profile(
+    browser: Browser,
+    profiles: &[(String, PathBuf)],
+    existing: &Config,
+) -> Result<Option<String>> {
+    let Some((first, _)) = profiles.first() else {
+        return Ok(None);
+    };
+    let chosen = if profiles.len() == 1 {
+        first.clone()
+    } else {
+        println!("\nProfiles of {browser}:");
+        for (i, (name, _)) in profiles.iter().enumerate() {
+            println!("  {}. {name}", i + 1);
+        }
+        let default_profile = existing
+            .profile
+            .clone()
+            .filter(|profile| {
+                existing.browser == Some(browser) && profiles.iter().any(|(n, _)| n == profile)
+            })
+            .unwrap_or_else(|| first.clone());
+        prompt_with(
+            &format!("Profile to analyze (name or number) [{default_profile}]:"),
+            default_profile,
+            |answer| {
+                answer
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| profiles.get(n.checked_sub(1)?))
+                    .or_else(|| profiles.iter().find(|(name, _)| name == answer))
+                    .map(|(name, _)| name.clone())
+            },
+        )?
+    };
+
+    let default_path = browser.get_history_path().ok();
+    Ok(profiles
+        .iter()
+        .find(|(name, path)| *name == chosen && Some(path) != default_path.as_ref())
+        .map(|(name, _)| name.clone()))
+}
+
+/// Interactively writes a config file and optionally the default pattern file
+pub fn run() -> Result<()> {
+    let config_path = config::config_path()?;
+    let existing = Config::load()?.unwrap_or_default();
+    println!("historee setup\n");
+
+    println!("Detected browsers:");
+    let mut detected = Vec::new();
+    for browser in Browser::value_variants() {
+        match sqlite::list_profiles(browser) {
+            Ok(profiles) if !profiles.is_empty() => {
+                for (i, (name, path)) in profiles.iter().enumerate() {
+                    let label = if i == 0 {
+                        value_name(browser)
+                    } else {
+                        String::new()
+                    };
+                    println!("  {label:<8} {name:<12} {}", path.display());
+                }
+                detected.push((*browser, profiles));
+            }
+            _ => println!("  {:<8} not found", value_name(browser)),
+        }
+    }
+    println!();
+
+    let default_browser = existing
+        .browser
+        .or_else(|| detected.first().map(|(browser, _)| *browser))
+        .unwrap_or_default();
+    let browser = prompt_with(
+        &format!("Default browser [{}]:", value_name(&default_browser)),
+        default_browser,
+        |answer| Browser::from_str(answer, true).ok(),
+    )?;
+    let profiles = detected
+        .iter()
+        .find(|(detected, _)| *detected == browser)
+        .map(|(_, profiles)| profiles.as_slice())
+        .unwrap_or_default();
+    if profiles.is_empty() {
+        println!("  Note: no history database was found for {browser}");
+    }
+    let profile = prompt_profile(browser, profiles, &existing)?;
+
+    let default_top = existing.top.unwrap_or(20);
+    let top = prompt_with(
+        &format!("Number of top domains to show [{default_top}]:"),
+        default_top,
+        |answer| answer.parse().ok().filter(|top| *top > 0),
+    )?;
+
+    let default_redact = existing.redact.is_some();
+    let redact = prompt_with(
+        &format!(
+            "Redact domain names in output? [{}]:",
+            if default_redact { "Y/n" } else { "y/N" }
+        ),
+        default_redact,
+        parse_yes_no,
+    )?;
+
+    let default_format = existing.format.unwrap_or_default();
+    let format = prompt_with(
+        &format!("Output format [{}]:", value_name(&default_format)),
+        default_format,
+        |answer| Format::from_str(answer, true).ok(),
+    )?;
+
+    let config = Config {
+        browser: Some(browser),
+        profile,
+        top: Some(top),
+        // Answering yes keeps a redaction mode chosen by editing the config file
+        redact: redact.then(|| existing.redact.unwrap_or_default()),
+        format: Some(format),
+        patterns: existing.patterns.clone(),
+    };
+    config.save(&config_path)?;
+    println!("\nWrote {}", config_path.display());
+
+    let patterns_path = patterns::default_patterns_path()?;
+    if !patterns_path.exists()
+        && prompt_with(
+            &format!(
+                "Create {} with the default patterns? [Y/n]:",
+                patterns_path.display()
+            ),
+            true,
+            parse_yes_no,
+        )?
+    {
+        patterns::init_default_patterns()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use std::fmt::{self, Write};
+
+use crate::args::{Args, SortBy};
+use crate::browser;
+use crate::utils::{self, DateFormat, Style};
+
+/// Months drawn in the sparkline, most recent last
+const SPARKLINE_MONTHS: usize = 60;
+
+/// Everything known about one domain in an analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainCard {
+    pub domain: String,
+    pub visits: u32,
+    /// Position in the ranking by count, from 1
+    pub rank: usize,
+    /// Number of ranked domains
+    pub domains: usize,
+    /// Percentage of all counted visits
+    pub share: f64,
+    pub first_visit: Option<DateTime<Utc>>,
+    pub last_visit: Option<DateTime<Utc>>,
+    /// Visits per month from the domain's first month to the last month with any visit,
+    /// months without visits included
+    pub monthly: Vec<(String, u32)>,
+    pub paths: Vec<(String, u32)>,
+    /// Domains whose pages led here, most navigations first
+    pub referrers: Vec<(String, u32)>,
+}
+
+/// `YYYY-MM` keys from `first` to `last`, inclusive
+fn month_range(first: &str, last: &str) -> Vec<String> {
+    let parse = |key: &str| NaiveDate::parse_from_str(&format!("{key}-01"), "%Y-%m-%d").ok();
+    let (Some(mut month), Some(last)) = (parse(first), parse(last)) else {
+        return Vec::new();
+    };
+    let mut months = Vec::new();
+    while month <= last {
+        months.push(month.format("%Y-%m").to_string());
+        let Some(next) = month.checked_add_months(Months::new(1)) else {
+            break;
+        };
+        month = next;
+    }
+    months
+}
+
+impl DomainCard {
+    /// Analyzes history with the other options in `args` and collects `domain`'s details
+    pub fn new(domain: &str, args: &Args) -> Result<Self> {
+        let mut card_args = args.clone();
+        card_args.domain = Some(domain.to_string());
+        card_args.trends = true;
+        card_args.nav_graph = true;
+        card_args.sort = SortBy::FirstVisit;
+        let result = browser::analyze_browser_history(&card_args)?;
+
+        // The paths keep the canonical, and with --anonymize pseudonymized, name
+        let Some(paths) = &result.paths else {
+            anyhow::bail!("No visits to {domain} found");
+        };
+        let domain = paths.domain.clone();
+        let counts = &result.stats.domain_counts;
+        let Some(&visits) = counts.get(&domain) else {
+            anyhow::bail!("No visits to {domain} found");
+        };
+        let rank = 1 + counts
+            .iter()
+            .filter(|(other, count)| **count > visits || (**count == visits && **other < domain))
+            .count();
+
+        let monthly = match &result.trends {
+            Some(trends) => {
+                let own = trends.monthly.get(&domain);
+                let first = own.and_then(|months| months.keys().next());
+                let last = trends.months().last().copied();
+                match (first, last) {
+                    (Some(first), Some(last)) => month_range(first, last)
+                        .into_iter()
+                        .map(|month| {
+                            let count = own.and_then(|own| own.get(&month)).copied();
+                            (month, count.unwrap_or(0))
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                }
+            }
+            None => Vec::new(),
+        };
+
+        let top = args.top.unwrap_or(10);
+        let mut referrers = result
+            .navigation
+            .as_ref()
+            .map(|navigation| navigation.referrers(&domain))
+            .unwrap_or_default();
+        referrers.truncate(top);
+
+        let spans = result.spans.as_ref();
+        Ok(DomainCard {
+            visits,
+            rank,
+            domains: counts.len(),
+            share: utils::percent(visits, result.stats.total()),
+            first_visit: spans.and_then(|spans| spans.first(&domain)),
+            last_visit: spans.and_then(|spans| spans.last(&domain)),
+            monthly,
+            paths: paths.top(top),
+            referrers,
+            domain,
+        })
+    }
+}
+
+pub fn run(domain: &str, args: &Args) -> Result<()> {
+    let card = DomainCard::new(domain, args)?;
+    let mut out = String::new();
+    write_card(&mut out, &card, args).expect("writing to a String cannot fail");
+    print!("{out}");
+    Ok(())
+}
+
+fn write_card(out: &mut String, card: &DomainCard, args: &Args) -> fmt::Result {
+    let color = utils::color_enabled(args);
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    let unit = args.weight.unit();
+    let zone = args.zone();
+    let when = |timestamp: DateTime<Utc>| {
+        let local = zone.local(timestamp);
+        format!(
+            "{} {}",
+            utils::format_date(local.date(), args, &DateFormat::Long),
+            local.format("%H:%M")
+        )
+    };
+
+    let domain = utils::display_domain(&card.domain, args);
+    writeln!(
+        out,
+        "\n{}",
+        utils::paint(&format!("--- {domain} ---"), Style::Bold, color)
+    )?;
+    writeln!(
+        out,
+        "{} {unit} ({:.1}% of all), ranked #{} of {} domains",
+        count(card.visits),
+        card.share,
+        card.rank,
+        count(card.domains as u32)
+    )?;
+    if let Some(first) = card.first_visit {
+        writeln!(out, "First visit: {}", when(first))?;
+    }
+    if let Some(last) = card.last_visit {
+        writeln!(out, "Last visit:  {}", when(last))?;
+    }
+
+    let shown = &card.monthly[card.monthly.len().saturating_sub(SPARKLINE_MONTHS)..];
+    if let (Some((first, _)), Some((last, _))) = (shown.first(), shown.last()) {
+        let values: Vec<u32> = shown.iter().map(|(_, visits)| *visits).collect();
+        writeln!(out, "\nVisits per month ({first} to {last}):")?;
+        writeln!(out, "  {}", utils::sparkline(&values))?;
+        if let Some((month, visits)) = card
+            .monthly
+            .iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        {
+            writeln!(out, "  Busiest month: {month} ({} {unit})", count(*visits))?;
+        }
+    }
+
+    if !card.paths.is_empty() {
+        writeln!(out, "\nTop paths:")?;
+        for (path, visits) in &card.paths {
+            let path = match args.redact {
+                Some(policy) => format!("/{}", policy.text(path)),
+                None => utils::truncate(path, 80),
+            };
+            writeln!(out, "- {path}: {} {unit}", count(*visits))?;
+        }
+    }
+
+    if !card.referrers.is_empty() {
+        writeln!(out, "\nTop referrers:")?;
+        for (referrer, navigations) in &card.referrers {
+            writeln!(
+                out,
+                "- {}: {} navigations",
+                utils::display_domain(referrer, args),
+                count(*navigations)
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,308 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::{http, json};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainChange {
+    pub domain: String,
+    pub previous: u32,
+    pub current: u32,
+}
+
+impl DomainChange {
+    pub fn is_new(&self) -> bool {
+        self.previous == 0
+    }
+}
+
+/// Events produced by long-running modes (watch, serve) for delivery to sinks
+#[derive(Debug, Clone)]
+pub enum Event {
+    Delta {
+        timestamp: DateTime<Utc>,
+        source: String,
+        changes: Vec<DomainChange>,
+    },
+    Alert {
+        timestamp: DateTime<Utc>,
+        source: String,
+        message: String,
+    },
+}
+
+impl Event {
+    pub fn to_json(&self) -> json::Value {
+        match self {
+            Event::Delta {
+                timestamp,
+                source,
+                changes,
+            } => json::Value::object()
+                .with("schema_version", json::SCHEMA_VERSION)
+                .with("type", "delta")
+                .with("timestamp", timestamp.to_rfc3339())
+                .with("source", source.as_str())
+                .with(
+                    "changes",
+                    changes
+                        .iter()
+                        .map(|change| {
+                            json::Value::object()
+                                .with("domain", change.domain.as_str())
+                                .with("previous", change.previous)
+                                .with("current", change.current)
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+            Event::Alert {
+                timestamp,
+                source,
+                message,
+            } => json::Value::object()
+                .with("schema_version", json::SCHEMA_VERSION)
+                .with("type", "alert")
+                .with("timestamp", timestamp.to_rfc3339())
+                .with("source", source.as_str())
+                .with("message", message.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Delta {
+                timestamp,
+                source,
+                changes,
+            } => {
+                let new_domains = changes.iter().filter(|c| c.is_new()).count();
+                write!(
+                    f,
+                    "[{}] {}: {} new domains, {} domains with more visits",
+                    timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    source,
+                    new_domains,
+                    changes.len() - new_domains
+                )?;
+                for change in changes {
+                    if change.is_new() {
+                        write!(f, "\n  + {} ({})", change.domain, change.current)?;
+                    } else {
+                        write!(
+                            f,
+                            "\n  ~ {} ({} -> {})",
+                            change.domain, change.previous, change.current
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+            Event::Alert {
+                timestamp,
+                source,
+                message,
+            } => write!(
+                f,
+                "[{}] {}: {}",
+                timestamp.format("%Y-%m-%d %H:%M:%S"),
+                source,
+                message
+            ),
+        }
+    }
+}
+
+/// Destination for watch-mode events
+pub trait EventSink: Send {
+    fn name(&self) -> &str;
+    fn emit(&mut self, event: &Event) -> Result<()>;
+}
+
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    fn emit(&mut self, event: &Event) -> Result<()> {
+        println!("{event}");
+        Ok(())
+    }
+}
+
+/// Appends one JSON object per event to a file
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl EventSink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn emit(&mut self, event: &Event) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open event file {:?}", self.path))?;
+        writeln!(file, "{}", event.to_json())?;
+        Ok(())
+    }
+}
+
+/// POSTs each event as JSON to a URL
+pub struct WebhookSink {
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn emit(&mut self, event: &Event) -> Result<()> {
+        http::post_json(&self.url, &event.to_json().to_string())
+    }
+}
+
+/// Shows a desktop notification using the platform's notification tool
+pub struct NotificationSink;
+
+impl EventSink for NotificationSink {
+    fn name(&self) -> &str {
+        "notify"
+    }
+
+    fn emit(&mut self, event: &Event) -> Result<()> {
+        let body = event.to_string();
+        let status = match std::env::consts::OS {
+            "linux" => Command::new("notify-send")
+                .args(["historee", &body])
+                .status(),
+            "macos" => {
+                let script = format!(
+                    "display notification {} with title \"historee\"",
+                    json::escape(&body)
+                );
+                Command::new("osascript").args(["-e", &script]).status()
+            }
+            os => anyhow::bail!("Desktop notifications are not supported on {os}"),
+        }
+        .context("Failed to run notification command")?;
+
+        if !status.success() {
+            anyhow::bail!("Notification command exited with {status}");
+        }
+        Ok(())
+    }
+}
+
+/// Sink configuration, parsed from `stdout`, `file:<path>`, `webhook:<url>` or `notify`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkSpec {
+    Stdout,
+    File(PathBuf),
+    Webhook(String),
+    Notification,
+}
+
+impl SinkSpec {
+    pub fn build(&self) -> Box<dyn EventSink> {
+        match self {
+            SinkSpec::Stdout => Box::new(StdoutSink),
+            SinkSpec::File(path) => Box::new(FileSink::new(path.clone())),
+            SinkSpec::Webhook(url) => Box::new(WebhookSink::new(url.clone())),
+            SinkSpec::Notification => Box::new(NotificationSink),
+        }
+    }
+}
+
+impl FromStr for SinkSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+
+        match (kind, value) {
+            ("stdout", None) => Ok(SinkSpec::Stdout),
+            ("notify", None) => Ok(SinkSpec::Notification),
+            ("file", Some(path)) if !path.is_empty() => Ok(SinkSpec::File(PathBuf::from(path))),
+            ("webhook", Some(url)) => {
+                let parsed = url::Url::parse(url).context("Invalid webhook URL")?;
+                if !matches!(parsed.scheme(), "http" | "https") {
+                    anyhow::bail!("Webhook URL must use http or https");
+                }
+                Ok(SinkSpec::Webhook(url.to_string()))
+            }
+            _ => anyhow::bail!(
+                "Invalid sink '{s}' (expected stdout, notify, file:<path> or webhook:<url>)"
+            ),
+        }
+    }
+}
+
+/// Fans events out to every configured sink
+pub struct Sinks {
+    sinks: Vec<Box<dyn EventSink>>,
+}
+
+impl Sinks {
+    pub fn new(sinks: Vec<Box<dyn EventSink>>) -> Self {
+        Self { sinks }
+    }
+
+    pub fn from_specs(specs: &[SinkSpec]) -> Self {
+        let sinks = if specs.is_empty() {
+            vec![SinkSpec::Stdout.build()]
+        } else {
+            specs.iter().map(SinkSpec::build).collect()
+        };
+        Self::new(sinks)
+    }
+
+    /// Delivers an event to all sinks; a failing sink does not prevent delivery to the others
+    pub fn emit(&mut self, event: &Event) {
+        for sink in &mut self.sinks {
+            match sink.emit(event) {
+                Ok(()) => {
+                    info!(
+                        action = "emit",
+                        component = "event_sink",
+                        sink = sink.name(),
+                        "Event delivered"
+                    )
+                }
+                Err(e) => {
+                    warn!(action = "emit", component = "event_sink", sink = sink.name(), error = %e, "Failed to deliver event")
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,739 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::args::{Args, SnapshotCommand};
+use crate::json::{self, Value};
+use crate::sink::DomainChange;
+use crate::stats::{AnalysisResult, DateRange};
+use crate::{browser, patterns, utils};
+
+/// Saved domain counts of one analysis, with what is needed to judge comparability
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub name: String,
+    pub generated_at: DateTime<Utc>,
+    pub version: String,
+    pub browser: String,
+    /// Machine the snapshot was taken on, absent in snapshots from older versions
+    pub host: Option<String>,
+    pub weight: String,
+    /// Normalization fingerprint; counts only line up between equal fingerprints
+    pub fingerprint: String,
+    /// Sources of the normalization patterns, absent in snapshots from older versions
+    pub patterns: Option<Vec<String>>,
+    pub date_range: (String, String),
+    /// First and last visit, absent in snapshots from older versions
+    pub range: Option<DateRange>,
+    pub domain_counts: HashMap<String, u32>,
+}
+
+impl Snapshot {
+    pub fn from_result(name: &str, result: &AnalysisResult, args: &Args) -> Self {
+        let provenance = &result.provenance;
+        Self {
+            name: name.to_string(),
+            generated_at: provenance.generated_at,
+            version: provenance.version.to_string(),
+            browser: if args.all_browsers {
+                "all".to_string()
+            } else {
+                args.browser.to_string()
+            },
+            host: utils::hostname(),
+            weight: provenance.weight.to_string(),
+            fingerprint: provenance.normalization.fingerprint.clone(),
+            patterns: Some(provenance.normalization.patterns.clone()),
+            date_range: crate::utils::date_range_labels(result.date_range, args),
+            range: result.date_range,
+            domain_counts: result.stats.domain_counts.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut domains: Vec<(&String, &u32)> = self.domain_counts.iter().collect();
+        domains.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let mut doc = Value::object()
+            .with("schema_version", json::SCHEMA_VERSION)
+            .with("name", self.name.as_str())
+            .with("generated_at", self.generated_at.to_rfc3339())
+            .with("version", self.version.as_str())
+            .with("browser", self.browser.as_str());
+        if let Some(host) = &self.host {
+            doc = doc.with("host", host.as_str());
+        }
+        doc = doc
+            .with("weight", self.weight.as_str())
+            .with("fingerprint", self.fingerprint.as_str());
+        if let Some(patterns) = &self.patterns {
+            doc = doc.with("patterns", patterns.clone());
+        }
+        doc = doc
+            .with("earliest", self.date_range.0.as_str())
+            .with("latest", self.date_range.1.as_str());
+        if let Some(range) = self.range {
+            doc = doc
+                .with("first_visit", range.earliest.to_rfc3339())
+                .with("last_visit", range.latest.to_rfc3339());
+        }
+        doc.with(
+            "domains",
+            Value::Object(
+                domains
+                    .into_iter()
+                    .map(|(domain, count)| (domain.clone(), (*count).into()))
+                    .collect(),
+            ),
+        )
+    }
+
+    pub fn from_json(doc: &Value) -> Result<Self> {
+        // Snapshots written before the field existed use the first layout
+        let schema_version = doc
+            .get("schema_version")
+            .and_then(Value::as_i64)
+            .unwrap_or(1);
+        if schema_version > json::SCHEMA_VERSION {
+            anyhow::bail!(
+                "Snapshot uses JSON schema version {schema_version}, newer than this historee \
+                 reads ({}); upgrade historee to load it",
+                json::SCHEMA_VERSION
+            );
+        }
+        let text = |key: &str| -> Result<String> {
+            doc.get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Snapshot is missing \"{key}\""))
+        };
+        let time = |key: &str| -> Result<DateTime<Utc>> {
+            Ok(DateTime::parse_from_rfc3339(&text(key)?)
+                .with_context(|| format!("Snapshot has an invalid \"{key}\""))?
+                .with_timezone(&Utc))
+        };
+        let generated_at = time("generated_at")?;
+        let range = if doc.get("first_visit").is_some() {
+            Some(DateRange {
+                earliest: time("first_visit")?,
+                latest: time("last_visit")?,
+            })
+        } else {
+            None
+        };
+        let patterns = match doc.get("patterns") {
+            Some(patterns) => Some(
+                patterns
+                    .as_array()
+                    .and_then(|patterns| {
+                        patterns
+                            .iter()
+                            .map(|pattern| pattern.as_str().map(str::to_string))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or_else(|| anyhow::anyhow!("Snapshot has an invalid \"patterns\""))?,
+            ),
+            None => None,
+        };
+        let domain_counts = doc
+            .get("domains")
+            .and_then(Value::as_object)
+            .ok_or_else(|| anyhow::anyhow!("Snapshot is missing \"domains\""))?
+            .iter()
+            .map(|(domain, count)| {
+                let count = count
+                    .as_i64()
+                    .and_then(|count| u32::try_from(count).ok())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid count for {domain} in snapshot"))?;
+                Ok((domain.clone(), count))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name: text("name")?,
+            generated_at,
+            version: text("version")?,
+            browser: text("browser")?,
+            host: doc.get("host").and_then(Value::as_str).map(str::to_string),
+            weight: text("weight")?,
+            fingerprint: text("fingerprint")?,
+            patterns,
+            date_range: (text("earliest")?, text("latest")?),
+            range,
+            domain_counts,
+        })
+    }
+
+    /// Loads a snapshot by name from the snapshot directory, or from a file path
+    pub fn load(spec: &str) -> Result<Self> {
+        let path = Path::new(spec);
+        let path = if path.is_file() {
+            path.to_path_buf()
+        } else {
+            snapshot_dir()?.join(format!("{spec}.json"))
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read snapshot {spec} at {path:?}"))?;
+        json::parse(&content)
+            .and_then(|doc| Snapshot::from_json(&doc))
+            .with_context(|| format!("Invalid snapshot {path:?}"))
+    }
+
+    /// Writes the snapshot into the snapshot directory, returning its path
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = snapshot_dir()?.join(format!("{}.json", self.name));
+        utils::write_atomic(&path, &format!("{}\n", self.to_json()))?;
+        Ok(path)
+    }
+}
+
+/// Where `snapshot save` stores snapshots: `$XDG_DATA_HOME/historee/snapshots`
+pub fn snapshot_dir() -> Result<PathBuf> {
+    Ok(utils::data_dir()?.join("snapshots"))
+}
+
+/// Domains that appeared, vanished or changed count between two snapshots
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotDiff {
+    /// Domains only in the later snapshot, most visited first
+    pub added: Vec<DomainChange>,
+    /// Domains only in the earlier snapshot, most visited first
+    pub removed: Vec<DomainChange>,
+    /// Domains in both with different counts, largest absolute change first
+    pub changed: Vec<DomainChange>,
+}
+
+impl SnapshotDiff {
+    pub fn between(before: &Snapshot, after: &Snapshot) -> Self {
+        Self::from_counts(&before.domain_counts, &after.domain_counts)
+    }
+
+    /// Changes from one set of domain counts to another
+    pub fn from_counts(before: &HashMap<String, u32>, after: &HashMap<String, u32>) -> Self {
+        let mut diff = SnapshotDiff::default();
+        for (domain, &current) in after {
+            let change = DomainChange {
+                domain: domain.clone(),
+                previous: before.get(domain).copied().unwrap_or(0),
+                current,
+            };
+            if change.is_new() {
+                diff.added.push(change);
+            } else if change.previous != change.current {
+                diff.changed.push(change);
+            }
+        }
+        for (domain, &previous) in before {
+            if !after.contains_key(domain) {
+                diff.removed.push(DomainChange {
+                    domain: domain.clone(),
+                    previous,
+                    current: 0,
+                });
+            }
+        }
+
+        let magnitude = |change: &DomainChange| change.current.abs_diff(change.previous);
+        diff.added.sort_by(|a, b| {
+            b.current
+                .cmp(&a.current)
+                .then_with(|| a.domain.cmp(&b.domain))
+        });
+        diff.removed.sort_by(|a, b| {
+            b.previous
+                .cmp(&a.previous)
+                .then_with(|| a.domain.cmp(&b.domain))
+        });
+        diff.changed.sort_by(|a, b| {
+            magnitude(b)
+                .cmp(&magnitude(a))
+                .then_with(|| a.domain.cmp(&b.domain))
+        });
+        diff
+    }
+}
+
+/// Snapshots of several machines combined into one set of domain counts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMerge {
+    /// Snapshots that contributed counts
+    pub sources: Vec<Snapshot>,
+    /// Snapshots left out as duplicates of a source, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Counts per domain, one column per source in `sources` order
+    pub counts: HashMap<String, Vec<u32>>,
+}
+
+impl SnapshotMerge {
+    /// Merges `snapshots`, keeping only the latest of several taken of the same browser on
+    /// the same machine, since history accumulates and summing them would count visits twice
+    pub fn new(snapshots: Vec<Snapshot>) -> Self {
+        let mut merge = Self {
+            sources: Vec::new(),
+            skipped: Vec::new(),
+            counts: HashMap::new(),
+        };
+        let mut snapshots = snapshots;
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.generated_at));
+        for snapshot in snapshots {
+            let duplicate = merge.sources.iter().find_map(|source| {
+                if *source == snapshot {
+                    Some(format!("same snapshot as {}", source.name))
+                } else if snapshot.host.is_some()
+                    && source.host == snapshot.host
+                    && source.browser == snapshot.browser
+                {
+                    Some(format!(
+                        "superseded by {}, a later snapshot of {} on {}",
+                        source.name,
+                        source.browser,
+                        source.host.as_deref().unwrap_or_default()
+                    ))
+                } else {
+                    None
+                }
+            });
+            match duplicate {
+                Some(reason) => merge.skipped.push((snapshot.name, reason)),
+                None => merge.sources.push(snapshot),
+            }
+        }
+        // Columns read oldest first, in the order the snapshots were taken
+        merge.sources.reverse();
+        merge.skipped.reverse();
+        for (column, source) in merge.sources.iter().enumerate() {
+            for (domain, count) in &source.domain_counts {
+                merge
+                    .counts
+                    .entry(domain.clone())
+                    .or_insert_with(|| vec![0; merge.sources.len()])[column] = *count;
+            }
+        }
+        merge
+    }
+
+    /// Summed counts per domain
+    pub fn totals(&self) -> HashMap<String, u32> {
+        self.counts
+            .iter()
+            .map(|(domain, counts)| (domain.clone(), counts.iter().sum()))
+            .collect()
+    }
+
+    /// Domains ordered by their summed count, highest first
+    pub fn ranked(&self) -> Vec<(&String, &Vec<u32>)> {
+        let mut ranked: Vec<_> = self.counts.iter().collect();
+        ranked.sort_by(|a, b| {
+            let total = |counts: &Vec<u32>| counts.iter().sum::<u32>();
+            total(b.1).cmp(&total(a.1)).then_with(|| a.0.cmp(b.0))
+        });
+        ranked
+    }
+
+    /// Union of the sources' visit ranges; snapshots from older versions don't record one
+    pub fn range(&self) -> Option<DateRange> {
+        self.sources
+            .iter()
+            .filter_map(|source| source.range)
+            .reduce(DateRange::merge)
+    }
+
+    /// Whether the sources were normalized or weighted differently
+    pub fn mixed(&self) -> bool {
+        self.sources.windows(2).any(|pair| {
+            pair[0].fingerprint != pair[1].fingerprint || pair[0].weight != pair[1].weight
+        })
+    }
+
+    /// The merged counts as a snapshot of their own, for later diffs
+    pub fn to_snapshot(&self, name: &str, args: &Args) -> Snapshot {
+        let mut browsers: Vec<&str> = Vec::new();
+        for source in &self.sources {
+            if !browsers.contains(&source.browser.as_str()) {
+                browsers.push(&source.browser);
+            }
+        }
+        let first = self.sources.first();
+        Snapshot {
+            name: name.to_string(),
+            generated_at: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            browser: browsers.join("+"),
+            host: None,
+            weight: first
+                .map(|source| source.weight.clone())
+                .unwrap_or_default(),
+            fingerprint: first
+                .map(|source| source.fingerprint.clone())
+                .unwrap_or_default(),
+            patterns: first.and_then(|source| source.patterns.clone()),
+            date_range: utils::date_range_labels(self.range(), args),
+            range: self.range(),
+            domain_counts: self.totals(),
+        }
+    }
+}
+
+/// Rejects names that would escape or hide in the snapshot directory
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        anyhow::bail!("Invalid snapshot name {name:?}");
+    }
+    Ok(())
+}
+
+pub fn run(command: &SnapshotCommand, args: &Args) -> Result<()> {
+    match command {
+        SnapshotCommand::Save { name } => {
+            let result = browser::analyze_browser_history(args)?;
+            let name = name.clone().unwrap_or_else(|| {
+                result
+                    .provenance
+                    .generated_at
+                    .format("%Y-%m-%dT%H-%M-%S")
+                    .to_string()
+            });
+            check_name(&name)?;
+            let path = Snapshot::from_result(&name, &result, args).save()?;
+            info!(action = "write", component = "snapshot", path = ?path, "Saved snapshot");
+            if !args.quiet {
+                println!("Saved snapshot {name} to {}", path.display());
+            }
+        }
+        SnapshotCommand::List => {
+            let dir = snapshot_dir()?;
+            let mut snapshots = Vec::new();
+            if dir.exists() {
+                for entry in std::fs::read_dir(&dir)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|ext| ext == "json") {
+                        snapshots.push(Snapshot::load(&path.to_string_lossy())?);
+                    }
+                }
+            }
+            snapshots.sort_by_key(|snapshot| snapshot.generated_at);
+            for snapshot in snapshots {
+                println!(
+                    "{}\t{}\t{}\t{} domains",
+                    snapshot.name,
+                    snapshot.generated_at.format("%Y-%m-%d %H:%M"),
+                    snapshot.browser,
+                    snapshot.domain_counts.len()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Loads two snapshots and prints what changed from `before` to `after`
+pub fn run_diff(before: &str, after: &str, args: &Args) -> Result<()> {
+    let before = Snapshot::load(before)?;
+    let after = Snapshot::load(after)?;
+    let diff = SnapshotDiff::between(&before, &after);
+    let mut out = String::new();
+    write_diff(&mut out, &before, &after, &diff, args).expect("writing to a String cannot fail");
+    print!("{out}");
+    Ok(())
+}
+
+fn write_diff(
+    out: &mut String,
+    before: &Snapshot,
+    after: &Snapshot,
+    diff: &SnapshotDiff,
+    args: &Args,
+) -> fmt::Result {
+    writeln!(
+        out,
+        "\n--- {} ({}) -> {} ({}) ---",
+        before.name,
+        before.generated_at.format("%Y-%m-%d"),
+        after.name,
+        after.generated_at.format("%Y-%m-%d")
+    )?;
+    if before.fingerprint != after.fingerprint || before.weight != after.weight {
+        writeln!(
+            out,
+            "Note: the snapshots use different normalization rules or weights, so counts may not be comparable"
+        )?;
+    }
+    writeln!(
+        out,
+        "Domains: {} -> {}",
+        before.domain_counts.len(),
+        after.domain_counts.len()
+    )?;
+
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    write_changes(out, "New domains", &diff.added, args, |change| {
+        count(change.current)
+    })?;
+    write_changes(out, "Vanished domains", &diff.removed, args, |change| {
+        count(change.previous)
+    })?;
+    write_changes(out, "Biggest changes", &diff.changed, args, |change| {
+        format!(
+            "{} -> {} ({:+})",
+            count(change.previous),
+            count(change.current),
+            change.current as i64 - change.previous as i64
+        )
+    })?;
+    if let Some(drifted) = drifted_domains(before, after) {
+        write_drift(out, &drifted, args)?;
+    }
+    if diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty() {
+        writeln!(out, "No changes")?;
+    }
+    Ok(())
+}
+
+/// Domains the two snapshots' patterns normalize differently, when both recorded their
+/// patterns and they differ
+fn drifted_domains(before: &Snapshot, after: &Snapshot) -> Option<Vec<String>> {
+    let (old, new) = (before.patterns.as_ref()?, after.patterns.as_ref()?);
+    if old == new {
+        return None;
+    }
+    let domains = before
+        .domain_counts
+        .keys()
+        .chain(after.domain_counts.keys())
+        .map(String::as_str);
+    match patterns::normalization_drift(old, new, domains) {
+        Ok(drifted) => Some(drifted),
+        Err(e) => {
+            warn!(action = "compare", component = "snapshot", error = %e, "Could not compare the snapshots' patterns");
+            None
+        }
+    }
+}
+
+/// Lists up to `--top` (default 10) domains whose counts moved because the rules changed
+fn write_drift(out: &mut String, drifted: &[String], args: &Args) -> fmt::Result {
+    if drifted.is_empty() {
+        return Ok(());
+    }
+    let limit = args.top.unwrap_or(10);
+    writeln!(
+        out,
+        "\nNormalized differently by the changed patterns ({}), so their changes reflect the rules:",
+        drifted.len()
+    )?;
+    for domain in drifted.iter().take(limit) {
+        writeln!(out, "  {}", utils::display_domain(domain, args))?;
+    }
+    if drifted.len() > limit {
+        writeln!(out, "  … and {} more", drifted.len() - limit)?;
+    }
+    Ok(())
+}
+
+/// Writes up to `--top` (default 10) changes under a heading with the full count
+fn write_changes(
+    out: &mut String,
+    title: &str,
+    changes: &[DomainChange],
+    args: &Args,
+    detail: impl Fn(&DomainChange) -> String,
+) -> fmt::Result {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let listed: Vec<(String, &DomainChange)> = changes
+        .iter()
+        .take(args.top.unwrap_or(10))
+        .map(|change| (utils::display_domain(&change.domain, args), change))
+        .collect();
+    let width = listed
+        .iter()
+        .map(|(domain, _)| domain.chars().count())
+        .max()
+        .unwrap_or(0);
+    writeln!(out, "\n{title} ({}):", changes.len())?;
+    for (domain, change) in listed {
+        writeln!(out, "  {domain:<width$}  {}", detail(change))?;
+    }
+    Ok(())
+}
+
+/// Merges snapshots taken on different machines and prints the combined counts, optionally
+/// saving them as a snapshot named `save`
+pub fn run_merge(specs: &[String], save: Option<&str>, args: &Args) -> Result<()> {
+    if let Some(name) = save {
+        check_name(name)?;
+    }
+    let snapshots = specs
+        .iter()
+        .map(|spec| Snapshot::load(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let merge = SnapshotMerge::new(snapshots);
+    for (name, reason) in &merge.skipped {
+        info!(action = "skip", component = "snapshot", snapshot = %name, reason = %reason, "Skipping duplicate snapshot");
+    }
+
+    let mut out = String::new();
+    if args.quiet {
+        write_merge_quiet(&mut out, &merge, args)
+    } else {
+        write_merge(&mut out, &merge, args)
+    }
+    .expect("writing to a String cannot fail");
+    print!("{out}");
+
+    if let Some(name) = save {
+        let path = merge.to_snapshot(name, args).save()?;
+        info!(action = "write", component = "snapshot", path = ?path, "Saved merged snapshot");
+        if !args.quiet {
+            println!("\nSaved merged snapshot {name} to {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Column headings for the sources: their names, qualified when two share one
+fn source_labels(sources: &[Snapshot]) -> Vec<String> {
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            let shared = sources
+                .iter()
+                .filter(|other| other.name == source.name)
+                .count()
+                > 1;
+            match (&source.host, shared) {
+                (_, false) => source.name.clone(),
+                (Some(host), true) => format!("{}@{host}", source.name),
+                (None, true) => format!("{}#{}", source.name, i + 1),
+            }
+        })
+        .collect()
+}
+
+/// Writes `domain<TAB>total<TAB>count...` lines, one count per source
+fn write_merge_quiet(out: &mut String, merge: &SnapshotMerge, args: &Args) -> fmt::Result {
+    let ranked = merge.ranked();
+    let limit = args.top.unwrap_or(ranked.len());
+    for (domain, counts) in ranked.into_iter().take(limit) {
+        let total: u32 = counts.iter().sum();
+        let counts: Vec<String> = counts.iter().map(u32::to_string).collect();
+        writeln!(out, "{domain}\t{total}\t{}", counts.join("\t"))?;
+    }
+    Ok(())
+}
+
+fn write_merge(out: &mut String, merge: &SnapshotMerge, args: &Args) -> fmt::Result {
+    let labels = source_labels(&merge.sources);
+    writeln!(out, "\n--- Merged: {} ---", labels.join(" + "))?;
+    for (name, reason) in &merge.skipped {
+        writeln!(out, "Skipped {name}: {reason}")?;
+    }
+    if merge.mixed() {
+        writeln!(
+            out,
+            "Note: the snapshots use different normalization rules or weights, so summed counts may not be comparable"
+        )?;
+    }
+
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    let totals = merge.totals();
+    let visits: u32 = totals.values().sum();
+    match merge.range() {
+        Some(range) => {
+            let (earliest, latest) = utils::date_range_labels(Some(range), args);
+            writeln!(
+                out,
+                "Date range: {earliest} to {latest} ({} days)",
+                range.days()
+            )?;
+        }
+        None => writeln!(out, "Date range: not recorded by these snapshots")?,
+    }
+    writeln!(
+        out,
+        "Domains: {}, visits: {}",
+        count(totals.len() as u32),
+        count(visits)
+    )?;
+
+    writeln!(out, "\nSources:")?;
+    let label_width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (label, source) in labels.iter().zip(&merge.sources) {
+        let machine = match &source.host {
+            Some(host) => format!("{} on {host}", source.browser),
+            None => source.browser.clone(),
+        };
+        writeln!(
+            out,
+            "  {label:<label_width$}  {machine}, taken {}, {} to {}: {} domains, {} visits",
+            source.generated_at.format("%Y-%m-%d"),
+            source.date_range.0,
+            source.date_range.1,
+            count(source.domain_counts.len() as u32),
+            count(source.domain_counts.values().sum())
+        )?;
+    }
+
+    let listed: Vec<(String, &Vec<u32>)> = merge
+        .ranked()
+        .into_iter()
+        .take(args.top.unwrap_or(20))
+        .map(|(domain, counts)| (utils::display_domain(domain, args), counts))
+        .collect();
+    let headings: Vec<&str> = std::iter::once("Total")
+        .chain(labels.iter().map(String::as_str))
+        .collect();
+    let cells: Vec<Vec<String>> = listed
+        .iter()
+        .map(|(_, counts)| {
+            std::iter::once(count(counts.iter().sum()))
+                .chain(counts.iter().map(|n| match n {
+                    0 => "-".to_string(),
+                    n => count(*n),
+                }))
+                .collect()
+        })
+        .collect();
+    let domain_width = listed
+        .iter()
+        .map(|(domain, _)| domain.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Domain".len());
+    let widths: Vec<usize> = headings
+        .iter()
+        .enumerate()
+        .map(|(column, heading)| {
+            cells
+                .iter()
+                .map(|row| row[column].chars().count())
+                .chain([heading.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    writeln!(out)?;
+    let mut header = format!("{:<domain_width$}", "Domain");
+    for (heading, width) in headings.iter().zip(&widths) {
+        header.push_str(&format!("  {heading:>width$}"));
+    }
+    writeln!(out, "{header}")?;
+    for ((domain, _), row) in listed.iter().zip(&cells) {
+        let mut line = format!("{domain:<domain_width$}");
+        for (cell, width) in row.iter().zip(&widths) {
+            line.push_str(&format!("  {cell:>width$}"));
+        }
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
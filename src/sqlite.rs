@@ -1,14 +1,24 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use rusqlite::{Connection, Result as SqliteResult};
+use rusqlite::{Connection, OpenFlags, MAIN_DB};
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use crate::args::Browser;
+use crate::args::{Browser, Weight};
+use crate::cancel::CancellationToken;
+use crate::domain::{normalize_host, DomainFilter, HostClass, Normalizer};
+use crate::error::HistoreeError;
+use crate::profiles::ProfilesIni;
+use crate::schema::{Schema, SchemaFamily, Transition};
+use crate::stats::{DateRange, Retention, Sampling, VisitTotals};
+use crate::timezone::Timezone;
 
 pub fn get_browser_history_path(browser: &Browser) -> Result<PathBuf> {
     let system = env::consts::OS;
@@ -42,7 +52,7 @@ pub fn get_browser_history_path(browser: &Browser) -> Result<PathBuf> {
             PathBuf::from(app_data).join("Mozilla/Firefox")
         }
         (Browser::Firefox, "macos") => {
-            PathBuf::from(home).join("Library/Application Support/Firefox/Profiles")
+            PathBuf::from(home).join("Library/Application Support/Firefox")
         }
         (Browser::Firefox, "linux") => PathBuf::from(home).join(".mozilla/firefox"),
 
@@ -50,9 +60,7 @@ pub fn get_browser_history_path(browser: &Browser) -> Result<PathBuf> {
             let app_data = env::var("APPDATA")?;
             PathBuf::from(app_data).join("zen")
         }
-        (Browser::Zen, "macos") => {
-            PathBuf::from(home).join("Library/Application Support/zen/Profiles")
-        }
+        (Browser::Zen, "macos") => PathBuf::from(home).join("Library/Application Support/zen"),
         (Browser::Zen, "linux") => PathBuf::from(home).join(".zen"),
 
         (Browser::Vivaldi, "windows") => {
@@ -94,112 +102,228 @@ pub fn get_zen_history_path() -> Result<PathBuf> {
     get_firefox_based_history_path(&Browser::Zen)
 }
 
-fn get_firefox_based_history_path(browser: &Browser) -> Result<PathBuf> {
+fn is_firefox_based(browser: &Browser) -> bool {
+    matches!(browser, Browser::Firefox | Browser::Zen)
+}
+
+/// Directory holding a Chromium browser's profile directories
+fn chromium_profiles_dir(browser: &Browser) -> Result<PathBuf> {
+    let path = get_browser_history_path(browser)?;
+    // Chromium paths end in `<profile>/History`
+    Ok(path
+        .parent()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or(path))
+}
+
+/// The profiles directory of a Firefox-based browser with its parsed profiles.ini
+fn read_profiles_ini(browser: &Browser) -> Result<(PathBuf, ProfilesIni)> {
     let profiles_dir = get_browser_history_path(browser)?;
 
     if !profiles_dir.exists() {
-        anyhow::bail!(
-            "{} profiles directory not found at {:?}",
-            browser,
-            profiles_dir
+        return Err(
+            HistoryNotFound::new(format!("{browser} profiles directory"), profiles_dir).into(),
         );
     }
 
-    // Read profiles.ini to find the default profile
     let profiles_ini = profiles_dir.join("profiles.ini");
     if !profiles_ini.exists() {
-        anyhow::bail!("{} profiles.ini not found at {:?}", browser, profiles_ini);
-    }
-
-    let profiles_content = fs::read_to_string(&profiles_ini)?;
-    let mut default_profile_path = None;
-    let mut profiles = std::collections::HashMap::new();
-
-    // Parse profiles.ini to find the default profile and all profile paths
-    let mut current_profile = None;
-    for line in profiles_content.lines() {
-        let line = line.trim();
-        if line.starts_with('[') && line.ends_with(']') {
-            // This is a profile section
-            current_profile = Some(line[1..line.len() - 1].to_string());
-        } else if let Some(profile) = &current_profile {
-            if line.starts_with("Path=") {
-                let path = line.split('=').nth(1).unwrap_or("").trim();
-                profiles.insert(profile.clone(), path.to_string());
-            }
-            // Note: We'll ignore the Default=1 flag and use our own logic
-        }
+        return Err(HistoryNotFound::new(format!("{browser} profiles.ini"), profiles_ini).into());
     }
 
-    info!(action = "debug", component = "profile_parsing", profiles = ?profiles, "Parsed profiles.ini");
+    let profiles = ProfilesIni::parse(&fs::read_to_string(&profiles_ini)?);
+    info!(
+        action = "parse",
+        component = "profile_parsing",
+        profiles = profiles.profiles.len(),
+        installs = profiles.installs.len(),
+        "Parsed profiles.ini"
+    );
+    Ok((profiles_dir, profiles))
+}
 
-    // First, try to find dev-edition profile (this is what actually exists)
-    for (profile_name, path) in &profiles {
-        if profile_name.contains("Profile0") || path.contains("dev-edition") {
-            default_profile_path = Some(path.clone());
+fn get_firefox_based_history_path(browser: &Browser) -> Result<PathBuf> {
+    let (profiles_dir, profiles) = read_profiles_ini(browser)?;
+
+    // The install default can point at a profile that was since deleted, so fall back to
+    // the next candidate whose history exists
+    let candidates = profiles.candidates();
+    let first = candidates
+        .first()
+        .ok_or_else(|| HistoreeError::ProfileNotFound {
+            browser: browser.to_string(),
+            path: profiles_dir.join("profiles.ini"),
+        })?;
+    let history_path = candidates
+        .iter()
+        .map(|profile| {
+            (
+                profile,
+                profile.directory(&profiles_dir).join("places.sqlite"),
+            )
+        })
+        .find(|(_, path)| path.exists())
+        .map(|(profile, path)| {
             info!(
-                action = "debug",
+                action = "select",
                 component = "profile_parsing",
-                selected_profile = profile_name,
-                path = path,
-                "Selected dev-edition profile"
+                profile = profile.name.as_deref().unwrap_or(&profile.path),
+                path = ?path,
+                "Selected profile"
             );
-            break;
-        }
-    }
-
-    // If no dev-edition found, try to find one with "default" in the name
-    if default_profile_path.is_none() {
-        for (profile_name, path) in &profiles {
-            if profile_name.to_lowercase().contains("default") {
-                default_profile_path = Some(path.clone());
-                info!(
-                    action = "debug",
-                    component = "profile_parsing",
-                    selected_profile = profile_name,
-                    path = path,
-                    "Selected default profile"
-                );
-                break;
+            path
+        });
+    history_path.ok_or_else(|| {
+        HistoryNotFound::new(
+            format!("{browser} history database"),
+            first.directory(&profiles_dir).join("places.sqlite"),
+        )
+        .into()
+    })
+}
+
+/// History database of the profile named `profile`: a Chromium profile directory such as
+/// `Profile 1`, or the name or directory of a profile listed in profiles.ini
+pub fn get_profile_history_path(browser: &Browser, profile: &str) -> Result<PathBuf> {
+    let path = if is_firefox_based(browser) {
+        let (profiles_dir, profiles) = read_profiles_ini(browser)?;
+        let found = profiles.candidates().into_iter().find(|p| {
+            p.name.as_deref() == Some(profile)
+                || p.path == profile
+                || p.directory(&profiles_dir).file_name() == Some(profile.as_ref())
+        });
+        match found {
+            Some(found) => found.directory(&profiles_dir).join("places.sqlite"),
+            None => {
+                return Err(HistoryNotFound::new(
+                    format!("{browser} profile '{profile}'"),
+                    profiles_dir.join("profiles.ini"),
+                )
+                .into())
             }
         }
+    } else {
+        chromium_profiles_dir(browser)?
+            .join(profile)
+            .join("History")
+    };
+
+    if !path.exists() {
+        return Err(HistoryNotFound::new(
+            format!("{browser} history database for profile '{profile}'"),
+            path,
+        )
+        .into());
     }
+    info!(action = "select", component = "profile_parsing", browser = ?browser, profile = profile, path = ?path, "Selected profile");
+    Ok(path)
+}
 
-    // If still no default, use the first profile
-    if default_profile_path.is_none() {
-        if let Some((profile_name, path)) = profiles.iter().next() {
-            default_profile_path = Some(path.clone());
-            info!(
-                action = "debug",
-                component = "profile_parsing",
-                selected_profile = profile_name,
-                path = path,
-                "Selected first available profile"
-            );
+/// Profiles of `browser` that have a history database, default profile first, as the
+/// name `--profile` accepts and the database path
+pub fn list_profiles(browser: &Browser) -> Result<Vec<(String, PathBuf)>> {
+    if is_firefox_based(browser) {
+        let (profiles_dir, profiles) = read_profiles_ini(browser)?;
+        return Ok(profiles
+            .candidates()
+            .into_iter()
+            .map(|profile| {
+                let path = profile.directory(&profiles_dir).join("places.sqlite");
+                (profile.name.unwrap_or(profile.path), path)
+            })
+            .filter(|(_, path)| path.exists())
+            .collect());
+    }
+
+    let default = get_browser_history_path(browser)?;
+    let default = default.parent().and_then(Path::file_name);
+    let mut profiles: Vec<(String, PathBuf)> = match fs::read_dir(chromium_profiles_dir(browser)?) {
+        Ok(entries) => entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let path = entry.path().join("History");
+                path.is_file()
+                    .then(|| (entry.file_name().to_string_lossy().into_owned(), path))
+            })
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    // `Profile 2` sorts before `Profile 10`
+    profiles.sort_by(|(a, _), (b, _)| {
+        let is_default = |name: &String| Some(name.as_ref()) == default;
+        is_default(b)
+            .cmp(&is_default(a))
+            .then(a.len().cmp(&b.len()))
+            .then(a.cmp(b))
+    });
+    Ok(profiles)
+}
+
+/// Error for a missing browser profile or history database,
+/// detectable with `error.is::<HistoryNotFound>()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryNotFound {
+    pub what: String,
+    pub path: PathBuf,
+}
+
+impl HistoryNotFound {
+    pub fn new(what: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            what: what.into(),
+            path: path.into(),
         }
     }
+}
 
-    let profile_path = default_profile_path.ok_or_else(|| {
-        anyhow::anyhow!("Could not find default {} profile in profiles.ini", browser)
-    })?;
+impl fmt::Display for HistoryNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} not found at {:?}", self.what, self.path)
+    }
+}
 
-    // The profile path is relative to the Firefox directory
-    let history_path = profiles_dir.join(profile_path).join("places.sqlite");
+impl std::error::Error for HistoryNotFound {}
 
-    info!(action = "debug", component = "profile_parsing", final_path = ?history_path, "Final history path");
+/// Temporary copy of a history database, removed when dropped so that errors and
+/// cancellation never leave stale copies behind
+#[derive(Debug)]
+pub struct TempDatabase {
+    path: PathBuf,
+}
 
-    if !history_path.exists() {
-        anyhow::bail!(
-            "{} history database not found at {:?}",
-            browser,
-            history_path
-        );
+impl TempDatabase {
+    /// Takes ownership of a database created at `path`
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!(action = "cleanup", component = "temp_file", error = %e, "Failed to remove temporary file");
+        }
     }
+}
 
-    Ok(history_path)
+/// Where history databases are copied when `--temp-path` is not given
+pub fn default_temp_path() -> PathBuf {
+    PathBuf::from(&format!(
+        "{}/browser_history_copy.db",
+        env::var("HOME").unwrap_or_else(|_| env::var("USERPROFILE").unwrap_or_default())
+    ))
 }
 
-pub fn copy_history_database(history_path: &Path, temp_path: Option<&Path>) -> Result<PathBuf> {
+pub fn copy_history_database(
+    history_path: &Path,
+    temp_path: Option<&Path>,
+) -> Result<TempDatabase> {
     let start_time = Instant::now();
     info!(
         action = "start",
@@ -207,17 +331,14 @@ pub fn copy_history_database(history_path: &Path, temp_path: Option<&Path>) -> R
         "Copying browser history database"
     );
 
-    let temp_path = temp_path.map(|p| p.to_path_buf()).unwrap_or_else(|| {
-        PathBuf::from(&format!(
-            "{}/browser_history_copy.db",
-            env::var("HOME").unwrap_or_else(|_| env::var("USERPROFILE").unwrap_or_default())
-        ))
-    });
+    let temp_path = temp_path
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(default_temp_path);
 
     info!(action = "copy", component = "database_copy", source = ?history_path, destination = ?temp_path, "Database copy paths");
 
     if !history_path.exists() {
-        anyhow::bail!("History file not found at {:?}", history_path);
+        return Err(HistoryNotFound::new("History file", history_path).into());
     }
 
     fs::copy(history_path, &temp_path)?;
@@ -229,281 +350,1209 @@ pub fn copy_history_database(history_path: &Path, temp_path: Option<&Path>) -> R
         duration_ms = copy_time.as_millis(),
         "Database copy completed"
     );
-    Ok(temp_path)
+    Ok(TempDatabase { path: temp_path })
+}
+
+/// How often `--wait-for-unlock` checks the profile lock again
+const UNLOCK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Lock file the running browser keeps in the profile holding `history_path`, if any:
+/// Chromium's `lockfile` (Windows) or `SingletonLock` (Linux, macOS) in the user data
+/// directory, Firefox's `parent.lock` (Windows) or `lock` (Linux, macOS) in the profile
+pub fn held_profile_lock(history_path: &Path, family: SchemaFamily) -> Option<PathBuf> {
+    let profile = history_path.parent()?;
+    let (dir, names): (&Path, [&str; 2]) = match family {
+        SchemaFamily::Chromium => (profile.parent()?, ["lockfile", "SingletonLock"]),
+        SchemaFamily::Firefox => (profile, ["parent.lock", "lock"]),
+    };
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        // `SingletonLock` and `lock` are symlinks to a host and process that may not exist
+        .find(|path| fs::symlink_metadata(path).is_ok())
+}
+
+/// Copies a browser's history database, minding the profile lock of a running browser.
+///
+/// With `wait`, waits up to that long for the browser to release the lock. A profile
+/// still locked is read through the SQLite backup API, which sees a consistent snapshot
+/// where a plain file copy could catch a write halfway.
+pub fn copy_profile_database(
+    history_path: &Path,
+    family: SchemaFamily,
+    temp_path: Option<&Path>,
+    wait: Option<Duration>,
+) -> Result<TempDatabase> {
+    let Some(mut lock) = held_profile_lock(history_path, family) else {
+        return copy_history_database(history_path, temp_path);
+    };
+    if let Some(wait) = wait {
+        info!(action = "wait", component = "profile_lock", lock = ?lock, timeout_secs = wait.as_secs(), "Waiting for the browser to release its profile lock");
+        let deadline = Instant::now() + wait;
+        while Instant::now() < deadline {
+            std::thread::sleep(UNLOCK_POLL_INTERVAL);
+            match held_profile_lock(history_path, family) {
+                Some(held) => lock = held,
+                None => return copy_history_database(history_path, temp_path),
+            }
+        }
+    }
+
+    warn!(action = "detect", component = "profile_lock", lock = ?lock, "The browser is running; reading its history through the SQLite backup API");
+    let temp_path = temp_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(default_temp_path);
+    match backup_history_database(history_path, &temp_path) {
+        Ok(database) => Ok(database),
+        Err(e) => {
+            warn!(action = "fallback", component = "profile_lock", error = %e, "Backup failed, copying the file instead; the copy may miss or tear recent writes, so close the browser or pass --wait-for-unlock");
+            copy_history_database(history_path, Some(&temp_path))
+        }
+    }
 }
 
-pub fn get_date_range(conn: &Connection) -> Result<(String, String, i64)> {
+/// Copies `history_path` to `temp_path` page by page through SQLite, which honors the
+/// locks of the browser writing to it
+fn backup_history_database(history_path: &Path, temp_path: &Path) -> Result<TempDatabase> {
+    let start_time = Instant::now();
+    let source = Connection::open_with_flags(
+        history_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    source.busy_timeout(Duration::from_secs(2))?;
+    source.backup(MAIN_DB, temp_path, None)?;
+    info!(
+        action = "complete",
+        component = "database_backup",
+        source = ?history_path,
+        destination = ?temp_path,
+        duration_ms = start_time.elapsed().as_millis(),
+        "Database backup completed"
+    );
+    Ok(TempDatabase::new(temp_path.to_path_buf()))
+}
+
+/// Database older Chromium versions moved visits past the retention window into, kept next
+/// to `History` in long-lived profiles
+pub const ARCHIVED_HISTORY_FILE: &str = "Archived History";
+
+/// Columns `table` has in both the main and the attached `archived` database, other than
+/// `skip`
+fn shared_columns(conn: &Connection, table: &str, skip: &[&str]) -> Result<Vec<String>> {
+    let columns = |schema: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!("PRAGMA {schema}.table_info({table})"))?;
+        let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+        Ok(names.collect::<rusqlite::Result<_>>()?)
+    };
+    let archived = columns("archived")?;
+    Ok(columns("main")?
+        .into_iter()
+        .filter(|column| archived.contains(column) && !skip.contains(&column.as_str()))
+        .collect())
+}
+
+/// Adds the visits in an `Archived History` database that predate every visit in the
+/// copied `database`, so overlapping periods aren't counted twice. URLs are matched by
+/// address, since the two databases number them independently. Returns the visits added.
+pub fn merge_archived_history(database: &TempDatabase, archived: &Path) -> Result<usize> {
+    let start_time = Instant::now();
+    let mut archived_path = database.path().as_os_str().to_owned();
+    archived_path.push("-archived");
+    let archived_copy = copy_history_database(archived, Some(Path::new(&archived_path)))?;
+
+    let mut conn = Connection::open(database.path())?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS archived",
+        [archived_copy.path().to_string_lossy()],
+    )
+    .context("Failed to open the archived history")?;
+    let url_columns = shared_columns(&conn, "urls", &["id", "url"])?;
+    // Links to other visits and segments would point at unrelated rows once merged
+    let visit_columns = shared_columns(
+        &conn,
+        "visits",
+        &["id", "url", "from_visit", "segment_id", "opener_visit"],
+    )?;
+    let list = |columns: &[String], alias: &str| -> String {
+        columns
+            .iter()
+            .map(|column| format!(", {alias}{column}"))
+            .collect()
+    };
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "INSERT INTO main.urls (url{}) SELECT a.url{} FROM archived.urls a \
+             WHERE NOT EXISTS (SELECT 1 FROM main.urls m WHERE m.url = a.url)",
+            list(&url_columns, ""),
+            list(&url_columns, "a.")
+        ),
+        [],
+    )
+    .context("Failed to merge archived URLs")?;
+    let added = tx
+        .execute(
+            &format!(
+                "INSERT INTO main.visits (url{}) SELECT m.id{} FROM archived.visits v \
+                 JOIN archived.urls a ON a.id = v.url JOIN main.urls m ON m.url = a.url \
+                 WHERE v.visit_time < (SELECT COALESCE(MIN(visit_time), 9223372036854775807) FROM main.visits)",
+                list(&visit_columns, ""),
+                list(&visit_columns, "v.")
+            ),
+            [],
+        )
+        .context("Failed to merge archived visits")?;
+    tx.commit()?;
+    conn.execute("DETACH DATABASE archived", [])?;
+
+    info!(
+        action = "complete",
+        component = "archived_history",
+        source = ?archived,
+        visits = added,
+        duration_ms = start_time.elapsed().as_millis(),
+        "Merged archived history"
+    );
+    Ok(added)
+}
+
+pub fn get_date_range(
+    conn: &Connection,
+    schema: &Schema,
+    visit_filter: &VisitFilter<'_>,
+) -> Result<Option<DateRange>> {
     let start_time = Instant::now();
     info!(
         action = "start",
         component = "date_range_query",
+        family = %schema.family,
         "Querying visit date range"
     );
 
+    let queries = schema.queries;
     let (earliest_timestamp, latest_timestamp): (Option<i64>, Option<i64>) = conn
         .query_row(
-            "SELECT MIN(visit_time), MAX(visit_time) FROM visits",
+            &format!(
+                "SELECT MIN(v.{time}), MAX(v.{time}) FROM {visits} v WHERE 1 = 1{filter}",
+                time = queries.visit_time_column,
+                visits = queries.visits_table,
+                filter = visit_filter.sql_conditions(schema, "v")
+            ),
             [],
             |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .context("Failed to query visit dates")?;
 
     if let (Some(earliest), Some(latest)) = (earliest_timestamp, latest_timestamp) {
-        // Chrome uses microseconds since 1601-01-01
-        let chrome_epoch =
-            DateTime::parse_from_rfc3339("1601-01-01T00:00:00Z")?.with_timezone(&Utc);
-        let earliest_date = chrome_epoch + chrono::Duration::microseconds(earliest);
-        let latest_date = chrome_epoch + chrono::Duration::microseconds(latest);
-
-        let days_between = (latest_date - earliest_date).num_days();
-        let query_time = start_time.elapsed();
-
+        let range = DateRange {
+            earliest: schema.timestamp_to_datetime(earliest),
+            latest: schema.timestamp_to_datetime(latest),
+        };
         info!(
             action = "complete",
             component = "date_range_query",
-            earliest_date = earliest_date.format("%B %-d, %Y").to_string(),
-            latest_date = latest_date.format("%B %-d, %Y").to_string(),
-            days_between,
-            duration_ms = query_time.as_millis(),
+            earliest = %range.earliest.to_rfc3339(),
+            latest = %range.latest.to_rfc3339(),
+            days_between = range.days(),
+            duration_ms = start_time.elapsed().as_millis(),
             "Date range query completed"
         );
-
-        Ok((
-            earliest_date.format("%B %-d, %Y").to_string(),
-            latest_date.format("%B %-d, %Y").to_string(),
-            days_between,
-        ))
+        Ok(Some(range))
     } else {
-        let query_time = start_time.elapsed();
         warn!(
             action = "complete",
             component = "date_range_query",
-            duration_ms = query_time.as_millis(),
+            duration_ms = start_time.elapsed().as_millis(),
             "No visit data found"
         );
-        Ok((
-            "No data available".to_string(),
-            "No data available".to_string(),
-            0,
-        ))
+        Ok(None)
+    }
+}
+
+/// Restricts which visits are analyzed; the time window's `until` is exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VisitFilter<'a> {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Skip visits that redirected onward, so only a chain's final destination counts
+    pub collapse_redirects: bool,
+    /// Lowercase URL schemes to keep; empty keeps every scheme
+    pub schemes: &'a [String],
+    /// Text the URL or title must contain, ignoring case
+    pub text: Option<&'a str>,
+}
+
+impl<'a> VisitFilter<'a> {
+    /// Builds a filter covering whole days in `zone`, with `until` inclusive
+    pub fn from_dates(since: Option<NaiveDate>, until: Option<NaiveDate>, zone: Timezone) -> Self {
+        Self {
+            since: since.map(|date| zone.start_of_day(date)),
+            until: until
+                .and_then(|date| date.succ_opt())
+                .map(|date| zone.start_of_day(date)),
+            collapse_redirects: false,
+            schemes: &[],
+            text: None,
+        }
+    }
+
+    pub fn collapse_redirects(mut self, collapse: bool) -> Self {
+        self.collapse_redirects = collapse;
+        self
+    }
+
+    pub fn schemes(mut self, schemes: &'a [String]) -> Self {
+        self.schemes = schemes;
+        self
+    }
+
+    pub fn matching(mut self, text: &'a str) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    /// Narrows the filter to visits after `time`, skipping those already analyzed
+    pub fn after(mut self, time: DateTime<Utc>) -> Self {
+        // Both families store microseconds, so the next microsecond is the first new visit
+        let next = time + chrono::Duration::microseconds(1);
+        self.since = Some(self.since.map_or(next, |since| since.max(next)));
+        self
+    }
+
+    /// Narrows the filter to visits at or before `time`
+    pub fn through(mut self, time: DateTime<Utc>) -> Self {
+        let next = time + chrono::Duration::microseconds(1);
+        self.until = Some(self.until.map_or(next, |until| until.min(next)));
+        self
+    }
+
+    /// Whether the filter places no conditions on individual visits; schemes are URL conditions
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none() && !self.collapse_redirects
+    }
+
+    /// SQL conditions (each prefixed with AND) restricting the visits table aliased as `alias`
+    fn sql_conditions(&self, schema: &Schema, alias: &str) -> String {
+        let column = schema.queries.visit_time_column;
+        let mut conditions = String::new();
+        if let Some(since) = self.since {
+            conditions.push_str(&format!(
+                " AND {alias}.{column} >= {}",
+                schema.datetime_to_timestamp(since)
+            ));
+        }
+        if let Some(until) = self.until {
+            conditions.push_str(&format!(
+                " AND {alias}.{column} < {}",
+                schema.datetime_to_timestamp(until)
+            ));
+        }
+        if self.collapse_redirects {
+            conditions.push_str(&format!(
+                " AND NOT ({})",
+                schema.queries.redirect_source_expr.replace("{v}", alias)
+            ));
+        }
+        conditions
+    }
+
+    /// SQL conditions (each prefixed with AND) restricting the urls table aliased as `alias`
+    /// by scheme and text
+    fn url_conditions(&self, schema: &Schema, alias: &str) -> String {
+        let url = schema.queries.url_column;
+        let mut conditions = String::new();
+        if !self.schemes.is_empty() {
+            // Schemes are validated to [a-z0-9+.-] by `validate_args`, so they can be inlined
+            let schemes: Vec<String> = self
+                .schemes
+                .iter()
+                .map(|scheme| format!("'{scheme}:'"))
+                .collect();
+            conditions.push_str(&format!(
+                " AND lower(substr({alias}.{url}, 1, instr({alias}.{url}, ':'))) IN ({})",
+                schemes.join(", ")
+            ));
+        }
+        if let Some(text) = self.text {
+            // instr has no wildcards to escape, unlike LIKE; quotes are doubled for the literal
+            let text = text.to_ascii_lowercase().replace('\'', "''");
+            conditions.push_str(&format!(
+                " AND (instr(lower({alias}.{url}), '{text}') > 0 \
+                 OR instr(lower(coalesce({alias}.{title}, '')), '{text}') > 0)",
+                title = schema.queries.title_column
+            ));
+        }
+        conditions
     }
 }
 
-pub fn get_firefox_date_range(conn: &Connection) -> Result<(String, String, i64)> {
+/// A single visit row joined with its URL
+#[derive(Debug, Clone)]
+pub struct Visit {
+    pub id: i64,
+    /// The visit this one was navigated from, when the browser recorded it
+    pub from_visit: Option<i64>,
+    pub url_id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    /// Time spent on the page, when the browser records it
+    pub duration: Option<chrono::Duration>,
+    pub transition: Transition,
+}
+
+/// Streams every visit in the date window, oldest first
+pub fn for_each_visit(
+    conn: &Connection,
+    schema: &Schema,
+    visit_filter: &VisitFilter<'_>,
+    sampling: Option<Sampling>,
+    cancel: &CancellationToken,
+    mut callback: impl FnMut(Visit),
+) -> Result<usize> {
     let start_time = Instant::now();
+    let queries = schema.queries;
+    let sql = format!(
+        "SELECT u.{url_id}, u.{url}, v.{time}, {duration}, v.{transition}, u.{title}, \
+         v.{visit_id}, v.{from_visit} FROM {visits} v \
+         JOIN {urls} u ON u.{url_id} = v.{visit_url} \
+         WHERE u.{url} IS NOT NULL{url_filter}{filter} ORDER BY v.{time}",
+        url = queries.url_column,
+        title = queries.title_column,
+        urls = queries.urls_table,
+        url_id = queries.url_id_column,
+        visits = queries.visits_table,
+        visit_url = queries.visit_url_column,
+        time = queries.visit_time_column,
+        duration = queries
+            .visit_duration_column
+            .map_or_else(|| "NULL".to_string(), |column| format!("v.{column}")),
+        transition = queries.visit_transition_column,
+        visit_id = queries.visit_id_column,
+        from_visit = queries.from_visit_column,
+        url_filter = visit_filter.url_conditions(schema, "u"),
+        filter = visit_filter.sql_conditions(schema, "v")
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        cancel.check()?;
+        let url_id: i64 = row.get(0)?;
+        if sampling.is_some_and(|sampling| !sampling.includes(url_id)) {
+            continue;
+        }
+        callback(Visit {
+            id: row.get(6)?,
+            from_visit: row.get::<_, Option<i64>>(7)?.filter(|id| *id > 0),
+            url_id,
+            url: row.get(1)?,
+            title: row
+                .get::<_, Option<String>>(5)?
+                .filter(|title| !title.is_empty()),
+            timestamp: schema.timestamp_to_datetime(row.get(2)?),
+            duration: row
+                .get::<_, Option<i64>>(3)?
+                .map(chrono::Duration::microseconds),
+            transition: schema.decode_transition(row.get::<_, Option<i64>>(4)?.unwrap_or(-1)),
+        });
+        count += 1;
+    }
+
     info!(
-        action = "start",
-        component = "firefox_date_range_query",
-        "Querying Firefox visit date range"
+        action = "complete",
+        component = "visit_stream",
+        visit_count = count,
+        duration_ms = start_time.elapsed().as_millis(),
+        "Visit stream completed"
     );
+    Ok(count)
+}
 
-    let (earliest_timestamp, latest_timestamp): (Option<i64>, Option<i64>) = conn
+/// Search terms the browser recorded for its configured search engines, keyed by URL id
+pub fn load_search_terms(conn: &Connection, schema: &Schema) -> Result<HashMap<i64, String>> {
+    let Some(sql) = schema.queries.search_terms_query else {
+        return Ok(HashMap::new());
+    };
+
+    // Older or trimmed profiles may lack the table; URL parsing still finds most searches
+    let mut stmt = match conn.prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            warn!(action = "load", component = "search_terms", error = %e, "Recorded search terms unavailable");
+            return Ok(HashMap::new());
+        }
+    };
+    let terms = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .filter_map(|row| match row {
+            Ok((url_id, term)) => crate::search::normalize_term(&term).map(|t| Ok((url_id, t))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<rusqlite::Result<HashMap<i64, String>>>()?;
+
+    info!(
+        action = "load",
+        component = "search_terms",
+        term_count = terms.len(),
+        "Loaded recorded search terms"
+    );
+    Ok(terms)
+}
+
+impl ExtractOptions<'_> {
+    /// Canonical domain of a URL under these options, if it has one
+    pub fn domain_of(&self, url: &str) -> Option<String> {
+        url::Url::parse(url)
+            .ok()
+            .and_then(|url| self.normalizer.canonical_url(&url))
+    }
+}
+
+/// Number of visits per URL scheme in the visit window, including filtered-out schemes
+pub fn scheme_counts(
+    conn: &Connection,
+    schema: &Schema,
+    visit_filter: &VisitFilter<'_>,
+) -> Result<crate::stats::SchemeCounts> {
+    let queries = schema.queries;
+    let sql = format!(
+        "SELECT lower(substr(u.{url}, 1, instr(u.{url}, ':') - 1)), COUNT(*) FROM {visits} v \
+         JOIN {urls} u ON u.{url_id} = v.{visit_url} \
+         WHERE u.{url} IS NOT NULL{filter} GROUP BY 1",
+        url = queries.url_column,
+        urls = queries.urls_table,
+        url_id = queries.url_id_column,
+        visits = queries.visits_table,
+        visit_url = queries.visit_url_column,
+        filter = visit_filter.sql_conditions(schema, "v")
+    );
+
+    let mut counts = crate::stats::SchemeCounts::default();
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let scheme: String = row.get(0)?;
+        let count: u32 = row.get(1)?;
+        let scheme = if scheme.is_empty() {
+            "(none)".to_string()
+        } else {
+            scheme
+        };
+        *counts.counts.entry(scheme).or_insert(0) += count;
+    }
+    Ok(counts)
+}
+
+/// Width of the buckets visits are grouped into before being assigned to local days;
+/// a quarter hour divides every real UTC offset
+const TOTALS_BUCKET_MICROS: i64 = 15 * 60 * 1_000_000;
+
+/// Counts visits and distinct URLs, and visits per day in `zone`
+/// FROM clause joining visits `v` to their URLs `u`, restricted by `visit_filter`
+fn filtered_visits(schema: &Schema, visit_filter: &VisitFilter<'_>) -> String {
+    let queries = schema.queries;
+    format!(
+        "FROM {visits} v JOIN {urls} u ON u.{url_id} = v.{visit_url} WHERE 1 = 1{filter}{url_filter}",
+        urls = queries.urls_table,
+        url_id = queries.url_id_column,
+        visits = queries.visits_table,
+        visit_url = queries.visit_url_column,
+        filter = visit_filter.sql_conditions(schema, "v"),
+        url_filter = visit_filter.url_conditions(schema, "u")
+    )
+}
+
+/// Visits matching `visit_filter` and the distinct URLs among them
+pub fn count_visits(
+    conn: &Connection,
+    schema: &Schema,
+    visit_filter: &VisitFilter<'_>,
+) -> Result<(u32, u32)> {
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*), COUNT(DISTINCT v.{}) {}",
+            schema.queries.visit_url_column,
+            filtered_visits(schema, visit_filter)
+        ),
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .context("Failed to count visits")
+}
+
+/// Sums of the per-URL all-time visit counters and of the visits still recorded, over the
+/// whole database
+pub fn retention_totals(conn: &Connection, schema: &Schema) -> Result<Retention> {
+    let queries = schema.queries;
+    let (all_time, recorded): (i64, i64) = conn
         .query_row(
-            "SELECT MIN(visit_date), MAX(visit_date) FROM moz_historyvisits",
+            &format!(
+                "SELECT (SELECT COALESCE(SUM({count}), 0) FROM {urls} u), (SELECT COUNT(*) FROM {visits})",
+                count = queries.visit_count_expr,
+                urls = queries.urls_table,
+                visits = queries.visits_table
+            ),
             [],
             |row| Ok((row.get(0)?, row.get(1)?)),
         )
-        .context("Failed to query Firefox visit dates")?;
+        .context("Failed to compare visit counters")?;
+    Ok(Retention {
+        all_time: all_time.max(0) as u64,
+        recorded: recorded.max(0) as u64,
+        all_time_counts: None,
+    })
+}
 
-    if let (Some(earliest), Some(latest)) = (earliest_timestamp, latest_timestamp) {
-        // Firefox uses microseconds since 1970-01-01
-        let unix_epoch = DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z")?.with_timezone(&Utc);
-        let earliest_date = unix_epoch + chrono::Duration::microseconds(earliest);
-        let latest_date = unix_epoch + chrono::Duration::microseconds(latest);
+pub fn visit_totals(
+    conn: &Connection,
+    schema: &Schema,
+    visit_filter: &VisitFilter<'_>,
+    zone: Timezone,
+) -> Result<VisitTotals> {
+    let start_time = Instant::now();
+    let queries = schema.queries;
+    let from = filtered_visits(schema, visit_filter);
+
+    let mut totals = VisitTotals {
+        unique_urls: conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(DISTINCT v.{}) {from}",
+                    queries.visit_url_column
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .context("Failed to count visited URLs")?,
+        ..VisitTotals::default()
+    };
+
+    // Grouping in SQL keeps this to a few rows per day, even for years of history
+    let mut stmt = conn.prepare(&format!(
+        "SELECT v.{time} / {TOTALS_BUCKET_MICROS}, COUNT(*) {from} GROUP BY 1",
+        time = queries.visit_time_column
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let bucket: i64 = row.get(0)?;
+        let visits: u32 = row.get(1)?;
+        let day = zone
+            .local(schema.timestamp_to_datetime(bucket * TOTALS_BUCKET_MICROS))
+            .date();
+        *totals.daily.entry(day).or_insert(0) += visits;
+        totals.visits += visits;
+    }
+
+    info!(
+        action = "complete",
+        component = "visit_totals",
+        visits = totals.visits,
+        unique_urls = totals.unique_urls,
+        days = totals.daily.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Visit totals query completed"
+    );
+    Ok(totals)
+}
+
+/// Options controlling how URLs are turned into domain counts
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractOptions<'a> {
+    pub normalizer: &'a Normalizer,
+    pub workers: Option<usize>,
+    pub weight: Weight,
+    pub sampling: Option<Sampling>,
+    pub visit_filter: VisitFilter<'a>,
+    pub filter: &'a DomainFilter,
+    /// Record which normalization pattern rewrote each host
+    pub pattern_stats: bool,
+    /// Keep the removed hosts for each removal reason
+    pub removed_samples: bool,
+    pub cancel: &'a CancellationToken,
+    /// Zone that downloads are grouped into months in
+    pub zone: Timezone,
+    /// Advanced as URL rows are extracted
+    pub progress: Option<crate::progress::Bar<'a>>,
+}
 
-        let days_between = (latest_date - earliest_date).num_days();
-        let query_time = start_time.elapsed();
+/// URLs handed to the worker pool at a time, so memory stays bounded on large histories
+const EXTRACT_CHUNK_SIZE: usize = 16_384;
+
+/// Turns chunks of weighted URLs into domain counts on a thread pool sized by `--workers`,
+/// or on the calling thread without the `parallel` feature
+struct DomainExtractor<'a> {
+    #[cfg(feature = "parallel")]
+    pool: rayon::ThreadPool,
+    normalizer: &'a Normalizer,
+    pattern_stats: bool,
+    removed_samples: bool,
+    cancel: &'a CancellationToken,
+    stats: crate::stats::DomainStats,
+}
+
+impl<'a> DomainExtractor<'a> {
+    #[cfg(feature = "parallel")]
+    fn new(options: &ExtractOptions<'a>, component_name: &str) -> Result<Self> {
+        // Without --workers, honor RAYON_NUM_THREADS before falling back to the CPU count
+        let max_workers = options
+            .workers
+            .or_else(|| {
+                env::var("RAYON_NUM_THREADS")
+                    .ok()
+                    .and_then(|threads| threads.parse().ok())
+                    .filter(|threads| *threads > 0)
+            })
+            .unwrap_or_else(|| {
+                let cpu_count = num_cpus::get();
+                std::cmp::min(cpu_count, 8)
+            });
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(max_workers)
+            .build()
+            .context("Failed to start worker threads")?;
 
         info!(
-            action = "complete",
-            component = "firefox_date_range_query",
-            earliest_date = earliest_date.format("%B %-d, %Y").to_string(),
-            latest_date = latest_date.format("%B %-d, %Y").to_string(),
-            days_between,
-            duration_ms = query_time.as_millis(),
-            "Firefox date range query completed"
+            action = "configure",
+            component = component_name,
+            worker_count = max_workers,
+            chunk_size = EXTRACT_CHUNK_SIZE,
+            "Using workers for processing"
         );
 
-        Ok((
-            earliest_date.format("%B %-d, %Y").to_string(),
-            latest_date.format("%B %-d, %Y").to_string(),
-            days_between,
-        ))
-    } else {
-        let query_time = start_time.elapsed();
-        warn!(
-            action = "complete",
-            component = "firefox_date_range_query",
-            duration_ms = query_time.as_millis(),
-            "No Firefox visit data found"
+        Ok(Self {
+            pool,
+            normalizer: options.normalizer,
+            pattern_stats: options.pattern_stats,
+            removed_samples: options.removed_samples,
+            cancel: options.cancel,
+            stats: Self::initial_stats(options),
+        })
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn new(options: &ExtractOptions<'a>, component_name: &str) -> Result<Self> {
+        info!(
+            action = "configure",
+            component = component_name,
+            worker_count = 1,
+            chunk_size = EXTRACT_CHUNK_SIZE,
+            "Using workers for processing"
         );
-        Ok((
-            "No data available".to_string(),
-            "No data available".to_string(),
-            0,
-        ))
+        Ok(Self {
+            normalizer: options.normalizer,
+            pattern_stats: options.pattern_stats,
+            removed_samples: options.removed_samples,
+            cancel: options.cancel,
+            stats: Self::initial_stats(options),
+        })
+    }
+
+    fn initial_stats(options: &ExtractOptions<'a>) -> crate::stats::DomainStats {
+        crate::stats::DomainStats {
+            pattern_hits: options
+                .pattern_stats
+                .then(|| crate::stats::PatternHits::new(&options.normalizer.patterns)),
+            removed_samples: options.removed_samples.then(Default::default),
+            ..Default::default()
+        }
+    }
+
+    /// Counts one chunk in parallel and merges it into the running totals
+    fn process(&mut self, urls: Vec<(String, u32)>) {
+        let (normalizer, pattern_stats, removed_samples, cancel) = (
+            self.normalizer,
+            self.pattern_stats,
+            self.removed_samples,
+            self.cancel,
+        );
+        let empty = || ChunkTally::new(normalizer, pattern_stats, removed_samples);
+        let count = |mut acc: ChunkTally, (url_str, weight): (String, u32)| {
+            if cancel.is_cancelled() {
+                return acc;
+            }
+            let Ok(url) = url::Url::parse(&url_str) else {
+                acc.remove(HostClass::Unparseable, &url_str);
+                return acc;
+            };
+            let Some(host) = url.host_str().map(normalize_host) else {
+                acc.remove(HostClass::NoHost, &url_str);
+                return acc;
+            };
+            let host = host.as_ref();
+            match normalizer.classify_with_rule(host) {
+                Err(class) => acc.remove(class, host),
+                Ok((domain, rule)) => {
+                    if let (Some(hits), Some(rule)) = (&mut acc.pattern_hits, rule) {
+                        hits.record(rule, host);
+                    }
+                    let root = matches!(url.path(), "" | "/") && url.query().is_none();
+                    acc.record(normalizer.with_port(domain, &url), weight, root);
+                }
+            }
+            acc
+        };
+        // Use Rayon's parallel iterator with work-stealing, then reduce the per-thread folds
+        #[cfg(feature = "parallel")]
+        let tally = self.pool.install(|| {
+            urls.into_par_iter()
+                .fold(empty, count)
+                .reduce(empty, ChunkTally::merge)
+        });
+        #[cfg(not(feature = "parallel"))]
+        let tally = urls.into_iter().fold(empty(), count);
+        tally.merge_into(&mut self.stats);
+    }
+
+    fn finish(self) -> Result<crate::stats::DomainStats> {
+        self.cancel.check()?;
+        Ok(self.stats)
     }
 }
 
-/// Generic domain extraction function that works for both Chrome-based and Firefox-based browsers
-fn extract_domains_from_urls_generic(
-    urls: Vec<String>,
-    patterns: &[regex::Regex],
-    max_workers: Option<usize>,
-    component_name: &str,
-) -> Result<crate::stats::DomainStats> {
-    let start_time = Instant::now();
-    info!(
-        action = "start",
-        component = component_name,
-        "Starting domain extraction from URLs"
-    );
+/// Per-thread counts for one chunk
+struct ChunkTally {
+    /// Domain -> (count, portion of the count on the root path)
+    counts: HashMap<String, (u32, u32)>,
+    removed: crate::stats::RemovedHosts,
+    pattern_hits: Option<crate::stats::PatternHits>,
+    removed_samples: Option<crate::stats::RemovedSamples>,
+}
 
-    let query_time = start_time.elapsed();
-    info!(
-        action = "query",
-        component = component_name,
-        url_count = urls.len(),
-        duration_ms = query_time.as_millis(),
-        "Found URLs to process"
-    );
+impl ChunkTally {
+    fn new(normalizer: &Normalizer, pattern_stats: bool, removed_samples: bool) -> Self {
+        Self {
+            counts: HashMap::new(),
+            removed: Default::default(),
+            pattern_hits: pattern_stats
+                .then(|| crate::stats::PatternHits::new(&normalizer.patterns)),
+            removed_samples: removed_samples.then(Default::default),
+        }
+    }
 
-    let max_workers = max_workers.unwrap_or_else(|| {
-        let cpu_count = num_cpus::get();
-        std::cmp::min(cpu_count, 8)
-    });
+    /// Counts a URL kept out of the rankings, with `sample` as its host or URL
+    fn remove(&mut self, class: HostClass, sample: &str) {
+        self.removed.record(class);
+        if let Some(samples) = &mut self.removed_samples {
+            samples.record(class, sample);
+        }
+    }
 
-    info!(
-        action = "configure",
-        component = component_name,
-        worker_count = max_workers,
-        "Using workers for processing"
-    );
+    fn record(&mut self, domain: String, weight: u32, root: bool) {
+        let tally = self.counts.entry(domain).or_default();
+        tally.0 += weight;
+        if root {
+            tally.1 += weight;
+        }
+    }
 
-    let processing_start = Instant::now();
-
-    // Use Rayon's built-in parallel iterator with automatic work-stealing
-    let batch_stats: Vec<crate::stats::DomainStats> = urls
-        .into_par_iter()
-        .fold(
-            || crate::stats::DomainStats {
-                unique_domains: Vec::new(),
-                domain_counts: std::collections::HashMap::new(),
-                domains_removed: 0,
-            },
-            |mut acc, url_str| {
-                if let Ok(url) = url::Url::parse(&url_str) {
-                    if let Some(host) = url.host_str() {
-                        if !crate::domain::has_valid_tld(host) {
-                            acc.domains_removed += 1;
-                        } else {
-                            let normalized_domain = crate::domain::normalize_domain(host, patterns);
-
-                            if !crate::domain::has_valid_tld(&normalized_domain) {
-                                acc.domains_removed += 1;
-                            } else {
-                                *acc.domain_counts.entry(normalized_domain).or_insert(0) += 1;
-                            }
-                        }
+    #[cfg(feature = "parallel")]
+    fn merge(mut self, other: ChunkTally) -> Self {
+        for (domain, (count, root)) in other.counts {
+            let tally = self.counts.entry(domain).or_default();
+            tally.0 += count;
+            tally.1 += root;
+        }
+        self.removed.merge(other.removed);
+        if let (Some(hits), Some(other_hits)) = (&mut self.pattern_hits, other.pattern_hits) {
+            hits.merge(other_hits);
+        }
+        if let (Some(samples), Some(other_samples)) =
+            (&mut self.removed_samples, other.removed_samples)
+        {
+            samples.merge(other_samples);
+        }
+        self
+    }
+
+    /// Adds the chunk to `stats`, copying a domain only the first time it has root visits
+    fn merge_into(self, stats: &mut crate::stats::DomainStats) {
+        for (domain, (count, root)) in self.counts {
+            if root > 0 {
+                match stats.root_counts.get_mut(&domain) {
+                    Some(total) => *total += root,
+                    None => {
+                        stats.root_counts.insert(domain.clone(), root);
                     }
                 }
-                acc
-            },
-        )
-        .collect();
-
-    // Merge all results from fold operations
-    let mut all_stats = crate::stats::DomainStats {
-        unique_domains: Vec::new(),
-        domain_counts: std::collections::HashMap::new(),
-        domains_removed: 0,
-    };
-
-    for stats in batch_stats {
-        all_stats.unique_domains.extend(stats.unique_domains);
-        for (domain, count) in stats.domain_counts {
-            *all_stats.domain_counts.entry(domain).or_insert(0) += count;
+            }
+            *stats.domain_counts.entry(domain).or_default() += count;
+        }
+        stats.domains_removed.merge(self.removed);
+        match (&mut stats.pattern_hits, self.pattern_hits) {
+            (Some(hits), Some(chunk_hits)) => hits.merge(chunk_hits),
+            (hits @ None, chunk_hits) => *hits = chunk_hits,
+            _ => {}
+        }
+        match (&mut stats.removed_samples, self.removed_samples) {
+            (Some(samples), Some(chunk_samples)) => samples.merge(chunk_samples),
+            (samples @ None, chunk_samples) => *samples = chunk_samples,
+            _ => {}
         }
-        all_stats.domains_removed += stats.domains_removed;
     }
+}
 
-    // Update unique_domains from the final domain_counts
-    all_stats.unique_domains = all_stats.domain_counts.keys().cloned().collect();
+/// A URL with its title and weight under the selected weighting
+#[derive(Debug, Clone)]
+pub struct WeightedUrl {
+    pub url: String,
+    pub title: Option<String>,
+    pub weight: u32,
+}
 
-    let total_processing_time = processing_start.elapsed();
-    let total_time = start_time.elapsed();
-    info!(
-        action = "complete",
-        component = component_name,
-        unique_domains = all_stats.unique_domains.len(),
-        domains_removed = all_stats.domains_removed,
-        "Domain extraction completed"
-    );
-    info!(
-        action = "timing",
-        component = component_name,
-        processing_time_ms = total_processing_time.as_millis(),
-        total_time_ms = total_time.as_millis(),
-        "Domain extraction timing"
-    );
+/// Queries every URL in the visit window with its weight, applying sampling
+fn query_weighted_urls(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+    with_titles: bool,
+) -> Result<Vec<WeightedUrl>> {
+    let mut urls = Vec::new();
+    for_each_weighted_url(conn, schema, options, with_titles, |url| urls.push(url))?;
+    Ok(urls)
+}
+
+/// Streams every URL in the visit window with its weight, applying sampling and the
+/// domain filter; returns how many URLs were passed to `callback`
+fn for_each_weighted_url(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+    with_titles: bool,
+    mut callback: impl FnMut(WeightedUrl),
+) -> Result<usize> {
+    let queries = schema.queries;
+    let title = if with_titles {
+        format!("u.{}", queries.title_column)
+    } else {
+        "NULL".to_string()
+    };
+    let sql = match options.weight {
+        Weight::Visits => format!(
+            "SELECT u.{url_id}, u.{url}, {title}, COUNT(*) FROM {visits} v \
+             JOIN {urls} u ON u.{url_id} = v.{visit_url} \
+             WHERE u.{url} IS NOT NULL{url_filter}{filter} GROUP BY u.{url_id}",
+            url = queries.url_column,
+            urls = queries.urls_table,
+            url_id = queries.url_id_column,
+            visits = queries.visits_table,
+            visit_url = queries.visit_url_column,
+            url_filter = options.visit_filter.url_conditions(schema, "u"),
+            filter = options.visit_filter.sql_conditions(schema, "v")
+        ),
+        Weight::Unique | Weight::VisitCount | Weight::Typed => {
+            let weight_expr = match options.weight {
+                Weight::VisitCount => queries.visit_count_expr,
+                Weight::Typed => queries.typed_count_expr,
+                _ => "1",
+            };
+            // Only unique counting can be windowed; the counters cover all time
+            let filter = if options.visit_filter.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " AND EXISTS (SELECT 1 FROM {visits} v WHERE v.{visit_url} = u.{url_id}{conditions})",
+                    visits = queries.visits_table,
+                    visit_url = queries.visit_url_column,
+                    url_id = queries.url_id_column,
+                    conditions = options.visit_filter.sql_conditions(schema, "v")
+                )
+            };
+            format!(
+                "SELECT id, url, title, weight FROM (\
+                 SELECT u.{url_id} AS id, u.{url} AS url, {title} AS title, {weight_expr} AS weight \
+                 FROM {urls} u WHERE u.{url} IS NOT NULL{url_filter}{filter}) WHERE weight > 0",
+                url = queries.url_column,
+                urls = queries.urls_table,
+                url_id = queries.url_id_column,
+                url_filter = options.visit_filter.url_conditions(schema, "u")
+            )
+        }
+    };
 
-    Ok(all_stats)
+    let mut stmt = conn.prepare(&sql)?;
+    let mut query = stmt.query([])?;
+    let mut count = 0;
+    while let Some(row) = query.next()? {
+        options.cancel.check()?;
+        let id: i64 = row.get(0)?;
+        if options
+            .sampling
+            .is_some_and(|sampling| !sampling.includes(id))
+        {
+            continue;
+        }
+        let url: String = row.get(1)?;
+        if !options.filter.is_empty() && !options.filter.allows(options.domain_of(&url).as_deref())
+        {
+            continue;
+        }
+        callback(WeightedUrl {
+            url,
+            title: row.get(2)?,
+            weight: row.get(3)?,
+        });
+        count += 1;
+    }
+    Ok(count)
 }
 
-pub fn extract_domains_from_urls(
+pub fn extract_domains(
     conn: &Connection,
-    patterns: &[regex::Regex],
-    max_workers: Option<usize>,
+    schema: &Schema,
+    options: &ExtractOptions,
 ) -> Result<crate::stats::DomainStats> {
     let start_time = Instant::now();
     info!(
         action = "start",
         component = "domain_extraction",
+        family = %schema.family,
+        weight = %options.weight,
         "Starting domain extraction from URLs"
     );
 
-    let urls: Vec<String> = conn
-        .prepare("SELECT url FROM urls")?
-        .query_map([], |row| row.get(0))?
-        .collect::<SqliteResult<Vec<String>>>()?;
+    let mut extractor = DomainExtractor::new(options, "domain_extraction")?;
+    if let Some(progress) = options.progress {
+        // The URL table size bounds the rows the extraction query can return
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}", schema.queries.urls_table),
+            [],
+            |row| row.get(0),
+        )?;
+        progress.start(total as u64);
+    }
+    let mut chunk = Vec::with_capacity(EXTRACT_CHUNK_SIZE);
+    let url_count = for_each_weighted_url(conn, schema, options, false, |url| {
+        chunk.push((url.url, url.weight));
+        if chunk.len() == EXTRACT_CHUNK_SIZE {
+            if let Some(progress) = options.progress {
+                progress.inc(EXTRACT_CHUNK_SIZE as u64);
+            }
+            extractor.process(std::mem::replace(
+                &mut chunk,
+                Vec::with_capacity(EXTRACT_CHUNK_SIZE),
+            ));
+        }
+    })?;
+    if let Some(progress) = options.progress {
+        progress.inc(chunk.len() as u64);
+    }
+    extractor.process(chunk);
+    let stats = extractor.finish()?;
 
-    let query_time = start_time.elapsed();
     info!(
-        action = "query",
+        action = "complete",
         component = "domain_extraction",
-        url_count = urls.len(),
-        duration_ms = query_time.as_millis(),
-        "Found URLs to process"
+        url_count,
+        unique_domains = stats.unique_domains(),
+        domains_removed = stats.domains_removed.total(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Domain extraction completed"
     );
+    Ok(stats)
+}
+
+/// Ranks individual pages rather than domains
+pub fn extract_pages(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+) -> Result<crate::stats::PageStats> {
+    let start_time = Instant::now();
+    let mut pages = crate::stats::PageStats::default();
+    for url in query_weighted_urls(conn, schema, options, true)? {
+        let page = crate::domain::strip_userinfo(&url.url).into_owned();
+        pages.record(page, url.title, url.weight);
+    }
 
-    extract_domains_from_urls_generic(urls, patterns, max_workers, "domain_extraction")
+    info!(
+        action = "complete",
+        component = "page_extraction",
+        page_count = pages.pages.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Page extraction completed"
+    );
+    Ok(pages)
 }
 
-pub fn extract_domains_from_firefox_urls(
+/// Counts paths of every URL whose canonical domain is `domain`
+pub fn extract_domain_paths(
     conn: &Connection,
-    patterns: &[regex::Regex],
-    max_workers: Option<usize>,
-) -> Result<crate::stats::DomainStats> {
+    schema: &Schema,
+    options: &ExtractOptions,
+    domain: &str,
+    depth: Option<usize>,
+) -> Result<crate::stats::DomainPaths> {
+    let mut paths = crate::stats::DomainPaths::new(domain.to_string());
+    for weighted in query_weighted_urls(conn, schema, options, false)? {
+        let Ok(url) = url::Url::parse(&weighted.url) else {
+            continue;
+        };
+        let matches = options
+            .normalizer
+            .canonical_url(&url)
+            .is_some_and(|canonical| canonical == domain);
+        if matches {
+            paths.record(
+                &crate::stats::path_prefix(url.path(), depth),
+                weighted.weight,
+            );
+        }
+    }
+    Ok(paths)
+}
+
+/// Counts keywords in the titles of every page, or only of pages on `domain`
+pub fn extract_title_keywords(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+    domain: Option<&str>,
+) -> Result<crate::keywords::TitleKeywords> {
     let start_time = Instant::now();
+    let mut keywords = crate::keywords::TitleKeywords::new(domain.map(str::to_string));
+    for weighted in query_weighted_urls(conn, schema, options, true)? {
+        let Some(title) = weighted.title else {
+            continue;
+        };
+        let Ok(url) = url::Url::parse(&weighted.url) else {
+            continue;
+        };
+        let Some(host) = url.host_str() else {
+            continue;
+        };
+        if let Some(domain) = domain {
+            let canonical = options.normalizer.canonical_url(&url);
+            if canonical.as_deref() != Some(domain) {
+                continue;
+            }
+        }
+        keywords.record(&title, host, weighted.weight);
+    }
+
     info!(
-        action = "start",
-        component = "firefox_domain_extraction",
-        "Starting Firefox domain extraction from URLs"
+        action = "complete",
+        component = "keyword_extraction",
+        keyword_count = keywords.counts.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Title keyword extraction completed"
     );
+    Ok(keywords)
+}
 
-    let urls: Vec<String> = conn
-        .prepare("SELECT url FROM moz_places WHERE url IS NOT NULL")?
-        .query_map([], |row| row.get(0))?
-        .collect::<SqliteResult<Vec<String>>>()?;
+/// Aggregates the browser's downloads by source domain, file type and month
+pub fn extract_downloads(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+) -> Result<crate::downloads::DownloadStats> {
+    let start_time = Instant::now();
+    let mut downloads = crate::downloads::DownloadStats::default();
+    let Some(sql) = schema.queries.downloads_query else {
+        return Ok(downloads);
+    };
+
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        options.cancel.check()?;
+        let started = schema.timestamp_to_datetime(row.get(0)?);
+        let filter = &options.visit_filter;
+        if filter.since.is_some_and(|since| started < since)
+            || filter.until.is_some_and(|until| started >= until)
+        {
+            continue;
+        }
+
+        let bytes: i64 = row.get(1)?;
+        let target_path: String = row.get(2)?;
+        let mime_type: String = row.get(3)?;
+        let source_url: Option<String> = row.get(4)?;
+        let source = source_url.as_deref().and_then(|url| options.domain_of(url));
+        if !options.filter.allows(source.as_deref()) {
+            continue;
+        }
+        let source = source.unwrap_or_else(|| "(unknown)".to_string());
+        downloads.record(
+            &source,
+            &crate::downloads::file_type(&target_path, &mime_type),
+            options.zone.local(started),
+            bytes.max(0) as u64,
+        );
+    }
 
-    let query_time = start_time.elapsed();
     info!(
-        action = "query",
-        component = "firefox_domain_extraction",
-        url_count = urls.len(),
-        duration_ms = query_time.as_millis(),
-        "Found Firefox URLs to process"
+        action = "complete",
+        component = "download_extraction",
+        download_count = downloads.total().downloads,
+        duration_ms = start_time.elapsed().as_millis(),
+        "Download extraction completed"
     );
+    Ok(downloads)
+}
+
+/// Reads the per-day visit counters Chromium keeps for its Top Sites, one row per domain
+/// and day, instead of reconstructing them from individual visits
+pub fn extract_segment_usage(
+    conn: &Connection,
+    schema: &Schema,
+    options: &ExtractOptions,
+) -> Result<crate::segments::SegmentUsage> {
+    let start_time = Instant::now();
+    let mut usage = crate::segments::SegmentUsage::default();
+    let Some(sql) = schema.queries.segment_usage_query else {
+        return Ok(usage);
+    };
+
+    let mut domains: HashMap<String, Option<String>> = HashMap::new();
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([])?;
+    let mut row_count = 0;
+    while let Some(row) = rows.next()? {
+        options.cancel.check()?;
+        let slot = schema.timestamp_to_datetime(row.get(2)?);
+        let filter = &options.visit_filter;
+        if filter.since.is_some_and(|since| slot < since)
+            || filter.until.is_some_and(|until| slot >= until)
+        {
+            continue;
+        }
+        let visits: i64 = row.get(3)?;
+        if visits <= 0 {
+            continue;
+        }
 
-    extract_domains_from_urls_generic(urls, patterns, max_workers, "firefox_domain_extraction")
+        // Segment names are URL-like keys, so fall back to them when the URL row is gone
+        let name: Option<String> = row.get(0)?;
+        let url: Option<String> = row.get(1)?;
+        let Some(url) = url.or(name) else {
+            continue;
+        };
+        let domain = domains
+            .entry(url)
+            .or_insert_with_key(|url| options.domain_of(url));
+        if !options.filter.allows(domain.as_deref()) {
+            continue;
+        }
+        if let Some(domain) = domain {
+            // Time slots are midnight in the browser's own zone, which is the system zone
+            let day = slot.with_timezone(&chrono::Local).date_naive();
+            usage.record(day, domain, visits.min(u32::MAX as i64) as u32);
+            row_count += 1;
+        }
+    }
+
+    info!(
+        action = "complete",
+        component = "segment_usage",
+        rows = row_count,
+        days = usage.days.len(),
+        duration_ms = start_time.elapsed().as_millis(),
+        "Segment usage extraction completed"
+    );
+    Ok(usage)
 }
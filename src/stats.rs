@@ -1,14 +1,1019 @@
-use std::collections::HashMap;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+use crate::anonymize::Anonymizer;
+use crate::args::{Browser, Weight};
+use crate::domain::HostClass;
+use crate::patterns::PatternSet;
+use crate::rdap::DomainAge;
+use crate::schema::Transition;
+use crate::search::SearchTerms;
+use crate::temporal::{
+    DomainFrecency, DomainNovelty, DomainSpans, DomainStreaks, DomainTrends, PeriodBreakdown,
+    PeriodComparison, SessionStats, TemporalStats, TimeSpent,
+};
+
+/// Number of URLs whose host was kept out of the rankings, per host class
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemovedHosts {
+    pub unparseable: u32,
+    pub no_host: u32,
+    pub ip: u32,
+    pub localhost: u32,
+    pub intranet: u32,
+    pub invalid: u32,
+    pub normalized_invalid: u32,
+}
+
+impl RemovedHosts {
+    pub fn record(&mut self, class: HostClass) {
+        self.add(class, 1);
+    }
+
+    pub fn add(&mut self, class: HostClass, count: u32) {
+        *self.count_mut(class) += count;
+    }
+
+    pub fn get(&self, class: HostClass) -> u32 {
+        match class {
+            HostClass::Unparseable => self.unparseable,
+            HostClass::NoHost => self.no_host,
+            HostClass::Ip => self.ip,
+            HostClass::Localhost => self.localhost,
+            HostClass::Intranet => self.intranet,
+            HostClass::Invalid => self.invalid,
+            HostClass::NormalizedInvalid => self.normalized_invalid,
+        }
+    }
+
+    fn count_mut(&mut self, class: HostClass) -> &mut u32 {
+        match class {
+            HostClass::Unparseable => &mut self.unparseable,
+            HostClass::NoHost => &mut self.no_host,
+            HostClass::Ip => &mut self.ip,
+            HostClass::Localhost => &mut self.localhost,
+            HostClass::Intranet => &mut self.intranet,
+            HostClass::Invalid => &mut self.invalid,
+            HostClass::NormalizedInvalid => &mut self.normalized_invalid,
+        }
+    }
+
+    pub fn merge(&mut self, other: RemovedHosts) {
+        for class in HostClass::ALL {
+            *self.count_mut(class) += other.get(class);
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        HostClass::ALL.iter().map(|class| self.get(*class)).sum()
+    }
+}
+
+/// Longest URL kept as a removal sample for hostless or unparseable URLs
+const REMOVED_SAMPLE_LEN: usize = 80;
+
+/// Removed hosts per reason with how many URLs each covered, for `--show-removed`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemovedSamples {
+    /// The host, or the URL itself when there is no usable host
+    pub hosts: HashMap<HostClass, HashMap<String, u32>>,
+}
+
+impl RemovedSamples {
+    pub fn record(&mut self, class: HostClass, sample: &str) {
+        let hosts = self.hosts.entry(class).or_default();
+        match hosts.get_mut(sample) {
+            Some(count) => *count += 1,
+            None => {
+                let sample = crate::utils::truncate(sample, REMOVED_SAMPLE_LEN);
+                *hosts.entry(sample).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: RemovedSamples) {
+        for (class, hosts) in other.hosts {
+            let merged = self.hosts.entry(class).or_default();
+            for (host, count) in hosts {
+                *merged.entry(host).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// The `n` most frequent samples removed for `class`, most frequent first
+    pub fn top(&self, class: HostClass, n: usize) -> Vec<(&str, u32)> {
+        let mut hosts: Vec<(&str, u32)> = self
+            .hosts
+            .get(&class)
+            .into_iter()
+            .flatten()
+            .map(|(host, count)| (host.as_str(), *count))
+            .collect();
+        hosts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        hosts.truncate(n);
+        hosts
+    }
+}
+
+/// Distinct hosts rewritten by each normalization pattern, for `--pattern-stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatternHits {
+    /// Pattern labels in the order they are tried
+    pub patterns: Vec<String>,
+    pub hosts: Vec<HashSet<String>>,
+}
+
+impl PatternHits {
+    pub fn new(patterns: &PatternSet) -> Self {
+        Self {
+            patterns: patterns
+                .rules()
+                .iter()
+                .map(|rule| rule.label().to_string())
+                .collect(),
+            hosts: vec![HashSet::new(); patterns.len()],
+        }
+    }
+
+    pub fn record(&mut self, index: usize, host: &str) {
+        if let Some(hosts) = self.hosts.get_mut(index) {
+            if !hosts.contains(host) {
+                hosts.insert(host.to_string());
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: PatternHits) {
+        for (hosts, other_hosts) in self.hosts.iter_mut().zip(other.hosts) {
+            hosts.extend(other_hosts);
+        }
+    }
+
+    /// Pattern labels with their hit counts, in pattern order
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        self.patterns
+            .iter()
+            .zip(&self.hosts)
+            .map(|(label, hosts)| (label.as_str(), hosts.len()))
+            .collect()
+    }
+}
+
+/// Domains dropped by `--min-visits`, summarized instead of listed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LongTail {
+    pub threshold: u32,
+    pub domains: u32,
+    pub count: u32,
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DomainStats {
-    pub unique_domains: Vec<String>,
     pub domain_counts: HashMap<String, u32>,
-    pub domains_removed: u32,
+    pub domains_removed: RemovedHosts,
+    /// Portion of each domain's count that landed on the site's root path
+    pub root_counts: HashMap<String, u32>,
+    /// Set once domains below a minimum count have been dropped
+    pub long_tail: Option<LongTail>,
+    /// Set when pattern hits are tracked during extraction
+    pub pattern_hits: Option<PatternHits>,
+    /// Set when removed hosts are sampled during extraction
+    pub removed_samples: Option<RemovedSamples>,
+}
+
+impl DomainStats {
+    /// Number of distinct domains counted
+    pub fn unique_domains(&self) -> usize {
+        self.domain_counts.len()
+    }
+
+    /// Adds another set of stats into this one
+    pub fn merge(&mut self, other: DomainStats) {
+        for (domain, count) in other.domain_counts {
+            *self.domain_counts.entry(domain).or_insert(0) += count;
+        }
+        for (domain, count) in other.root_counts {
+            *self.root_counts.entry(domain).or_insert(0) += count;
+        }
+        self.domains_removed.merge(other.domains_removed);
+        match (&mut self.pattern_hits, other.pattern_hits) {
+            (Some(hits), Some(other_hits)) => hits.merge(other_hits),
+            (hits @ None, other_hits) => *hits = other_hits,
+            _ => {}
+        }
+        match (&mut self.removed_samples, other.removed_samples) {
+            (Some(samples), Some(other_samples)) => samples.merge(other_samples),
+            (samples @ None, other_samples) => *samples = other_samples,
+            _ => {}
+        }
+    }
+
+    /// Sum of all domain counts, including domains summarized as the long tail
+    pub fn total(&self) -> u32 {
+        let listed: u32 = self.domain_counts.values().sum();
+        listed + self.long_tail.map_or(0, |long_tail| long_tail.count)
+    }
+
+    /// How spread out visits are across domains; `None` when nothing was counted.
+    /// Domains summarized as the long tail are left out
+    pub fn diversity(&self, top: usize) -> Option<Diversity> {
+        let mut counts: Vec<u32> = self.domain_counts.values().copied().collect();
+        counts.sort_unstable();
+        let total: f64 = counts.iter().map(|count| *count as f64).sum();
+        if total == 0.0 {
+            return None;
+        }
+
+        let entropy: f64 = counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        let n = counts.len() as f64;
+        let weighted: f64 = counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (i + 1) as f64 * *count as f64)
+            .sum();
+        let top = top.min(counts.len());
+        let top_total: f64 = counts.iter().rev().take(top).map(|c| *c as f64).sum();
+        Some(Diversity {
+            entropy,
+            evenness: if counts.len() > 1 {
+                entropy / n.log2()
+            } else {
+                0.0
+            },
+            gini: 2.0 * weighted / (n * total) - (n + 1.0) / n,
+            top,
+            top_share: top_total / total,
+        })
+    }
+
+    /// Domains grouped under their registrable domain, largest group first
+    pub fn grouped(&self) -> Vec<DomainGroup> {
+        let list = crate::psl::suffix_list();
+        let mut groups: HashMap<&str, DomainGroup> = HashMap::new();
+        for (domain, count) in &self.domain_counts {
+            let parent = list.registrable_domain(domain).unwrap_or(domain);
+            let group = groups.entry(parent).or_insert_with(|| DomainGroup {
+                domain: parent.to_string(),
+                count: 0,
+                subdomains: Vec::new(),
+            });
+            group.count += count;
+            group.subdomains.push((domain.clone(), *count));
+        }
+        let mut groups: Vec<DomainGroup> = groups.into_values().collect();
+        for group in &mut groups {
+            group
+                .subdomains
+                .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        }
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(&b.domain)));
+        groups
+    }
+
+    /// Fraction of a domain's count that went to its homepage rather than deep links
+    pub fn root_share(&self, domain: &str) -> Option<f64> {
+        let total = *self.domain_counts.get(domain)?;
+        if total == 0 {
+            return None;
+        }
+        let root = self.root_counts.get(domain).copied().unwrap_or(0);
+        Some(root as f64 / total as f64)
+    }
+
+    /// The `n` domains with the highest counts, highest first
+    /// Number of most visited domains that together reach `share` percent of visits
+    pub fn count_for_share(&self, share: f64) -> usize {
+        count_for_share(
+            self.domain_counts.values().copied().collect(),
+            self.total(),
+            share,
+        )
+    }
+
+    pub fn top(&self, n: usize) -> Vec<(String, u32)> {
+        let mut sorted: Vec<(String, u32)> = self
+            .domain_counts
+            .iter()
+            .map(|(domain, count)| (domain.clone(), *count))
+            .collect();
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sorted.truncate(n);
+        sorted
+    }
+
+    /// The top `n` domains followed by any `pinned` ones ranked below them, each with its
+    /// rank from 1
+    pub fn top_with_pins(&self, n: usize, pinned: &[String]) -> Vec<(usize, String, u32)> {
+        self.top(usize::MAX)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, (domain, _))| *index < n || pinned.contains(domain))
+            .map(|(index, (domain, count))| (index + 1, domain, count))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Page {
+    pub url: String,
+    pub title: Option<String>,
+    pub count: u32,
+}
+
+/// Counts per full URL, for ranking pages instead of domains
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageStats {
+    pub pages: HashMap<String, Page>,
+}
+
+impl PageStats {
+    pub fn record(&mut self, url: String, title: Option<String>, count: u32) {
+        let title = title.filter(|title| !title.trim().is_empty());
+        match self.pages.get_mut(&url) {
+            Some(page) => {
+                page.count += count;
+                if page.title.is_none() {
+                    page.title = title;
+                }
+            }
+            None => {
+                self.pages.insert(url.clone(), Page { url, title, count });
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: PageStats) {
+        for (_, page) in other.pages {
+            self.record(page.url, page.title, page.count);
+        }
+    }
+
+    /// Pages sorted by count, most visited first when `descending`
+    pub fn sorted(&self, descending: bool) -> Vec<&Page> {
+        let mut pages: Vec<&Page> = self.pages.values().collect();
+        pages.sort_by(|a, b| {
+            let by_count = if descending {
+                b.count.cmp(&a.count)
+            } else {
+                a.count.cmp(&b.count)
+            };
+            by_count.then_with(|| a.url.cmp(&b.url))
+        });
+        pages
+    }
+}
+
+/// Path counts for the URLs of a single domain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainPaths {
+    pub domain: String,
+    pub counts: HashMap<String, u32>,
+}
+
+impl DomainPaths {
+    pub fn new(domain: String) -> Self {
+        Self {
+            domain,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, path: &str, count: u32) {
+        match self.counts.get_mut(path) {
+            Some(total) => *total += count,
+            None => {
+                self.counts.insert(path.to_string(), count);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &DomainPaths) {
+        for (path, count) in &other.counts {
+            self.record(path, *count);
+        }
+    }
+
+    /// Most visited paths, ties broken alphabetically
+    pub fn top(&self, n: usize) -> Vec<(String, u32)> {
+        let mut paths: Vec<(String, u32)> = self
+            .counts
+            .iter()
+            .map(|(path, count)| (path.clone(), *count))
+            .collect();
+        paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        paths.truncate(n);
+        paths
+    }
+}
+
+/// Truncates a URL path to its first `depth` segments, e.g. `/r/rust/comments/x` to `/r/rust`
+pub fn path_prefix(path: &str, depth: Option<usize>) -> String {
+    let Some(depth) = depth else {
+        return path.to_string();
+    };
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .take(depth)
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Visit counts per URL scheme (http, https, file, chrome-extension, ...)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchemeCounts {
+    pub counts: HashMap<String, u32>,
+}
+
+impl SchemeCounts {
+    pub fn merge(&mut self, other: &SchemeCounts) {
+        for (scheme, count) in &other.counts {
+            *self.counts.entry(scheme.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Schemes by descending count, ties broken alphabetically
+    pub fn sorted(&self) -> Vec<(String, u32)> {
+        let mut schemes: Vec<(String, u32)> = self
+            .counts
+            .iter()
+            .map(|(scheme, count)| (scheme.clone(), *count))
+            .collect();
+        schemes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        schemes
+    }
+}
+
+/// Visit counts per transition type, overall and per domain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransitionStats {
+    pub overall: [u32; Transition::ALL.len()],
+    pub by_domain: HashMap<String, [u32; Transition::ALL.len()]>,
+}
+
+impl TransitionStats {
+    pub fn record(&mut self, transition: Transition, domain: Option<&str>) {
+        self.overall[transition.index()] += 1;
+        if let Some(domain) = domain {
+            let counts = match self.by_domain.get_mut(domain) {
+                Some(counts) => counts,
+                None => self.by_domain.entry(domain.to_string()).or_default(),
+            };
+            counts[transition.index()] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &TransitionStats) {
+        for (total, count) in self.overall.iter_mut().zip(other.overall) {
+            *total += count;
+        }
+        for (domain, other_counts) in &other.by_domain {
+            let counts = self.by_domain.entry(domain.clone()).or_default();
+            for (total, count) in counts.iter_mut().zip(other_counts) {
+                *total += count;
+            }
+        }
+    }
+
+    /// Non-zero transition counts, most common first
+    pub fn breakdown(counts: &[u32; Transition::ALL.len()]) -> Vec<(Transition, u32)> {
+        let mut breakdown: Vec<(Transition, u32)> = Transition::ALL
+            .iter()
+            .map(|transition| (*transition, counts[transition.index()]))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        breakdown.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        breakdown
+    }
+}
+
+/// Deterministic sampling of history entries
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sampling {
+    pub rate: f64,
+    pub seed: u64,
+}
+
+impl Sampling {
+    /// Whether the entry with the given row id is part of the sample
+    pub fn includes(&self, id: i64) -> bool {
+        // SplitMix64 finalizer, so the decision only depends on the seed and the row id
+        let mut z = self.seed ^ (id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z as f64 / u64::MAX as f64) < self.rate
+    }
+}
+
+/// Normalization rules in effect for a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Normalization {
+    pub fingerprint: String,
+    pub patterns: Vec<String>,
+}
+
+/// First and last visit in the analyzed history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateRange {
+    pub earliest: DateTime<Utc>,
+    pub latest: DateTime<Utc>,
+}
+
+impl DateRange {
+    /// Whole days between the first and last visit
+    pub fn days(&self) -> i64 {
+        (self.latest - self.earliest).num_days()
+    }
+
+    /// The range covering both `self` and `other`
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            earliest: self.earliest.min(other.earliest),
+            latest: self.latest.max(other.latest),
+        }
+    }
+}
+
+/// Most visited domains whose share of visits is reported as the concentration
+pub const CONCENTRATION_TOP: usize = 10;
+
+/// Spread of visits across domains
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diversity {
+    /// Shannon entropy of the domain distribution, in bits
+    pub entropy: f64,
+    /// Entropy relative to visiting every domain equally often, from 0 to 1
+    pub evenness: f64,
+    /// Gini coefficient of the counts; 0 is perfectly even, near 1 is one dominant domain
+    pub gini: f64,
+    /// Number of most visited domains `top_share` covers
+    pub top: usize,
+    pub top_share: f64,
+}
+
+/// A registrable domain with the counted domains beneath it, itself included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainGroup {
+    pub domain: String,
+    pub count: u32,
+    /// Domains in the group by descending count
+    pub subdomains: Vec<(String, u32)>,
+}
+
+/// Number of `counts`, taken largest first, that together reach `share` percent of
+/// `total`; all of them when they never do
+pub fn count_for_share(mut counts: Vec<u32>, total: u32, share: f64) -> usize {
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    let needed = total as f64 * share / 100.0;
+    let mut covered = 0u64;
+    for (i, count) in counts.iter().enumerate() {
+        covered += *count as u64;
+        if covered as f64 >= needed {
+            return i + 1;
+        }
+    }
+    counts.len()
+}
+
+/// How much of a browser's history its visits table still holds. Chromium drops visits
+/// after about 90 days but keeps counting them in each URL's all-time `visit_count`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Retention {
+    /// Sum of the per-URL all-time visit counters
+    pub all_time: u64,
+    /// Visits still recorded one by one
+    pub recorded: u64,
+    /// Per-domain counts from the all-time counters, collected with `--retention`
+    pub all_time_counts: Option<HashMap<String, u32>>,
+}
+
+impl Retention {
+    /// How far the all-time counters run ahead of the recorded visits, as a percentage of
+    /// the recorded visits
+    pub fn excess_percent(&self) -> f64 {
+        let excess = self.all_time.saturating_sub(self.recorded);
+        match (excess, self.recorded) {
+            (0, _) => 0.0,
+            (_, 0) => f64::INFINITY,
+            (excess, recorded) => excess as f64 * 100.0 / recorded as f64,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Retention) {
+        self.all_time += other.all_time;
+        self.recorded += other.recorded;
+        if let Some(other_counts) = &other.all_time_counts {
+            let counts = self.all_time_counts.get_or_insert_with(HashMap::new);
+            for (domain, count) in other_counts {
+                *counts.entry(domain.clone()).or_insert(0) += count;
+            }
+        }
+    }
+}
+
+/// Visit and URL totals for the summary header, with visits per local day
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VisitTotals {
+    pub visits: u32,
+    /// Distinct URLs visited; summed across browsers, since each keeps its own URL table
+    pub unique_urls: u32,
+    pub daily: BTreeMap<NaiveDate, u32>,
+}
+
+impl VisitTotals {
+    pub fn merge(&mut self, other: &VisitTotals) {
+        self.visits += other.visits;
+        self.unique_urls += other.unique_urls;
+        for (day, visits) in &other.daily {
+            *self.daily.entry(*day).or_insert(0) += visits;
+        }
+    }
+
+    /// The day with the most visits; ties go to the earliest day
+    pub fn busiest_day(&self) -> Option<(NaiveDate, u32)> {
+        self.daily
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(day, visits)| (*day, *visits))
+    }
+
+    /// Visits per calendar day from the first to the last day with visits
+    pub fn average_per_day(&self) -> f64 {
+        match (self.daily.keys().next(), self.daily.keys().next_back()) {
+            (Some(first), Some(last)) => {
+                self.visits as f64 / ((*last - *first).num_days() + 1) as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// How a result was produced, so that runs can be compared and reproduced
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    pub generated_at: DateTime<Utc>,
+    pub version: String,
+    pub weight: Weight,
+    pub sampling: Option<Sampling>,
+    pub normalization: Normalization,
+}
+
+impl Provenance {
+    /// Whether domain counts from two runs can be compared directly
+    pub fn is_comparable_with(&self, other: &Provenance) -> bool {
+        self.normalization.fingerprint == other.normalization.fingerprint
+            && self.weight == other.weight
+    }
+}
+
+/// A browser left out of a multi-browser run, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedBrowser {
+    pub browser: Browser,
+    /// Whether the browser simply has no history database on this machine
+    pub not_installed: bool,
+    pub reason: String,
+}
+
+/// Wall-clock duration of each analysis phase, in the order the phases ran
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timings {
+    pub phases: Vec<(String, Duration)>,
+}
+
+impl Timings {
+    /// Records the time elapsed since `start` under `phase`
+    pub fn record(&mut self, phase: &str, start: Instant) {
+        self.phases.push((phase.to_string(), start.elapsed()));
+    }
+
+    /// Runs `f` and records how long it took under `phase`
+    pub fn time<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let value = f();
+        self.record(phase, start);
+        value
+    }
+
+    /// Appends the phases of another run, labelled with `prefix`
+    pub fn extend_prefixed(&mut self, prefix: &str, other: Timings) {
+        self.phases.extend(
+            other
+                .phases
+                .into_iter()
+                .map(|(phase, duration)| (format!("{prefix}: {phase}"), duration)),
+        );
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, _)| phase.chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "Timings:")?;
+        for (phase, duration) in &self.phases {
+            writeln!(
+                f,
+                "  {phase:<width$}  {:>9.1} ms",
+                duration.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnalysisResult {
-    pub date_range: (String, String, i64),
+    /// `None` when no visits matched
+    pub date_range: Option<DateRange>,
+    /// Visit totals for the summary header; `None` when they could not be read
+    pub totals: Option<VisitTotals>,
+    /// Kept visits against the all-time counters; `None` when they could not be read
+    pub retention: Option<Retention>,
     pub stats: DomainStats,
+    pub provenance: Provenance,
+    pub domain_ages: Vec<DomainAge>,
+    pub temporal: Option<TemporalStats>,
+    pub periods: Option<PeriodBreakdown>,
+    pub trends: Option<DomainTrends>,
+    /// Domain visits in the two windows compared with `--compare-periods`
+    pub period_comparison: Option<PeriodComparison>,
+    pub streaks: Option<DomainStreaks>,
+    pub novelty: Option<DomainNovelty>,
+    /// First and last visits per domain, collected for `--sort first-visit|last-visit`
+    pub spans: Option<DomainSpans>,
+    pub sessions: Option<SessionStats>,
+    pub time_spent: Option<TimeSpent>,
+    pub transitions: Option<TransitionStats>,
+    pub searches: Option<SearchTerms>,
+    /// Per-page counts, collected with `--by urls`
+    pub pages: Option<PageStats>,
+    /// Path breakdown of the domain selected with `--domain`
+    pub paths: Option<DomainPaths>,
+    /// Visits per URL scheme, collected with `--scheme-stats`
+    pub schemes: Option<SchemeCounts>,
+    /// Downloads breakdown, collected with `--downloads`
+    pub downloads: Option<crate::downloads::DownloadStats>,
+    /// Per-day visits per domain from Top Sites counters, collected with `--segments`
+    pub segments: Option<crate::segments::SegmentUsage>,
+    /// Watch time per domain from Chromium's Media History, collected with `--media`
+    pub media: Option<crate::media::MediaStats>,
+    /// Domain-to-domain navigations, collected with `--nav-graph`
+    pub navigation: Option<crate::navigation::NavigationGraph>,
+    /// Domains leading to the one selected with `--referrers`
+    pub referrers: Option<crate::navigation::DomainReferrers>,
+    /// Page-title keyword counts, collected with `--keywords`
+    pub keywords: Option<crate::keywords::TitleKeywords>,
+    /// Visited domains found in `--blocklist` files
+    pub blocked: Option<crate::blocklist::BlockedStats>,
+    /// Domains from the pin list, listed with their rank even outside `--top`
+    pub pinned: Vec<String>,
+    /// Counts summed per category, collected with `--categories`
+    pub categories: Option<crate::categories::CategoryBreakdown>,
+    /// Visits per category and hour of day, collected with `--category-hours`
+    pub category_hours: Option<crate::categories::CategoryHours>,
+    /// Counts per top-level domain, set with `--by tld`
+    pub tlds: Option<crate::tld::TldBreakdown>,
+    /// Recency-weighted scores, collected for renderers that show visit intensity
+    pub frecency: Option<DomainFrecency>,
+    /// Sections added by custom analyses
+    pub reports: Vec<crate::analysis::Report>,
+    /// Failures skipped in `--best-effort` mode
+    pub warnings: Vec<String>,
+    /// Browsers left out of an `--all-browsers` run
+    pub skipped_browsers: Vec<SkippedBrowser>,
+    /// Phase durations, collected with `--timings`
+    pub timings: Option<Timings>,
+}
+
+impl AnalysisResult {
+    /// Drops domains counted fewer than `threshold` times from every per-domain breakdown,
+    /// keeping a summary of what was dropped in `stats.long_tail`
+    pub fn apply_min_visits(&mut self, threshold: u32) {
+        let dropped: HashSet<String> = self
+            .stats
+            .domain_counts
+            .iter()
+            .filter(|(_, count)| **count < threshold)
+            .map(|(domain, _)| domain.clone())
+            .collect();
+        let count = dropped
+            .iter()
+            .map(|domain| self.stats.domain_counts[domain])
+            .sum();
+        self.stats.long_tail = Some(LongTail {
+            threshold,
+            domains: dropped.len() as u32,
+            count,
+        });
+        self.remove_domains(&dropped);
+    }
+
+    /// Replaces every domain, URL, path and term in the result with its pseudonym and drops
+    /// page titles, so no output format discloses what was visited
+    pub fn anonymize(&mut self, anonymizer: &Anonymizer) {
+        let domain = |domain: &str| anonymizer.domain(domain);
+        rename_keys(&mut self.stats.domain_counts, domain);
+        rename_keys(&mut self.stats.root_counts, domain);
+        if let Some(counts) = self
+            .retention
+            .as_mut()
+            .and_then(|retention| retention.all_time_counts.as_mut())
+        {
+            rename_keys(counts, domain);
+        }
+        if let Some(hits) = &mut self.stats.pattern_hits {
+            for hosts in &mut hits.hosts {
+                *hosts = hosts.iter().map(|host| domain(host)).collect();
+            }
+        }
+        if let Some(samples) = &mut self.stats.removed_samples {
+            for (class, hosts) in &mut samples.hosts {
+                match class {
+                    HostClass::Unparseable | HostClass::NoHost => {
+                        rename_keys(hosts, |url| anonymizer.url(url))
+                    }
+                    _ => rename_keys(hosts, domain),
+                }
+            }
+        }
+        if let Some(periods) = &mut self.periods {
+            for bucket in periods.buckets.values_mut() {
+                bucket.domains = bucket.domains.iter().map(|d| domain(d)).collect();
+            }
+        }
+        if let Some(comparison) = &mut self.period_comparison {
+            rename_keys(&mut comparison.recent, domain);
+            rename_keys(&mut comparison.previous, domain);
+        }
+        if let Some(trends) = &mut self.trends {
+            rename_keys(&mut trends.monthly, domain);
+        }
+        if let Some(streaks) = &mut self.streaks {
+            rename_keys(&mut streaks.days, domain);
+        }
+        if let Some(novelty) = &mut self.novelty {
+            rename_keys(&mut novelty.months, domain);
+        }
+        if let Some(spans) = &mut self.spans {
+            rename_keys(&mut spans.spans, domain);
+        }
+        if let Some(time_spent) = &mut self.time_spent {
+            rename_keys(&mut time_spent.by_domain, domain);
+        }
+        if let Some(transitions) = &mut self.transitions {
+            rename_keys(&mut transitions.by_domain, domain);
+        }
+        if let Some(frecency) = &mut self.frecency {
+            rename_keys(&mut frecency.scores, domain);
+        }
+        for pinned in &mut self.pinned {
+            *pinned = domain(pinned);
+        }
+        if let Some(blocked) = &mut self.blocked {
+            rename_keys(&mut blocked.domain_counts, domain);
+        }
+        if let Some(segments) = &mut self.segments {
+            for domains in segments.days.values_mut() {
+                rename_keys(domains, domain);
+            }
+        }
+        if let Some(media) = &mut self.media {
+            rename_keys(&mut media.by_domain, domain);
+        }
+        if let Some(referrers) = &mut self.referrers {
+            referrers.domain = domain(&referrers.domain);
+            for (referrer, _) in &mut referrers.referrers {
+                *referrer = domain(referrer);
+            }
+        }
+        if let Some(navigation) = &mut self.navigation {
+            navigation.rename(domain);
+        }
+        if let Some(downloads) = &mut self.downloads {
+            rename_keys(&mut downloads.sources, domain);
+        }
+        if let Some(pages) = &mut self.pages {
+            pages.pages = pages
+                .pages
+                .drain()
+                .map(|(url, page)| {
+                    let url = anonymizer.url(&url);
+                    let page = Page {
+                        url: url.clone(),
+                        title: None,
+                        count: page.count,
+                    };
+                    (url, page)
+                })
+                .collect();
+        }
+        if let Some(paths) = &mut self.paths {
+            paths.domain = domain(&paths.domain);
+            rename_keys(&mut paths.counts, |path| {
+                format!("/{}", anonymizer.token(path))
+            });
+        }
+        if let Some(searches) = &mut self.searches {
+            rename_keys(&mut searches.counts, |term| anonymizer.token(term));
+        }
+        if let Some(keywords) = &mut self.keywords {
+            keywords.domain = keywords.domain.as_deref().map(domain);
+            rename_keys(&mut keywords.counts, |word| anonymizer.token(word));
+        }
+        for age in &mut self.domain_ages {
+            age.domain = domain(&age.domain);
+        }
+    }
+
+    /// Removes `dropped` domains from the rankings and every per-domain breakdown
+    pub fn remove_domains(&mut self, dropped: &HashSet<String>) {
+        let keep = |domain: &String| !dropped.contains(domain);
+        self.stats.domain_counts.retain(|domain, _| keep(domain));
+        self.stats.root_counts.retain(|domain, _| keep(domain));
+        if let Some(counts) = self
+            .retention
+            .as_mut()
+            .and_then(|retention| retention.all_time_counts.as_mut())
+        {
+            counts.retain(|domain, _| keep(domain));
+        }
+        if let Some(comparison) = &mut self.period_comparison {
+            comparison.recent.retain(|domain, _| keep(domain));
+            comparison.previous.retain(|domain, _| keep(domain));
+        }
+        if let Some(trends) = &mut self.trends {
+            trends.monthly.retain(|domain, _| keep(domain));
+        }
+        if let Some(streaks) = &mut self.streaks {
+            streaks.days.retain(|domain, _| keep(domain));
+        }
+        if let Some(novelty) = &mut self.novelty {
+            novelty.months.retain(|domain, _| keep(domain));
+        }
+        if let Some(spans) = &mut self.spans {
+            spans.spans.retain(|domain, _| keep(domain));
+        }
+        if let Some(time_spent) = &mut self.time_spent {
+            time_spent.by_domain.retain(|domain, _| keep(domain));
+        }
+        if let Some(transitions) = &mut self.transitions {
+            transitions.by_domain.retain(|domain, _| keep(domain));
+        }
+        if let Some(frecency) = &mut self.frecency {
+            frecency.scores.retain(|domain, _| keep(domain));
+        }
+        if let Some(segments) = &mut self.segments {
+            segments.remove_domains(dropped);
+        }
+        if let Some(media) = &mut self.media {
+            media.remove_domains(dropped);
+        }
+        if let Some(navigation) = &mut self.navigation {
+            navigation.remove_domains(dropped);
+        }
+        self.domain_ages.retain(|age| keep(&age.domain));
+    }
+}
+
+/// Re-keys `map` through `rename`, keeping one entry per distinct new key
+fn rename_keys<V>(map: &mut HashMap<String, V>, rename: impl Fn(&str) -> String) {
+    *map = map
+        .drain()
+        .map(|(key, value)| (rename(&key), value))
+        .collect();
 }
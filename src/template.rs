@@ -0,0 +1,587 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::args::Args;
+use crate::json::Value;
+use crate::report::{self, RenderOptions, Renderer};
+use crate::stats::AnalysisResult;
+use crate::utils;
+
+/// Domains in the `domains` list when `--top` is not given
+const DEFAULT_TEMPLATE_TOP: usize = 50;
+
+/// Parsed `--template` file: a Handlebars-style subset with `{{value}}`, `{{{raw}}}`,
+/// `{{#each list}}`, `{{#if value}}`, `{{#unless value}}`, `{{else}}` and `{{! comments }}`
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// Whether `{{value}}` escapes HTML, which it does in `.html` and `.htm` templates
+    escape: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Value { path: String, raw: bool },
+    Block(Block),
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    path: String,
+    body: Vec<Node>,
+    otherwise: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Each,
+    If,
+    Unless,
+}
+
+impl BlockKind {
+    fn name(self) -> &'static str {
+        match self {
+            BlockKind::Each => "each",
+            BlockKind::If => "if",
+            BlockKind::Unless => "unless",
+        }
+    }
+}
+
+/// A `{{...}}` tag or the text between tags, with the line it starts on
+enum Token<'a> {
+    Text(&'a str),
+    Tag {
+        inner: &'a str,
+        raw: bool,
+        line: usize,
+    },
+}
+
+impl Token<'_> {
+    /// Block and comment tags vanish along with their line when nothing else is on it
+    fn standalone(&self) -> bool {
+        matches!(self, Token::Tag { inner, raw: false, .. }
+            if inner.starts_with(['#', '/', '!']) || *inner == "else")
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let line = source[..source.len() - rest.len() + start]
+            .matches('\n')
+            .count()
+            + 1;
+        let after = &rest[start..];
+        let (raw, open, close) = if after.starts_with("{{{") {
+            (true, 3, "}}}")
+        } else {
+            (false, 2, "}}")
+        };
+        let Some(end) = after[open..].find(close) else {
+            anyhow::bail!("line {line}: unclosed {}", &after[..open]);
+        };
+        tokens.push(Token::Tag {
+            inner: after[open..open + end].trim(),
+            raw,
+            line,
+        });
+        rest = &after[open + end + close.len()..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    strip_standalone(&mut tokens);
+    Ok(tokens)
+}
+
+/// Drops the indentation before and the line break after tags that stand alone on a line,
+/// so block tags don't leave blank lines in the output
+fn strip_standalone(tokens: &mut [Token<'_>]) {
+    let line_start = |text: &str, first: bool| {
+        (text.contains('\n') || first)
+            && text
+                .rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .is_empty()
+    };
+    let line_end = |text: &str, last: bool| {
+        (text.contains('\n') || last)
+            && text
+                .split('\n')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .is_empty()
+    };
+    // Decide on the original text first, since stripping one tag's line break must not
+    // make its neighbour look like it shares the line
+    let standalone: Vec<bool> = (0..tokens.len())
+        .map(|i| {
+            let before = match i.checked_sub(1).map(|j| &tokens[j]) {
+                None => true,
+                Some(Token::Text(text)) => line_start(text, i == 1),
+                Some(Token::Tag { .. }) => false,
+            };
+            let after = match tokens.get(i + 1) {
+                None => true,
+                Some(Token::Text(text)) => line_end(text, i + 2 == tokens.len()),
+                Some(Token::Tag { .. }) => false,
+            };
+            tokens[i].standalone() && before && after
+        })
+        .collect();
+    for (i, standalone) in standalone.into_iter().enumerate() {
+        if !standalone {
+            continue;
+        }
+        if let Some(Token::Text(text)) = i.checked_sub(1).map(|j| &mut tokens[j]) {
+            *text = &text[..text.rfind('\n').map_or(0, |newline| newline + 1)];
+        }
+        if let Some(Token::Text(text)) = tokens.get_mut(i + 1) {
+            *text = text.find('\n').map_or("", |newline| &text[newline + 1..]);
+        }
+    }
+}
+
+/// Parses tokens up to the closing tag of `open`, or to the end for the top level,
+/// returning the nodes before and after an `{{else}}`
+fn parse_nodes<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+    open: Option<(BlockKind, usize)>,
+) -> Result<(Vec<Node>, Vec<Node>)> {
+    let mut body = Vec::new();
+    let mut otherwise = None;
+    while let Some(token) = tokens.next() {
+        let nodes = otherwise.as_mut().unwrap_or(&mut body);
+        let (inner, raw, line) = match token {
+            Token::Text(text) => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text.to_string()));
+                }
+                continue;
+            }
+            Token::Tag { inner, raw, line } => (inner, raw, line),
+        };
+        if raw {
+            nodes.push(Node::Value {
+                path: inner.to_string(),
+                raw: true,
+            });
+        } else if inner.starts_with('!') {
+            continue;
+        } else if inner == "else" {
+            if open.is_none() || otherwise.is_some() {
+                anyhow::bail!("line {line}: unexpected {{{{else}}}}");
+            }
+            otherwise = Some(Vec::new());
+        } else if let Some(close) = inner.strip_prefix('/') {
+            return match open {
+                Some((kind, _)) if close.trim() == kind.name() => {
+                    Ok((body, otherwise.unwrap_or_default()))
+                }
+                Some((kind, opened)) => anyhow::bail!(
+                    "line {line}: {{{{/{}}}}} does not close the {{{{#{}}}}} from line {opened}",
+                    close.trim(),
+                    kind.name()
+                ),
+                None => anyhow::bail!("line {line}: unexpected {{{{/{}}}}}", close.trim()),
+            };
+        } else if let Some(block) = inner.strip_prefix('#') {
+            let (name, path) = block.split_once(char::is_whitespace).unwrap_or((block, ""));
+            let kind = match name {
+                "each" => BlockKind::Each,
+                "if" => BlockKind::If,
+                "unless" => BlockKind::Unless,
+                _ => anyhow::bail!("line {line}: unknown block {{{{#{name}}}}}"),
+            };
+            let path = path.trim();
+            if path.is_empty() {
+                anyhow::bail!("line {line}: {{{{#{name}}}}} needs a value");
+            }
+            let (block_body, block_otherwise) = parse_nodes(tokens, Some((kind, line)))?;
+            nodes.push(Node::Block(Block {
+                kind,
+                path: path.to_string(),
+                body: block_body,
+                otherwise: block_otherwise,
+            }));
+        } else {
+            nodes.push(Node::Value {
+                path: inner.to_string(),
+                raw: false,
+            });
+        }
+    }
+    match open {
+        Some((kind, line)) => {
+            anyhow::bail!("line {line}: {{{{#{}}}}} is never closed", kind.name())
+        }
+        None => Ok((body, otherwise.unwrap_or_default())),
+    }
+}
+
+/// A value in scope while rendering, with its position when it is an `{{#each}}` item
+struct Frame<'a> {
+    value: &'a Value,
+    index: Option<(usize, usize)>,
+    key: Option<&'a str>,
+}
+
+/// Value at a dotted path such as `domain` or `domains.0.count`
+fn resolve<'a>(scopes: &[Frame<'a>], path: &str) -> Option<&'a Value> {
+    let frame = scopes.last()?;
+    if path == "this" || path == "." {
+        return Some(frame.value);
+    }
+    let path = path.strip_prefix("this.").unwrap_or(path);
+    let mut parts = path.split('.');
+    let first = parts.next()?;
+    // Names resolve in the innermost scope that has them, so loops can reach outer values
+    let mut value = scopes
+        .iter()
+        .rev()
+        .find_map(|frame| frame.value.get(first))?;
+    for part in parts {
+        value = match value {
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => value.get(part)?,
+        };
+    }
+    Some(value)
+}
+
+/// Like `resolve`, also answering `@index`, `@number`, `@first`, `@last` and `@key` inside
+/// `{{#each}}`
+fn lookup(scopes: &[Frame<'_>], path: &str) -> Option<Value> {
+    let frame = scopes.last()?;
+    match path {
+        "@index" => frame.index.map(|(index, _)| index.into()),
+        "@number" => frame.index.map(|(index, _)| (index + 1).into()),
+        "@first" => frame.index.map(|(index, _)| (index == 0).into()),
+        "@last" => frame.index.map(|(index, len)| (index + 1 == len).into()),
+        "@key" => frame.key.map(Value::from),
+        path => resolve(scopes, path).cloned(),
+    }
+}
+
+fn truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::Int(n)) => *n != 0,
+        Some(Value::Float(n)) => *n != 0.0,
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Object(entries)) => !entries.is_empty(),
+        Some(Value::Bool(true)) => true,
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Float(n) => format!("{}", (n * 100.0).round() / 100.0),
+        value => value.to_string(),
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str, escape: bool) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let (nodes, _) = parse_nodes(&mut tokens.into_iter(), None)?;
+        Ok(Self { nodes, escape })
+    }
+
+    /// Reads and parses a template file; `.html` and `.htm` files escape `{{values}}`
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {path:?}"))?;
+        let escape = path
+            .extension()
+            .is_some_and(|extension| extension == "html" || extension == "htm");
+        Self::parse(&source, escape).with_context(|| format!("Invalid template {path:?}"))
+    }
+
+    pub fn render(&self, data: &Value) -> String {
+        let mut out = String::new();
+        let mut scopes = vec![Frame {
+            value: data,
+            index: None,
+            key: None,
+        }];
+        self.render_nodes(&self.nodes, &mut scopes, &mut out);
+        out
+    }
+
+    fn render_nodes<'a>(&self, nodes: &[Node], scopes: &mut Vec<Frame<'a>>, out: &mut String) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Value { path, raw } => {
+                    let value = lookup(scopes, path)
+                        .map(|value| text(&value))
+                        .unwrap_or_default();
+                    if self.escape && !raw {
+                        out.push_str(&report::escape_html(&value));
+                    } else {
+                        out.push_str(&value);
+                    }
+                }
+                Node::Block(block) => self.render_block(block, scopes, out),
+            }
+        }
+    }
+
+    fn render_block<'a>(&self, block: &Block, scopes: &mut Vec<Frame<'a>>, out: &mut String) {
+        let shown = truthy(lookup(scopes, &block.path).as_ref());
+        match block.kind {
+            BlockKind::If | BlockKind::Unless => {
+                let nodes = if shown == (block.kind == BlockKind::If) {
+                    &block.body
+                } else {
+                    &block.otherwise
+                };
+                self.render_nodes(nodes, scopes, out);
+            }
+            BlockKind::Each => {
+                let items: Vec<(Option<&'a str>, &'a Value)> = match resolve(scopes, &block.path) {
+                    _ if !shown => Vec::new(),
+                    Some(Value::Array(items)) => items.iter().map(|item| (None, item)).collect(),
+                    Some(Value::Object(entries)) => entries
+                        .iter()
+                        .map(|(key, item)| (Some(key.as_str()), item))
+                        .collect(),
+                    Some(value) => vec![(None, value)],
+                    None => Vec::new(),
+                };
+                if items.is_empty() {
+                    self.render_nodes(&block.otherwise, scopes, out);
+                }
+                let len = items.len();
+                for (index, (key, value)) in items.into_iter().enumerate() {
+                    scopes.push(Frame {
+                        value,
+                        index: Some((index, len)),
+                        key,
+                    });
+                    self.render_nodes(&block.body, scopes, out);
+                    scopes.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Renders the analysis through a `--template` file
+pub struct TemplateRenderer {
+    pub template: Template,
+}
+
+impl Renderer for TemplateRenderer {
+    fn render(&self, result: &AnalysisResult, options: &RenderOptions) -> String {
+        self.template.render(&context(result, options.args()))
+    }
+}
+
+/// A percentage to one decimal place, as the text report shows them
+fn rounded(percent: f64) -> f64 {
+    (percent * 10.0).round() / 10.0
+}
+
+/// The values a template can use: the summary fields plus lists for each collected analysis
+pub fn context(result: &AnalysisResult, args: &Args) -> Value {
+    let total = result.stats.total();
+    let domains: Vec<Value> = result
+        .stats
+        .top(args.top.unwrap_or(DEFAULT_TEMPLATE_TOP))
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (domain, count))| {
+            Value::object()
+                .with("rank", rank + 1)
+                .with("domain", utils::display_domain(&domain, args))
+                .with("count", count)
+                .with("visits", utils::format_count(count, args.humanize))
+                .with("percent", rounded(utils::percent(count, total)))
+        })
+        .collect();
+    let mut data = report::summary(result, args)
+        .with("version", result.provenance.version.to_string())
+        .with("domains", domains);
+
+    if let Some(periods) = &result.periods {
+        let series: Vec<Value> = periods
+            .series()
+            .into_iter()
+            .map(|(period, visits)| {
+                Value::object()
+                    .with("period", period)
+                    .with("visits", visits)
+            })
+            .collect();
+        data = data.with("periods", series);
+    }
+    if let Some(categories) = &result.categories {
+        let total = categories.total();
+        let sorted: Vec<Value> = categories
+            .sorted()
+            .into_iter()
+            .map(|(category, count)| {
+                Value::object()
+                    .with("category", category)
+                    .with("count", count)
+                    .with("percent", rounded(utils::percent(count, total)))
+            })
+            .collect();
+        data = data.with("categories", sorted);
+    }
+    if let Some(searches) = &result.searches {
+        let top: Vec<Value> = searches
+            .top(args.top.unwrap_or(10))
+            .into_iter()
+            .map(|(term, count)| Value::object().with("term", term).with("count", count))
+            .collect();
+        data = data.with("searches", top);
+    }
+    if let Some(time_spent) = &result.time_spent {
+        let top: Vec<Value> = time_spent
+            .top(args.top.unwrap_or(10))
+            .into_iter()
+            .map(|(domain, duration)| {
+                Value::object()
+                    .with("domain", utils::display_domain(&domain, args))
+                    .with("duration", utils::format_duration(duration))
+                    .with("seconds", duration.num_seconds())
+            })
+            .collect();
+        data = data.with("time_spent", top);
+    }
+    if let Some(media) = &result.media {
+        let top: Vec<Value> = media
+            .top(args.top.unwrap_or(10))
+            .into_iter()
+            .map(|(domain, time)| {
+                Value::object()
+                    .with("domain", utils::display_domain(domain, args))
+                    .with("watch_time", utils::format_duration(time.watch_time()))
+                    .with("seconds", time.watch_seconds as i64)
+                    .with("playbacks", time.playbacks)
+            })
+            .collect();
+        data = data.with("media", top);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, data: &Value) -> String {
+        Template::parse(source, false).unwrap().render(data)
+    }
+
+    fn parse_error(source: &str) -> String {
+        Template::parse(source, false).unwrap_err().to_string()
+    }
+
+    fn sites() -> Value {
+        Value::object()
+            .with("site", "home")
+            .with("items", vec!["a", "b"])
+            .with("empty", Vec::<&str>::new())
+    }
+
+    #[test]
+    fn values_resolve_dotted_paths_and_missing_ones_render_empty() {
+        let data = Value::object()
+            .with("name", "report")
+            .with("top", Value::object().with("count", 3u32))
+            .with("list", vec!["x", "y"])
+            .with("share", 12.3456)
+            .with("gone", Value::Null);
+        assert_eq!(
+            render(
+                "{{name}} {{top.count}} {{list.1}} {{share}} [{{missing}}{{gone}}{{! note }}]",
+                &data
+            ),
+            "report 3 y 12.35 []"
+        );
+    }
+
+    #[test]
+    fn html_templates_escape_values_unless_triple_braced() {
+        let data = Value::object().with("value", "<b>&");
+        let source = "{{value}} {{{value}}}";
+        let escaped = Template::parse(source, true).unwrap().render(&data);
+        assert_eq!(escaped, "&lt;b&gt;&amp; <b>&");
+        assert_eq!(render(source, &data), "<b>& <b>&");
+    }
+
+    #[test]
+    fn each_exposes_position_and_outer_scopes() {
+        assert_eq!(
+            render(
+                "{{#each items}}{{@number}}.{{this}}@{{site}}{{#unless @last}},{{/unless}}{{/each}}",
+                &sites()
+            ),
+            "1.a@home,2.b@home"
+        );
+        let data = Value::object().with("counts", Value::object().with("a", 1u32).with("b", 2u32));
+        assert_eq!(
+            render("{{#each counts}}{{@key}}={{this}};{{/each}}", &data),
+            "a=1;b=2;"
+        );
+    }
+
+    #[test]
+    fn else_branches_render_for_falsy_values() {
+        let data = sites();
+        assert_eq!(render("{{#if site}}yes{{else}}no{{/if}}", &data), "yes");
+        assert_eq!(render("{{#if missing}}yes{{else}}no{{/if}}", &data), "no");
+        assert_eq!(render("{{#unless empty}}none{{/unless}}", &data), "none");
+        assert_eq!(
+            render("{{#each empty}}x{{else}}none{{/each}}", &data),
+            "none"
+        );
+    }
+
+    #[test]
+    fn standalone_block_tags_drop_their_lines() {
+        let source = "Items:\n  {{#each items}}\n- {{this}}\n  {{/each}}\n{{! end }}\ndone\n";
+        assert_eq!(render(source, &sites()), "Items:\n- a\n- b\ndone\n");
+    }
+
+    #[test]
+    fn malformed_templates_report_the_line() {
+        assert_eq!(parse_error("a\n{{name"), "line 2: unclosed {{");
+        assert_eq!(
+            parse_error("{{#if a}}\n"),
+            "line 1: {{#if}} is never closed"
+        );
+        assert_eq!(
+            parse_error("{{#if a}}\n{{/each}}"),
+            "line 2: {{/each}} does not close the {{#if}} from line 1"
+        );
+        assert_eq!(parse_error("{{else}}"), "line 1: unexpected {{else}}");
+        assert_eq!(
+            parse_error("{{#with a}}{{/with}}"),
+            "line 1: unknown block {{#with}}"
+        );
+        assert_eq!(
+            parse_error("{{#each}}{{/each}}"),
+            "line 1: {{#each}} needs a value"
+        );
+    }
+}
@@ -0,0 +1,816 @@
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc,
+    Weekday,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::args::Period;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Visit counts bucketed by hour of day and day of week
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TemporalStats {
+    pub by_hour: [u32; 24],
+    /// Indexed from Monday (0) to Sunday (6)
+    pub by_weekday: [u32; 7],
+}
+
+impl TemporalStats {
+    /// Counts a visit at `timestamp`, a wall-clock time in the report's zone
+    pub fn record(&mut self, timestamp: NaiveDateTime) {
+        self.by_hour[timestamp.hour() as usize] += 1;
+        self.by_weekday[timestamp.weekday().num_days_from_monday() as usize] += 1;
+    }
+
+    pub fn merge(&mut self, other: &TemporalStats) {
+        for (total, count) in self.by_hour.iter_mut().zip(other.by_hour) {
+            *total += count;
+        }
+        for (total, count) in self.by_weekday.iter_mut().zip(other.by_weekday) {
+            *total += count;
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.by_hour.iter().sum()
+    }
+
+    /// Hour histogram as `(label, count)` pairs
+    pub fn hour_buckets(&self) -> Vec<(String, u32)> {
+        self.by_hour
+            .iter()
+            .enumerate()
+            .map(|(hour, count)| (format!("{hour:02}:00"), *count))
+            .collect()
+    }
+
+    /// Weekday histogram as `(label, count)` pairs, Monday first
+    pub fn weekday_buckets(&self) -> Vec<(String, u32)> {
+        WEEKDAYS
+            .iter()
+            .zip(self.by_weekday)
+            .map(|(day, count)| (day.to_string(), count))
+            .collect()
+    }
+}
+
+/// Label of the calendar period containing `timestamp`, sortable as a string
+pub fn period_key(period: Period, timestamp: NaiveDateTime) -> String {
+    match period {
+        Period::Day => timestamp.format("%Y-%m-%d").to_string(),
+        Period::Month => timestamp.format("%Y-%m").to_string(),
+        Period::Year => timestamp.format("%Y").to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeriodBucket {
+    pub visits: u32,
+    pub domains: HashSet<String>,
+}
+
+/// Visit totals and distinct domains per calendar period
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeriodBreakdown {
+    pub period: Period,
+    pub buckets: BTreeMap<String, PeriodBucket>,
+}
+
+impl PeriodBreakdown {
+    pub fn new(period: Period) -> Self {
+        Self {
+            period,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, timestamp: NaiveDateTime, domain: Option<&str>) {
+        let bucket = self
+            .buckets
+            .entry(period_key(self.period, timestamp))
+            .or_default();
+        bucket.visits += 1;
+        if let Some(domain) = domain {
+            if !bucket.domains.contains(domain) {
+                bucket.domains.insert(domain.to_string());
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &PeriodBreakdown) {
+        for (key, other_bucket) in &other.buckets {
+            let bucket = self.buckets.entry(key.clone()).or_default();
+            bucket.visits += other_bucket.visits;
+            bucket.domains.extend(other_bucket.domains.iter().cloned());
+        }
+    }
+
+    /// Visits per period from the first to the last recorded one, with empty periods as zero
+    pub fn series(&self) -> Vec<(String, u32)> {
+        let (Some(first), Some(last)) = (self.buckets.keys().next(), self.buckets.keys().last())
+        else {
+            return Vec::new();
+        };
+        let parse = |key: &str| -> Option<NaiveDate> {
+            let date = match self.period {
+                Period::Day => key.to_string(),
+                Period::Month => format!("{key}-01"),
+                Period::Year => format!("{key}-01-01"),
+            };
+            NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()
+        };
+        let (Some(mut current), Some(end)) = (parse(first), parse(last)) else {
+            return Vec::new();
+        };
+
+        let mut series = Vec::new();
+        while current <= end {
+            let key = period_key(self.period, current.and_time(NaiveTime::MIN));
+            let visits = self.buckets.get(&key).map_or(0, |bucket| bucket.visits);
+            series.push((key, visits));
+            let next = match self.period {
+                Period::Day => current.succ_opt(),
+                Period::Month => current.checked_add_months(Months::new(1)),
+                Period::Year => current.checked_add_months(Months::new(12)),
+            };
+            let Some(next) = next else {
+                break;
+            };
+            current = next;
+        }
+        series
+    }
+}
+
+/// Change in a domain's visits between the earlier and later half of the analyzed months
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainTrend {
+    pub domain: String,
+    pub earlier: u32,
+    pub later: u32,
+}
+
+impl DomainTrend {
+    pub fn change(&self) -> i64 {
+        self.later as i64 - self.earlier as i64
+    }
+}
+
+/// Per-domain visit counts by month
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainTrends {
+    pub monthly: HashMap<String, BTreeMap<String, u32>>,
+}
+
+impl DomainTrends {
+    pub fn record(&mut self, timestamp: NaiveDateTime, domain: &str) {
+        let months = match self.monthly.get_mut(domain) {
+            Some(months) => months,
+            None => self.monthly.entry(domain.to_string()).or_default(),
+        };
+        *months
+            .entry(period_key(Period::Month, timestamp))
+            .or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &DomainTrends) {
+        for (domain, other_months) in &other.monthly {
+            let months = self.monthly.entry(domain.clone()).or_default();
+            for (month, count) in other_months {
+                *months.entry(month.clone()).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// All months with at least one visit, oldest first
+    pub fn months(&self) -> Vec<&String> {
+        let months: BTreeSet<&String> = self.monthly.values().flat_map(|m| m.keys()).collect();
+        months.into_iter().collect()
+    }
+
+    /// Compares the first and last half of the observed months; with an odd month count
+    /// the middle month is left out so both halves cover the same number of months
+    pub fn trends(&self) -> Vec<DomainTrend> {
+        let months = self.months();
+        let half = months.len() / 2;
+        if half == 0 {
+            return Vec::new();
+        }
+        let (earlier_months, later_months) = (&months[..half], &months[months.len() - half..]);
+        let sum = |counts: &BTreeMap<String, u32>, range: &[&String]| -> u32 {
+            range.iter().filter_map(|month| counts.get(*month)).sum()
+        };
+
+        self.monthly
+            .iter()
+            .map(|(domain, counts)| DomainTrend {
+                domain: domain.clone(),
+                earlier: sum(counts, earlier_months),
+                later: sum(counts, later_months),
+            })
+            .filter(|trend| trend.change() != 0)
+            .collect()
+    }
+
+    /// Domains whose visits grew the most, largest increase first
+    pub fn growing(&self, n: usize) -> Vec<DomainTrend> {
+        let mut trends: Vec<DomainTrend> = self
+            .trends()
+            .into_iter()
+            .filter(|trend| trend.change() > 0)
+            .collect();
+        trends.sort_by(|a, b| b.change().cmp(&a.change()).then(a.domain.cmp(&b.domain)));
+        trends.truncate(n);
+        trends
+    }
+
+    /// Domains whose visits fell the most, largest decrease first
+    pub fn declining(&self, n: usize) -> Vec<DomainTrend> {
+        let mut trends: Vec<DomainTrend> = self
+            .trends()
+            .into_iter()
+            .filter(|trend| trend.change() < 0)
+            .collect();
+        trends.sort_by(|a, b| a.change().cmp(&b.change()).then(a.domain.cmp(&b.domain)));
+        trends.truncate(n);
+        trends
+    }
+}
+
+/// Domains visited in a month, split by whether the month is the first they appear in
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoveltyMonth {
+    pub month: String,
+    pub new: u32,
+    pub returning: u32,
+}
+
+impl NoveltyMonth {
+    /// Fraction of the month's domains that were new
+    pub fn rate(&self) -> f64 {
+        self.new as f64 / (self.new + self.returning).max(1) as f64
+    }
+}
+
+/// Months in which each domain was visited; a domain is new in the first month it
+/// appears in the analyzed history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainNovelty {
+    pub months: HashMap<String, BTreeSet<String>>,
+}
+
+impl DomainNovelty {
+    pub fn record(&mut self, timestamp: NaiveDateTime, domain: &str) {
+        let months = match self.months.get_mut(domain) {
+            Some(months) => months,
+            None => self.months.entry(domain.to_string()).or_default(),
+        };
+        months.insert(period_key(Period::Month, timestamp));
+    }
+
+    pub fn merge(&mut self, other: &DomainNovelty) {
+        for (domain, other_months) in &other.months {
+            self.months
+                .entry(domain.clone())
+                .or_default()
+                .extend(other_months.iter().cloned());
+        }
+    }
+
+    /// New and returning domain counts for every month with visits, oldest first
+    pub fn series(&self) -> Vec<NoveltyMonth> {
+        let mut series: BTreeMap<&String, NoveltyMonth> = BTreeMap::new();
+        for months in self.months.values() {
+            for (i, month) in months.iter().enumerate() {
+                let entry = series.entry(month).or_insert_with(|| NoveltyMonth {
+                    month: month.clone(),
+                    new: 0,
+                    returning: 0,
+                });
+                if i == 0 {
+                    entry.new += 1;
+                } else {
+                    entry.returning += 1;
+                }
+            }
+        }
+        series.into_values().collect()
+    }
+}
+
+/// Consecutive-day visit runs for a single domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainStreak {
+    pub domain: String,
+    pub longest: u32,
+    pub longest_end: NaiveDate,
+    /// Run ending today or yesterday, zero if the domain was not visited recently
+    pub current: u32,
+}
+
+/// First and last visit time of each domain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainSpans {
+    pub spans: HashMap<String, (DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl DomainSpans {
+    pub fn record(&mut self, timestamp: DateTime<Utc>, domain: &str) {
+        match self.spans.get_mut(domain) {
+            Some((first, last)) => {
+                *first = (*first).min(timestamp);
+                *last = (*last).max(timestamp);
+            }
+            None => {
+                self.spans
+                    .insert(domain.to_string(), (timestamp, timestamp));
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &DomainSpans) {
+        for (domain, (first, last)) in &other.spans {
+            self.record(*first, domain);
+            self.record(*last, domain);
+        }
+    }
+
+    pub fn first(&self, domain: &str) -> Option<DateTime<Utc>> {
+        self.spans.get(domain).map(|(first, _)| *first)
+    }
+
+    pub fn last(&self, domain: &str) -> Option<DateTime<Utc>> {
+        self.spans.get(domain).map(|(_, last)| *last)
+    }
+
+    /// Domains with at least `min_count` visits whose last visit is before `cutoff`,
+    /// most visited first
+    pub fn stale(
+        &self,
+        counts: &HashMap<String, u32>,
+        min_count: u32,
+        cutoff: DateTime<Utc>,
+    ) -> Vec<StaleDomain> {
+        let mut stale: Vec<StaleDomain> = counts
+            .iter()
+            .filter(|(_, count)| **count >= min_count)
+            .filter_map(|(domain, count)| {
+                let last = self.last(domain).filter(|last| *last < cutoff)?;
+                Some(StaleDomain {
+                    domain: domain.clone(),
+                    count: *count,
+                    last,
+                })
+            })
+            .collect();
+        stale.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(&b.domain)));
+        stale
+    }
+}
+
+/// A once frequently visited domain that has not been visited recently
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StaleDomain {
+    pub domain: String,
+    pub count: u32,
+    pub last: DateTime<Utc>,
+}
+
+/// Days (in the report's zone) on which each domain was visited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainStreaks {
+    pub days: HashMap<String, BTreeSet<NaiveDate>>,
+}
+
+impl DomainStreaks {
+    pub fn record(&mut self, timestamp: NaiveDateTime, domain: &str) {
+        let days = match self.days.get_mut(domain) {
+            Some(days) => days,
+            None => self.days.entry(domain.to_string()).or_default(),
+        };
+        days.insert(timestamp.date());
+    }
+
+    pub fn merge(&mut self, other: &DomainStreaks) {
+        for (domain, other_days) in &other.days {
+            self.days
+                .entry(domain.clone())
+                .or_default()
+                .extend(other_days.iter().copied());
+        }
+    }
+
+    /// Streaks for every domain, measured against `today`
+    pub fn streaks(&self, today: NaiveDate) -> Vec<DomainStreak> {
+        self.days
+            .iter()
+            .filter_map(|(domain, days)| {
+                let mut longest = (0, *days.first()?);
+                let mut run = 0;
+                let mut previous: Option<NaiveDate> = None;
+                for day in days {
+                    run = match previous {
+                        Some(prev) if prev.succ_opt() == Some(*day) => run + 1,
+                        _ => 1,
+                    };
+                    if run > longest.0 {
+                        longest = (run, *day);
+                    }
+                    previous = Some(*day);
+                }
+
+                let last = previous?;
+                let current = if today - last <= Duration::days(1) {
+                    run
+                } else {
+                    0
+                };
+                Some(DomainStreak {
+                    domain: domain.clone(),
+                    longest: longest.0,
+                    longest_end: longest.1,
+                    current,
+                })
+            })
+            .collect()
+    }
+
+    /// Domains with the longest streaks, longest first
+    pub fn longest(&self, today: NaiveDate, n: usize) -> Vec<DomainStreak> {
+        let mut streaks = self.streaks(today);
+        streaks.sort_by(|a, b| {
+            b.longest
+                .cmp(&a.longest)
+                .then(b.current.cmp(&a.current))
+                .then(a.domain.cmp(&b.domain))
+        });
+        streaks.truncate(n);
+        streaks
+    }
+}
+
+/// Age buckets (days, weight) for frecency scoring, modelled on Firefox's frecency buckets
+const FRECENCY_BUCKETS: [(i64, f64); 4] = [(4, 100.0), (14, 70.0), (31, 50.0), (90, 30.0)];
+const FRECENCY_OLD_WEIGHT: f64 = 10.0;
+
+/// Recency-weighted visit scores per domain
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DomainFrecency {
+    /// Point in time that visit ages are measured from
+    pub reference: DateTime<Utc>,
+    pub scores: HashMap<String, f64>,
+}
+
+impl DomainFrecency {
+    pub fn new(reference: DateTime<Utc>) -> Self {
+        Self {
+            reference,
+            scores: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, timestamp: DateTime<Utc>, domain: &str) {
+        let age_days = (self.reference - timestamp).num_days();
+        let weight = FRECENCY_BUCKETS
+            .iter()
+            .find(|(max_age, _)| age_days <= *max_age)
+            .map_or(FRECENCY_OLD_WEIGHT, |(_, weight)| *weight);
+        match self.scores.get_mut(domain) {
+            Some(score) => *score += weight,
+            None => {
+                self.scores.insert(domain.to_string(), weight);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &DomainFrecency) {
+        for (domain, score) in &other.scores {
+            *self.scores.entry(domain.clone()).or_insert(0.0) += score;
+        }
+    }
+
+    pub fn score(&self, domain: &str) -> f64 {
+        self.scores.get(domain).copied().unwrap_or_default()
+    }
+
+    /// Score scaled to 0..=1 relative to the highest-scoring domain
+    pub fn intensity(&self, domain: &str) -> f64 {
+        let max = self.scores.values().copied().fold(0.0, f64::max);
+        if max > 0.0 {
+            self.score(domain) / max
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Per-domain visits in the window of `length` ending at `end` and in the window before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeriodComparison {
+    pub end: DateTime<Utc>,
+    #[cfg_attr(feature = "serde", serde(with = "duration_ms"))]
+    pub length: Duration,
+    pub recent: HashMap<String, u32>,
+    pub previous: HashMap<String, u32>,
+}
+
+impl PeriodComparison {
+    pub fn new(end: DateTime<Utc>, length: Duration) -> Self {
+        Self {
+            end,
+            length,
+            recent: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, timestamp: DateTime<Utc>, domain: &str) {
+        let age = self.end - timestamp;
+        let window = if age < Duration::zero() {
+            return;
+        } else if age < self.length {
+            &mut self.recent
+        } else if age < self.length * 2 {
+            &mut self.previous
+        } else {
+            return;
+        };
+        match window.get_mut(domain) {
+            Some(count) => *count += 1,
+            None => {
+                window.insert(domain.to_string(), 1);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &PeriodComparison) {
+        for (window, other_window) in [
+            (&mut self.recent, &other.recent),
+            (&mut self.previous, &other.previous),
+        ] {
+            for (domain, count) in other_window {
+                *window.entry(domain.clone()).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Every domain visited in either window, by recent rank, then the ones that dropped out
+    /// by their previous rank
+    pub fn movements(&self) -> Vec<RankMovement> {
+        let recent = ranks(&self.recent);
+        let previous = ranks(&self.previous);
+        let mut movements: Vec<RankMovement> = recent
+            .keys()
+            .chain(previous.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|domain| RankMovement {
+                domain: domain.to_string(),
+                recent_rank: recent.get(domain).copied(),
+                previous_rank: previous.get(domain).copied(),
+                recent: self.recent.get(*domain).copied().unwrap_or(0),
+                previous: self.previous.get(*domain).copied().unwrap_or(0),
+            })
+            .collect();
+        movements.sort_by_key(|movement| {
+            (
+                movement.recent_rank.is_none(),
+                movement.recent_rank.or(movement.previous_rank),
+            )
+        });
+        movements
+    }
+}
+
+/// Ranks from 1 by count, ties alphabetically
+fn ranks(counts: &HashMap<String, u32>) -> HashMap<&str, usize> {
+    let mut ranked: Vec<(&String, &u32)> = counts.iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(index, (domain, _))| (domain.as_str(), index + 1))
+        .collect()
+}
+
+/// A domain's rank in the recent and previous window of a [`PeriodComparison`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RankMovement {
+    pub domain: String,
+    pub recent_rank: Option<usize>,
+    pub previous_rank: Option<usize>,
+    pub recent: u32,
+    pub previous: u32,
+}
+
+impl RankMovement {
+    /// Positions gained (positive) or lost (negative); `None` unless ranked in both windows
+    pub fn change(&self) -> Option<i64> {
+        Some(self.previous_rank? as i64 - self.recent_rank? as i64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OpenSession {
+    start: DateTime<Utc>,
+    last: DateTime<Utc>,
+    domains: HashSet<String>,
+}
+
+/// Browsing sessions split wherever consecutive visits are more than `gap` apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionStats {
+    #[cfg_attr(feature = "serde", serde(with = "duration_ms"))]
+    pub gap: Duration,
+    pub sessions: u32,
+    #[cfg_attr(feature = "serde", serde(with = "duration_ms"))]
+    pub total_duration: Duration,
+    pub total_domains: u64,
+    #[cfg_attr(feature = "serde", serde(with = "duration_ms"))]
+    pub longest: Duration,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    current: Option<OpenSession>,
+}
+
+impl SessionStats {
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            sessions: 0,
+            total_duration: Duration::zero(),
+            total_domains: 0,
+            longest: Duration::zero(),
+            current: None,
+        }
+    }
+
+    /// Records a visit; visits must arrive in chronological order
+    pub fn record(&mut self, timestamp: DateTime<Utc>, domain: Option<&str>) {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|session| timestamp - session.last > self.gap)
+        {
+            self.finish();
+        }
+
+        let session = self.current.get_or_insert_with(|| OpenSession {
+            start: timestamp,
+            last: timestamp,
+            domains: HashSet::new(),
+        });
+        session.last = timestamp;
+        if let Some(domain) = domain {
+            if !session.domains.contains(domain) {
+                session.domains.insert(domain.to_string());
+            }
+        }
+    }
+
+    /// Closes the session in progress, if any
+    pub fn finish(&mut self) {
+        if let Some(session) = self.current.take() {
+            let duration = session.last - session.start;
+            self.sessions += 1;
+            self.total_duration += duration;
+            self.total_domains += session.domains.len() as u64;
+            self.longest = self.longest.max(duration);
+        }
+    }
+
+    /// Adds another browser's sessions; both must already be finished
+    pub fn merge(&mut self, other: &SessionStats) {
+        self.sessions += other.sessions;
+        self.total_duration += other.total_duration;
+        self.total_domains += other.total_domains;
+        self.longest = self.longest.max(other.longest);
+    }
+
+    pub fn average_duration(&self) -> Duration {
+        if self.sessions == 0 {
+            return Duration::zero();
+        }
+        self.total_duration / self.sessions as i32
+    }
+
+    pub fn average_domains(&self) -> f64 {
+        if self.sessions == 0 {
+            return 0.0;
+        }
+        self.total_domains as f64 / self.sessions as f64
+    }
+}
+
+/// Estimated time spent per domain, summed from recorded visit durations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeSpent {
+    #[cfg_attr(feature = "serde", serde(with = "duration_ms_map"))]
+    pub by_domain: HashMap<String, Duration>,
+}
+
+impl TimeSpent {
+    pub fn record(&mut self, domain: &str, duration: Duration) {
+        match self.by_domain.get_mut(domain) {
+            Some(total) => *total += duration,
+            None => {
+                self.by_domain.insert(domain.to_string(), duration);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &TimeSpent) {
+        for (domain, duration) in &other.by_domain {
+            *self
+                .by_domain
+                .entry(domain.clone())
+                .or_insert_with(Duration::zero) += *duration;
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.by_domain
+            .values()
+            .fold(Duration::zero(), |sum, d| sum + *d)
+    }
+
+    /// Domains with the most time spent, longest first
+    pub fn top(&self, n: usize) -> Vec<(String, Duration)> {
+        let mut domains: Vec<(String, Duration)> = self
+            .by_domain
+            .iter()
+            .map(|(domain, duration)| (domain.clone(), *duration))
+            .collect();
+        domains.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        domains.truncate(n);
+        domains
+    }
+}
+
+/// Serializes a `chrono::Duration` as whole milliseconds
+#[cfg(feature = "serde")]
+mod duration_ms {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_milliseconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        i64::deserialize(deserializer).map(Duration::milliseconds)
+    }
+}
+
+/// Serializes a map of `chrono::Duration` values as whole milliseconds
+#[cfg(feature = "serde")]
+mod duration_ms_map {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        durations: &HashMap<String, Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            durations
+                .iter()
+                .map(|(domain, duration)| (domain, duration.num_milliseconds())),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, Duration>, D::Error> {
+        let millis = HashMap::<String, i64>::deserialize(deserializer)?;
+        Ok(millis
+            .into_iter()
+            .map(|(domain, millis)| (domain, Duration::milliseconds(millis)))
+            .collect())
+    }
+}
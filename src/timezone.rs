@@ -0,0 +1,591 @@
+use anyhow::{Context, Result};
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone,
+    Utc,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// Zone that visit timestamps are bucketed into days and hours in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timezone {
+    /// The system zone, or the zone named by the `TZ` environment variable
+    #[default]
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+    /// A zone read from the system time zone database
+    Named(&'static ZoneRules),
+}
+
+/// Offsets of a zone from the system time zone database: the transitions its TZif file
+/// records, then the POSIX rule in the file's footer for instants after the last one
+#[derive(Clone, PartialEq, Eq)]
+pub struct ZoneRules {
+    name: String,
+    /// Unix seconds from which each offset east of UTC applies, ascending
+    transitions: Vec<(i64, i32)>,
+    /// Offset before the first transition
+    initial: i32,
+    rule: Option<PosixRule>,
+}
+
+impl fmt::Debug for ZoneRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ZoneRules").field(&self.name).finish()
+    }
+}
+
+/// Length of a TZif header: magic, version, 15 reserved bytes and six counts
+const TZIF_HEADER_LEN: usize = 44;
+
+/// Version and `[isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt]` of a TZif header
+fn tzif_header(data: &[u8]) -> Result<(u8, [usize; 6])> {
+    if data.len() < TZIF_HEADER_LEN || !data.starts_with(b"TZif") {
+        anyhow::bail!("not TZif data");
+    }
+    let mut counts = [0; 6];
+    for (i, count) in counts.iter_mut().enumerate() {
+        let at = 20 + i * 4;
+        *count = u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize;
+    }
+    Ok((data[4], counts))
+}
+
+/// Length of the data block following a header, with transition times `time_size` bytes wide
+fn tzif_block_len(counts: [usize; 6], time_size: usize) -> usize {
+    let [isut, isstd, leap, times, types, chars] = counts;
+    times * (time_size + 1) + types * 6 + chars + leap * (time_size + 4) + isstd + isut
+}
+
+impl ZoneRules {
+    /// Parses TZif data (RFC 8536), reading the 64-bit block of version 2 and later files
+    fn parse(name: &str, data: &[u8]) -> Result<Self> {
+        let (version, counts) = tzif_header(data)?;
+        let (block, counts, time_size) = if version >= b'2' {
+            let rest = data
+                .get(TZIF_HEADER_LEN + tzif_block_len(counts, 4)..)
+                .context("truncated version 1 block")?;
+            let (_, counts) = tzif_header(rest)?;
+            (&rest[TZIF_HEADER_LEN..], counts, 8)
+        } else {
+            (&data[TZIF_HEADER_LEN..], counts, 4)
+        };
+        let [_, _, _, time_count, type_count, _] = counts;
+        let block_len = tzif_block_len(counts, time_size);
+        if block.len() < block_len || type_count == 0 {
+            anyhow::bail!("truncated data block");
+        }
+
+        let (times, rest) = block.split_at(time_count * time_size);
+        let (indices, rest) = rest.split_at(time_count);
+        let types = &rest[..type_count * 6];
+        let offset_of = |index: usize| -> Result<i32> {
+            let info = types
+                .get(index * 6..index * 6 + 4)
+                .with_context(|| format!("transition to undefined type {index}"))?;
+            Ok(i32::from_be_bytes([info[0], info[1], info[2], info[3]]))
+        };
+        let transitions = times
+            .chunks_exact(time_size)
+            .zip(indices)
+            .map(|(time, index)| {
+                let time = match *time {
+                    [a, b, c, d, e, f, g, h] => i64::from_be_bytes([a, b, c, d, e, f, g, h]),
+                    [a, b, c, d] => i32::from_be_bytes([a, b, c, d]).into(),
+                    _ => unreachable!("transition times are 4 or 8 bytes"),
+                };
+                Ok((time, offset_of((*index).into())?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Version 2 and later end with a newline-enclosed POSIX TZ string, possibly empty
+        let footer = if version >= b'2' {
+            std::str::from_utf8(&block[block_len..])
+                .ok()
+                .and_then(|footer| footer.strip_prefix('\n'))
+                .and_then(|footer| footer.split('\n').next())
+                .unwrap_or_default()
+        } else {
+            ""
+        };
+        let rule = match footer {
+            "" => None,
+            footer => Some(
+                PosixRule::parse(footer)
+                    .with_context(|| format!("invalid POSIX TZ rule '{footer}'"))?,
+            ),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            transitions,
+            initial: offset_of(0)?,
+            rule,
+        })
+    }
+
+    /// Offset east of UTC in seconds at `time`, in Unix seconds
+    fn offset_at(&self, time: i64) -> i32 {
+        let applied = self
+            .transitions
+            .partition_point(|(start, _)| *start <= time);
+        match &self.rule {
+            Some(rule) if applied == self.transitions.len() => rule.offset_at(time),
+            _ if applied == 0 => self.initial,
+            _ => self.transitions[applied - 1].1,
+        }
+    }
+
+    /// Earliest instant showing `local` on the clock, or `None` if a transition skips it
+    fn earliest_instant(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let naive = local.and_utc().timestamp();
+        [naive - 86_400, naive, naive + 86_400]
+            .into_iter()
+            .map(|near| self.offset_at(near))
+            .filter_map(|offset| {
+                let time = naive - i64::from(offset);
+                (self.offset_at(time) == offset).then_some(time)
+            })
+            .min()
+            .and_then(|time| DateTime::from_timestamp(time, 0))
+    }
+}
+
+/// A POSIX TZ rule such as `EST5EDT,M3.2.0,M11.1.0`, with offsets east of UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PosixRule {
+    standard: i32,
+    daylight: Option<DaylightRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DaylightRule {
+    offset: i32,
+    /// Day and local standard time, in seconds, that daylight time starts at
+    start: (RuleDay, i32),
+    /// Day and local daylight time, in seconds, that daylight time ends at
+    end: (RuleDay, i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDay {
+    /// `Jn`: day 1 to 365, never counting February 29
+    Julian(i64),
+    /// `n`: day 0 to 365, counting February 29
+    Ordinal(i64),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` of month `m`, 5 meaning the last
+    MonthWeek { month: u32, week: u32, weekday: u32 },
+}
+
+/// Splits a leading zone abbreviation, either letters or `<...>`, off `s`
+fn split_abbreviation(s: &str) -> Option<&str> {
+    if let Some(quoted) = s.strip_prefix('<') {
+        return quoted.split_once('>').map(|(_, rest)| rest);
+    }
+    let len = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    (len >= 3).then(|| &s[len..])
+}
+
+/// Splits a leading `[+-]hh[:mm[:ss]]` off `s`, in seconds
+fn split_duration(s: &str) -> Option<(i32, &str)> {
+    let (sign, unsigned) = match s.as_bytes().first()? {
+        b'-' => (-1, &s[1..]),
+        b'+' => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let len = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(unsigned.len());
+    let mut seconds = 0;
+    let mut parts = 0;
+    for (part, unit) in unsigned[..len].split(':').zip([3600, 60, 1]) {
+        seconds += part.parse::<i32>().ok()? * unit;
+        parts += 1;
+    }
+    (parts > 0 && unsigned[..len].split(':').count() == parts)
+        .then_some((sign * seconds, &unsigned[len..]))
+}
+
+impl RuleDay {
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(day) = s.strip_prefix('J') {
+            return day
+                .parse()
+                .ok()
+                .filter(|day| (1..=365).contains(day))
+                .map(RuleDay::Julian);
+        }
+        if let Some(date) = s.strip_prefix('M') {
+            let mut fields = date.split('.').map(|field| field.parse::<u32>().ok());
+            let (Some(Some(month)), Some(Some(week)), Some(Some(weekday)), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return None;
+            };
+            return ((1..=12).contains(&month) && (1..=5).contains(&week) && weekday <= 6)
+                .then_some(RuleDay::MonthWeek {
+                    month,
+                    week,
+                    weekday,
+                });
+        }
+        s.parse()
+            .ok()
+            .filter(|day| (0..=365).contains(day))
+            .map(RuleDay::Ordinal)
+    }
+
+    fn date(self, year: i32) -> Option<NaiveDate> {
+        let january = NaiveDate::from_yo_opt(year, 1)?;
+        match self {
+            RuleDay::Julian(day) => {
+                let leap_day = i64::from(january.leap_year() && day >= 60);
+                january.checked_add_signed(chrono::Duration::days(day - 1 + leap_day))
+            }
+            RuleDay::Ordinal(day) => january.checked_add_signed(chrono::Duration::days(day)),
+            RuleDay::MonthWeek {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let offset = (weekday + 7 - first.weekday().num_days_from_sunday()) % 7;
+                let mut day = first + chrono::Duration::days(i64::from(offset + (week - 1) * 7));
+                while day.month() != month {
+                    day -= chrono::Duration::days(7);
+                }
+                Some(day)
+            }
+        }
+    }
+}
+
+/// Splits a `,date[/time]` transition off `s`; the time defaults to 02:00
+fn split_transition(s: &str) -> Option<((RuleDay, i32), &str)> {
+    let s = s.strip_prefix(',')?;
+    let len = s.find([',', '/']).unwrap_or(s.len());
+    let day = RuleDay::parse(&s[..len])?;
+    match s[len..].strip_prefix('/') {
+        Some(time) => {
+            let (time, rest) = split_duration(time)?;
+            Some(((day, time), rest))
+        }
+        None => Some(((day, 7200), &s[len..])),
+    }
+}
+
+impl PosixRule {
+    fn parse(s: &str) -> Option<Self> {
+        // POSIX offsets count hours west of UTC
+        let (standard, rest) = split_duration(split_abbreviation(s)?)?;
+        let standard = -standard;
+        if rest.is_empty() {
+            return Some(Self {
+                standard,
+                daylight: None,
+            });
+        }
+        let rest = split_abbreviation(rest)?;
+        let (offset, rest) = match split_duration(rest) {
+            Some((offset, rest)) => (-offset, rest),
+            None => (standard + 3600, rest),
+        };
+        let (start, rest) = split_transition(rest)?;
+        let (end, rest) = split_transition(rest)?;
+        rest.is_empty().then_some(Self {
+            standard,
+            daylight: Some(DaylightRule { offset, start, end }),
+        })
+    }
+
+    fn offset_at(&self, time: i64) -> i32 {
+        let Some(daylight) = self.daylight else {
+            return self.standard;
+        };
+        let Some(year) =
+            DateTime::from_timestamp(time + i64::from(self.standard), 0).map(|local| local.year())
+        else {
+            return self.standard;
+        };
+        let instant = |(day, seconds): (RuleDay, i32), offset: i32| {
+            day.date(year).map(|date| {
+                date.and_time(NaiveTime::MIN).and_utc().timestamp() + i64::from(seconds - offset)
+            })
+        };
+        let (Some(start), Some(end)) = (
+            instant(daylight.start, self.standard),
+            instant(daylight.end, daylight.offset),
+        ) else {
+            return self.standard;
+        };
+        // Southern zones start daylight time late in the year and end it early in the next
+        let daylight_time = if start <= end {
+            start <= time && time < end
+        } else {
+            time < end || start <= time
+        };
+        if daylight_time {
+            daylight.offset
+        } else {
+            self.standard
+        }
+    }
+}
+
+/// The rules of zone `name`, read from the database once and kept for the whole process so
+/// `Timezone` stays `Copy`
+fn named_zone(name: &str) -> Result<&'static ZoneRules> {
+    static ZONES: OnceLock<Mutex<HashMap<String, &'static ZoneRules>>> = OnceLock::new();
+    let mut zones = ZONES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(rules) = zones.get(name) {
+        return Ok(rules);
+    }
+    let path = zoneinfo_dir().join(name);
+    let data =
+        std::fs::read(&path).with_context(|| format!("Failed to read time zone {path:?}"))?;
+    let rules = ZoneRules::parse(name, &data)
+        .with_context(|| format!("Invalid time zone file {path:?}"))?;
+    let rules: &'static ZoneRules = Box::leak(Box::new(rules));
+    zones.insert(name.to_string(), rules);
+    Ok(rules)
+}
+
+/// Directory of the system time zone database
+fn zoneinfo_dir() -> PathBuf {
+    std::env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"))
+}
+
+/// Parses `+HH:MM`, `-HH:MM`, `+HHMM` or `+HH` into an offset east of UTC
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl Timezone {
+    /// Parses `local`, `utc`, a fixed offset such as `+05:30`, or a zone name from the
+    /// system database such as `America/New_York`
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Timezone::Local);
+        }
+        if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+            return Ok(Timezone::Utc);
+        }
+        if let Some(offset) = parse_offset(s) {
+            return Ok(Timezone::Fixed(offset));
+        }
+        if Self::is_named(s) {
+            return named_zone(s).map(Timezone::Named);
+        }
+        anyhow::bail!(
+            "Unknown time zone '{s}': expected local, utc, an offset such as +05:30, \
+             or a name from {}",
+            zoneinfo_dir().display()
+        )
+    }
+
+    /// Whether `s` names a zone in the system time zone database
+    pub fn is_named(s: &str) -> bool {
+        !s.is_empty()
+            && !s.starts_with('/')
+            && !s.split('/').any(|part| part == "..")
+            && zoneinfo_dir().join(s).is_file()
+    }
+
+    /// Offset from UTC in effect at `utc`
+    pub fn offset(self, utc: DateTime<Utc>) -> FixedOffset {
+        match self {
+            Timezone::Local => utc.with_timezone(&Local).offset().fix(),
+            Timezone::Utc => Utc.fix(),
+            Timezone::Fixed(offset) => offset,
+            Timezone::Named(rules) => {
+                FixedOffset::east_opt(rules.offset_at(utc.timestamp())).unwrap_or(Utc.fix())
+            }
+        }
+    }
+
+    /// Wall-clock time in this zone at `utc`
+    pub fn local(self, utc: DateTime<Utc>) -> NaiveDateTime {
+        utc.with_timezone(&self.offset(utc)).naive_local()
+    }
+
+    /// Earliest instant showing `local` on the clock in this zone, or `None` if a
+    /// transition skips it
+    fn earliest_instant(self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Timezone::Local => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|start| start.with_timezone(&Utc)),
+            Timezone::Utc => Some(local.and_utc()),
+            Timezone::Fixed(offset) => Some((local - offset).and_utc()),
+            Timezone::Named(rules) => rules.earliest_instant(local),
+        }
+    }
+
+    /// First instant of `date` in this zone
+    pub fn start_of_day(self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = date.and_time(NaiveTime::MIN);
+        // A DST change at midnight skips it; the day then starts at the first valid time
+        self.earliest_instant(midnight)
+            .or_else(|| self.earliest_instant(midnight + chrono::Duration::hours(1)))
+            .unwrap_or_else(|| midnight.and_utc())
+    }
+
+    pub fn today(self) -> NaiveDate {
+        self.local(Utc::now()).date()
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timezone::Local => write!(f, "local"),
+            Timezone::Utc => write!(f, "utc"),
+            Timezone::Fixed(offset) => write!(f, "{offset}"),
+            Timezone::Named(rules) => write!(f, "{}", rules.name),
+        }
+    }
+}
+
+/// Validates a `--timezone` value, keeping it as given
+pub fn parse_name(s: &str) -> Result<String> {
+    Timezone::parse(s).map(|_| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> PosixRule {
+        PosixRule::parse(s).unwrap()
+    }
+
+    fn unix(s: &str) -> i64 {
+        s.parse::<DateTime<Utc>>().unwrap().timestamp()
+    }
+
+    /// Version 2 TZif data with an empty version 1 block
+    fn tzif(transitions: &[(i64, u8)], offsets: &[i32], footer: &str) -> Vec<u8> {
+        let header = |counts: [usize; 6]| {
+            let mut header = b"TZif2".to_vec();
+            header.extend([0; 15]);
+            for count in counts {
+                header.extend((count as u32).to_be_bytes());
+            }
+            header
+        };
+        let mut data = header([0; 6]);
+        data.extend(header([0, 0, 0, transitions.len(), offsets.len(), 1]));
+        for (time, _) in transitions {
+            data.extend(time.to_be_bytes());
+        }
+        data.extend(transitions.iter().map(|(_, index)| index));
+        for offset in offsets {
+            data.extend(offset.to_be_bytes());
+            data.extend([0, 0]);
+        }
+        data.push(0);
+        data.extend(format!("\n{footer}\n").bytes());
+        data
+    }
+
+    #[test]
+    fn posix_rules_switch_at_local_times() {
+        let eastern = rule("EST5EDT,M3.2.0,M11.1.0");
+        assert_eq!(eastern.offset_at(unix("2024-03-10T06:59:59Z")), -5 * 3600);
+        assert_eq!(eastern.offset_at(unix("2024-03-10T07:00:00Z")), -4 * 3600);
+        assert_eq!(eastern.offset_at(unix("2024-11-03T05:59:59Z")), -4 * 3600);
+        assert_eq!(eastern.offset_at(unix("2024-11-03T06:00:00Z")), -5 * 3600);
+
+        let sydney = rule("AEST-10AEDT,M10.1.0,M4.1.0/3");
+        assert_eq!(sydney.offset_at(unix("2024-01-15T00:00:00Z")), 11 * 3600);
+        assert_eq!(sydney.offset_at(unix("2024-07-15T00:00:00Z")), 10 * 3600);
+    }
+
+    #[test]
+    fn posix_rules_parse_quoted_names_and_minutes() {
+        assert_eq!(rule("<+0530>-5:30").offset_at(0), 5 * 3600 + 1800);
+        assert_eq!(rule("<-03>3").offset_at(0), -3 * 3600);
+        for invalid in ["EST", "E5", "EST5EDT,M3.2.0", "EST5EDT,M13.1.0,M11.1.0"] {
+            assert_eq!(PosixRule::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn tzif_transitions_hand_over_to_the_footer_rule() {
+        let data = tzif(
+            &[(-1_000_000_000, 1)],
+            &[3208, 3600],
+            "CET-1CEST,M3.5.0,M10.5.0/3",
+        );
+        let rules = ZoneRules::parse("Europe/Test", &data).unwrap();
+        assert_eq!(rules.offset_at(-2_000_000_000), 3208);
+        assert_eq!(rules.offset_at(unix("2024-01-15T00:00:00Z")), 3600);
+        assert_eq!(rules.offset_at(unix("2024-07-15T00:00:00Z")), 7200);
+        assert!(ZoneRules::parse("Europe/Test", b"not a zone file").is_err());
+        assert!(ZoneRules::parse("Europe/Test", &data[..60]).is_err());
+    }
+
+    #[test]
+    fn days_skipping_midnight_start_at_the_first_valid_time() {
+        // Daylight time starts at 24:00 on the first Saturday of September, as in Santiago
+        let rules = ZoneRules {
+            name: "America/Test".to_string(),
+            transitions: Vec::new(),
+            initial: 0,
+            rule: Some(rule("<-04>4<-03>,M9.1.6/24,M4.1.6/24")),
+        };
+        let zone = Timezone::Named(Box::leak(Box::new(rules)));
+        let day = |s: &str| zone.start_of_day(s.parse().unwrap()).to_rfc3339();
+        assert_eq!(day("2024-09-07"), "2024-09-07T04:00:00+00:00");
+        assert_eq!(day("2024-09-08"), "2024-09-08T04:00:00+00:00");
+        assert_eq!(day("2024-09-09"), "2024-09-09T03:00:00+00:00");
+    }
+
+    #[test]
+    fn zone_names_resolve_from_the_database() {
+        assert!(Timezone::parse("Nowhere/Atlantis").is_err());
+        if !Timezone::is_named("America/New_York") {
+            return;
+        }
+        let zone = Timezone::parse("America/New_York").unwrap();
+        assert_eq!(zone.to_string(), "America/New_York");
+        let july = "2024-07-01T12:00:00Z".parse().unwrap();
+        assert_eq!(zone.offset(july).local_minus_utc(), -4 * 3600);
+        assert_eq!(
+            zone.start_of_day("2024-01-01".parse().unwrap())
+                .to_rfc3339(),
+            "2024-01-01T05:00:00+00:00"
+        );
+    }
+}
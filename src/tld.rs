@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+/// Country-code top-level domains and the country or territory each belongs to
+const COUNTRY_CODES: &[(&str, &str)] = &[
+    ("ac", "Ascension Island"),
+    ("ad", "Andorra"),
+    ("ae", "United Arab Emirates"),
+    ("af", "Afghanistan"),
+    ("ag", "Antigua and Barbuda"),
+    ("ai", "Anguilla"),
+    ("al", "Albania"),
+    ("am", "Armenia"),
+    ("ao", "Angola"),
+    ("aq", "Antarctica"),
+    ("ar", "Argentina"),
+    ("as", "American Samoa"),
+    ("at", "Austria"),
+    ("au", "Australia"),
+    ("aw", "Aruba"),
+    ("ax", "Åland Islands"),
+    ("az", "Azerbaijan"),
+    ("ba", "Bosnia and Herzegovina"),
+    ("bb", "Barbados"),
+    ("bd", "Bangladesh"),
+    ("be", "Belgium"),
+    ("bf", "Burkina Faso"),
+    ("bg", "Bulgaria"),
+    ("bh", "Bahrain"),
+    ("bi", "Burundi"),
+    ("bj", "Benin"),
+    ("bm", "Bermuda"),
+    ("bn", "Brunei"),
+    ("bo", "Bolivia"),
+    ("br", "Brazil"),
+    ("bs", "Bahamas"),
+    ("bt", "Bhutan"),
+    ("bw", "Botswana"),
+    ("by", "Belarus"),
+    ("bz", "Belize"),
+    ("ca", "Canada"),
+    ("cc", "Cocos (Keeling) Islands"),
+    ("cd", "DR Congo"),
+    ("cf", "Central African Republic"),
+    ("cg", "Republic of the Congo"),
+    ("ch", "Switzerland"),
+    ("ci", "Côte d'Ivoire"),
+    ("ck", "Cook Islands"),
+    ("cl", "Chile"),
+    ("cm", "Cameroon"),
+    ("cn", "China"),
+    ("co", "Colombia"),
+    ("cr", "Costa Rica"),
+    ("cu", "Cuba"),
+    ("cv", "Cape Verde"),
+    ("cw", "Curaçao"),
+    ("cx", "Christmas Island"),
+    ("cy", "Cyprus"),
+    ("cz", "Czechia"),
+    ("de", "Germany"),
+    ("dj", "Djibouti"),
+    ("dk", "Denmark"),
+    ("dm", "Dominica"),
+    ("do", "Dominican Republic"),
+    ("dz", "Algeria"),
+    ("ec", "Ecuador"),
+    ("ee", "Estonia"),
+    ("eg", "Egypt"),
+    ("er", "Eritrea"),
+    ("es", "Spain"),
+    ("et", "Ethiopia"),
+    ("eu", "European Union"),
+    ("fi", "Finland"),
+    ("fj", "Fiji"),
+    ("fk", "Falkland Islands"),
+    ("fm", "Micronesia"),
+    ("fo", "Faroe Islands"),
+    ("fr", "France"),
+    ("ga", "Gabon"),
+    ("gd", "Grenada"),
+    ("ge", "Georgia"),
+    ("gf", "French Guiana"),
+    ("gg", "Guernsey"),
+    ("gh", "Ghana"),
+    ("gi", "Gibraltar"),
+    ("gl", "Greenland"),
+    ("gm", "Gambia"),
+    ("gn", "Guinea"),
+    ("gp", "Guadeloupe"),
+    ("gq", "Equatorial Guinea"),
+    ("gr", "Greece"),
+    ("gs", "South Georgia and the South Sandwich Islands"),
+    ("gt", "Guatemala"),
+    ("gu", "Guam"),
+    ("gw", "Guinea-Bissau"),
+    ("gy", "Guyana"),
+    ("hk", "Hong Kong"),
+    ("hm", "Heard Island and McDonald Islands"),
+    ("hn", "Honduras"),
+    ("hr", "Croatia"),
+    ("ht", "Haiti"),
+    ("hu", "Hungary"),
+    ("id", "Indonesia"),
+    ("ie", "Ireland"),
+    ("il", "Israel"),
+    ("im", "Isle of Man"),
+    ("in", "India"),
+    ("io", "British Indian Ocean Territory"),
+    ("iq", "Iraq"),
+    ("ir", "Iran"),
+    ("is", "Iceland"),
+    ("it", "Italy"),
+    ("je", "Jersey"),
+    ("jm", "Jamaica"),
+    ("jo", "Jordan"),
+    ("jp", "Japan"),
+    ("ke", "Kenya"),
+    ("kg", "Kyrgyzstan"),
+    ("kh", "Cambodia"),
+    ("ki", "Kiribati"),
+    ("km", "Comoros"),
+    ("kn", "Saint Kitts and Nevis"),
+    ("kp", "North Korea"),
+    ("kr", "South Korea"),
+    ("kw", "Kuwait"),
+    ("ky", "Cayman Islands"),
+    ("kz", "Kazakhstan"),
+    ("la", "Laos"),
+    ("lb", "Lebanon"),
+    ("lc", "Saint Lucia"),
+    ("li", "Liechtenstein"),
+    ("lk", "Sri Lanka"),
+    ("lr", "Liberia"),
+    ("ls", "Lesotho"),
+    ("lt", "Lithuania"),
+    ("lu", "Luxembourg"),
+    ("lv", "Latvia"),
+    ("ly", "Libya"),
+    ("ma", "Morocco"),
+    ("mc", "Monaco"),
+    ("md", "Moldova"),
+    ("me", "Montenegro"),
+    ("mg", "Madagascar"),
+    ("mh", "Marshall Islands"),
+    ("mk", "North Macedonia"),
+    ("ml", "Mali"),
+    ("mm", "Myanmar"),
+    ("mn", "Mongolia"),
+    ("mo", "Macau"),
+    ("mp", "Northern Mariana Islands"),
+    ("mq", "Martinique"),
+    ("mr", "Mauritania"),
+    ("ms", "Montserrat"),
+    ("mt", "Malta"),
+    ("mu", "Mauritius"),
+    ("mv", "Maldives"),
+    ("mw", "Malawi"),
+    ("mx", "Mexico"),
+    ("my", "Malaysia"),
+    ("mz", "Mozambique"),
+    ("na", "Namibia"),
+    ("nc", "New Caledonia"),
+    ("ne", "Niger"),
+    ("nf", "Norfolk Island"),
+    ("ng", "Nigeria"),
+    ("ni", "Nicaragua"),
+    ("nl", "Netherlands"),
+    ("no", "Norway"),
+    ("np", "Nepal"),
+    ("nr", "Nauru"),
+    ("nu", "Niue"),
+    ("nz", "New Zealand"),
+    ("om", "Oman"),
+    ("pa", "Panama"),
+    ("pe", "Peru"),
+    ("pf", "French Polynesia"),
+    ("pg", "Papua New Guinea"),
+    ("ph", "Philippines"),
+    ("pk", "Pakistan"),
+    ("pl", "Poland"),
+    ("pm", "Saint Pierre and Miquelon"),
+    ("pn", "Pitcairn Islands"),
+    ("pr", "Puerto Rico"),
+    ("ps", "Palestine"),
+    ("pt", "Portugal"),
+    ("pw", "Palau"),
+    ("py", "Paraguay"),
+    ("qa", "Qatar"),
+    ("re", "Réunion"),
+    ("ro", "Romania"),
+    ("rs", "Serbia"),
+    ("ru", "Russia"),
+    ("rw", "Rwanda"),
+    ("sa", "Saudi Arabia"),
+    ("sb", "Solomon Islands"),
+    ("sc", "Seychelles"),
+    ("sd", "Sudan"),
+    ("se", "Sweden"),
+    ("sg", "Singapore"),
+    ("sh", "Saint Helena"),
+    ("si", "Slovenia"),
+    ("sk", "Slovakia"),
+    ("sl", "Sierra Leone"),
+    ("sm", "San Marino"),
+    ("sn", "Senegal"),
+    ("so", "Somalia"),
+    ("sr", "Suriname"),
+    ("ss", "South Sudan"),
+    ("st", "São Tomé and Príncipe"),
+    ("su", "Soviet Union"),
+    ("sv", "El Salvador"),
+    ("sx", "Sint Maarten"),
+    ("sy", "Syria"),
+    ("sz", "Eswatini"),
+    ("tc", "Turks and Caicos Islands"),
+    ("td", "Chad"),
+    ("tf", "French Southern Territories"),
+    ("tg", "Togo"),
+    ("th", "Thailand"),
+    ("tj", "Tajikistan"),
+    ("tk", "Tokelau"),
+    ("tl", "Timor-Leste"),
+    ("tm", "Turkmenistan"),
+    ("tn", "Tunisia"),
+    ("to", "Tonga"),
+    ("tr", "Turkey"),
+    ("tt", "Trinidad and Tobago"),
+    ("tv", "Tuvalu"),
+    ("tw", "Taiwan"),
+    ("tz", "Tanzania"),
+    ("ua", "Ukraine"),
+    ("ug", "Uganda"),
+    ("uk", "United Kingdom"),
+    ("us", "United States"),
+    ("uy", "Uruguay"),
+    ("uz", "Uzbekistan"),
+    ("va", "Vatican City"),
+    ("vc", "Saint Vincent and the Grenadines"),
+    ("ve", "Venezuela"),
+    ("vg", "British Virgin Islands"),
+    ("vi", "U.S. Virgin Islands"),
+    ("vn", "Vietnam"),
+    ("vu", "Vanuatu"),
+    ("wf", "Wallis and Futuna"),
+    ("ws", "Samoa"),
+    ("ye", "Yemen"),
+    ("yt", "Mayotte"),
+    ("za", "South Africa"),
+    ("zm", "Zambia"),
+    ("zw", "Zimbabwe"),
+];
+
+/// Country or territory of a country-code TLD such as `de`
+pub fn country(tld: &str) -> Option<&'static str> {
+    COUNTRY_CODES
+        .binary_search_by(|(code, _)| (*code).cmp(tld))
+        .ok()
+        .map(|i| COUNTRY_CODES[i].1)
+}
+
+/// Visits and distinct domains under one top-level domain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TldCount {
+    pub visits: u32,
+    pub domains: u32,
+}
+
+/// Domain counts summed per top-level domain, the last label of each domain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TldBreakdown {
+    pub tlds: HashMap<String, TldCount>,
+}
+
+impl TldBreakdown {
+    pub fn from_counts(domain_counts: &HashMap<String, u32>) -> Self {
+        let mut tlds: HashMap<String, TldCount> = HashMap::new();
+        for (domain, count) in domain_counts {
+            let (host, _) = crate::domain::split_port(domain);
+            let tld = host.rsplit('.').next().unwrap_or(host);
+            let entry = match tlds.get_mut(tld) {
+                Some(entry) => entry,
+                None => tlds.entry(tld.to_string()).or_default(),
+            };
+            entry.visits += count;
+            entry.domains += 1;
+        }
+        Self { tlds }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.tlds.values().map(|count| count.visits).sum()
+    }
+
+    /// TLDs by descending visits, ties broken alphabetically
+    pub fn sorted(&self) -> Vec<(&str, TldCount)> {
+        let mut tlds: Vec<(&str, TldCount)> = self
+            .tlds
+            .iter()
+            .map(|(tld, count)| (tld.as_str(), *count))
+            .collect();
+        tlds.sort_by(|a, b| b.1.visits.cmp(&a.1.visits).then_with(|| a.0.cmp(b.0)));
+        tlds
+    }
+
+    /// Country-code TLDs with their country, by descending visits
+    pub fn countries(&self) -> Vec<(&str, &'static str, TldCount)> {
+        self.sorted()
+            .into_iter()
+            .filter_map(|(tld, count)| Some((tld, country(tld)?, count)))
+            .collect()
+    }
+}
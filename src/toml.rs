@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+
+use crate::json::Value;
+
+/// Parses the TOML subset used by historee's own files into a `json::Value` object
+pub fn parse(input: &str) -> Result<Value> {
+    let mut root = Vec::new();
+    // Path of the table that `key = value` lines currently write into
+    let mut current: Vec<String> = Vec::new();
+
+    for (line_num, line) in input.lines().enumerate() {
+        let parsed: Result<()> = (|| {
+            let mut cursor = Cursor::new(line);
+            cursor.skip_space();
+            match cursor.peek() {
+                None | Some('#') => return Ok(()),
+                Some('[') => {
+                    let array = cursor.eat("[[");
+                    if !array {
+                        cursor.eat("[");
+                    }
+                    let path = cursor.parse_key_path()?;
+                    if !cursor.eat(if array { "]]" } else { "]" }) {
+                        anyhow::bail!("expected closing bracket");
+                    }
+                    cursor.expect_end()?;
+                    if array {
+                        push_table(&mut root, &path)?;
+                    } else {
+                        table_at(&mut root, &path)?;
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let path = cursor.parse_key_path()?;
+                    cursor.skip_space();
+                    if !cursor.eat("=") {
+                        anyhow::bail!("expected '=' after key");
+                    }
+                    cursor.skip_space();
+                    let value = cursor.parse_value()?;
+                    cursor.expect_end()?;
+
+                    let (key, parents) = path.split_last().expect("key paths are non-empty");
+                    let mut full_path = current.clone();
+                    full_path.extend(parents.iter().cloned());
+                    let table = table_at(&mut root, &full_path)?;
+                    if table.iter().any(|(existing, _)| existing == key) {
+                        anyhow::bail!("duplicate key '{key}'");
+                    }
+                    table.push((key.clone(), value));
+                }
+            }
+            Ok(())
+        })();
+        parsed.with_context(|| format!("Invalid TOML at line {}", line_num + 1))?;
+    }
+
+    Ok(Value::Object(root))
+}
+
+/// The table at `path`, created if missing; arrays of tables resolve to their last entry
+fn table_at<'a>(
+    mut table: &'a mut Vec<(String, Value)>,
+    path: &[String],
+) -> Result<&'a mut Vec<(String, Value)>> {
+    for key in path {
+        let index = match table.iter().position(|(existing, _)| existing == key) {
+            Some(index) => index,
+            None => {
+                table.push((key.clone(), Value::object()));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            Value::Object(fields) => fields,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Object(fields)) => fields,
+                _ => anyhow::bail!("'{key}' is not a table"),
+            },
+            _ => anyhow::bail!("'{key}' is not a table"),
+        };
+    }
+    Ok(table)
+}
+
+/// Appends a new table to the array of tables at `path`
+fn push_table(root: &mut Vec<(String, Value)>, path: &[String]) -> Result<()> {
+    let (key, parents) = path.split_last().expect("key paths are non-empty");
+    let table = table_at(root, parents)?;
+    match table.iter_mut().find(|(existing, _)| existing == key) {
+        Some((_, Value::Array(items))) => items.push(Value::object()),
+        Some(_) => anyhow::bail!("'{key}' is not an array of tables"),
+        None => table.push((key.clone(), Value::Array(vec![Value::object()]))),
+    }
+    Ok(())
+}
+
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { rest: line }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        Some(c)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t']);
+    }
+
+    /// Allows only whitespace and a comment after a complete line
+    fn expect_end(&mut self) -> Result<()> {
+        self.skip_space();
+        match self.peek() {
+            None | Some('#') => Ok(()),
+            Some(c) => anyhow::bail!("unexpected '{c}'"),
+        }
+    }
+
+    fn parse_key_path(&mut self) -> Result<Vec<String>> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_space();
+            let key = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let end = self
+                        .rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                        .unwrap_or(self.rest.len());
+                    if end == 0 {
+                        anyhow::bail!("expected a key");
+                    }
+                    let key = self.rest[..end].to_string();
+                    self.rest = &self.rest[end..];
+                    key
+                }
+            };
+            path.push(key);
+            self.skip_space();
+            if !self.eat(".") {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => {
+                if self.rest.starts_with("\"\"\"") {
+                    anyhow::bail!("multi-line strings are not supported");
+                }
+                Ok(Value::String(self.parse_basic_string()?))
+            }
+            Some('\'') => {
+                if self.rest.starts_with("'''") {
+                    anyhow::bail!("multi-line strings are not supported");
+                }
+                Ok(Value::String(self.parse_literal_string()?))
+            }
+            Some('[') => self.parse_array(),
+            Some(_) => self.parse_scalar(),
+            None => anyhow::bail!("expected a value"),
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => anyhow::bail!("unterminated string"),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| anyhow::anyhow!("invalid \\u escape"))?;
+                        out.push(code);
+                    }
+                    Some(c) => anyhow::bail!("unknown escape '\\{c}'"),
+                    None => anyhow::bail!("unterminated string"),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String> {
+        self.bump();
+        let end = self
+            .rest
+            .find('\'')
+            .ok_or_else(|| anyhow::anyhow!("unterminated string"))?;
+        let value = self.rest[..end].to_string();
+        self.rest = &self.rest[end + 1..];
+        Ok(value)
+    }
+
+    fn parse_array(&mut self) -> Result<Value> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            if self.eat("]") {
+                return Ok(Value::Array(items));
+            }
+            if self.peek().is_none() {
+                anyhow::bail!("multi-line arrays are not supported");
+            }
+            items.push(self.parse_value()?);
+            self.skip_space();
+            if !self.eat(",") {
+                self.skip_space();
+                if !self.eat("]") {
+                    anyhow::bail!("expected ',' or ']' in array");
+                }
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn parse_scalar(&mut self) -> Result<Value> {
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+            .unwrap_or(self.rest.len());
+        let token = &self.rest[..end];
+        self.rest = &self.rest[end..];
+
+        match token {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        let number = token.replace('_', "");
+        if let Ok(int) = number.parse::<i64>() {
+            return Ok(Value::Int(int));
+        }
+        if let Ok(float) = number.parse::<f64>() {
+            if number.chars().any(|c| c.is_ascii_digit()) {
+                return Ok(Value::Float(float));
+            }
+        }
+        anyhow::bail!("unsupported value '{token}'")
+    }
+}
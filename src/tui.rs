@@ -0,0 +1,577 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Local};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::args::{Args, Browser};
+use crate::browser::{self, BrowserHandler};
+use crate::stats::AnalysisResult;
+use crate::utils;
+
+/// Time windows cycled with `w`, as days back from today
+const WINDOWS: [(&str, Option<i64>); 5] = [
+    ("all time", None),
+    ("last 7 days", Some(7)),
+    ("last 30 days", Some(30)),
+    ("last 90 days", Some(90)),
+    ("last year", Some(365)),
+];
+/// Paths listed in the detail pane once loaded
+const DETAIL_PATHS: usize = 10;
+const HELP: &str = "↑↓ move  / search  s sort  b browser  w window  enter paths  r reload  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Visits,
+    Name,
+    Trend,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Sort::Visits => Sort::Name,
+            Sort::Name => Sort::Trend,
+            Sort::Trend => Sort::Visits,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Sort::Visits => "visits",
+            Sort::Name => "name",
+            Sort::Trend => "trend",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// Decodes one read from the terminal: an escape sequence, which arrives in a single
+/// read, or one key per character when several were typed or pasted at once
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let key = match bytes {
+        [0x1b, b'[', b'A'] | [0x1b, b'O', b'A'] => Key::Up,
+        [0x1b, b'[', b'B'] | [0x1b, b'O', b'B'] => Key::Down,
+        [0x1b, b'[', b'5', b'~'] => Key::PageUp,
+        [0x1b, b'[', b'6', b'~'] => Key::PageDown,
+        [0x1b, b'[', b'H'] | [0x1b, b'[', b'1', b'~'] => Key::Home,
+        [0x1b, b'[', b'F'] | [0x1b, b'[', b'4', b'~'] => Key::End,
+        [0x1b] => Key::Escape,
+        [0x1b, ..] => return Vec::new(),
+        _ => {
+            return String::from_utf8_lossy(bytes)
+                .chars()
+                .filter_map(|c| match c {
+                    '\r' | '\n' => Some(Key::Enter),
+                    '\x7f' | '\x08' => Some(Key::Backspace),
+                    '\x03' | '\x04' => Some(Key::Interrupt),
+                    c if c.is_control() => None,
+                    c => Some(Key::Char(c)),
+                })
+                .collect()
+        }
+    };
+    vec![key]
+}
+
+/// Runs `stty` against the controlling terminal, which is inherited as stdin
+fn stty(args: &[&str]) -> Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .context("Failed to run stty")?;
+    if !output.status.success() {
+        anyhow::bail!("stty {} failed", args.join(" "));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Raw-mode alternate screen; the previous terminal settings are restored when dropped
+struct Terminal {
+    saved: String,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        if !cfg!(unix) {
+            anyhow::bail!("The TUI needs a Unix terminal");
+        }
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            anyhow::bail!("The TUI needs an interactive terminal");
+        }
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+        Ok(Self { saved })
+    }
+
+    /// Rows and columns of the terminal
+    fn size(&self) -> (usize, usize) {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| {
+                let (rows, cols) = size.split_once(' ')?;
+                Some((rows.parse().ok()?, cols.parse().ok()?))
+            })
+            .unwrap_or((24, 80))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// `text` cut or padded to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    if len == width && text.chars().count() > width && width > 0 {
+        fitted.pop();
+        fitted.push('…');
+    }
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+/// One loaded analysis: a browser or all browsers, over one time window
+struct View {
+    result: AnalysisResult,
+    /// Change between the earlier and later half of the window's months, per domain
+    trends: HashMap<String, i64>,
+}
+
+struct App<'a> {
+    args: &'a Args,
+    /// Browsers with a history database; `None` analyzes them all together
+    sources: Vec<Option<Browser>>,
+    source: usize,
+    window: usize,
+    views: HashMap<(usize, usize), View>,
+    paths: HashMap<(usize, usize, String), Vec<(String, u32)>>,
+    query: String,
+    searching: bool,
+    sort: Sort,
+    selected: usize,
+    offset: usize,
+    status: String,
+}
+
+impl<'a> App<'a> {
+    fn new(args: &'a Args) -> Self {
+        let mut sources: Vec<Option<Browser>> = Browser::ALL
+            .iter()
+            .filter(|browser| {
+                **browser == args.browser
+                    || !args.exclude_browsers.contains(browser)
+                        && browser.get_history_path().is_ok_and(|path| path.exists())
+            })
+            .map(|browser| Some(*browser))
+            .collect();
+        sources.push(None);
+        let source = if args.all_browsers {
+            sources.len() - 1
+        } else {
+            sources
+                .iter()
+                .position(|source| *source == Some(args.browser))
+                .unwrap_or(0)
+        };
+        Self {
+            args,
+            sources,
+            source,
+            window: 0,
+            views: HashMap::new(),
+            paths: HashMap::new(),
+            query: String::new(),
+            searching: false,
+            sort: Sort::Visits,
+            selected: 0,
+            offset: 0,
+            status: String::new(),
+        }
+    }
+
+    fn source_label(&self) -> String {
+        match self.sources[self.source] {
+            Some(browser) => browser.to_string(),
+            None => "all browsers".to_string(),
+        }
+    }
+
+    /// The run's arguments narrowed to the current browser and window
+    fn analysis_args(&self, domain: Option<&str>) -> Args {
+        let mut args = self.args.clone();
+        match self.sources[self.source] {
+            Some(browser) => {
+                args.browser = browser;
+                args.all_browsers = false;
+            }
+            None => args.all_browsers = true,
+        }
+        if let Some(days) = WINDOWS[self.window].1 {
+            args.since = Some(Local::now().date_naive() - Duration::days(days));
+        }
+        args.quiet = true;
+        args.trends = domain.is_none();
+        args.domain = domain.map(str::to_string);
+        args
+    }
+
+    fn view(&self) -> Option<&View> {
+        self.views.get(&(self.source, self.window))
+    }
+
+    /// Analyzes the current browser and window unless already loaded
+    fn load(&mut self, terminal: &Terminal) -> Result<()> {
+        let key = (self.source, self.window);
+        if self.views.contains_key(&key) {
+            return Ok(());
+        }
+        self.status = format!("Analyzing {}…", self.source_label());
+        self.draw(terminal)?;
+        match browser::analyze_browser_history(&self.analysis_args(None)) {
+            Ok(result) => {
+                let trends = result
+                    .trends
+                    .as_ref()
+                    .map(|trends| {
+                        trends
+                            .trends()
+                            .into_iter()
+                            .map(|trend| (trend.domain.clone(), trend.change()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.status.clear();
+                self.views.insert(key, View { result, trends });
+            }
+            Err(e) => self.status = format!("{}: {e}", self.source_label()),
+        }
+        Ok(())
+    }
+
+    /// Reads the selected domain's paths, which needs a pass over its URLs
+    fn load_paths(&mut self, terminal: &Terminal) -> Result<()> {
+        let Some(domain) = self
+            .rows()
+            .get(self.selected)
+            .map(|(domain, _)| domain.clone())
+        else {
+            return Ok(());
+        };
+        let key = (self.source, self.window, domain.clone());
+        if self.paths.contains_key(&key) {
+            return Ok(());
+        }
+        self.status = format!("Reading paths of {domain}…");
+        self.draw(terminal)?;
+        match browser::analyze_browser_history(&self.analysis_args(Some(&domain))) {
+            Ok(result) => {
+                let paths = result
+                    .paths
+                    .map(|paths| paths.top(DETAIL_PATHS))
+                    .unwrap_or_default();
+                self.status.clear();
+                self.paths.insert(key, paths);
+            }
+            Err(e) => self.status = format!("{domain}: {e}"),
+        }
+        Ok(())
+    }
+
+    /// Domains matching the search, in the current sort order
+    fn rows(&self) -> Vec<(String, u32)> {
+        let Some(view) = self.view() else {
+            return Vec::new();
+        };
+        let query = self.query.to_lowercase();
+        let mut rows: Vec<(String, u32)> = view
+            .result
+            .stats
+            .domain_counts
+            .iter()
+            .filter(|(domain, _)| domain.contains(&query))
+            .map(|(domain, count)| (domain.clone(), *count))
+            .collect();
+        match self.sort {
+            Sort::Visits => rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))),
+            Sort::Name => rows.sort_by(|a, b| a.0.cmp(&b.0)),
+            Sort::Trend => {
+                let change = |domain: &str| view.trends.get(domain).copied().unwrap_or(0);
+                rows.sort_by(|a, b| change(&b.0).cmp(&change(&a.0)).then_with(|| a.0.cmp(&b.0)))
+            }
+        }
+        rows
+    }
+
+    /// Lines of the detail pane for `domain`
+    fn detail(&self, domain: &str, count: u32, width: usize) -> Vec<String> {
+        let Some(view) = self.view() else {
+            return Vec::new();
+        };
+        let result = &view.result;
+        let total: u32 = result.stats.domain_counts.values().sum();
+        let mut lines = vec![
+            utils::display_domain(domain, self.args),
+            String::new(),
+            format!(
+                "{} {} ({:.1}% of all)",
+                utils::format_count(count, self.args.humanize),
+                self.args.weight.unit(),
+                utils::percent(count, total)
+            ),
+        ];
+        if let Some(root) = result.stats.root_counts.get(domain) {
+            lines.push(format!(
+                "{:.0}% on the root page",
+                utils::percent(*root, count)
+            ));
+        }
+        if let Some(trends) = &result.trends {
+            let months = trends.months();
+            let counts = trends.monthly.get(domain);
+            let values: Vec<u32> = months
+                .iter()
+                .map(|month| counts.and_then(|c| c.get(*month)).copied().unwrap_or(0))
+                .collect();
+            let shown = values.len().min(width);
+            if shown > 0 {
+                lines.push(String::new());
+                lines.push("Visits per month:".to_string());
+                lines.push(utils::sparkline(&values[values.len() - shown..]));
+                lines.push(format!(
+                    "{} – {}",
+                    months[months.len() - shown],
+                    months[months.len() - 1]
+                ));
+            }
+            if let Some(change) = view.trends.get(domain) {
+                lines.push(format!("Trend: {change:+} visits, later vs earlier half"));
+            }
+        }
+        lines.push(String::new());
+        match self
+            .paths
+            .get(&(self.source, self.window, domain.to_string()))
+        {
+            Some(paths) if paths.is_empty() => lines.push("No paths recorded".to_string()),
+            Some(paths) => {
+                lines.push("Top paths:".to_string());
+                for (path, count) in paths {
+                    lines.push(format!(
+                        "{:>6}  {path}",
+                        utils::format_count(*count, self.args.humanize)
+                    ));
+                }
+            }
+            None => lines.push("Press enter for top paths".to_string()),
+        }
+        lines
+    }
+
+    fn draw(&self, terminal: &Terminal) -> Result<()> {
+        let (height, width) = terminal.size();
+        let rows = self.rows();
+        let table_height = height.saturating_sub(4);
+        let table_width = if width >= 80 { width * 11 / 20 } else { width };
+        let detail_width = width.saturating_sub(table_width + 3);
+
+        let mut frame = String::from("\x1b[H\x1b[2J");
+        let line = |frame: &mut String, row: usize, col: usize, text: &str| {
+            let _ = write!(frame, "\x1b[{};{}H{text}", row + 1, col + 1);
+        };
+        let header = format!(
+            " historee — {} — {} — sort: {} — {} domains",
+            self.source_label(),
+            WINDOWS[self.window].0,
+            self.sort.label(),
+            rows.len()
+        );
+        line(
+            &mut frame,
+            0,
+            0,
+            &format!("\x1b[7m{}\x1b[0m", fit(&header, width)),
+        );
+        let search = if self.searching || !self.query.is_empty() {
+            format!(
+                " /{}{}  {}",
+                self.query,
+                if self.searching { "▏" } else { "" },
+                self.status
+            )
+        } else {
+            format!(" {}", self.status)
+        };
+        line(&mut frame, 1, 0, &fit(&search, width));
+
+        let count_width = 10;
+        let domain_width = table_width.saturating_sub(count_width + 7);
+        line(
+            &mut frame,
+            2,
+            0,
+            &format!(
+                "\x1b[1m{:>5}  {}{:>count_width$}\x1b[0m",
+                "#",
+                fit("Domain", domain_width),
+                self.args.weight.unit()
+            ),
+        );
+        for (i, (domain, count)) in rows.iter().enumerate().skip(self.offset).take(table_height) {
+            let text = format!(
+                "{:>5}  {}{:>count_width$}",
+                i + 1,
+                fit(&utils::display_domain(domain, self.args), domain_width),
+                utils::format_count(*count, self.args.humanize)
+            );
+            let text = if i == self.selected {
+                format!("\x1b[7m{text}\x1b[0m")
+            } else {
+                text
+            };
+            line(&mut frame, 3 + i - self.offset, 0, &text);
+        }
+
+        if detail_width >= 20 {
+            if let Some((domain, count)) = rows.get(self.selected) {
+                for (i, text) in self
+                    .detail(domain, *count, detail_width)
+                    .iter()
+                    .take(table_height + 1)
+                    .enumerate()
+                {
+                    line(&mut frame, 2 + i, table_width + 3, &fit(text, detail_width));
+                }
+            }
+        }
+        line(
+            &mut frame,
+            height.saturating_sub(1),
+            0,
+            &format!("\x1b[2m{}\x1b[0m", fit(HELP, width)),
+        );
+
+        let mut out = std::io::stdout().lock();
+        out.write_all(frame.as_bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Keeps the selection within the rows and scrolled into view
+    fn clamp(&mut self, page: usize) {
+        let len = self.rows().len();
+        self.selected = self.selected.min(len.saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + page {
+            self.offset = self.selected + 1 - page;
+        }
+    }
+
+    /// Applies a key press; returns false once the user quits
+    fn handle(&mut self, key: Key, terminal: &Terminal) -> Result<bool> {
+        let page = terminal.size().0.saturating_sub(4).max(1);
+        if self.searching {
+            match key {
+                Key::Char(c) => self.query.push(c.to_ascii_lowercase()),
+                Key::Backspace => {
+                    self.query.pop();
+                }
+                Key::Escape => {
+                    self.query.clear();
+                    self.searching = false;
+                }
+                Key::Enter => self.searching = false,
+                Key::Interrupt => return Ok(false),
+                _ => {}
+            }
+            self.selected = 0;
+            self.offset = 0;
+            return Ok(true);
+        }
+        match key {
+            Key::Char('q') | Key::Interrupt => return Ok(false),
+            Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.selected += 1,
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected += page,
+            Key::Home | Key::Char('g') => self.selected = 0,
+            Key::End | Key::Char('G') => self.selected = usize::MAX,
+            Key::Char('/') => self.searching = true,
+            Key::Escape => self.query.clear(),
+            Key::Char('s') => {
+                self.sort = self.sort.next();
+                self.selected = 0;
+            }
+            Key::Char('b') => {
+                self.source = (self.source + 1) % self.sources.len();
+                self.selected = 0;
+                self.load(terminal)?;
+            }
+            Key::Char('w') => {
+                self.window = (self.window + 1) % WINDOWS.len();
+                self.selected = 0;
+                self.load(terminal)?;
+            }
+            Key::Char('r') => {
+                self.views.remove(&(self.source, self.window));
+                self.paths.retain(|(source, window, _), _| {
+                    (*source, *window) != (self.source, self.window)
+                });
+                self.load(terminal)?;
+            }
+            Key::Enter => self.load_paths(terminal)?,
+            _ => {}
+        }
+        self.clamp(page);
+        Ok(true)
+    }
+}
+
+/// Browses the analyzed domains in a full-screen table until the user quits
+pub fn run(args: &Args) -> Result<()> {
+    let terminal = Terminal::enter()?;
+    let mut app = App::new(args);
+    app.load(&terminal)?;
+    app.draw(&terminal)?;
+
+    let mut stdin = std::io::stdin().lock();
+    let mut buf = [0u8; 16];
+    loop {
+        let read = stdin.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        for key in parse_keys(&buf[..read]) {
+            if !app.handle(key, &terminal)? {
+                return Ok(());
+            }
+        }
+        app.draw(&terminal)?;
+    }
+    Ok(())
+}
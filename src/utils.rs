@@ -1,71 +1,661 @@
-use tracing_subscriber::{fmt, EnvFilter};
+/// Directory for cached data, created on demand
+pub fn cache_dir() -> anyhow::Result<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(std::path::PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache")))
+        .ok_or_else(|| anyhow::anyhow!("Could not determine a cache directory"))?;
 
-pub fn setup_logging(verbose: bool) {
-    let filter = if verbose {
-        EnvFilter::from_default_env().add_directive("info".parse().unwrap())
-    } else {
-        EnvFilter::from_default_env().add_directive("error".parse().unwrap())
-    };
+    let dir = base.join("historee");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Directory for persistent application data (not created)
+pub fn data_dir() -> anyhow::Result<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(std::path::PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".local/share"))
+        })
+        .ok_or_else(|| anyhow::anyhow!("Could not determine a data directory"))?;
+
+    Ok(base.join("historee"))
+}
+
+/// Directory for user configuration (not created)
+pub fn config_dir() -> anyhow::Result<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(std::path::PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })
+        .ok_or_else(|| anyhow::anyhow!("Could not determine a configuration directory"))?;
+
+    Ok(base.join("historee"))
+}
+
+/// The first of `names` that exists in the config directory, else in the working directory,
+/// where older versions looked for them
+pub fn find_config_file(names: &[&str]) -> Option<std::path::PathBuf> {
+    if let Ok(dir) = config_dir() {
+        if let Some(path) = names
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+        {
+            return Some(path);
+        }
+    }
+    let path = names
+        .iter()
+        .map(std::path::PathBuf::from)
+        .find(|path| path.exists())?;
+    tracing::info!(
+        action = "load",
+        component = "config_file",
+        file_path = ?path,
+        "Using a file from the working directory; move it to the config directory so it applies everywhere"
+    );
+    Some(path)
+}
+
+/// Name of this machine, if the environment or the kernel reports one
+pub fn hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .chain(
+            ["/proc/sys/kernel/hostname", "/etc/hostname"]
+                .iter()
+                .filter_map(|path| std::fs::read_to_string(path).ok()),
+        )
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
 
-    fmt()
-        .with_timer(fmt::time::LocalTime::new(
-            time::macros::format_description!(
-                "[hour]:[minute]:[second].[subsecond digits:3] [period]"
-            ),
-        ))
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_thread_names(false)
-        .with_file(false)
-        .with_line_number(false)
-        .with_env_filter(filter)
-        .init();
+/// Thousands separator and decimal mark for the user's numeric locale
+fn number_separators() -> (&'static str, char) {
+    static SEPARATORS: std::sync::OnceLock<(&'static str, char)> = std::sync::OnceLock::new();
+    *SEPARATORS.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+        match language {
+            "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" => (".", ','),
+            "fr" | "ru" | "pl" | "cs" | "sv" | "fi" | "nb" | "uk" => ("\u{202f}", ','),
+            _ => (",", '.'),
+        }
+    })
 }
 
 pub fn format_number(num: u32) -> String {
+    let (separator, _) = number_separators();
     num.to_string()
         .as_bytes()
         .rchunks(3)
         .rev()
         .map(|chunk| std::str::from_utf8(chunk).unwrap())
         .collect::<Vec<_>>()
-        .join(",")
+        .join(separator)
 }
 
-pub fn redact_domain(domain: &str) -> String {
-    let parts: Vec<&str> = domain.split('.').collect();
-    if parts.len() <= 1 {
-        return domain.to_string();
+/// Abbreviates a count to one decimal place with a k/M/B suffix
+pub fn humanize_number(num: u32) -> String {
+    const UNITS: [(f64, &str); 3] = [(1e9, "B"), (1e6, "M"), (1e3, "k")];
+    let value = num as f64;
+    // Pick the unit by the rounded value so 999,960 becomes "1.0M" rather than "1000.0k"
+    let Some((scale, suffix)) = UNITS.iter().find(|(scale, _)| value >= scale * 0.99995) else {
+        return num.to_string();
+    };
+
+    let (_, decimal_mark) = number_separators();
+    let scaled = format!("{:.1}", value / scale).replace('.', &decimal_mark.to_string());
+    format!("{scaled}{suffix}")
+}
+
+/// Formats a count for text output, abbreviated when `humanize` is set
+pub fn format_count(num: u32, humanize: bool) -> String {
+    if humanize {
+        humanize_number(num)
+    } else {
+        format_number(num)
     }
+}
 
-    if parts.len() >= 2 && parts[parts.len() - 2].len() <= 3 {
-        return format!("???.{}", parts[parts.len() - 1]);
+/// `part` as a percentage of `total`, zero when the total is zero
+pub fn percent(part: u32, total: u32) -> f64 {
+    part as f64 * 100.0 / total.max(1) as f64
+}
+
+/// Formats a byte count with a decimal unit, e.g. "512 B" or "1.5 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [(f64, &str); 4] = [(1e12, "TB"), (1e9, "GB"), (1e6, "MB"), (1e3, "kB")];
+    let value = bytes as f64;
+    let Some((scale, suffix)) = UNITS.iter().find(|(scale, _)| value >= scale * 0.99995) else {
+        return format!("{bytes} B");
+    };
+
+    let (_, decimal_mark) = number_separators();
+    let scaled = format!("{:.1}", value / scale).replace('.', &decimal_mark.to_string());
+    format!("{scaled} {suffix}")
+}
+
+/// Formats a duration as e.g. "1h 05m", "12m 30s" or "45s"
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
     }
+}
+
+/// Parses an interval such as "90s", "15m", "1h" or "1d"
+pub fn parse_interval(s: &str) -> anyhow::Result<std::time::Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid interval '{s}' (expected e.g. 30m, 1h or 1d)"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => anyhow::bail!("Invalid interval unit in '{s}' (expected s, m, h or d)"),
+    };
+    if number == 0 {
+        anyhow::bail!("Interval must be greater than zero");
+    }
+    Ok(std::time::Duration::from_secs(number * seconds))
+}
+
+/// Parses an interval like [`parse_interval`] into a window of visit time
+pub fn parse_window(s: &str) -> anyhow::Result<chrono::Duration> {
+    let interval = parse_interval(s)?;
+    chrono::Duration::from_std(interval).map_err(|_| anyhow::anyhow!("Window '{s}' is too long"))
+}
 
-    let redacted_parts: Vec<String> = parts[..parts.len() - 1]
+/// Describes a window as whole days when it is one, e.g. "30 days"
+pub fn format_window(window: chrono::Duration) -> String {
+    match window.num_days() {
+        1 if window == chrono::Duration::days(1) => "day".to_string(),
+        days if days > 0 && window == chrono::Duration::days(days) => format!("{days} days"),
+        _ => format_duration(window),
+    }
+}
+
+/// Renders a horizontal bar proportional to `value / max`
+pub fn bar(value: u32, max: u32, width: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    const PARTIALS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let eighths = (value as u64 * width as u64 * 8 / max as u64) as usize;
+    let (full, remainder) = (eighths / 8, eighths % 8);
+    let mut bar = "█".repeat(full);
+    if remainder > 0 {
+        bar.push(PARTIALS[remainder - 1]);
+    }
+    bar
+}
+
+/// One block character per value, scaled between zero and the largest value
+pub fn sparkline(values: &[u32]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
         .iter()
-        .map(|part| "*".repeat(part.len()))
-        .collect();
+        .map(|&value| {
+            if max == 0 {
+                LEVELS[0]
+            } else {
+                LEVELS[(value as u64 * 7 / max as u64) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Writes a labelled histogram with proportional bars
+pub fn write_histogram(
+    out: &mut String,
+    title: &str,
+    buckets: &[(String, u32)],
+    humanize: bool,
+) -> std::fmt::Result {
+    use std::fmt::Write;
+
+    let max = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let label_width = buckets
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+
+    writeln!(out, "\n{title}:")?;
+    for (label, count) in buckets {
+        writeln!(
+            out,
+            "{:<label_width$} {:<30} {}",
+            label,
+            bar(*count, max, 30),
+            format_count(*count, humanize)
+        )?;
+    }
+    Ok(())
+}
+
+/// Shortens `text` to at most `max` characters, ending with an ellipsis when cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Decodes punycode (`xn--`) labels back to Unicode, leaving the domain as-is if invalid
+pub fn decode_idn(domain: &str) -> String {
+    if !domain.contains("xn--") {
+        return domain.to_string();
+    }
+    let decoded = url::quirks::domain_to_unicode(domain);
+    if decoded.is_empty() {
+        domain.to_string()
+    } else {
+        decoded
+    }
+}
 
-    let mut result = redacted_parts.join(".");
-    result.push('.');
-    result.push_str(parts[parts.len() - 1]);
-    result
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so
+/// readers never see a partial file; parent directories are created as needed
+pub fn write_atomic(path: &std::path::Path, contents: &str) -> anyhow::Result<()> {
+    write_atomic_with(path, |out| Ok(out.write_all(contents.as_bytes())?))
+}
+
+/// Like `write_atomic`, streaming the contents through `write` instead of holding them in memory
+pub fn write_atomic_with(
+    path: &std::path::Path,
+    write: impl FnOnce(&mut dyn std::io::Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use std::io::Write;
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {parent:?}"))?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Output path has no file name: {path:?}"))?;
+    let temp_path = parent.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let written = std::fs::File::create(&temp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            write(&mut out)?;
+            out.flush()?;
+            Ok(())
+        })
+        .with_context(|| format!("Failed to write {temp_path:?}"));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    std::fs::rename(&temp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_path);
+        anyhow::anyhow!("Failed to replace {path:?}: {e}")
+    })
+}
+
+/// ANSI styles used in text output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Green,
+    Dim,
+    Bold,
+}
+
+/// Whether text output should be colored under `--color` and `NO_COLOR`
+pub fn color_enabled(args: &crate::args::Args) -> bool {
+    use std::io::IsTerminal;
+
+    match args.color {
+        crate::args::ColorChoice::Always => true,
+        crate::args::ColorChoice::Never => false,
+        crate::args::ColorChoice::Auto => {
+            args.output.is_empty()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && std::io::stdout().is_terminal()
+        }
+    }
+}
+
+/// Wraps already padded text in an ANSI style when `enabled`
+pub fn paint(text: &str, style: Style, enabled: bool) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+    let code = match style {
+        Style::Green => "32",
+        Style::Dim => "2",
+        Style::Bold => "1",
+    };
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+/// A domain as it should appear in output, honoring `--decode-idn` and `--redact`
+pub fn display_domain(domain: &str, args: &crate::args::Args) -> String {
+    let domain = if args.decode_idn {
+        decode_idn(domain)
+    } else {
+        domain.to_string()
+    };
+    match args.redact {
+        Some(policy) => policy.domain(&domain),
+        None => domain,
+    }
+}
+
+/// How `--redact` hides domains, search terms and page-title keywords
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RedactionPolicy {
+    /// Replace each character with `*`, keeping label lengths and the TLD
+    #[default]
+    Stars,
+    /// Show only the TLD
+    TldOnly,
+    /// Replace the name with a short hash keyed per run, keeping the TLD
+    Hash,
+    /// Keep the first letter of each label and the TLD
+    FirstLetter,
+}
+
+impl std::fmt::Display for RedactionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactionPolicy::Stars => write!(f, "stars"),
+            RedactionPolicy::TldOnly => write!(f, "tld-only"),
+            RedactionPolicy::Hash => write!(f, "hash"),
+            RedactionPolicy::FirstLetter => write!(f, "first-letter"),
+        }
+    }
+}
+
+/// Keyed hash of `text`; the key is random per run, so common names cannot be looked up
+/// in a precomputed table
+fn short_hash(text: &str) -> String {
+    static KEY: std::sync::OnceLock<crate::anonymize::Anonymizer> = std::sync::OnceLock::new();
+    KEY.get_or_init(crate::anonymize::Anonymizer::random)
+        .token(text)
+}
+
+/// FNV-1a, for identifiers such as fingerprints and cache keys that must stay stable
+/// across builds and platforms; not for anything that has to resist guessing
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+/// FNV-1a of `bytes` in one call
+pub fn fnv(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv::default();
+    hash.write(bytes);
+    hash.finish()
+}
+
+impl RedactionPolicy {
+    pub fn domain(self, domain: &str) -> String {
+        if let (host, Some(port)) = crate::domain::split_port(domain) {
+            return format!("{}:{port}", self.domain(host));
+        }
+        let Some((name, tld)) = domain.rsplit_once('.') else {
+            return domain.to_string();
+        };
+        match self {
+            RedactionPolicy::Stars => {
+                // Short second-level labels such as `co` would otherwise reveal the suffix
+                if name
+                    .rsplit('.')
+                    .next()
+                    .is_some_and(|label| label.len() <= 3)
+                {
+                    return format!("???.{tld}");
+                }
+                let stars: Vec<String> = name
+                    .split('.')
+                    .map(|label| "*".repeat(label.len()))
+                    .collect();
+                format!("{}.{tld}", stars.join("."))
+            }
+            RedactionPolicy::TldOnly => format!("*.{tld}"),
+            RedactionPolicy::Hash => format!("{}.{tld}", short_hash(name)),
+            RedactionPolicy::FirstLetter => {
+                let initials: Vec<String> = name
+                    .split('.')
+                    .map(|label| format!("{}…", label.chars().next().unwrap_or('?')))
+                    .collect();
+                format!("{}.{tld}", initials.join("."))
+            }
+        }
+    }
+
+    /// Hides free text such as a search term, word by word
+    pub fn text(self, text: &str) -> String {
+        match self {
+            RedactionPolicy::Stars => text
+                .split(' ')
+                .map(|word| "*".repeat(word.chars().count()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            RedactionPolicy::TldOnly => "…".to_string(),
+            RedactionPolicy::Hash => short_hash(text),
+            RedactionPolicy::FirstLetter => text
+                .split(' ')
+                .map(|word| format!("{}…", word.chars().next().unwrap_or('?')))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// How `--date-format` prints dates: a preset or a strftime string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `2024-01-31`
+    Iso,
+    /// `January 31, 2024`
+    Long,
+    /// `Jan 31, 2024`
+    Short,
+    Custom(String),
+}
+
+impl DateFormat {
+    /// Parses `iso`, `long`, `short` or a strftime string using only date fields
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let format = match s.to_ascii_lowercase().as_str() {
+            "iso" => DateFormat::Iso,
+            "long" => DateFormat::Long,
+            "short" => DateFormat::Short,
+            _ => DateFormat::Custom(s.to_string()),
+        };
+        // Time fields and invalid specifiers only fail when formatting, so try it once here
+        let mut sample = String::new();
+        if std::fmt::Write::write_fmt(
+            &mut sample,
+            format_args!("{}", chrono::NaiveDate::MIN.format(format.pattern())),
+        )
+        .is_err()
+        {
+            anyhow::bail!(
+                "Invalid date format '{s}': expected iso, long, short or a strftime string \
+                 with date fields only"
+            );
+        }
+        Ok(format)
+    }
+
+    fn pattern(&self) -> &str {
+        match self {
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::Long => "%B %-d, %Y",
+            DateFormat::Short => "%b %-d, %Y",
+            DateFormat::Custom(pattern) => pattern,
+        }
+    }
+
+    pub fn format(&self, date: chrono::NaiveDate) -> String {
+        date.format(self.pattern()).to_string()
+    }
+}
+
+/// Formats `date` with `--date-format`, or with `default` when it is not given
+pub fn format_date(
+    date: chrono::NaiveDate,
+    args: &crate::args::Args,
+    default: &DateFormat,
+) -> String {
+    args.date_format.as_ref().unwrap_or(default).format(date)
+}
+
+/// First and last visit dates for report headings, in the report's zone
+pub fn date_range_labels(
+    range: Option<crate::stats::DateRange>,
+    args: &crate::args::Args,
+) -> (String, String) {
+    let zone = args.zone();
+    match range {
+        Some(range) => (
+            format_date(zone.local(range.earliest).date(), args, &DateFormat::Long),
+            format_date(zone.local(range.latest).date(), args, &DateFormat::Long),
+        ),
+        None => (
+            "No data available".to_string(),
+            "No data available".to_string(),
+        ),
+    }
 }
 
 pub fn validate_args(args: &crate::args::Args) -> anyhow::Result<()> {
+    // Catch template mistakes before the analysis rather than after it
+    if let Some(path) = &args.template {
+        crate::template::Template::load(path)?;
+    }
+    if args.all_browsers && args.browsers().is_empty() {
+        anyhow::bail!("--exclude-browser leaves no browser for --all-browsers");
+    }
     if let Some(top) = args.top {
         if top == 0 {
             anyhow::bail!("--top must be greater than 0");
         }
     }
 
+    if args.category_file.is_some() && !args.categories && !args.category_hours {
+        anyhow::bail!("--category-file needs --categories or --category-hours");
+    }
+
+    if let Some(share) = args.top_share {
+        if !(share > 0.0 && share <= 100.0) {
+            anyhow::bail!("--top-share must be a percentage above 0 and at most 100");
+        }
+    }
+
     if let Some(bottom) = args.bottom {
         if bottom == 0 {
             anyhow::bail!("--bottom must be greater than 0");
         }
     }
 
+    if let (Some(since), Some(until)) = (args.since, args.until) {
+        if since > until {
+            anyhow::bail!("--since must not be later than --until");
+        }
+    }
+
+    if (args.since.is_some() || args.until.is_some())
+        && matches!(
+            args.weight,
+            crate::args::Weight::VisitCount | crate::args::Weight::Typed
+        )
+    {
+        anyhow::bail!(
+            "--since/--until cannot be combined with --weight {}, which uses all-time counters",
+            args.weight
+        );
+    }
+
+    if args.collapse_redirects
+        && matches!(
+            args.weight,
+            crate::args::Weight::VisitCount | crate::args::Weight::Typed
+        )
+    {
+        anyhow::bail!(
+            "--collapse-redirects cannot be combined with --weight {}, which uses all-time counters",
+            args.weight
+        );
+    }
+
+    if let Some(sample) = args.sample {
+        if !(sample > 0.0 && sample <= 1.0) {
+            anyhow::bail!("--sample must be greater than 0 and at most 1");
+        }
+    }
+
+    for scheme in args.scheme_filter() {
+        let valid = scheme.starts_with(|c: char| c.is_ascii_lowercase())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+.-".contains(c));
+        if !valid {
+            anyhow::bail!("Invalid URL scheme '{scheme}' in --schemes");
+        }
+    }
+
+    if args.path_depth == Some(0) {
+        anyhow::bail!("--path-depth must be greater than 0");
+    }
+
+    if args.sessions == Some(0) {
+        anyhow::bail!("--sessions must be greater than 0");
+    }
+
     if let Some(workers) = args.workers {
         if workers == 0 {
             anyhow::bail!("--workers must be greater than 0");
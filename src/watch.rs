@@ -0,0 +1,122 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::args::Args;
+use crate::sink::{DomainChange, Event, SinkSpec, Sinks};
+use crate::stats::AnalysisResult;
+use crate::{browser, metrics, utils};
+
+/// New domains and domains whose count grew, new domains first, then by largest increase
+pub fn domain_changes(
+    previous: &HashMap<String, u32>,
+    current: &HashMap<String, u32>,
+) -> Vec<DomainChange> {
+    let mut changes: Vec<DomainChange> = current
+        .iter()
+        .map(|(domain, &count)| DomainChange {
+            domain: domain.clone(),
+            previous: previous.get(domain).copied().unwrap_or(0),
+            current: count,
+        })
+        .filter(|change| change.current > change.previous)
+        .collect();
+    changes.sort_by(|a, b| {
+        b.is_new()
+            .cmp(&a.is_new())
+            .then_with(|| (b.current - b.previous).cmp(&(a.current - a.previous)))
+            .then_with(|| a.domain.cmp(&b.domain))
+    });
+    changes
+}
+
+fn write_metrics(
+    path: Option<&Path>,
+    result: &AnalysisResult,
+    args: &Args,
+    interval_visits: Option<u32>,
+) {
+    let Some(path) = path else {
+        return;
+    };
+    if let Err(e) = utils::write_atomic(path, &metrics::render(result, args, interval_visits)) {
+        warn!(action = "write", component = "metrics", path = ?path, error = %e, "Failed to write metrics");
+    }
+}
+
+/// Analyzes once for a baseline, then re-analyzes every `interval` and emits the changes
+pub fn run(
+    interval: Duration,
+    specs: &[SinkSpec],
+    metrics_file: Option<&Path>,
+    args: &Args,
+) -> Result<()> {
+    let source = if args.all_browsers {
+        "all browsers".to_string()
+    } else {
+        args.browser.to_string()
+    };
+    let mut sinks = Sinks::from_specs(specs);
+    let baseline = browser::analyze_browser_history(args)?;
+    write_metrics(metrics_file, &baseline, args, None);
+    let mut provenance = baseline.provenance;
+    let mut previous = baseline.stats.domain_counts;
+    info!(
+        action = "start",
+        component = "watch",
+        domains = previous.len(),
+        interval_secs = interval.as_secs(),
+        "Recorded watch baseline"
+    );
+
+    loop {
+        std::thread::sleep(interval);
+        let result = match browser::analyze_browser_history(args) {
+            Ok(result) => result,
+            Err(e) => {
+                warn!(action = "analyze", component = "watch", error = %e, "Re-analysis failed");
+                sinks.emit(&Event::Alert {
+                    timestamp: Utc::now(),
+                    source: source.clone(),
+                    message: format!("Re-analysis failed: {e}"),
+                });
+                continue;
+            }
+        };
+        // A pattern file edited while watching would otherwise show up as a burst of changes
+        if !result.provenance.is_comparable_with(&provenance) {
+            warn!(
+                action = "compare",
+                component = "watch",
+                "Normalization rules or weight changed; starting a new baseline"
+            );
+            write_metrics(metrics_file, &result, args, None);
+            provenance = result.provenance;
+            previous = result.stats.domain_counts;
+            continue;
+        }
+        let changes = domain_changes(&previous, &result.stats.domain_counts);
+        let interval_visits = changes
+            .iter()
+            .map(|change| change.current - change.previous)
+            .sum();
+        write_metrics(metrics_file, &result, args, Some(interval_visits));
+        info!(
+            action = "compare",
+            component = "watch",
+            changes = changes.len(),
+            "Compared with previous run"
+        );
+        if !changes.is_empty() {
+            sinks.emit(&Event::Delta {
+                timestamp: Utc::now(),
+                source: source.clone(),
+                changes,
+            });
+        }
+        previous = result.stats.domain_counts;
+    }
+}
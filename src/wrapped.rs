@@ -0,0 +1,292 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use std::fmt::{self, Write};
+
+use crate::args::{Args, SortBy};
+use crate::browser;
+use crate::temporal::DomainStreak;
+use crate::utils::{self, DateFormat, Style};
+
+/// Hours counted as late-night browsing, from midnight to 5 AM
+const LATE_NIGHT_HOURS: std::ops::Range<usize> = 0..5;
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// A domain first visited during the year
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovery {
+    pub domain: String,
+    pub visits: u32,
+    pub first_visit: NaiveDate,
+}
+
+/// Highlights of one calendar year of browsing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wrapped {
+    pub year: i32,
+    /// Last day covered: December 31, or today while the year is in progress
+    pub through: NaiveDate,
+    pub visits: u32,
+    pub domains: usize,
+    /// Days with at least one visit
+    pub active_days: usize,
+    pub top_domains: Vec<(String, u32)>,
+    pub top_searches: Vec<(String, u32)>,
+    pub busiest_day: Option<(NaiveDate, u32)>,
+    /// Hour of day (0-23) with the most visits and its count
+    pub peak_hour: Option<(usize, u32)>,
+    /// Weekday from Monday (0) with the most visits and its count
+    pub busiest_weekday: Option<(usize, u32)>,
+    pub longest_streak: Option<DomainStreak>,
+    /// Share of visits between midnight and 5 AM, as a percentage
+    pub late_night_percent: f64,
+    /// Domains first visited this year, most visited first; `None` when the history
+    /// starts during the year, so every domain would count as new
+    pub discoveries: Option<Vec<Discovery>>,
+}
+
+/// Hour or weekday index with the highest count, earliest first on ties
+fn busiest(counts: &[u32]) -> Option<(usize, u32)> {
+    counts
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+}
+
+impl Wrapped {
+    /// Analyzes `year` with the other options in `args`, plus the history before it to
+    /// tell which domains were new
+    pub fn new(year: i32, args: &Args) -> Result<Self> {
+        let first = NaiveDate::from_ymd_opt(year, 1, 1)
+            .ok_or_else(|| anyhow::anyhow!("Invalid year {year}"))?;
+        let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap_or(first);
+        let today = args.zone().today();
+        if first > today {
+            anyhow::bail!("{year} has not started yet");
+        }
+        let through = last.min(today);
+
+        let mut year_args = args.clone();
+        year_args.since = Some(first);
+        year_args.until = Some(through);
+        year_args.temporal = true;
+        year_args.searches = true;
+        year_args.streaks = true;
+        let result = browser::analyze_browser_history(&year_args)?;
+        let totals = result.totals.clone().unwrap_or_default();
+        if result.stats.domain_counts.is_empty() {
+            anyhow::bail!("No visits in {year}");
+        }
+
+        let top = args.top.unwrap_or(5);
+        let mut top_domains: Vec<(String, u32)> = result
+            .stats
+            .domain_counts
+            .iter()
+            .map(|(domain, count)| (domain.clone(), *count))
+            .collect();
+        top_domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_domains.truncate(top);
+
+        let temporal = result.temporal.unwrap_or_default();
+        let late_night: u32 = temporal.by_hour[LATE_NIGHT_HOURS].iter().sum();
+
+        // First visits up to the end of the year; domains first seen before it aren't new
+        let mut history_args = args.clone();
+        history_args.since = None;
+        history_args.until = Some(through);
+        history_args.sort = SortBy::FirstVisit;
+        let history = browser::analyze_browser_history(&history_args)?;
+        let zone = args.zone();
+        let starts_before = history
+            .date_range
+            .is_some_and(|range| zone.local(range.earliest).date() < first);
+        let discoveries = match (&history.spans, starts_before) {
+            (Some(spans), true) => {
+                let mut discoveries: Vec<Discovery> = result
+                    .stats
+                    .domain_counts
+                    .iter()
+                    .filter_map(|(domain, visits)| {
+                        let first_visit = zone.local(spans.first(domain)?).date();
+                        (first_visit >= first).then(|| Discovery {
+                            domain: domain.clone(),
+                            visits: *visits,
+                            first_visit,
+                        })
+                    })
+                    .collect();
+                discoveries.sort_by(|a, b| {
+                    b.visits
+                        .cmp(&a.visits)
+                        .then_with(|| a.domain.cmp(&b.domain))
+                });
+                Some(discoveries)
+            }
+            _ => None,
+        };
+
+        Ok(Wrapped {
+            year,
+            through,
+            visits: totals.visits,
+            domains: result.stats.unique_domains(),
+            active_days: totals.daily.values().filter(|visits| **visits > 0).count(),
+            top_domains,
+            top_searches: result
+                .searches
+                .map(|searches| searches.top(top))
+                .unwrap_or_default(),
+            busiest_day: totals.busiest_day(),
+            peak_hour: busiest(&temporal.by_hour),
+            busiest_weekday: busiest(&temporal.by_weekday),
+            longest_streak: result
+                .streaks
+                .and_then(|streaks| streaks.longest(through, 1).pop()),
+            late_night_percent: utils::percent(late_night, temporal.total()),
+            discoveries,
+        })
+    }
+}
+
+pub fn run(year: Option<i32>, args: &Args) -> Result<()> {
+    let year = year.unwrap_or_else(|| args.zone().today().year());
+    let wrapped = Wrapped::new(year, args)?;
+    let mut out = String::new();
+    write_wrapped(&mut out, &wrapped, args).expect("writing to a String cannot fail");
+    print!("{out}");
+    Ok(())
+}
+
+/// `9 PM`-style label for an hour of the day
+fn hour_label(hour: usize) -> String {
+    match hour {
+        0 => "midnight".to_string(),
+        12 => "noon".to_string(),
+        1..=11 => format!("{hour} AM"),
+        _ => format!("{} PM", hour - 12),
+    }
+}
+
+fn write_wrapped(out: &mut String, wrapped: &Wrapped, args: &Args) -> fmt::Result {
+    let color = utils::color_enabled(args);
+    let count = |n: u32| utils::format_count(n, args.humanize);
+    let date = |day: NaiveDate| utils::format_date(day, args, &DateFormat::Long);
+    let heading = |text: &str| utils::paint(text, Style::Bold, color);
+
+    let in_progress = wrapped.through.month() != 12 || wrapped.through.day() != 31;
+    writeln!(
+        out,
+        "\n{}",
+        heading(&format!("=== Your {} in browsing ===", wrapped.year))
+    )?;
+    if in_progress {
+        writeln!(out, "(so far, through {})", date(wrapped.through))?;
+    }
+    writeln!(
+        out,
+        "\n{} visits to {} domains on {} days",
+        count(wrapped.visits),
+        count(wrapped.domains as u32),
+        count(wrapped.active_days as u32)
+    )?;
+
+    writeln!(out, "\n{}", heading("Top domains"))?;
+    let width = wrapped
+        .top_domains
+        .iter()
+        .map(|(domain, _)| utils::display_domain(domain, args).chars().count())
+        .max()
+        .unwrap_or(0);
+    for (i, (domain, visits)) in wrapped.top_domains.iter().enumerate() {
+        let domain = utils::display_domain(domain, args);
+        writeln!(
+            out,
+            "  {}. {domain:<width$}  {} visits",
+            i + 1,
+            count(*visits)
+        )?;
+    }
+
+    if !wrapped.top_searches.is_empty() {
+        writeln!(out, "\n{}", heading("Top searches"))?;
+        for (i, (term, searches)) in wrapped.top_searches.iter().enumerate() {
+            let term = if args.redact.is_some() {
+                "???"
+            } else {
+                term.as_str()
+            };
+            writeln!(out, "  {}. \"{term}\" ({}x)", i + 1, count(*searches))?;
+        }
+    }
+
+    writeln!(out, "\n{}", heading("Rhythm"))?;
+    if let Some((day, visits)) = wrapped.busiest_day {
+        writeln!(
+            out,
+            "  Busiest day: {} ({} visits)",
+            date(day),
+            count(visits)
+        )?;
+    }
+    if let Some((weekday, _)) = wrapped.busiest_weekday {
+        writeln!(
+            out,
+            "  Favorite day of the week: {}",
+            WEEKDAY_NAMES[weekday]
+        )?;
+    }
+    if let Some((hour, _)) = wrapped.peak_hour {
+        writeln!(out, "  Peak hour: {}", hour_label(hour))?;
+    }
+    writeln!(
+        out,
+        "  Late-night browsing (midnight to 5 AM): {:.1}% of visits",
+        wrapped.late_night_percent
+    )?;
+    if let Some(streak) = &wrapped.longest_streak {
+        writeln!(
+            out,
+            "  Longest streak: {} on {} days in a row, ending {}",
+            utils::display_domain(&streak.domain, args),
+            streak.longest,
+            date(streak.longest_end)
+        )?;
+    }
+
+    match &wrapped.discoveries {
+        Some(discoveries) if !discoveries.is_empty() => {
+            writeln!(
+                out,
+                "\n{}",
+                heading(&format!("New discoveries ({})", discoveries.len()))
+            )?;
+            for discovery in discoveries.iter().take(args.top.unwrap_or(5)) {
+                writeln!(
+                    out,
+                    "  {}: {} visits since {}",
+                    utils::display_domain(&discovery.domain, args),
+                    count(discovery.visits),
+                    date(discovery.first_visit)
+                )?;
+            }
+        }
+        Some(_) => writeln!(out, "\nNo new domains this year")?,
+        None => writeln!(
+            out,
+            "\nNew discoveries need history from before {}",
+            wrapped.year
+        )?,
+    }
+    Ok(())
+}